@@ -0,0 +1,375 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::services::anthropic::StreamedToolUse;
+
+/// XML形式で認識するツール名
+pub const TOOL_NAMES: &[&str] = &[
+    "execute_command",
+    "read_file",
+    "write_to_file",
+    "apply_diff",
+    "search_files",
+    "codebase_search",
+    "list_files",
+    "list_code_definition_names",
+    "browser_action",
+    "get_page_text",
+    "fetch",
+    "ask_followup_question",
+    "attempt_completion",
+    "use_mcp_tool",
+    "access_mcp_resource",
+    "switch_mode",
+    "new_task",
+    "insert_content",
+    "search_and_replace",
+    "update_todo_list",
+];
+
+/// 値にタグを含み得るパラメータ（最後の閉じタグまでを値とする）
+const RAW_PARAMS: &[&str] = &["content", "diff", "operations", "todos", "body"];
+
+lazy_static! {
+    static ref TOOL_OPEN_TAG: Regex = Regex::new(&format!("<({})>", TOOL_NAMES.join("|"))).unwrap();
+    static ref PARAM_OPEN_TAG: Regex = Regex::new(r"<([a-z_]+)>").unwrap();
+    static ref SUGGEST_TAG: Regex = Regex::new(r"(?s)<suggest>(.*?)</suggest>").unwrap();
+}
+
+/// ツール呼び出しの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallFormat {
+    /// システムプロンプトで説明したXMLタグで呼び出す
+    #[default]
+    Xml,
+    /// プロバイダーのネイティブなツール呼び出し（`tool_use` ブロック）を使う
+    Native,
+}
+
+/// アシスタントの応答に含まれるツール使用
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolUse {
+    /// ネイティブ形式の場合のツール使用ID（結果を返す際に必要）
+    pub id: Option<String>,
+    pub name: String,
+    pub params: HashMap<String, String>,
+}
+
+impl ToolUse {
+    /// ネイティブ形式のツール使用を変換する（文字列以外の値はJSONのまま渡す）
+    pub fn from_native(tool_use: &StreamedToolUse) -> Self {
+        let params = match tool_use.input() {
+            serde_json::Value::Object(input) => input
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        serde_json::Value::String(value) => value,
+                        other => other.to_string(),
+                    };
+                    (key, value)
+                })
+                .collect(),
+            _ => HashMap::new(),
+        };
+        Self {
+            id: Some(tool_use.id.clone()),
+            name: tool_use.name.clone(),
+            params,
+        }
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+}
+
+/// ストリーミング中の（閉じタグがまだ届いていない）XML形式のツール使用
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialToolUse {
+    pub name: String,
+    /// 途中までのパラメータの値も含める
+    pub params: HashMap<String, String>,
+    /// 値がまだ途中のパラメータ
+    pub partial_param: Option<String>,
+}
+
+impl PartialToolUse {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+
+    /// 値の閉じタグまで届いたパラメータ
+    pub fn complete_param(&self, name: &str) -> Option<&str> {
+        if self.partial_param.as_deref() == Some(name) {
+            return None;
+        }
+        self.param(name)
+    }
+}
+
+/// ネイティブ形式とXML形式の両方からツール使用を集める
+///
+/// 完了していないネイティブのツール使用、閉じタグのないXMLは含めない。
+/// XML形式では組み込みのツールに加えて `custom_tool_names` のタグも認識する。
+pub fn collect_tool_uses(
+    text: &str,
+    native: &[(usize, StreamedToolUse)],
+    custom_tool_names: &[&str],
+) -> Vec<ToolUse> {
+    let mut tool_uses: Vec<ToolUse> = native
+        .iter()
+        .filter(|(_, tool_use)| tool_use.complete)
+        .map(|(_, tool_use)| ToolUse::from_native(tool_use))
+        .collect();
+    if custom_tool_names.is_empty() {
+        tool_uses.extend(parse_xml_tool_uses(text));
+    } else {
+        let names: Vec<String> = TOOL_NAMES
+            .iter()
+            .chain(custom_tool_names)
+            .map(|name| regex::escape(name))
+            .collect();
+        let open_tag = Regex::new(&format!("<({})>", names.join("|"))).unwrap();
+        tool_uses.extend(parse_xml_tool_uses_with(text, &open_tag));
+    }
+    tool_uses
+}
+
+/// XML形式のツール使用を抽出する
+pub fn parse_xml_tool_uses(text: &str) -> Vec<ToolUse> {
+    parse_xml_tool_uses_with(text, &TOOL_OPEN_TAG)
+}
+
+/// `open_tag` に一致する開始タグのツール使用を抽出する
+fn parse_xml_tool_uses_with(text: &str, open_tag: &Regex) -> Vec<ToolUse> {
+    let mut tool_uses = Vec::new();
+    let mut rest = text;
+    while let Some(captures) = open_tag.captures(rest) {
+        let name = captures[1].to_string();
+        let body_start = captures.get(0).unwrap().end();
+        let close_tag = format!("</{}>", name);
+        let Some(body_len) = rest[body_start..].find(&close_tag) else {
+            break;
+        };
+
+        tool_uses.push(ToolUse {
+            id: None,
+            params: parse_params(&rest[body_start..body_start + body_len]),
+            name,
+        });
+        rest = &rest[body_start + body_len + close_tag.len()..];
+    }
+    tool_uses
+}
+
+/// `ask_followup_question` の `follow_up` から回答の候補を取り出す
+///
+/// `<suggest>` タグで囲んだ候補のほか、ネイティブ形式で渡された文字列の配列も受け付ける。
+pub fn parse_suggestions(follow_up: &str) -> Vec<String> {
+    let suggestions = match serde_json::from_str::<Vec<String>>(follow_up.trim()) {
+        Ok(suggestions) => suggestions,
+        Err(_) => SUGGEST_TAG
+            .captures_iter(follow_up)
+            .map(|captures| captures[1].to_string())
+            .collect(),
+    };
+    suggestions
+        .into_iter()
+        .map(|suggestion| suggestion.trim().to_string())
+        .filter(|suggestion| !suggestion.is_empty())
+        .collect()
+}
+
+/// ストリーミング中の応答の末尾にある、閉じていないツール使用を取り出す
+///
+/// 応答が届くたびに呼び出し、ホストが書き込み中の内容を表示できるようにする。
+/// 閉じタグの途中まで届いた部分は値に含めない。
+pub fn parse_partial_tool_use(text: &str) -> Option<PartialToolUse> {
+    let mut rest = text;
+    while let Some(captures) = TOOL_OPEN_TAG.captures(rest) {
+        let name = captures[1].to_string();
+        let body_start = captures.get(0).unwrap().end();
+        let close_tag = format!("</{}>", name);
+        match rest[body_start..].find(&close_tag) {
+            Some(body_len) => rest = &rest[body_start + body_len + close_tag.len()..],
+            None => {
+                let (params, partial_param) = parse_params_partial(&rest[body_start..], true);
+                return Some(PartialToolUse {
+                    name,
+                    params,
+                    partial_param,
+                });
+            }
+        }
+    }
+    None
+}
+
+fn parse_params(body: &str) -> HashMap<String, String> {
+    parse_params_partial(body, false).0
+}
+
+/// パラメータを取り出す（`partial` の場合は閉じていない最後のパラメータの値も含め、その名前を返す）
+fn parse_params_partial(body: &str, partial: bool) -> (HashMap<String, String>, Option<String>) {
+    let mut params = HashMap::new();
+    let mut rest = body;
+    while let Some(captures) = PARAM_OPEN_TAG.captures(rest) {
+        let name = captures[1].to_string();
+        let value_start = captures.get(0).unwrap().end();
+        let close_tag = format!("</{}>", name);
+        let is_raw = RAW_PARAMS.contains(&name.as_str());
+        let value_len = if is_raw {
+            rest[value_start..].rfind(&close_tag)
+        } else {
+            rest[value_start..].find(&close_tag)
+        };
+        let Some(value_len) = value_len else {
+            if partial {
+                let value = strip_partial_tag(&rest[value_start..]);
+                let value = if is_raw {
+                    value.strip_prefix('\n').unwrap_or(value)
+                } else {
+                    value.trim_start()
+                };
+                params.insert(name.clone(), value.to_string());
+                return (params, Some(name));
+            }
+            break;
+        };
+
+        let value = &rest[value_start..value_start + value_len];
+        let value = if is_raw {
+            // 前後の改行1つだけを取り除き、インデントは保持する
+            let value = value.strip_prefix('\n').unwrap_or(value);
+            value.strip_suffix('\n').unwrap_or(value)
+        } else {
+            value.trim()
+        };
+        params.insert(name, value.to_string());
+        rest = &rest[value_start + value_len + close_tag.len()..];
+    }
+    (params, None)
+}
+
+/// 末尾の閉じていないタグ（`</con` など）を除く
+fn strip_partial_tag(value: &str) -> &str {
+    match value.rfind('<') {
+        Some(index) if !value[index..].contains('>') => &value[..index],
+        _ => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_xml_tool_uses() {
+        let text = "I'll write the file.\n\n<write_to_file>\n<path> src/main.rs </path>\n<content>\nfn main() {\n    println!(\"<path>x</path>\");\n}\n</content>\n<line_count>3</line_count>\n</write_to_file>\n\n<attempt_completion>\n<result>Done</result>\n</attempt_completion>";
+        let tool_uses = parse_xml_tool_uses(text);
+
+        assert_eq!(tool_uses.len(), 2);
+        assert_eq!(tool_uses[0].name, "write_to_file");
+        assert_eq!(tool_uses[0].param("path"), Some("src/main.rs"));
+        assert_eq!(
+            tool_uses[0].param("content"),
+            Some("fn main() {\n    println!(\"<path>x</path>\");\n}")
+        );
+        assert_eq!(tool_uses[0].param("line_count"), Some("3"));
+        assert_eq!(tool_uses[1].name, "attempt_completion");
+        assert_eq!(tool_uses[1].param("result"), Some("Done"));
+    }
+
+    #[test]
+    fn test_parse_partial_tool_use() {
+        let text = "Writing the file.\n<read_file>\n<path>a.rs</path>\n</read_file>\n<write_to_file>\n<path>src/main.rs</path>\n<content>\nfn main() {\n    println!(\"hi\");\n</cont";
+        let tool_use = parse_partial_tool_use(text).unwrap();
+        assert_eq!(tool_use.name, "write_to_file");
+        assert_eq!(tool_use.complete_param("path"), Some("src/main.rs"));
+        assert_eq!(
+            tool_use.param("content"),
+            Some("fn main() {\n    println!(\"hi\");\n")
+        );
+        assert_eq!(tool_use.complete_param("content"), None);
+        assert_eq!(tool_use.partial_param.as_deref(), Some("content"));
+
+        let tool_use = parse_partial_tool_use("<write_to_file>\n<path>src/ma").unwrap();
+        assert_eq!(tool_use.param("path"), Some("src/ma"));
+        assert_eq!(tool_use.complete_param("path"), None);
+
+        // 閉じたツール使用・途中のタグは含めない
+        assert_eq!(
+            parse_partial_tool_use("<read_file>\n<path>a.rs</path>\n</read_file>"),
+            None
+        );
+        assert_eq!(parse_partial_tool_use("Let me <write_to"), None);
+    }
+
+    #[test]
+    fn test_parse_suggestions() {
+        let text = "<ask_followup_question>\n<question>Which file?</question>\n<follow_up>\n<suggest>src/lib.rs</suggest>\n<suggest>\n  src/main.rs\n</suggest>\n<suggest> </suggest>\n</follow_up>\n</ask_followup_question>";
+        let tool_uses = parse_xml_tool_uses(text);
+        assert_eq!(tool_uses[0].param("question"), Some("Which file?"));
+        assert_eq!(
+            parse_suggestions(tool_uses[0].param("follow_up").unwrap()),
+            vec!["src/lib.rs", "src/main.rs"]
+        );
+        assert_eq!(parse_suggestions(r#"["Yes", "No"]"#), vec!["Yes", "No"]);
+        assert!(parse_suggestions("Just answer").is_empty());
+    }
+
+    #[test]
+    fn test_incomplete_and_unknown_tags_are_ignored() {
+        assert!(parse_xml_tool_uses("<tool>read_file</tool>").is_empty());
+        assert!(parse_xml_tool_uses("<read_file>\n<path>src/lib.rs</path>").is_empty());
+    }
+
+    #[test]
+    fn test_collect_native_and_xml_tool_uses() {
+        let native = vec![
+            (
+                1,
+                StreamedToolUse {
+                    id: "toolu_1".to_string(),
+                    name: "list_files".to_string(),
+                    input_json: r#"{"path":"src","recursive":true}"#.to_string(),
+                    complete: true,
+                },
+            ),
+            (
+                2,
+                StreamedToolUse {
+                    id: "toolu_2".to_string(),
+                    name: "read_file".to_string(),
+                    input_json: r#"{"path":"#.to_string(),
+                    complete: false,
+                },
+            ),
+        ];
+        let tool_uses = collect_tool_uses("<read_file><path>a.rs</path></read_file>", &native, &[]);
+
+        assert_eq!(tool_uses.len(), 2);
+        assert_eq!(tool_uses[0].id.as_deref(), Some("toolu_1"));
+        assert_eq!(tool_uses[0].param("path"), Some("src"));
+        assert_eq!(tool_uses[0].param("recursive"), Some("true"));
+        assert_eq!(tool_uses[1].id, None);
+        assert_eq!(tool_uses[1].param("path"), Some("a.rs"));
+    }
+
+    #[test]
+    fn test_collect_custom_xml_tool_uses() {
+        let text = "<create_ticket>\n<title>Fix login</title>\n</create_ticket>\n<read_file><path>a.rs</path></read_file>";
+        let tool_uses = collect_tool_uses(text, &[], &["create_ticket"]);
+        assert_eq!(tool_uses.len(), 2);
+        assert_eq!(tool_uses[0].name, "create_ticket");
+        assert_eq!(tool_uses[0].param("title"), Some("Fix login"));
+        assert_eq!(tool_uses[1].name, "read_file");
+
+        // 登録していないツールは認識しない
+        assert_eq!(collect_tool_uses(text, &[], &[]).len(), 1);
+    }
+}
@@ -0,0 +1,180 @@
+use async_trait::async_trait;
+use std::fmt::Debug;
+
+/// タスクの予算（指定しない項目は無制限）
+///
+/// 上限に達した後に続行を承認すると、その時点の使用量から同じ大きさの予算を改めて使える。
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TaskBudget {
+    /// API料金の上限（USD）
+    pub max_cost: Option<f64>,
+    /// 入出力とキャッシュの読み書きを合わせたトークン数の上限
+    pub max_tokens: Option<u64>,
+    /// APIリクエスト数の上限
+    pub max_requests: Option<u32>,
+}
+
+/// タスクの使用量
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BudgetUsage {
+    pub cost: f64,
+    pub tokens: u64,
+    pub requests: u32,
+}
+
+impl BudgetUsage {
+    fn since(&self, baseline: &BudgetUsage) -> BudgetUsage {
+        BudgetUsage {
+            cost: self.cost - baseline.cost,
+            tokens: self.tokens.saturating_sub(baseline.tokens),
+            requests: self.requests.saturating_sub(baseline.requests),
+        }
+    }
+}
+
+/// 上限に達した予算の項目
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetLimit {
+    Cost { used: f64, max: f64 },
+    Tokens { used: u64, max: u64 },
+    Requests { used: u32, max: u32 },
+}
+
+impl std::fmt::Display for BudgetLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BudgetLimit::Cost { used, max } => {
+                write!(f, "cost budget of ${:.2} reached (${:.2} used)", max, used)
+            }
+            BudgetLimit::Tokens { used, max } => {
+                write!(f, "token budget of {} reached ({} used)", max, used)
+            }
+            BudgetLimit::Requests { used, max } => {
+                write!(f, "request budget of {} reached ({} used)", max, used)
+            }
+        }
+    }
+}
+
+impl TaskBudget {
+    /// `baseline` 以降の使用量が上限に達した項目（複数の場合は最初の項目）
+    pub fn exceeded(&self, usage: &BudgetUsage, baseline: &BudgetUsage) -> Option<BudgetLimit> {
+        let used = usage.since(baseline);
+        if let Some(max) = self.max_cost.filter(|max| used.cost >= *max) {
+            return Some(BudgetLimit::Cost {
+                used: used.cost,
+                max,
+            });
+        }
+        if let Some(max) = self.max_tokens.filter(|max| used.tokens >= *max) {
+            return Some(BudgetLimit::Tokens {
+                used: used.tokens,
+                max,
+            });
+        }
+        self.max_requests
+            .filter(|max| used.requests >= *max)
+            .map(|max| BudgetLimit::Requests {
+                used: used.requests,
+                max,
+            })
+    }
+
+    /// `baseline` 以降の使用量を除いた残りの予算（上限のない項目は `None`）
+    pub fn remaining(&self, usage: &BudgetUsage, baseline: &BudgetUsage) -> BudgetRemaining {
+        let used = usage.since(baseline);
+        BudgetRemaining {
+            cost: self.max_cost.map(|max| (max - used.cost).max(0.0)),
+            tokens: self.max_tokens.map(|max| max.saturating_sub(used.tokens)),
+            requests: self
+                .max_requests
+                .map(|max| max.saturating_sub(used.requests)),
+        }
+    }
+}
+
+/// 残りの予算
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BudgetRemaining {
+    pub cost: Option<f64>,
+    pub tokens: Option<u64>,
+    pub requests: Option<u32>,
+}
+
+impl std::fmt::Display for BudgetRemaining {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(cost) = self.cost {
+            parts.push(format!("${:.2}", cost));
+        }
+        if let Some(tokens) = self.tokens {
+            parts.push(format!("{} tokens", tokens));
+        }
+        if let Some(requests) = self.requests {
+            parts.push(format!("{} requests", requests));
+        }
+        if parts.is_empty() {
+            return write!(f, "unlimited");
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// 予算の上限に達したときに続行するか確認する（ヘッドレス実行時のホストが実装する）
+///
+/// 確認する相手がいない場合はタスクを停止する。
+#[async_trait]
+pub trait BudgetApprover: Debug + Send + Sync {
+    async fn approve(&self, limit: &BudgetLimit, usage: &BudgetUsage) -> bool;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_budget_exceeded_since_baseline() {
+        let budget = TaskBudget {
+            max_cost: Some(1.0),
+            max_requests: Some(3),
+            ..Default::default()
+        };
+        let usage = BudgetUsage {
+            cost: 0.5,
+            tokens: 10_000,
+            requests: 3,
+        };
+        assert_eq!(
+            budget.exceeded(&usage, &BudgetUsage::default()),
+            Some(BudgetLimit::Requests { used: 3, max: 3 })
+        );
+        assert_eq!(budget.exceeded(&usage, &usage), None);
+
+        let later = BudgetUsage {
+            cost: 1.75,
+            tokens: 20_000,
+            requests: 4,
+        };
+        let limit = budget.exceeded(&later, &usage).unwrap();
+        assert_eq!(
+            limit,
+            BudgetLimit::Cost {
+                used: 1.25,
+                max: 1.0
+            }
+        );
+        assert_eq!(
+            limit.to_string(),
+            "cost budget of $1.00 reached ($1.25 used)"
+        );
+        assert_eq!(TaskBudget::default().exceeded(&later, &usage), None);
+
+        let remaining = budget.remaining(&later, &usage);
+        assert_eq!(remaining.to_string(), "$0.00, 2 requests");
+        assert_eq!(
+            TaskBudget::default().remaining(&later, &usage).to_string(),
+            "unlimited"
+        );
+    }
+}
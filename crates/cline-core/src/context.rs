@@ -0,0 +1,224 @@
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+
+use crate::services::anthropic::{ContentBlock, Message};
+
+/// 後で読み直したファイルの古い内容の代わりに残す注記
+pub const STALE_READ_PLACEHOLDER: &str =
+    "[File re-read later in the conversation; this earlier content was removed to save context.]";
+
+lazy_static! {
+    static ref READ_RESULT_HEADER: Regex =
+        Regex::new(r"^\[read_file for '([^']+)'\] Result:").unwrap();
+    static ref FILE_CONTENT: Regex =
+        Regex::new(r#"(?s)<file_content path="([^"]+)">\n?(.*?)\n?</file_content>"#).unwrap();
+}
+
+/// 古い読み込み結果を置き換えた結果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneStats {
+    /// 置き換えた読み込み結果の数
+    pub pruned: usize,
+    /// 削除した内容の文字数
+    pub reclaimed_chars: usize,
+}
+
+/// 同じファイルを複数回読み込んでいる場合、最後以外の内容を注記に置き換える
+///
+/// `read_file` の結果（XML形式のテキストとネイティブのツール結果）と、
+/// メンションで追加した `<file_content>` を対象にする。
+pub fn prune_stale_file_reads(history: &mut [Message]) -> PruneStats {
+    let read_file_ids = read_file_tool_uses(history);
+
+    // 1回目で各ファイルの最後の読み込みを調べ、2回目でそれ以前の内容を置き換える
+    let mut last_read = HashMap::new();
+    let mut position = 0;
+    for message in history.iter() {
+        for block in &message.content {
+            for path in file_reads(block, &read_file_ids) {
+                last_read.insert(path, position);
+                position += 1;
+            }
+        }
+    }
+
+    let mut stats = PruneStats::default();
+    let mut position = 0;
+    let mut is_stale = |path: &str| {
+        let stale = last_read.get(&normalize_path(path)) != Some(&position);
+        position += 1;
+        stale
+    };
+    for message in history.iter_mut() {
+        for block in &mut message.content {
+            match block {
+                ContentBlock::Text { text } => {
+                    if let Some(header) = READ_RESULT_HEADER.captures(text) {
+                        let header_len = header[0].len();
+                        if is_stale(&header[1]) {
+                            let content = text[header_len..].trim();
+                            if content != STALE_READ_PLACEHOLDER {
+                                stats.pruned += 1;
+                                stats.reclaimed_chars += content.len();
+                                *text =
+                                    format!("{}\n{}", &text[..header_len], STALE_READ_PLACEHOLDER);
+                            }
+                        }
+                        continue;
+                    }
+                    let replaced = FILE_CONTENT.replace_all(text, |captures: &Captures| {
+                        let content = &captures[2];
+                        if !is_stale(&captures[1]) || content == STALE_READ_PLACEHOLDER {
+                            return captures[0].to_string();
+                        }
+                        stats.pruned += 1;
+                        stats.reclaimed_chars += content.len();
+                        format!(
+                            "<file_content path=\"{}\">\n{}\n</file_content>",
+                            &captures[1], STALE_READ_PLACEHOLDER
+                        )
+                    });
+                    if let std::borrow::Cow::Owned(replaced) = replaced {
+                        *text = replaced;
+                    }
+                }
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    ..
+                } => {
+                    let Some(path) = read_file_ids.get(tool_use_id.as_str()) else {
+                        continue;
+                    };
+                    if !is_stale(path) || is_placeholder(content) {
+                        continue;
+                    }
+                    stats.pruned += 1;
+                    stats.reclaimed_chars +=
+                        Message::new("user", std::mem::take(content)).text().len();
+                    *content = vec![ContentBlock::text(STALE_READ_PLACEHOLDER)];
+                }
+                _ => {}
+            }
+        }
+    }
+    stats
+}
+
+/// ブロックに含まれる読み込み結果のファイル
+fn file_reads(block: &ContentBlock, read_file_ids: &HashMap<String, String>) -> Vec<String> {
+    match block {
+        ContentBlock::Text { text } => match READ_RESULT_HEADER.captures(text) {
+            Some(header) => vec![normalize_path(&header[1])],
+            None => FILE_CONTENT
+                .captures_iter(text)
+                .map(|captures| normalize_path(&captures[1]))
+                .collect(),
+        },
+        ContentBlock::ToolResult { tool_use_id, .. } => read_file_ids
+            .get(tool_use_id)
+            .map(|path| normalize_path(path))
+            .into_iter()
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// ネイティブの `read_file` 呼び出しのIDと読み込んだパス
+fn read_file_tool_uses(history: &[Message]) -> HashMap<String, String> {
+    history
+        .iter()
+        .flat_map(|message| &message.content)
+        .filter_map(|block| match block {
+            ContentBlock::ToolUse { id, name, input } if name == "read_file" => input
+                .get("path")
+                .and_then(|path| path.as_str())
+                .map(|path| (id.clone(), path.to_string())),
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_placeholder(content: &[ContentBlock]) -> bool {
+    matches!(content, [ContentBlock::Text { text }] if text == STALE_READ_PLACEHOLDER)
+}
+
+fn normalize_path(path: &str) -> String {
+    path.trim().trim_start_matches("./").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn test_prune_stale_file_reads() {
+        let mut history = vec![
+            Message::new(
+                "user",
+                vec![
+                    ContentBlock::text("[read_file for 'src/main.rs'] Result:\nfn main() {}"),
+                    ContentBlock::text(
+                        "Check <file_content path=\"src/lib.rs\">\npub fn a() {}\n</file_content> and <file_content path=\"README.md\">\n# Demo\n</file_content>",
+                    ),
+                ],
+            ),
+            Message::new(
+                "assistant",
+                vec![ContentBlock::ToolUse {
+                    id: "toolu_1".to_string(),
+                    name: "read_file".to_string(),
+                    input: json!({ "path": "./src/lib.rs" }),
+                }],
+            ),
+            Message::new(
+                "user",
+                vec![
+                    ContentBlock::ToolResult {
+                        tool_use_id: "toolu_1".to_string(),
+                        content: vec![ContentBlock::text("pub fn a() {}\npub fn b() {}")],
+                        is_error: None,
+                    },
+                    ContentBlock::text("[read_file for 'src/main.rs'] Result:\nfn main() { run() }"),
+                ],
+            ),
+        ];
+
+        let stats = prune_stale_file_reads(&mut history);
+        assert_eq!(
+            stats,
+            PruneStats {
+                pruned: 2,
+                reclaimed_chars: "fn main() {}".len() + "pub fn a() {}".len(),
+            }
+        );
+        assert_eq!(
+            history[0].content,
+            vec![
+                ContentBlock::text(format!(
+                    "[read_file for 'src/main.rs'] Result:\n{}",
+                    STALE_READ_PLACEHOLDER
+                )),
+                ContentBlock::text(format!(
+                    "Check <file_content path=\"src/lib.rs\">\n{}\n</file_content> and <file_content path=\"README.md\">\n# Demo\n</file_content>",
+                    STALE_READ_PLACEHOLDER
+                )),
+            ]
+        );
+        // 最新の読み込み結果は残す
+        assert_eq!(
+            history[2].content[1],
+            ContentBlock::text("[read_file for 'src/main.rs'] Result:\nfn main() { run() }")
+        );
+        assert!(!is_placeholder(match &history[2].content[0] {
+            ContentBlock::ToolResult { content, .. } => content,
+            _ => unreachable!(),
+        }));
+
+        // 置き換え済みの内容は数えない
+        assert_eq!(prune_stale_file_reads(&mut history), PruneStats::default());
+    }
+}
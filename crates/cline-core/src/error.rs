@@ -0,0 +1,135 @@
+use std::fmt::Display;
+
+/// `Cline` の公開APIが返すエラー
+///
+/// 内部のサービスは `anyhow` を使うが、種類の分かるエラーは `ClineError` を包んで返すため、
+/// `anyhow::Error` から変換すると元の種類に戻る。
+#[derive(Debug, thiserror::Error)]
+pub enum ClineError {
+    /// プロバイダのAPIリクエストの失敗
+    #[error("{message}")]
+    Provider {
+        /// HTTPステータス（ストリーム中のエラーや接続の失敗では `None`）
+        status: Option<u16>,
+        /// 時間をおいて再試行すれば成功する見込みがある
+        retryable: bool,
+        message: String,
+    },
+    /// ツールを実行できない
+    #[error("{message}")]
+    Tool { tool: String, message: String },
+    /// ワークスペースの外やポリシーで禁止されたパスへのアクセス
+    #[error("{0}")]
+    AccessDenied(String),
+    /// 差分を適用できない
+    #[error("Failed to apply diff: {0}")]
+    Diff(String),
+    /// MCPサーバーとのやり取りの失敗
+    #[error("{0}")]
+    Mcp(String),
+    /// ポリシーで禁止された操作
+    #[error("{0}")]
+    PolicyViolation(String),
+    /// 予算の上限に達し、続行が承認されなかった
+    #[error("Task stopped: {0}")]
+    BudgetExceeded(String),
+    /// タスクを中断した
+    #[error("Task aborted")]
+    Aborted,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+pub type Result<T, E = ClineError> = std::result::Result<T, E>;
+
+impl ClineError {
+    /// HTTPステータスからAPIリクエストの失敗を作る（タイムアウト・競合・レート制限・サーバーエラーは再試行できる）
+    pub fn from_status(status: u16, body: &str) -> Self {
+        Self::Provider {
+            status: Some(status),
+            retryable: matches!(status, 408 | 409 | 429) || status >= 500,
+            message: format!("API request failed: {}", body),
+        }
+    }
+
+    /// 接続の失敗やタイムアウトなど、応答を受け取る前のAPIリクエストの失敗
+    pub fn from_request(error: reqwest::Error) -> Self {
+        Self::Provider {
+            status: error.status().map(|status| status.as_u16()),
+            retryable: error.is_timeout() || error.is_connect(),
+            message: format!("API request failed: {}", error),
+        }
+    }
+
+    /// その他のエラー
+    pub fn other(message: impl Display) -> Self {
+        Self::Other(anyhow::anyhow!("{}", message))
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        matches!(self, Self::Aborted)
+    }
+
+    /// 再試行すれば成功する見込みがあるか
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Provider {
+                retryable: true,
+                ..
+            }
+        )
+    }
+}
+
+impl From<anyhow::Error> for ClineError {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<ClineError>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+        match error.downcast::<std::io::Error>() {
+            Ok(error) => Self::Io(error),
+            Err(error) => Self::Other(error),
+        }
+    }
+}
+
+impl From<serde_json::Error> for ClineError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Other(error.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_from_anyhow_keeps_kind() {
+        let error: ClineError = anyhow::Error::from(ClineError::from_status(529, "overloaded"))
+            .context("while streaming")
+            .into();
+        assert!(matches!(
+            error,
+            ClineError::Provider {
+                status: Some(529),
+                retryable: true,
+                ..
+            }
+        ));
+        assert_eq!(error.to_string(), "API request failed: overloaded");
+        assert!(!ClineError::from_status(400, "invalid").is_retryable());
+
+        let error: ClineError =
+            anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::NotFound)).into();
+        assert!(matches!(error, ClineError::Io(_)));
+        let error: ClineError = anyhow::anyhow!("No changes to commit").into();
+        assert!(matches!(error, ClineError::Other(_)));
+        assert_eq!(error.to_string(), "No changes to commit");
+        assert!(ClineError::Aborted.is_aborted());
+    }
+}
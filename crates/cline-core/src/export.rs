@@ -0,0 +1,201 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::services::anthropic::Message;
+use crate::shared::message::{ClineApiReqInfo, ClineAsk, ClineMessage, ClineSay};
+
+/// エクスポート形式のバージョン
+pub const EXPORT_VERSION: u32 = 1;
+
+/// タスクのエクスポート（JSONで保存し、別のワークスペースで読み込める）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskExport {
+    pub version: u32,
+    pub task_id: String,
+    /// エクスポート元のワークスペース（参考情報）
+    pub workspace_path: String,
+    pub exported_at: i64,
+    pub total_cost: f64,
+    pub cline_messages: Vec<ClineMessage>,
+    pub api_conversation_history: Vec<Message>,
+}
+
+impl TaskExport {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let export: Self = serde_json::from_str(json)?;
+        if export.version > EXPORT_VERSION {
+            anyhow::bail!(
+                "Task export version {} is newer than supported version {}",
+                export.version,
+                EXPORT_VERSION
+            );
+        }
+        Ok(export)
+    }
+
+    /// 共有用のMarkdown形式の記録
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!(
+            "# Task {}\n\n- Exported: {}\n- Workspace: `{}`\n- Total cost: ${:.4}\n",
+            self.task_id,
+            format_ts(self.exported_at),
+            self.workspace_path,
+            self.total_cost
+        );
+
+        for message in &self.cline_messages {
+            if message.is_partial() {
+                continue;
+            }
+            if let Some(section) = render_message(message) {
+                markdown.push_str("\n---\n\n");
+                markdown.push_str(&section);
+                markdown.push('\n');
+            }
+        }
+        markdown
+    }
+}
+
+fn render_message(message: &ClineMessage) -> Option<String> {
+    let ts = format_ts(message.ts());
+    let text = message.text().unwrap_or_default();
+    let section = match message {
+        ClineMessage::Say { say, images, .. } => match say {
+            ClineSay::Task | ClineSay::UserFeedback => {
+                let mut section = format!("**User** ({}):\n\n{}", ts, text);
+                let image_count = images.as_ref().map_or(0, Vec::len);
+                if image_count > 0 {
+                    section.push_str(&format!("\n\n_({} image(s) attached)_", image_count));
+                }
+                section
+            }
+            ClineSay::Text | ClineSay::CompletionResult => {
+                format!("**Assistant** ({}):\n\n{}", ts, text)
+            }
+            ClineSay::Reasoning => format!(
+                "**Assistant (reasoning)** ({}):\n\n{}",
+                ts,
+                text.lines()
+                    .map(|line| format!("> {}", line))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+            ClineSay::ApiReqStarted => {
+                let info: ClineApiReqInfo = serde_json::from_str(text).ok()?;
+                format!(
+                    "_API request ({}): {} tokens in, {} tokens out, ${:.4}_",
+                    ts,
+                    info.tokens_in.unwrap_or(0),
+                    info.tokens_out.unwrap_or(0),
+                    info.cost.unwrap_or(0.0)
+                )
+            }
+            ClineSay::Tool | ClineSay::BrowserAction | ClineSay::Command => {
+                format!("**Tool** ({}):\n\n```\n{}\n```", ts, text)
+            }
+            ClineSay::CommandOutput
+            | ClineSay::BrowserActionResult
+            | ClineSay::McpServerResponse => {
+                format!("**Tool Result** ({}):\n\n```\n{}\n```", ts, text)
+            }
+            ClineSay::Error => format!("**Error** ({}):\n\n{}", ts, text),
+            _ => return None,
+        },
+        ClineMessage::Ask { ask, .. } => match ask {
+            ClineAsk::Followup | ClineAsk::CompletionResult => {
+                format!("**Assistant** ({}):\n\n{}", ts, text)
+            }
+            ClineAsk::Command | ClineAsk::Tool | ClineAsk::UseMcpServer => {
+                format!("**Tool** ({}):\n\n```\n{}\n```", ts, text)
+            }
+            _ => return None,
+        },
+    };
+    Some(section)
+}
+
+fn format_ts(ts: i64) -> String {
+    DateTime::<Utc>::from_timestamp_millis(ts)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| ts.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::anthropic::ContentBlock;
+    use pretty_assertions::assert_eq;
+
+    fn say(ts: i64, say: ClineSay, text: &str) -> ClineMessage {
+        ClineMessage::Say {
+            ts,
+            text: Some(text.to_string()),
+            say,
+            images: None,
+            partial: None,
+            reasoning: None,
+        }
+    }
+
+    #[test]
+    fn test_export_markdown_and_json() {
+        let export = TaskExport {
+            version: EXPORT_VERSION,
+            task_id: "task-1".to_string(),
+            workspace_path: "/workspace".to_string(),
+            exported_at: 0,
+            total_cost: 0.0123,
+            cline_messages: vec![
+                say(0, ClineSay::Task, "Fix the bug"),
+                say(
+                    1000,
+                    ClineSay::ApiReqStarted,
+                    r#"{"tokensIn":100,"tokensOut":20,"cost":0.0123}"#,
+                ),
+                say(2000, ClineSay::Reasoning, "Look at main.rs"),
+                ClineMessage::Say {
+                    ts: 3000,
+                    text: Some("Do".to_string()),
+                    say: ClineSay::Text,
+                    images: None,
+                    partial: Some(true),
+                    reasoning: None,
+                },
+                say(3000, ClineSay::Text, "Done"),
+            ],
+            api_conversation_history: vec![Message::new(
+                "user",
+                vec![ContentBlock::text("Fix the bug")],
+            )],
+        };
+
+        assert_eq!(
+            export.to_markdown(),
+            "# Task task-1\n\n\
+             - Exported: 1970-01-01 00:00:00 UTC\n\
+             - Workspace: `/workspace`\n\
+             - Total cost: $0.0123\n\
+             \n---\n\n**User** (1970-01-01 00:00:00 UTC):\n\nFix the bug\n\
+             \n---\n\n_API request (1970-01-01 00:00:01 UTC): 100 tokens in, 20 tokens out, $0.0123_\n\
+             \n---\n\n**Assistant (reasoning)** (1970-01-01 00:00:02 UTC):\n\n> Look at main.rs\n\
+             \n---\n\n**Assistant** (1970-01-01 00:00:03 UTC):\n\nDone\n"
+        );
+
+        let restored = TaskExport::from_json(&export.to_json().unwrap()).unwrap();
+        assert_eq!(restored, export);
+        assert!(TaskExport::from_json(
+            &export
+                .to_json()
+                .unwrap()
+                .replace("\"version\": 1", "\"version\": 2")
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,399 @@
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::storage::write_atomic;
+
+/// 追記形式の会話履歴のファイル名
+pub const HISTORY_LOG_FILE_NAME: &str = "history.jsonl";
+
+/// 会話履歴の保存形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HistoryStorage {
+    /// メッセージの種類ごとのJSONファイルを毎回書き直す（以前からの形式）
+    #[default]
+    Json,
+    /// 変更したメッセージだけを1つのファイルに追記する（長いタスク向け）
+    AppendLog,
+}
+
+/// 履歴に保存するメッセージの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryKind {
+    /// `ui_messages.json` に当たるメッセージ
+    Ui,
+    /// `api_conversation_history.json` に当たるメッセージ
+    Api,
+}
+
+/// 追記する1行
+///
+/// `data` がある場合は `index` 番目のメッセージを置き換え（末尾なら追加し）、
+/// ない場合はメッセージを `index` 件に切り詰める。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct HistoryRecord {
+    kind: HistoryKind,
+    index: usize,
+    ts: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+/// 履歴の一部
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryPage<T> {
+    pub items: Vec<T>,
+    /// 保存されているメッセージの総数
+    pub total: usize,
+}
+
+/// 保存済みのメッセージの内容のハッシュ（変更したメッセージだけを追記するために使う）
+#[derive(Debug, Default)]
+struct Persisted {
+    ui: Vec<u64>,
+    api: Vec<u64>,
+}
+
+impl Persisted {
+    fn get_mut(&mut self, kind: HistoryKind) -> &mut Vec<u64> {
+        match kind {
+            HistoryKind::Ui => &mut self.ui,
+            HistoryKind::Api => &mut self.api,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.ui.len() + self.api.len()
+    }
+}
+
+/// メッセージの変更を1行ずつ追記するタスクの会話履歴
+///
+/// 保存のたびに前回から変わったメッセージだけを書き込み、追記した行がメッセージの数に比べて
+/// 多くなったら現在の内容だけに書き直す。
+#[derive(Debug)]
+pub struct HistoryLog {
+    path: PathBuf,
+    persisted: Option<Persisted>,
+    /// ファイルの行数
+    records: usize,
+    /// ファイルが改行で終わっていない（書き込み途中で終わった行の後に追記しない）
+    needs_newline: bool,
+}
+
+impl HistoryLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            persisted: None,
+            records: 0,
+            needs_newline: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// `items` を `kind` のメッセージとして保存する（変更がなければ何も書き込まない）
+    pub async fn save<T: Serialize>(&mut self, kind: HistoryKind, items: &[T]) -> Result<()> {
+        if self.persisted.is_none() {
+            self.load_persisted().await?;
+        }
+        let ts = now_ms();
+        let mut records = Vec::new();
+        let mut hashes = Vec::with_capacity(items.len());
+        {
+            let persisted = self.persisted.as_mut().expect("loaded above").get_mut(kind);
+            if persisted.len() > items.len() {
+                records.push(HistoryRecord {
+                    kind,
+                    index: items.len(),
+                    ts,
+                    data: None,
+                });
+            }
+            for (index, item) in items.iter().enumerate() {
+                let data = serde_json::to_value(item)?;
+                let hash = content_hash(&data);
+                if persisted.get(index) != Some(&hash) {
+                    records.push(HistoryRecord {
+                        kind,
+                        index,
+                        ts,
+                        data: Some(data),
+                    });
+                }
+                hashes.push(hash);
+            }
+            *persisted = hashes;
+        }
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines = String::new();
+        if std::mem::take(&mut self.needs_newline) {
+            lines.push('\n');
+        }
+        for record in &records {
+            lines.push_str(&serde_json::to_string(record)?);
+            lines.push('\n');
+        }
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(lines.as_bytes()).await?;
+        file.sync_data().await?;
+        self.records += records.len();
+
+        let live = self.persisted.as_ref().map_or(0, Persisted::len);
+        if self.records > live * 2 + 64 {
+            self.compact().await?;
+        }
+        Ok(())
+    }
+
+    /// `kind` のメッセージのうち `offset` 番目から最大 `limit` 件を読み込む
+    pub async fn read_page<T: DeserializeOwned>(
+        &self,
+        kind: HistoryKind,
+        offset: usize,
+        limit: usize,
+    ) -> Result<HistoryPage<T>> {
+        let end = offset.saturating_add(limit);
+        let mut total = 0;
+        let mut page: Vec<Option<Value>> = Vec::new();
+        self.replay(|record| {
+            if record.kind != kind {
+                return;
+            }
+            match record.data {
+                Some(data) => {
+                    total = total.max(record.index + 1);
+                    if (offset..end).contains(&record.index) {
+                        let slot = record.index - offset;
+                        if page.len() <= slot {
+                            page.resize(slot + 1, None);
+                        }
+                        page[slot] = Some(data);
+                    }
+                }
+                None => {
+                    total = total.min(record.index);
+                    page.truncate(record.index.saturating_sub(offset));
+                }
+            }
+        })
+        .await?;
+        let items = page
+            .into_iter()
+            .flatten()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<T>, _>>()
+            .with_context(|| format!("Invalid message in {}", self.path.display()))?;
+        Ok(HistoryPage { items, total })
+    }
+
+    /// `kind` のすべてのメッセージ
+    pub async fn read_all<T: DeserializeOwned>(&self, kind: HistoryKind) -> Result<Vec<T>> {
+        Ok(self.read_page(kind, 0, usize::MAX).await?.items)
+    }
+
+    pub async fn exists(&self) -> bool {
+        tokio::fs::metadata(&self.path).await.is_ok()
+    }
+
+    /// ファイルの行を順に `f` に渡す（書き込み途中で終わった最後の行は無視する）
+    async fn replay(&self, mut f: impl FnMut(HistoryRecord)) -> Result<usize> {
+        let file = match tokio::fs::File::open(&self.path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut lines = BufReader::new(file).lines();
+        let mut count = 0;
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(record) => f(record),
+                Err(_) => continue,
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// ファイルから保存済みのメッセージのハッシュを復元する
+    async fn load_persisted(&mut self) -> Result<()> {
+        let mut persisted = Persisted::default();
+        self.records = self
+            .replay(|record| {
+                let hashes = persisted.get_mut(record.kind);
+                match record.data {
+                    Some(data) => {
+                        if hashes.len() <= record.index {
+                            hashes.resize(record.index + 1, 0);
+                        }
+                        hashes[record.index] = content_hash(&data);
+                    }
+                    None => hashes.truncate(record.index),
+                }
+            })
+            .await?;
+        self.persisted = Some(persisted);
+        self.needs_newline = match tokio::fs::read(&self.path).await {
+            Ok(bytes) => bytes.last().is_some_and(|&byte| byte != b'\n'),
+            Err(_) => false,
+        };
+        Ok(())
+    }
+
+    /// 現在のメッセージだけのファイルに書き直す
+    async fn compact(&mut self) -> Result<()> {
+        let mut contents = String::new();
+        let mut records = 0;
+        let ts = now_ms();
+        for kind in [HistoryKind::Ui, HistoryKind::Api] {
+            let items: Vec<Value> = self.read_all(kind).await?;
+            for (index, data) in items.into_iter().enumerate() {
+                contents.push_str(&serde_json::to_string(&HistoryRecord {
+                    kind,
+                    index,
+                    ts,
+                    data: Some(data),
+                })?);
+                contents.push('\n');
+                records += 1;
+            }
+        }
+        write_atomic(&self.path, contents.as_bytes()).await?;
+        self.records = records;
+        Ok(())
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// 内容のハッシュ（FNV-1a）
+fn content_hash(data: &Value) -> u64 {
+    data.to_string()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn line_count(path: &Path) -> usize {
+        std::fs::read_to_string(path).unwrap().lines().count()
+    }
+
+    #[tokio::test]
+    async fn test_history_log_appends_changes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(HISTORY_LOG_FILE_NAME);
+        let mut log = HistoryLog::new(&path);
+
+        log.save(HistoryKind::Ui, &["a", "b"]).await.unwrap();
+        log.save(HistoryKind::Api, &[1, 2, 3]).await.unwrap();
+        assert_eq!(line_count(&path), 5);
+        // 変わったメッセージと追加したメッセージだけを追記する
+        log.save(HistoryKind::Ui, &["a", "B", "c"]).await.unwrap();
+        assert_eq!(line_count(&path), 7);
+        log.save(HistoryKind::Ui, &["a", "B", "c"]).await.unwrap();
+        assert_eq!(line_count(&path), 7);
+        log.save(HistoryKind::Api, &[1]).await.unwrap();
+        assert_eq!(line_count(&path), 8);
+
+        assert_eq!(
+            log.read_all::<String>(HistoryKind::Ui).await.unwrap(),
+            vec!["a", "B", "c"]
+        );
+        assert_eq!(
+            log.read_all::<i32>(HistoryKind::Api).await.unwrap(),
+            vec![1]
+        );
+        assert_eq!(
+            log.read_page::<String>(HistoryKind::Ui, 1, 1)
+                .await
+                .unwrap(),
+            HistoryPage {
+                items: vec!["B".to_string()],
+                total: 3
+            }
+        );
+        assert_eq!(
+            log.read_page::<String>(HistoryKind::Ui, 5, 10)
+                .await
+                .unwrap()
+                .items,
+            Vec::<String>::new()
+        );
+
+        // 別のインスタンスで続きから追記する
+        let mut log = HistoryLog::new(&path);
+        log.save(HistoryKind::Ui, &["a", "B", "c", "d"])
+            .await
+            .unwrap();
+        assert_eq!(line_count(&path), 9);
+
+        // 書き込み途中で終わった行は無視する
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        std::io::Write::write_all(&mut file, br#"{"kind":"ui","ind"#).unwrap();
+        assert_eq!(
+            log.read_all::<String>(HistoryKind::Ui).await.unwrap(),
+            vec!["a", "B", "c", "d"]
+        );
+        let mut log = HistoryLog::new(&path);
+        log.save(HistoryKind::Ui, &["a", "B", "c", "d", "e"])
+            .await
+            .unwrap();
+        assert_eq!(
+            log.read_all::<String>(HistoryKind::Ui).await.unwrap(),
+            vec!["a", "B", "c", "d", "e"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_history_log_compacts() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(HISTORY_LOG_FILE_NAME);
+        let mut log = HistoryLog::new(&path);
+        for i in 0..100 {
+            log.save(HistoryKind::Ui, &[format!("partial {}", i)])
+                .await
+                .unwrap();
+        }
+        assert!(line_count(&path) <= 66);
+        assert_eq!(
+            log.read_all::<String>(HistoryKind::Ui).await.unwrap(),
+            vec!["partial 99"]
+        );
+    }
+}
@@ -0,0 +1,33 @@
+mod verify;
+
+pub use verify::{VerifyConfig, VerifyHook};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::fmt::Debug;
+
+use crate::cline::ToolResponse;
+
+/// タスクの開始・ツールの実行・完了時に呼ばれるフック
+///
+/// 完了後にリンターやテストを自動で実行し、失敗をモデルに伝えてタスクを続けるために使う。
+/// フックのエラーはログに記録し、タスクは続行する。
+#[async_trait]
+pub trait TaskHook: Debug + Send + Sync {
+    /// タスクの開始時（最初のリクエストの前）
+    async fn on_task_start(&self, _task: Option<&str>) -> Result<()> {
+        Ok(())
+    }
+
+    /// ツールの実行後
+    async fn on_tool_result(&self, _tool: &str, _response: &ToolResponse) -> Result<()> {
+        Ok(())
+    }
+
+    /// `attempt_completion` の後
+    ///
+    /// `Some` を返した場合は、その内容をユーザーのフィードバックとして送りタスクを続ける。
+    async fn on_completion(&self, _result: Option<&str>) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
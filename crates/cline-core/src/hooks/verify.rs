@@ -0,0 +1,194 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::process::Command;
+
+use super::TaskHook;
+
+/// 失敗を伝えてタスクを続ける既定の最大回数
+pub const DEFAULT_VERIFY_MAX_RETRIES: usize = 3;
+/// フィードバックに含める出力の既定の最大行数
+pub const DEFAULT_VERIFY_OUTPUT_LINES: usize = 50;
+/// 失敗を示す行の後に含める行数
+const CONTEXT_LINES: usize = 2;
+
+lazy_static! {
+    static ref FAILURE_LINE: Regex =
+        Regex::new(r"(?i)\b(error|failed|failures?|panicked|assertion)\b").unwrap();
+}
+
+/// 完了後に実行する検証コマンドの設定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyConfig {
+    /// シェルで実行するコマンド（`cargo test` など）
+    pub command: String,
+    /// 失敗を伝えてタスクを続ける最大回数（超えた場合は失敗のまま完了する）
+    pub max_retries: usize,
+    /// フィードバックに含める出力の最大行数
+    pub max_output_lines: usize,
+}
+
+impl VerifyConfig {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            max_retries: DEFAULT_VERIFY_MAX_RETRIES,
+            max_output_lines: DEFAULT_VERIFY_OUTPUT_LINES,
+        }
+    }
+}
+
+/// `attempt_completion` の後に検証コマンドを実行し、失敗した場合は出力の要約をフィードバックにするフック
+#[derive(Debug)]
+pub struct VerifyHook {
+    config: VerifyConfig,
+    cwd: PathBuf,
+    /// 失敗を伝えた回数（タスクの開始時に0に戻す）
+    retries: AtomicUsize,
+}
+
+impl VerifyHook {
+    pub fn new(config: VerifyConfig, cwd: PathBuf) -> Self {
+        Self {
+            config,
+            cwd,
+            retries: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &VerifyConfig {
+        &self.config
+    }
+
+    /// コマンドを実行し、失敗した場合は終了コードと出力を返す
+    async fn run(&self) -> Result<Option<(Option<i32>, String)>> {
+        let mut command = if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.arg("/C");
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.arg("-c");
+            command
+        };
+        let output = command
+            .arg(&self.config.command)
+            .current_dir(&self.cwd)
+            // タスクの中断で待機をやめた場合もプロセスを残さない
+            .kill_on_drop(true)
+            .output()
+            .await?;
+        if output.status.success() {
+            return Ok(None);
+        }
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(Some((output.status.code(), text)))
+    }
+}
+
+#[async_trait]
+impl TaskHook for VerifyHook {
+    async fn on_task_start(&self, _task: Option<&str>) -> Result<()> {
+        self.retries.store(0, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn on_completion(&self, _result: Option<&str>) -> Result<Option<String>> {
+        let Some((code, output)) = self.run().await? else {
+            return Ok(None);
+        };
+        let attempt = self.retries.fetch_add(1, Ordering::SeqCst) + 1;
+        if attempt > self.config.max_retries {
+            return Ok(None);
+        }
+        let code = code.map_or_else(
+            || "terminated by signal".to_string(),
+            |code| format!("exit code {}", code),
+        );
+        Ok(Some(format!(
+            "The verification command `{}` failed ({}, attempt {} of {}):\n```\n{}\n```\nFix the failures, then attempt completion again.",
+            self.config.command,
+            code,
+            attempt,
+            self.config.max_retries,
+            summarize_output(&output, self.config.max_output_lines)
+        )))
+    }
+}
+
+/// 失敗を示す行とその後の数行を抜き出す（見つからない場合は末尾の行）
+pub fn summarize_output(output: &str, max_lines: usize) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    let mut selected = vec![false; lines.len()];
+    for (index, line) in lines.iter().enumerate() {
+        if FAILURE_LINE.is_match(line) {
+            let end = (index + CONTEXT_LINES + 1).min(lines.len());
+            selected[index..end].iter_mut().for_each(|s| *s = true);
+        }
+    }
+    let mut relevant: Vec<&str> = lines
+        .iter()
+        .zip(&selected)
+        .filter(|(_, selected)| **selected)
+        .map(|(line, _)| *line)
+        .collect();
+    if relevant.is_empty() {
+        relevant = lines[lines.len().saturating_sub(max_lines)..].to_vec();
+    }
+    let omitted = relevant.len().saturating_sub(max_lines);
+    relevant.truncate(max_lines);
+    let mut summary = relevant.join("\n");
+    if omitted > 0 {
+        summary.push_str(&format!("\n... ({} more lines)", omitted));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_summarize_output() {
+        let output = "   Compiling app v0.1.0\nrunning 2 tests\ntest a ... ok\ntest b ... FAILED\n\nfailures:\n---- b stdout ----\nthread 'b' panicked at src/lib.rs:3:5\nleft: 1\nright: 2\nnote: run with RUST_BACKTRACE=1";
+        assert_eq!(
+            summarize_output(output, 50),
+            "test b ... FAILED\n\nfailures:\n---- b stdout ----\nthread 'b' panicked at src/lib.rs:3:5\nleft: 1\nright: 2"
+        );
+        assert_eq!(
+            summarize_output(output, 2),
+            "test b ... FAILED\n\n... (5 more lines)"
+        );
+        assert_eq!(summarize_output("one\ntwo\nthree", 2), "two\nthree");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_verify_hook_retries_until_green() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let hook = VerifyHook::new(
+            VerifyConfig {
+                max_retries: 2,
+                ..VerifyConfig::new("test -f fixed || { echo 'error: not fixed'; exit 3; }")
+            },
+            temp_dir.path().to_path_buf(),
+        );
+
+        let feedback = hook.on_completion(None).await.unwrap().unwrap();
+        assert!(feedback.contains("failed (exit code 3, attempt 1 of 2)"));
+        assert!(feedback.contains("error: not fixed"));
+        assert!(hook.on_completion(None).await.unwrap().is_some());
+        // 上限に達した場合は失敗のまま完了する
+        assert_eq!(hook.on_completion(None).await.unwrap(), None);
+
+        hook.on_task_start(None).await.unwrap();
+        assert!(hook.on_completion(None).await.unwrap().is_some());
+        std::fs::write(temp_dir.path().join("fixed"), "").unwrap();
+        assert_eq!(hook.on_completion(None).await.unwrap(), None);
+    }
+}
@@ -1,11 +1,46 @@
+mod assistant_message;
+mod budget;
 mod cline;
+mod context;
+mod error;
+mod export;
+mod history;
+mod hooks;
 pub mod mentions;
+mod policy;
 mod prompts;
+mod sandbox;
 pub mod services;
 mod shared;
+mod state;
+mod stats;
+mod storage;
+pub mod tools;
 
-pub use cline::Cline;
+pub use assistant_message::{parse_suggestions, ToolCallFormat, ToolUse};
+pub use budget::{BudgetApprover, BudgetLimit, BudgetRemaining, BudgetUsage, TaskBudget};
+pub use cline::{
+    AskResponse, Cline, EditorInfoProvider, QueuedMessage, TaskAbortHandle, ToolApproval,
+    ToolResponse, UserMessageQueue,
+};
+pub use context::{prune_stale_file_reads, PruneStats, STALE_READ_PLACEHOLDER};
+pub use error::{ClineError, Result};
+pub use export::TaskExport;
+pub use history::{HistoryKind, HistoryLog, HistoryPage, HistoryStorage, HISTORY_LOG_FILE_NAME};
+pub use hooks::{TaskHook, VerifyConfig, VerifyHook};
+pub use policy::{Policy, DEFAULT_POLICY_PATH, POLICY_PATH_ENV};
+pub use sandbox::{
+    validate_disjoint_paths, validate_workspace_roots, OutsideWorkspaceApprover, WorkspaceRoot,
+    WorkspaceSandbox,
+};
+pub use shared::message::{ClineAsk, ClineMessage, ClineSay, ExtensionState};
+pub use shared::message_store::{MessageEvent, MessageKind, MessageListener, MessageStore};
 pub use shared::modes::{
-    get_mode_by_slug, get_role_definition, CustomModePrompts, Mode, ModeConfig, PromptComponent,
-    DEFAULT_MODE_SLUG, MODES,
+    get_mode_by_slug, get_role_definition, is_tool_allowed_for_mode, CustomModePrompts, Mode,
+    ModeConfig, PromptComponent, ToolGroup, ALWAYS_AVAILABLE_TOOLS, DEFAULT_MODE_SLUG, MODES,
+    TOOL_GROUPS,
 };
+pub use state::{JsonFileStateStore, StateStore, TaskHistory, STATE_FILE_NAME};
+pub use stats::{TaskStats, ToolOutcome, ToolStats, TASK_STATS_FILE_NAME};
+pub use storage::StoragePaths;
+pub use tools::{CustomTool, ToolSettings};
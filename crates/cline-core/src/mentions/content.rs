@@ -1,78 +1,279 @@
 use anyhow::Result;
-use std::path::Path;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::fs;
 
 use crate::services::browser::BrowserSession;
-use crate::services::diagnostics::DiagnosticsProvider;
+use crate::services::diagnostics::{DiagnosticsFilter, DiagnosticsProvider};
+use crate::services::fs::{read_file_text, DEFAULT_MAX_READ_BYTES};
 use crate::services::git::GitService;
+use crate::services::terminal::{process_terminal_output, TerminalManager};
 
-/// ファイルまたはフォルダの内容を取得
-pub async fn get_file_or_folder_content(
-    workspace_path: &Path,
-    mention_path: &str,
-) -> Result<String> {
-    let abs_path = workspace_path.join(mention_path);
+use super::folder::{latest_modified, render_folder, FolderOptions};
 
-    let metadata = fs::metadata(&abs_path).await?;
-    if metadata.is_dir() {
-        let mut entries = fs::read_dir(&abs_path).await?;
-        let mut folder_content = String::new();
-        let mut file_contents = Vec::new();
-        let mut entry_count = 0;
-
-        while let Some(entry) = entries.next_entry().await? {
-            let file_type = entry.file_type().await?;
-            let name = entry.file_name();
-            let name_str = name.to_string_lossy();
-
-            // ツリー表示のためのプレフィックス
-            let line_prefix = "├── ";
-
-            if file_type.is_file() {
-                folder_content.push_str(&format!("{}{}\n", line_prefix, name_str));
-
-                // ファイルの内容を取得（バイナリファイルは除外）
-                let file_path = entry.path();
-                if let Ok(content) = fs::read_to_string(&file_path).await {
-                    let rel_path = file_path.strip_prefix(workspace_path)?.to_string_lossy();
-                    file_contents.push(format!(
-                        "<file_content path=\"{}\">\n{}\n</file_content>",
-                        rel_path, content
-                    ));
+/// メンション内容のキャッシュの既定の最大件数
+pub const DEFAULT_CACHE_CAPACITY: usize = 64;
+
+/// URLメンションのキャッシュの既定の有効期間
+pub const DEFAULT_URL_TTL: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum CacheKey {
+    Path(PathBuf),
+    Url(String),
+}
+
+#[derive(Debug, Clone)]
+enum Freshness {
+    /// 取得時点の更新日時
+    Modified(SystemTime),
+    /// 取得した時刻
+    FetchedAt(Instant),
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    content: String,
+    freshness: Freshness,
+}
+
+/// メンション内容のLRUキャッシュ
+///
+/// ファイル・フォルダは更新日時が変わるまで、URLは有効期間が切れるまで
+/// 取得済みの内容を再利用する。
+#[derive(Debug)]
+pub struct MentionCache {
+    capacity: usize,
+    url_ttl: Duration,
+    entries: HashMap<CacheKey, CacheEntry>,
+    // 先頭ほど長く使われていない
+    order: VecDeque<CacheKey>,
+}
+
+impl Default for MentionCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_CAPACITY, DEFAULT_URL_TTL)
+    }
+}
+
+impl MentionCache {
+    pub fn new(capacity: usize, url_ttl: Duration) -> Self {
+        Self {
+            capacity,
+            url_ttl,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 更新日時が一致する場合のみファイル・フォルダの内容を返す
+    pub fn get_path(&mut self, path: &Path, modified: SystemTime) -> Option<String> {
+        self.get(
+            &CacheKey::Path(path.to_path_buf()),
+            |freshness| matches!(freshness, Freshness::Modified(cached) if *cached == modified),
+        )
+    }
+
+    pub fn insert_path(&mut self, path: &Path, modified: SystemTime, content: String) {
+        self.insert(
+            CacheKey::Path(path.to_path_buf()),
+            CacheEntry {
+                content,
+                freshness: Freshness::Modified(modified),
+            },
+        );
+    }
+
+    /// 有効期間内の場合のみURLの内容を返す
+    pub fn get_url(&mut self, url: &str) -> Option<String> {
+        let ttl = self.url_ttl;
+        self.get(&CacheKey::Url(url.to_string()), |freshness| {
+            matches!(freshness, Freshness::FetchedAt(fetched_at) if fetched_at.elapsed() < ttl)
+        })
+    }
+
+    pub fn insert_url(&mut self, url: &str, content: String) {
+        self.insert(
+            CacheKey::Url(url.to_string()),
+            CacheEntry {
+                content,
+                freshness: Freshness::FetchedAt(Instant::now()),
+            },
+        );
+    }
+
+    /// 変更されたパス自体と、その親フォルダ・配下のエントリを破棄する
+    pub fn invalidate_path(&mut self, changed: &Path) {
+        let stale: Vec<CacheKey> = self
+            .entries
+            .keys()
+            .filter(|key| match key {
+                CacheKey::Path(path) => changed.starts_with(path) || path.starts_with(changed),
+                CacheKey::Url(_) => false,
+            })
+            .cloned()
+            .collect();
+        for key in stale {
+            self.remove(&key);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    fn get(&mut self, key: &CacheKey, is_fresh: impl Fn(&Freshness) -> bool) -> Option<String> {
+        let entry = self.entries.get(key)?;
+        if !is_fresh(&entry.freshness) {
+            self.remove(key);
+            return None;
+        }
+        let content = entry.content.clone();
+        self.touch(key);
+        Some(content)
+    }
+
+    fn insert(&mut self, key: CacheKey, entry: CacheEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.insert(key.clone(), entry).is_some() {
+            self.touch(&key);
+            return;
+        }
+        self.order.push_back(key);
+        while self.entries.len() > self.capacity {
+            match self.order.pop_front() {
+                Some(oldest) => {
+                    self.entries.remove(&oldest);
                 }
-            } else if file_type.is_dir() {
-                folder_content.push_str(&format!("{}{}/\n", line_prefix, name_str));
-            } else {
-                folder_content.push_str(&format!("{}{}\n", line_prefix, name_str));
+                None => break,
             }
+        }
+    }
 
-            entry_count += 1;
-            if entry_count >= 100 {
-                folder_content.push_str("\n(Directory listing truncated at 100 entries)");
-                break;
+    fn touch(&mut self, key: &CacheKey) {
+        if let Some(pos) = self.order.iter().position(|k| k == key) {
+            if let Some(key) = self.order.remove(pos) {
+                self.order.push_back(key);
             }
         }
+    }
 
-        // 最後のエントリのプレフィックスを修正
-        if let Some(last_line_pos) = folder_content.rfind("├── ") {
-            folder_content.replace_range(last_line_pos..last_line_pos + 4, "└── ");
-        }
+    fn remove(&mut self, key: &CacheKey) {
+        self.entries.remove(key);
+        self.order.retain(|k| k != key);
+    }
+}
 
-        // ファイル内容を追加
-        if !file_contents.is_empty() {
-            folder_content.push_str("\n\n");
-            folder_content.push_str(&file_contents.join("\n\n"));
+/// ワークスペースを監視し、変更されたパスのキャッシュを破棄する
+///
+/// 返されたウォッチャーを破棄すると監視は停止する。
+pub fn watch_mention_cache(
+    cache: Arc<Mutex<MentionCache>>,
+    workspace_path: &Path,
+) -> Result<RecommendedWatcher> {
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            if event.kind.is_access() {
+                return;
+            }
+            if let Ok(mut cache) = cache.lock() {
+                for path in &event.paths {
+                    cache.invalidate_path(path);
+                }
+            }
         }
+    })?;
+    watcher.watch(workspace_path, RecursiveMode::Recursive)?;
+    Ok(watcher)
+}
+
+/// キャッシュを利用してファイルまたはフォルダの内容を取得
+pub async fn get_cached_file_or_folder_content(
+    workspace_path: &Path,
+    mention_path: &str,
+    options: &FolderOptions,
+    cache: Option<&Mutex<MentionCache>>,
+) -> Result<String> {
+    let Some(cache) = cache else {
+        return get_file_or_folder_content(workspace_path, mention_path, options).await;
+    };
 
-        Ok(folder_content)
+    let abs_path = workspace_path.join(mention_path);
+    let modified = {
+        let abs_path = abs_path.clone();
+        let options = *options;
+        tokio::task::spawn_blocking(move || latest_modified(&abs_path, &options)).await??
+    };
+    let cached = cache.lock().unwrap().get_path(&abs_path, modified);
+    if let Some(content) = cached {
+        return Ok(content);
+    }
+
+    let content = get_file_or_folder_content(workspace_path, mention_path, options).await?;
+    cache
+        .lock()
+        .unwrap()
+        .insert_path(&abs_path, modified, content.clone());
+    Ok(content)
+}
+
+/// キャッシュを利用してURLの内容を取得
+pub async fn get_cached_url_content(
+    url: &str,
+    browser_session: &mut BrowserSession,
+    cache: Option<&Mutex<MentionCache>>,
+) -> Result<String> {
+    let Some(cache) = cache else {
+        return get_url_content(url, browser_session).await;
+    };
+
+    let cached = cache.lock().unwrap().get_url(url);
+    if let Some(content) = cached {
+        return Ok(content);
+    }
+
+    let content = get_url_content(url, browser_session).await?;
+    cache.lock().unwrap().insert_url(url, content.clone());
+    Ok(content)
+}
+
+/// ファイルまたはフォルダの内容を取得
+pub async fn get_file_or_folder_content(
+    workspace_path: &Path,
+    mention_path: &str,
+    options: &FolderOptions,
+) -> Result<String> {
+    let abs_path = workspace_path.join(mention_path);
+
+    let metadata = fs::metadata(&abs_path).await?;
+    if metadata.is_dir() {
+        let workspace_path = workspace_path.to_path_buf();
+        let options = *options;
+        tokio::task::spawn_blocking(move || render_folder(&workspace_path, &abs_path, &options))
+            .await?
     } else {
-        // ファイルの場合は内容を直接返す
-        fs::read_to_string(&abs_path).await.map_err(Into::into)
+        // ファイルの場合は内容を直接返す（PDF・DOCXはテキストを抽出する）
+        read_file_text(&abs_path, DEFAULT_MAX_READ_BYTES).await
     }
 }
 
-/// URLの内容を取得
+/// URLの内容を取得（`BrowserSession::on_demand` のセッションは最初のURLで起動する）
 pub async fn get_url_content(url: &str, browser_session: &mut BrowserSession) -> Result<String> {
+    browser_session.ensure_launched().await?;
     if !browser_session.is_initialized() {
         return Err(anyhow::anyhow!("Browser not initialized"));
     }
@@ -80,8 +281,12 @@ pub async fn get_url_content(url: &str, browser_session: &mut BrowserSession) ->
 }
 
 /// ワークスペースの問題を取得
-pub async fn get_workspace_problems(diagnostics_provider: &DiagnosticsProvider) -> Result<String> {
-    Ok(diagnostics_provider.format_diagnostics())
+pub async fn get_workspace_problems(
+    diagnostics_provider: &DiagnosticsProvider,
+    filter: &DiagnosticsFilter,
+    workspace_path: &Path,
+) -> Result<String> {
+    Ok(diagnostics_provider.format_filtered_diagnostics(filter, Some(workspace_path)))
 }
 
 /// Git変更を取得
@@ -97,3 +302,153 @@ pub async fn get_git_commit_info(commit_hash: &str, workspace_path: &Path) -> Re
         .get_commit_info(workspace_path, commit_hash)
         .await
 }
+
+/// ブランチ間の比較情報を取得
+pub async fn get_git_comparison(base: &str, head: &str, workspace_path: &Path) -> Result<String> {
+    let git_service = GitService::new();
+    git_service
+        .get_branch_comparison(workspace_path, base, head)
+        .await
+}
+
+/// ターミナルの未取得の出力を取得
+pub async fn get_terminal_output(
+    terminal_manager: &mut dyn TerminalManager,
+    line_limit: usize,
+) -> Result<String> {
+    // 実行中のターミナルを先に、その後に非アクティブなターミナルを並べる
+    let mut terminals = terminal_manager.get_terminals(true);
+    for terminal in terminal_manager.get_terminals(false) {
+        if !terminals.iter().any(|t| t.id == terminal.id) {
+            terminals.push(terminal);
+        }
+    }
+
+    let mut sections = Vec::new();
+    for terminal in terminals {
+        if let Some(output) = terminal_manager.get_unretrieved_output(terminal.id) {
+            let output = process_terminal_output(&output, line_limit);
+            if output.trim().is_empty() {
+                continue;
+            }
+            sections.push(format!(
+                "## Terminal {} (`{}`)\n{}",
+                terminal.id,
+                terminal.last_command,
+                output.trim_end()
+            ));
+        }
+    }
+
+    if sections.is_empty() {
+        Ok("(No new terminal output)".to_string())
+    } else {
+        Ok(sections.join("\n\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let mut cache = MentionCache::new(2, DEFAULT_URL_TTL);
+        cache.insert_path(Path::new("/w/a"), at(1), "a".to_string());
+        cache.insert_path(Path::new("/w/b"), at(1), "b".to_string());
+        assert_eq!(
+            cache.get_path(Path::new("/w/a"), at(1)),
+            Some("a".to_string())
+        );
+
+        cache.insert_url("https://example.com", "page".to_string());
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get_path(Path::new("/w/b"), at(1)), None);
+        assert_eq!(
+            cache.get_path(Path::new("/w/a"), at(1)),
+            Some("a".to_string())
+        );
+        assert_eq!(
+            cache.get_url("https://example.com"),
+            Some("page".to_string())
+        );
+    }
+
+    #[test]
+    fn test_cache_checks_modified_time_and_ttl() {
+        let mut cache = MentionCache::new(8, Duration::ZERO);
+        cache.insert_path(Path::new("/w/a"), at(1), "old".to_string());
+        assert_eq!(cache.get_path(Path::new("/w/a"), at(2)), None);
+        assert!(cache.is_empty());
+
+        cache.insert_url("https://example.com", "page".to_string());
+        assert_eq!(cache.get_url("https://example.com"), None);
+    }
+
+    #[test]
+    fn test_invalidate_path_drops_related_entries() {
+        let mut cache = MentionCache::default();
+        cache.insert_path(Path::new("/w/src"), at(1), "folder".to_string());
+        cache.insert_path(Path::new("/w/src/lib.rs"), at(1), "file".to_string());
+        cache.insert_path(Path::new("/w/README.md"), at(1), "readme".to_string());
+        cache.insert_url("https://example.com", "page".to_string());
+
+        cache.invalidate_path(Path::new("/w/src/lib.rs"));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get_path(Path::new("/w/src"), at(1)).is_none());
+        assert!(cache.get_path(Path::new("/w/README.md"), at(1)).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cached_content_is_refreshed_after_change() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("a.txt");
+        std::fs::write(&file_path, "first").unwrap();
+        let cache = Mutex::new(MentionCache::default());
+
+        let content = get_cached_file_or_folder_content(
+            temp_dir.path(),
+            "a.txt",
+            &FolderOptions::default(),
+            Some(&cache),
+        )
+        .await
+        .unwrap();
+        assert_eq!(content, "first");
+        assert_eq!(cache.lock().unwrap().len(), 1);
+
+        // 更新日時を変えずに書き換えた場合でも、監視による破棄で再取得される
+        let modified = std::fs::metadata(&file_path).unwrap().modified().unwrap();
+        std::fs::write(&file_path, "second").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&file_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let content = get_cached_file_or_folder_content(
+            temp_dir.path(),
+            "a.txt",
+            &FolderOptions::default(),
+            Some(&cache),
+        )
+        .await
+        .unwrap();
+        assert_eq!(content, "first");
+
+        cache.lock().unwrap().invalidate_path(&file_path);
+        let content = get_cached_file_or_folder_content(
+            temp_dir.path(),
+            "a.txt",
+            &FolderOptions::default(),
+            Some(&cache),
+        )
+        .await
+        .unwrap();
+        assert_eq!(content, "second");
+    }
+}
@@ -0,0 +1,374 @@
+use anyhow::Result;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// 項目の説明に含める最大文字数
+const MAX_DOC_CHARS: usize = 4000;
+/// 子項目の一覧の説明（最初の段落）の最大文字数
+const MAX_SUMMARY_CHARS: usize = 160;
+/// HTMLのドキュメントのページを探す項目の種類
+const HTML_ITEM_KINDS: &[&str] = &[
+    "struct", "enum", "trait", "fn", "macro", "type", "constant", "union", "static", "attr",
+    "derive",
+];
+
+/// `@docs:crate::path` のドキュメントを、ワークスペースでビルドしたrustdocの出力から取得する
+///
+/// `target/doc/<crate>.json`（rustdocのJSON出力）があればAPIの一覧を要約し、
+/// なければ `target/doc` のHTMLをMarkdownにして返す。
+pub async fn get_docs_content(item_path: &str, workspace_path: &Path) -> Result<String> {
+    let segments: Vec<String> = item_path
+        .split("::")
+        .filter(|segment| !segment.is_empty())
+        .map(String::from)
+        .collect();
+    let Some(crate_name) = segments.first().map(|name| name.replace('-', "_")) else {
+        anyhow::bail!("Invalid docs mention: specify a crate such as @docs:tokio::sync");
+    };
+    let doc_dir = doc_dir(workspace_path);
+
+    let json_path = doc_dir.join(format!("{}.json", crate_name));
+    if tokio::fs::try_exists(&json_path).await.unwrap_or(false) {
+        let content = tokio::fs::read_to_string(&json_path).await?;
+        let summary = tokio::task::spawn_blocking(move || -> Result<String> {
+            let krate: Value = serde_json::from_str(&content)?;
+            summarize_rustdoc_json(&krate, &segments)
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Docs task failed: {}", e))??;
+        return Ok(summary);
+    }
+
+    let mut segments = segments;
+    segments[0] = crate_name;
+    for page in html_candidates(&doc_dir, &segments) {
+        if let Ok(html) = tokio::fs::read_to_string(&page).await {
+            return Ok(html_to_docs(&html));
+        }
+    }
+    anyhow::bail!(
+        "No documentation found for `{}` in {} (run `cargo doc` to build it)",
+        item_path,
+        doc_dir.display()
+    )
+}
+
+/// rustdocの出力先（`CARGO_TARGET_DIR` があればその下）
+fn doc_dir(workspace_path: &Path) -> PathBuf {
+    let target_dir = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("target"));
+    workspace_path.join(target_dir).join("doc")
+}
+
+/// 項目のHTMLのページの候補（モジュールの `index.html` と種類ごとのページ）
+fn html_candidates(doc_dir: &Path, segments: &[String]) -> Vec<PathBuf> {
+    let module_dir = segments
+        .iter()
+        .fold(doc_dir.to_path_buf(), |dir, segment| dir.join(segment));
+    let mut candidates = vec![module_dir.join("index.html")];
+    if let Some((name, parents)) = segments
+        .split_last()
+        .filter(|(_, parents)| !parents.is_empty())
+    {
+        let parent_dir = parents
+            .iter()
+            .fold(doc_dir.to_path_buf(), |dir, segment| dir.join(segment));
+        candidates.extend(
+            HTML_ITEM_KINDS
+                .iter()
+                .map(|kind| parent_dir.join(format!("{}.{}.html", kind, name))),
+        );
+    }
+    candidates
+}
+
+/// rustdocのHTMLの本文をMarkdownにする
+fn html_to_docs(html: &str) -> String {
+    let main = html
+        .find("<section id=\"main-content\"")
+        .and_then(|start| {
+            let end = html[start..].find("</section>")? + start;
+            Some(&html[start..end])
+        })
+        .unwrap_or(html);
+    truncate_chars(html2md::parse_html(main).trim(), MAX_DOC_CHARS)
+}
+
+/// rustdocのJSON出力から項目の説明と公開されている子項目の一覧を作る
+fn summarize_rustdoc_json(krate: &Value, segments: &[String]) -> Result<String> {
+    let index = krate
+        .get("index")
+        .and_then(Value::as_object)
+        .ok_or_else(|| anyhow::anyhow!("Invalid rustdoc JSON: missing index"))?;
+    let item = find_item(krate, segments).ok_or_else(|| {
+        anyhow::anyhow!("`{}` not found in the documentation", segments.join("::"))
+    })?;
+
+    let kind = item_kind(item);
+    let mut summary = format!("# {} ({})\n", segments.join("::"), kind);
+    if let Some(docs) = item.get("docs").and_then(Value::as_str) {
+        summary.push_str(&format!(
+            "\n{}\n",
+            truncate_chars(docs.trim(), MAX_DOC_CHARS)
+        ));
+    }
+
+    let inner = item.get("inner").and_then(|inner| inner.get(kind));
+    let ids = |key: &str| -> Vec<String> {
+        inner
+            .and_then(|inner| inner.get(key))
+            .and_then(Value::as_array)
+            .map(|ids| ids.iter().map(id_key).collect())
+            .unwrap_or_default()
+    };
+    let list = |ids: &[String]| -> Vec<String> {
+        ids.iter()
+            .filter_map(|id| index.get(id))
+            .filter(|child| child.get("name").and_then(Value::as_str).is_some())
+            .map(child_line)
+            .collect()
+    };
+
+    let (heading, children) = match kind {
+        "module" => ("Items", list(&ids("items"))),
+        "trait" => ("Required and Provided Items", list(&ids("items"))),
+        "enum" => ("Variants", list(&ids("variants"))),
+        _ => ("", Vec::new()),
+    };
+    if !children.is_empty() {
+        summary.push_str(&format!("\n## {}\n{}\n", heading, children.join("\n")));
+    }
+
+    // 型の固有のメソッドと実装しているトレイト
+    let mut methods = Vec::new();
+    let mut traits = Vec::new();
+    for impl_item in ids("impls").iter().filter_map(|id| index.get(id)) {
+        let Some(impl_inner) = impl_item.get("inner").and_then(|inner| inner.get("impl")) else {
+            continue;
+        };
+        let synthetic = impl_inner.get("is_synthetic").and_then(Value::as_bool) == Some(true)
+            || impl_inner
+                .get("blanket_impl")
+                .is_some_and(|blanket| !blanket.is_null());
+        if synthetic {
+            continue;
+        }
+        match impl_inner.get("trait").filter(|t| !t.is_null()) {
+            Some(trait_) => {
+                if let Some(name) = trait_
+                    .get("path")
+                    .or_else(|| trait_.get("name"))
+                    .and_then(Value::as_str)
+                {
+                    traits.push(name.to_string());
+                }
+            }
+            None => {
+                let items: Vec<String> = impl_inner
+                    .get("items")
+                    .and_then(Value::as_array)
+                    .map(|ids| ids.iter().map(id_key).collect())
+                    .unwrap_or_default();
+                methods.extend(list(&items));
+            }
+        }
+    }
+    if !methods.is_empty() {
+        summary.push_str(&format!("\n## Methods\n{}\n", methods.join("\n")));
+    }
+    if !traits.is_empty() {
+        traits.sort();
+        traits.dedup();
+        summary.push_str(&format!(
+            "\n## Trait Implementations\n{}\n",
+            traits.join(", ")
+        ));
+    }
+    Ok(summary.trim_end().to_string())
+}
+
+/// パスの項目を探す（見つからない場合は親の型の固有のメソッドから探す）
+fn find_item<'a>(krate: &'a Value, segments: &[String]) -> Option<&'a Value> {
+    let index = krate.get("index")?.as_object()?;
+    if segments.len() == 1 {
+        return index.get(&id_key(krate.get("root")?));
+    }
+    let found = krate
+        .get("paths")?
+        .as_object()?
+        .iter()
+        .find(|(_, summary)| {
+            summary.get("crate_id").and_then(Value::as_u64) == Some(0)
+                && summary
+                    .get("path")
+                    .and_then(Value::as_array)
+                    .is_some_and(|path| {
+                        path.len() == segments.len()
+                            && path
+                                .iter()
+                                .zip(segments)
+                                .all(|(a, b)| a.as_str() == Some(b.as_str()))
+                    })
+        })
+        .and_then(|(id, _)| index.get(id));
+    if found.is_some() {
+        return found;
+    }
+
+    let (name, parent) = segments.split_last()?;
+    let parent = find_item(krate, parent)?;
+    let impls = parent
+        .get("inner")?
+        .get(item_kind(parent))?
+        .get("impls")?
+        .as_array()?;
+    impls
+        .iter()
+        .filter_map(|id| index.get(&id_key(id))?.get("inner")?.get("impl"))
+        .filter(|impl_inner| impl_inner.get("trait").is_none_or(Value::is_null))
+        .filter_map(|impl_inner| impl_inner.get("items")?.as_array())
+        .flatten()
+        .filter_map(|id| index.get(&id_key(id)))
+        .find(|item| item.get("name").and_then(Value::as_str) == Some(name.as_str()))
+}
+
+/// 項目の種類（`inner` のキー）
+fn item_kind(item: &Value) -> &str {
+    item.get("inner")
+        .and_then(Value::as_object)
+        .and_then(|inner| inner.keys().next())
+        .map_or("item", String::as_str)
+}
+
+/// 子項目の1行の説明（`- fn channel — Creates a bounded channel.`）
+fn child_line(child: &Value) -> String {
+    let name = child
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let kind = match item_kind(child) {
+        "function" => "fn",
+        kind => kind,
+    };
+    let summary = child
+        .get("docs")
+        .and_then(Value::as_str)
+        .and_then(|docs| docs.split("\n\n").next())
+        .map(|paragraph| paragraph.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|paragraph| !paragraph.is_empty());
+    match summary {
+        Some(summary) => format!(
+            "- {} {} — {}",
+            kind,
+            name,
+            truncate_chars(&summary, MAX_SUMMARY_CHARS)
+        ),
+        None => format!("- {} {}", kind, name),
+    }
+}
+
+/// IDの表記（古い形式の文字列と新しい形式の数値の両方に対応する）
+fn id_key(id: &Value) -> String {
+    match id {
+        Value::String(id) => id.clone(),
+        id => id.to_string(),
+    }
+}
+
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn rustdoc_json() -> Value {
+        json!({
+            "root": 0,
+            "format_version": 39,
+            "index": {
+                "0": { "name": "chan", "docs": "Channels.", "inner": { "module": { "is_crate": true, "items": [1] } } },
+                "1": { "name": "mpsc", "docs": "Multi-producer channels.\n\nMore details.", "inner": { "module": { "is_crate": false, "items": [2, 3] } } },
+                "2": { "name": "Sender", "docs": "Sends values\nto a receiver.", "inner": { "struct": { "impls": [4, 5, 6] } } },
+                "3": { "name": "channel", "docs": "Creates a channel.", "inner": { "function": {} } },
+                "4": { "name": null, "inner": { "impl": { "trait": null, "items": [7], "is_synthetic": false, "blanket_impl": null } } },
+                "5": { "name": null, "inner": { "impl": { "trait": { "path": "Clone", "id": 20 }, "items": [], "is_synthetic": false, "blanket_impl": null } } },
+                "6": { "name": null, "inner": { "impl": { "trait": { "path": "Send", "id": 21 }, "items": [], "is_synthetic": true, "blanket_impl": null } } },
+                "7": { "name": "send", "docs": "Sends a value, waiting until there is capacity.", "inner": { "function": {} } }
+            },
+            "paths": {
+                "0": { "crate_id": 0, "path": ["chan"], "kind": "module" },
+                "1": { "crate_id": 0, "path": ["chan", "mpsc"], "kind": "module" },
+                "2": { "crate_id": 0, "path": ["chan", "mpsc", "Sender"], "kind": "struct" },
+                "3": { "crate_id": 0, "path": ["chan", "mpsc", "channel"], "kind": "function" }
+            }
+        })
+    }
+
+    fn segments(path: &str) -> Vec<String> {
+        path.split("::").map(String::from).collect()
+    }
+
+    #[test]
+    fn test_summarize_rustdoc_json() {
+        let krate = rustdoc_json();
+        assert_eq!(
+            summarize_rustdoc_json(&krate, &segments("chan::mpsc")).unwrap(),
+            "# chan::mpsc (module)\n\nMulti-producer channels.\n\nMore details.\n\n## Items\n- struct Sender — Sends values to a receiver.\n- fn channel — Creates a channel."
+        );
+        assert_eq!(
+            summarize_rustdoc_json(&krate, &segments("chan::mpsc::Sender")).unwrap(),
+            "# chan::mpsc::Sender (struct)\n\nSends values\nto a receiver.\n\n## Methods\n- fn send — Sends a value, waiting until there is capacity.\n\n## Trait Implementations\nClone"
+        );
+        assert!(
+            summarize_rustdoc_json(&krate, &segments("chan::mpsc::Sender::send"))
+                .unwrap()
+                .starts_with("# chan::mpsc::Sender::send (function)\n\nSends a value")
+        );
+        assert!(summarize_rustdoc_json(&krate, &segments("chan"))
+            .unwrap()
+            .contains("- module mpsc — Multi-producer channels."));
+        assert!(summarize_rustdoc_json(&krate, &segments("chan::missing")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_docs_content() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let doc_dir = temp_dir.path().join("target/doc");
+        std::fs::create_dir_all(doc_dir.join("my_crate/sync")).unwrap();
+        std::fs::write(
+            doc_dir.join("chan.json"),
+            serde_json::to_string(&rustdoc_json()).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            doc_dir.join("my_crate/sync/struct.Mutex.html"),
+            "<nav>sidebar</nav><section id=\"main-content\" class=\"content\"><h1>Struct Mutex</h1><p>A mutual exclusion primitive.</p></section>",
+        )
+        .unwrap();
+
+        let content = get_docs_content("chan::mpsc", temp_dir.path())
+            .await
+            .unwrap();
+        assert!(content.starts_with("# chan::mpsc (module)"));
+
+        let content = get_docs_content("my-crate::sync::Mutex", temp_dir.path())
+            .await
+            .unwrap();
+        assert!(content.contains("Struct Mutex"));
+        assert!(content.contains("A mutual exclusion primitive."));
+        assert!(!content.contains("sidebar"));
+
+        let error = get_docs_content("serde::Serialize", temp_dir.path())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("run `cargo doc`"));
+    }
+}
@@ -0,0 +1,296 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::services::fs::{
+    binary_placeholder, is_binary_content, is_binary_extension, too_large_placeholder,
+};
+
+/// 展開しないディレクトリ（ツリーには名前のみ表示する）
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules", "target"];
+
+/// フォルダメンションの展開方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FolderOptions {
+    /// ツリーを展開する深さ（1で直下のみ）
+    pub max_depth: usize,
+    /// ツリーに表示する最大エントリ数
+    pub max_entries: usize,
+    /// 内容を含めるファイルの最大サイズ（バイト）
+    pub max_file_bytes: u64,
+    /// 含めるファイル内容の合計の上限（バイト）
+    pub max_total_bytes: usize,
+    /// falseの場合はツリーのみを表示する
+    pub include_contents: bool,
+}
+
+impl Default for FolderOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 3,
+            max_entries: 200,
+            max_file_bytes: 100_000,
+            max_total_bytes: 300_000,
+            include_contents: true,
+        }
+    }
+}
+
+impl FolderOptions {
+    /// ファイル内容を含めず、ツリーのみを表示する
+    pub fn tree_only() -> Self {
+        Self {
+            include_contents: false,
+            ..Self::default()
+        }
+    }
+}
+
+struct FolderRenderer<'a> {
+    workspace_path: &'a Path,
+    options: &'a FolderOptions,
+    tree: String,
+    file_contents: Vec<String>,
+    entry_count: usize,
+    total_bytes: usize,
+    entries_truncated: bool,
+    contents_truncated: bool,
+}
+
+/// フォルダをツリー形式で表示し、必要に応じてファイル内容を付加する
+pub fn render_folder(
+    workspace_path: &Path,
+    abs_path: &Path,
+    options: &FolderOptions,
+) -> Result<String> {
+    let mut renderer = FolderRenderer {
+        workspace_path,
+        options,
+        tree: String::new(),
+        file_contents: Vec::new(),
+        entry_count: 0,
+        total_bytes: 0,
+        entries_truncated: false,
+        contents_truncated: false,
+    };
+    renderer.walk(abs_path, "", 1)?;
+
+    let mut result = renderer.tree.trim_end_matches('\n').to_string();
+    if renderer.entries_truncated {
+        result.push_str(&format!(
+            "\n(Directory listing truncated at {} entries)",
+            options.max_entries
+        ));
+    }
+    if renderer.contents_truncated {
+        result.push_str(&format!(
+            "\n(File contents truncated at {} bytes)",
+            options.max_total_bytes
+        ));
+    }
+    if !renderer.file_contents.is_empty() {
+        result.push_str("\n\n");
+        result.push_str(&renderer.file_contents.join("\n\n"));
+    }
+    Ok(result)
+}
+
+impl FolderRenderer<'_> {
+    fn walk(&mut self, dir: &Path, indent: &str, depth: usize) -> Result<()> {
+        let entries = sorted_entries(dir)?;
+        let count = entries.len();
+
+        for (index, entry) in entries.into_iter().enumerate() {
+            if self.entry_count >= self.options.max_entries {
+                self.entries_truncated = true;
+                return Ok(());
+            }
+            self.entry_count += 1;
+
+            let is_last = index + 1 == count;
+            let branch = if is_last { "└── " } else { "├── " };
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+
+            if file_type.is_dir() {
+                self.tree
+                    .push_str(&format!("{}{}{}/\n", indent, branch, name));
+                if depth < self.options.max_depth && !SKIPPED_DIRS.contains(&name.as_str()) {
+                    let child_indent =
+                        format!("{}{}", indent, if is_last { "    " } else { "│   " });
+                    self.walk(&path, &child_indent, depth + 1)?;
+                }
+            } else if file_type.is_file() {
+                let note = self.collect_file_content(&path, entry.metadata()?.len());
+                self.tree
+                    .push_str(&format!("{}{}{}{}\n", indent, branch, name, note));
+            } else {
+                self.tree
+                    .push_str(&format!("{}{}{}\n", indent, branch, name));
+            }
+        }
+        Ok(())
+    }
+
+    /// ファイル内容を収集し、ツリーに付ける注記を返す
+    fn collect_file_content(&mut self, path: &Path, size: u64) -> String {
+        if !self.options.include_contents {
+            return String::new();
+        }
+        if is_binary_extension(path) {
+            return format!(" {}", binary_placeholder(size));
+        }
+        if size > self.options.max_file_bytes {
+            return format!(" {}", too_large_placeholder(size));
+        }
+        if self.contents_truncated {
+            return String::new();
+        }
+
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(_) => return String::new(),
+        };
+        let content = match std::str::from_utf8(&bytes) {
+            Ok(content) if !is_binary_content(&bytes) => content,
+            _ => return format!(" {}", binary_placeholder(size)),
+        };
+        if self.total_bytes + content.len() > self.options.max_total_bytes {
+            self.contents_truncated = true;
+            return String::new();
+        }
+
+        self.total_bytes += content.len();
+        let rel_path = path
+            .strip_prefix(self.workspace_path)
+            .unwrap_or(path)
+            .to_string_lossy();
+        self.file_contents.push(format!(
+            "<file_content path=\"{}\">\n{}\n</file_content>",
+            rel_path, content
+        ));
+        String::new()
+    }
+}
+
+/// ディレクトリ → ファイルの順、名前順に並べたエントリ
+fn sorted_entries(dir: &Path) -> Result<Vec<fs::DirEntry>> {
+    let mut entries = fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| {
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        (!is_dir, entry.file_name())
+    });
+    Ok(entries)
+}
+
+/// 展開対象の範囲で最も新しい更新日時を取得する
+pub fn latest_modified(abs_path: &Path, options: &FolderOptions) -> Result<SystemTime> {
+    let metadata = fs::metadata(abs_path)?;
+    let mut latest = metadata.modified()?;
+    if metadata.is_dir() {
+        latest = latest.max(latest_modified_in(abs_path, 1, options)?);
+    }
+    Ok(latest)
+}
+
+fn latest_modified_in(dir: &Path, depth: usize, options: &FolderOptions) -> Result<SystemTime> {
+    let mut latest = SystemTime::UNIX_EPOCH;
+    for entry in fs::read_dir(dir)?.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if let Ok(modified) = metadata.modified() {
+            latest = latest.max(modified);
+        }
+        let skipped = SKIPPED_DIRS.contains(&entry.file_name().to_string_lossy().as_ref());
+        if metadata.is_dir() && depth < options.max_depth && !skipped {
+            latest = latest.max(latest_modified_in(&entry.path(), depth + 1, options)?);
+        }
+    }
+    Ok(latest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn setup_folder() -> tempfile::TempDir {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().join("src");
+        fs::create_dir_all(root.join("nested/deeper")).unwrap();
+        fs::write(root.join("lib.rs"), "pub mod nested;").unwrap();
+        fs::write(root.join("nested/mod.rs"), "mod deeper;").unwrap();
+        fs::write(root.join("nested/deeper/deep.rs"), "// deep").unwrap();
+        fs::write(root.join("image.png"), [0x89, b'P', b'N', b'G', 0, 0]).unwrap();
+        fs::write(root.join("data.bin.txt"), [b'x', 0, b'y']).unwrap();
+        temp_dir
+    }
+
+    #[test]
+    fn test_render_folder_tree() {
+        let temp_dir = setup_folder();
+        let result = render_folder(
+            temp_dir.path(),
+            &temp_dir.path().join("src"),
+            &FolderOptions {
+                max_depth: 2,
+                ..FolderOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            result,
+            "├── nested/\n\
+             │   ├── deeper/\n\
+             │   └── mod.rs\n\
+             ├── data.bin.txt (binary file, 3 B)\n\
+             ├── image.png (binary file, 6 B)\n\
+             └── lib.rs\n\
+             \n\
+             <file_content path=\"src/nested/mod.rs\">\nmod deeper;\n</file_content>\n\
+             \n\
+             <file_content path=\"src/lib.rs\">\npub mod nested;\n</file_content>"
+        );
+    }
+
+    #[test]
+    fn test_render_folder_limits() {
+        let temp_dir = setup_folder();
+        let src = temp_dir.path().join("src");
+
+        let tree_only = render_folder(temp_dir.path(), &src, &FolderOptions::tree_only()).unwrap();
+        assert!(tree_only.contains("│   │   └── deep.rs"));
+        assert!(tree_only.ends_with("└── lib.rs"));
+        assert!(!tree_only.contains("<file_content"));
+
+        let limited = render_folder(
+            temp_dir.path(),
+            &src,
+            &FolderOptions {
+                max_entries: 2,
+                max_file_bytes: 10,
+                ..FolderOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(limited.contains("(Directory listing truncated at 2 entries)"));
+        assert!(!limited.contains("lib.rs"));
+
+        let small_total = render_folder(
+            temp_dir.path(),
+            &src,
+            &FolderOptions {
+                max_total_bytes: 12,
+                ..FolderOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(small_total.contains("// deep"));
+        assert!(!small_total.contains("pub mod nested;"));
+        assert!(small_total.contains("(File contents truncated at 12 bytes)"));
+    }
+}
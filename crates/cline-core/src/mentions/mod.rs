@@ -1,29 +1,71 @@
 mod content;
+mod docs;
+mod folder;
+mod syntax;
 mod types;
 
+pub use content::{watch_mention_cache, MentionCache, DEFAULT_CACHE_CAPACITY, DEFAULT_URL_TTL};
+pub use folder::FolderOptions;
+pub use syntax::{MentionMatch, MentionSyntax};
 pub use types::*;
 
 use anyhow::Result;
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::path::Path;
+use std::sync::Mutex;
 
+use crate::sandbox::{WorkspaceRoot, WorkspaceSandbox};
+use crate::services::audit::{ApprovalStatus, AuditLog, AuditOperation};
 use crate::services::browser::BrowserSession;
-use crate::services::diagnostics::DiagnosticsProvider;
+use crate::services::diagnostics::{DiagnosticSeverity, DiagnosticsFilter, DiagnosticsProvider};
+use crate::services::environment::{list_workspace_files_cached, DirectoryCache};
+use crate::services::fs::{match_path, FuzzyPathMatch, MAX_FUZZY_INDEX_FILES};
+use crate::services::terminal::{TerminalManager, DEFAULT_TERMINAL_OUTPUT_LINE_LIMIT};
 
 use self::content::{
-    get_file_or_folder_content, get_git_changes, get_git_commit_info, get_url_content,
-    get_workspace_problems,
+    get_cached_file_or_folder_content, get_cached_url_content, get_git_changes,
+    get_git_commit_info, get_git_comparison, get_terminal_output, get_workspace_problems,
 };
 
 lazy_static! {
-    /// メンション検出用の正規表現
-    /// - `@/path/to/file` - ファイルパス
-    /// - `@http://...` - URL
+    /// メンション検出用の正規表現（既定の `@` 記法）
+    /// - `@path/to/file`, `@/path/to/file` - ファイルパス
+    /// - `@path/to/folder/` - フォルダパス
+    /// - `@https://...` - URL
     /// - `@problems` - ワークスペースの問題
-    /// - `@git-changes` - Git変更
-    /// - `@1234567` - Gitコミットハッシュ (7-40文字の16進数)
-    pub static ref MENTION_REGEX: Regex = Regex::new(r"@([^\s]+)").unwrap();
+    /// - `@problems:warnings:src/**` - 重大度・パスで絞り込んだ問題
+    /// - `@git` - Git変更
+    /// - `@git:1234567`, `@git:HEAD~3` - Gitコミット
+    /// - `@git-compare:main..feature` - ブランチ間の比較
+    /// - `@terminal` - ターミナルの最新の出力
+    /// - `@docs:tokio::sync::mpsc` - クレート・項目のドキュメント（`cargo doc` の出力）
+    pub static ref MENTION_REGEX: Regex = MentionSyntax::default().regex();
+}
+
+/// メンション解析時に参照する設定とサービス
+#[derive(Debug, Default)]
+pub struct MentionContext<'a> {
+    /// メンションの記法
+    pub syntax: MentionSyntax,
+    /// `@terminal` で参照するターミナル
+    pub terminal_manager: Option<&'a mut dyn TerminalManager>,
+    /// ファイル・フォルダ・URLの内容のキャッシュ
+    pub cache: Option<&'a Mutex<MentionCache>>,
+    /// フォルダメンションの展開方法
+    pub folder_options: FolderOptions,
+    /// `@problems` で参照する診断情報
+    pub diagnostics_provider: Option<&'a DiagnosticsProvider>,
+    /// `@terminal` の出力の最大行数（`None` の場合は既定値）
+    pub terminal_output_line_limit: Option<usize>,
+    /// ワークスペース外のファイル・フォルダのメンションを許可する
+    pub allow_outside_workspace: bool,
+    /// 追加のワークスペースのルート（`@name:path`・`@name:git` でそのルートを参照する）
+    pub workspace_roots: &'a [WorkspaceRoot],
+    /// URLのメンションで開いたページを記録する操作ログ
+    pub audit_log: Option<&'a AuditLog>,
+    /// 存在しないファイルのメンションを推測するためのファイル一覧のキャッシュ（`None` の場合は推測しない）
+    pub file_list_cache: Option<&'a DirectoryCache>,
 }
 
 /// メンションを解析する
@@ -32,92 +74,246 @@ pub async fn parse_mentions(
     browser_session: &mut BrowserSession,
     workspace_path: &Path,
 ) -> Result<String> {
-    let mentions = extract_mentions(text);
+    parse_mentions_with_context(
+        text,
+        browser_session,
+        workspace_path,
+        MentionContext::default(),
+    )
+    .await
+}
+
+/// 記法やターミナル、キャッシュを指定してメンションを解析する
+pub async fn parse_mentions_with_context(
+    text: &str,
+    browser_session: &mut BrowserSession,
+    workspace_path: &Path,
+    mut context: MentionContext<'_>,
+) -> Result<String> {
+    let syntax = context.syntax;
+    let mentions = syntax.find_mentions(text);
     if mentions.is_empty() {
         return Ok(text.to_string());
     }
 
-    let mut result = text.to_string();
-    let diagnostics_provider = DiagnosticsProvider::new();
-
-    // メンションを長い順にソートして、部分文字列の置換を防ぐ
-    let mut sorted_mentions = mentions;
-    sorted_mentions.sort_by_key(|b| std::cmp::Reverse(b.len()));
-
-    for mention in sorted_mentions {
-        let (mention_type, content) = if mention.starts_with("http") {
-            let content = get_url_content(&mention, browser_session).await?;
-            (MentionType::Url, content)
-        } else if mention == "#git" {
-            let content = get_git_changes(workspace_path).await?;
-            (MentionType::GitChanges, content)
-        } else if mention.starts_with("#git:") {
-            let commit_hash = mention.trim_start_matches("#git:");
-            let content = get_git_commit_info(commit_hash, workspace_path).await?;
-            (MentionType::GitCommit, content)
-        } else if mention == "#problems" {
-            let content = get_workspace_problems(&diagnostics_provider).await?;
-            (MentionType::Problems, content)
-        } else {
-            let path = mention.trim_start_matches('#');
-            let content = get_file_or_folder_content(workspace_path, path).await?;
-            if mention.ends_with('/') {
-                (MentionType::Folder, content)
-            } else {
-                (MentionType::File, content)
+    let sandbox = WorkspaceSandbox::new(workspace_path)
+        .with_roots(context.workspace_roots)
+        .with_allow_outside_workspace(context.allow_outside_workspace);
+    let empty_diagnostics = DiagnosticsProvider::new();
+    let diagnostics_provider = context.diagnostics_provider.unwrap_or(&empty_diagnostics);
+
+    // 同じメンションは一度だけ取得する
+    let mut contents: Vec<(MentionContent, String)> = Vec::new();
+    for mention in &mentions {
+        if contents.iter().any(|(c, _)| c.value == mention.value) {
+            continue;
+        }
+        // `name:` で始まるメンションは追加のルートで解決する
+        let (root_name, root_path, value) = sandbox.split_root(&mention.value);
+        let root_sandbox;
+        let (mention_root, mention_sandbox) = match root_name {
+            Some(_) => {
+                root_sandbox = WorkspaceSandbox::new(root_path)
+                    .with_allow_outside_workspace(context.allow_outside_workspace);
+                (root_path, &root_sandbox)
             }
+            None => (workspace_path, &sandbox),
         };
+        let resolved = resolve_mention(
+            value,
+            browser_session,
+            mention_root,
+            mention_sandbox,
+            diagnostics_provider,
+            context.terminal_manager.as_deref_mut(),
+            context
+                .terminal_output_line_limit
+                .unwrap_or(DEFAULT_TERMINAL_OUTPUT_LINE_LIMIT),
+            context.cache,
+            &context.folder_options,
+            context.file_list_cache,
+        )
+        .await;
+        if let (Some(audit_log), true) = (context.audit_log, is_url(value)) {
+            audit_log.record(
+                AuditOperation::BrowserNavigate,
+                value,
+                ApprovalStatus::AutoApproved,
+                resolved.is_ok(),
+            );
+        }
+        let (mention_type, content) = resolved?;
+        contents.push((
+            MentionContent {
+                mention_type,
+                value: mention.value.clone(),
+                description: None,
+            },
+            content,
+        ));
+    }
 
-        let mention_content = MentionContent {
-            mention_type,
-            value: mention.clone(),
-            description: None,
-        };
+    // メンションを置換
+    let mut result = String::new();
+    let mut last_end = 0;
+    for mention in &mentions {
+        result.push_str(&syntax.unescape(&text[last_end..mention.start]));
+        result.push_str(&format!(
+            "{} (see below for content)",
+            syntax.format(&mention.value)
+        ));
+        last_end = mention.end;
+    }
+    result.push_str(&syntax.unescape(&text[last_end..]));
+
+    // コンテンツを追加
+    for (mention_content, content) in contents {
+        result.push_str(&format!(
+            "\n\n{}\n{}\n",
+            syntax.format(&mention_content.value),
+            content
+        ));
+    }
 
-        // メンションを置換
-        let pattern = regex::escape(&mention);
-        let re = Regex::new(&pattern).unwrap();
-        result = re
-            .replace_all(&result, format!("{} (see below for content)", mention))
-            .to_string();
+    Ok(result)
+}
 
-        // コンテンツを追加
-        result.push_str(&format!("\n\n{}\n{}\n", mention_content.value, content));
+fn is_url(mention: &str) -> bool {
+    mention.starts_with("http://") || mention.starts_with("https://")
+}
+
+/// プレフィックスを除いたメンションの値から内容を取得する
+#[allow(clippy::too_many_arguments)]
+async fn resolve_mention(
+    mention: &str,
+    browser_session: &mut BrowserSession,
+    workspace_path: &Path,
+    sandbox: &WorkspaceSandbox,
+    diagnostics_provider: &DiagnosticsProvider,
+    terminal_manager: Option<&mut dyn TerminalManager>,
+    terminal_output_line_limit: usize,
+    cache: Option<&Mutex<MentionCache>>,
+    folder_options: &FolderOptions,
+    file_list_cache: Option<&DirectoryCache>,
+) -> Result<(MentionType, String)> {
+    if is_url(mention) {
+        let content = get_cached_url_content(mention, browser_session, cache).await?;
+        Ok((MentionType::Url, content))
+    } else if mention == "git" {
+        let content = get_git_changes(workspace_path).await?;
+        Ok((MentionType::GitChanges, content))
+    } else if let Some(range) = mention.strip_prefix("git-compare:") {
+        let (base, head) = parse_compare_range(range)?;
+        let content = get_git_comparison(base, head, workspace_path).await?;
+        Ok((MentionType::GitCompare, content))
+    } else if let Some(commit_hash) = mention.strip_prefix("git:") {
+        let content = get_git_commit_info(commit_hash, workspace_path).await?;
+        Ok((MentionType::GitCommit, content))
+    } else if let Some(item_path) = mention.strip_prefix("docs:") {
+        let content = docs::get_docs_content(item_path, workspace_path).await?;
+        Ok((MentionType::Docs, content))
+    } else if mention == "terminal" {
+        let terminal_manager =
+            terminal_manager.ok_or_else(|| anyhow::anyhow!("Terminal manager not initialized"))?;
+        let content = get_terminal_output(terminal_manager, terminal_output_line_limit).await?;
+        Ok((MentionType::Terminal, content))
+    } else if mention == "problems" || mention.starts_with("problems:") {
+        let filter = parse_problems_filter(mention.strip_prefix("problems:").unwrap_or(""))?;
+        let content = get_workspace_problems(diagnostics_provider, &filter, workspace_path).await?;
+        Ok((MentionType::Problems, content))
+    } else {
+        let mut path = mention.trim_start_matches('/').to_string();
+        let mut note = String::new();
+        let missing = !mention.starts_with('/') && !workspace_path.join(&path).exists();
+        if let (true, Some(file_list_cache)) = (missing, file_list_cache) {
+            let files = list_workspace_files_cached(
+                workspace_path,
+                MAX_FUZZY_INDEX_FILES,
+                file_list_cache,
+                None,
+            )
+            .0;
+            match match_path(&files, &path) {
+                FuzzyPathMatch::Unique(resolved) => {
+                    note = format!("(resolved to {})\n", resolved);
+                    path = resolved;
+                }
+                FuzzyPathMatch::Ambiguous(candidates) => {
+                    let content = format!(
+                        "Error: {} does not exist. Did you mean one of these?\n{}",
+                        path,
+                        candidates
+                            .iter()
+                            .map(|candidate| format!("- {}", candidate))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    );
+                    return Ok((MentionType::File, content));
+                }
+                FuzzyPathMatch::NotFound => {}
+            }
+        }
+        sandbox.check(&path)?;
+        let content =
+            get_cached_file_or_folder_content(workspace_path, &path, folder_options, cache).await?;
+        let content = note + &content;
+        if mention.ends_with('/') {
+            Ok((MentionType::Folder, content))
+        } else {
+            Ok((MentionType::File, content))
+        }
     }
+}
 
-    Ok(result)
+/// `base..head` 形式の範囲を分解する（headを省略した場合はHEAD）
+fn parse_compare_range(range: &str) -> Result<(&str, &str)> {
+    let (base, head) = range
+        .split_once("..")
+        .ok_or_else(|| anyhow::anyhow!("Invalid git compare range: {}", range))?;
+    let head = head.trim_start_matches('.');
+    if base.is_empty() {
+        return Err(anyhow::anyhow!("Invalid git compare range: {}", range));
+    }
+    Ok((base, if head.is_empty() { "HEAD" } else { head }))
 }
 
-/// メンションを抽出する
-fn extract_mentions(text: &str) -> Vec<String> {
-    let mut mentions = Vec::new();
-
-    // メンションパターンを定義
-    let mention_patterns = [
-        // ハッシュで始まるメンション
-        Regex::new(r"#[^\s]+").unwrap(),
-        // HTTPで始まるメンション
-        Regex::new(r"https?://[^\s]+").unwrap(),
-    ];
-
-    for pattern in &mention_patterns {
-        for capture in pattern.find_iter(text) {
-            mentions.push(capture.as_str().to_string());
+/// `errors`, `warnings`, `all` とパスのglobを `:` 区切りで指定した絞り込み条件を解析する
+fn parse_problems_filter(args: &str) -> Result<DiagnosticsFilter> {
+    let mut filter = DiagnosticsFilter::default();
+    for arg in args.split(':').filter(|arg| !arg.is_empty()) {
+        match arg {
+            "errors" => filter.min_severity = DiagnosticSeverity::Error,
+            "warnings" => filter.min_severity = DiagnosticSeverity::Warning,
+            "all" => filter.min_severity = DiagnosticSeverity::Hint,
+            pattern if filter.path_pattern.is_none() => {
+                filter.path_pattern = Some(pattern.to_string())
+            }
+            _ => return Err(anyhow::anyhow!("Invalid problems filter: {}", args)),
         }
     }
+    Ok(filter)
+}
 
-    mentions
+/// メンションを抽出する（既定の記法、プレフィックス付き）
+#[cfg(test)]
+fn extract_mentions(text: &str) -> Vec<String> {
+    let syntax = MentionSyntax::default();
+    syntax
+        .find_mentions(text)
+        .into_iter()
+        .map(|m| syntax.format(&m.value))
+        .collect()
 }
 
 /// メンションを処理する必要があるかどうかを判定する
 pub fn should_process_mentions(text: &str) -> bool {
-    text.contains('#') && text.split_whitespace().any(|word| word.starts_with('#'))
-        || text.contains("http")
+    MentionSyntax::default().has_mentions(text)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::services::terminal::{Process, TerminalInfo};
     use std::path::PathBuf;
 
     // テスト用のヘルパー関数
@@ -129,27 +325,35 @@ mod tests {
     fn test_extract_mentions() {
         let test_cases = vec![
             (
-                "Check #src/main.rs and #tests/test.rs\nAlso #git and #git:abc123\nAnd https://example.com",
-                vec!["#src/main.rs", "#tests/test.rs", "#git", "#git:abc123", "https://example.com"],
+                "Check @src/main.rs and @tests/test.rs\nAlso @git and @git:abc123\nAnd @https://example.com",
+                vec!["@src/main.rs", "@tests/test.rs", "@git", "@git:abc123", "@https://example.com"],
             ),
             (
                 "No mentions here",
                 vec![],
             ),
             (
-                "#git #git:1234567 #problems",
-                vec!["#git", "#git:1234567", "#problems"],
+                "@git @git:1234567 @problems",
+                vec!["@git", "@git:1234567", "@problems"],
+            ),
+            (
+                "Multiple @urls @https://example1.com https://example2.com",
+                vec!["@urls", "@https://example1.com"],
+            ),
+            (
+                "Review @git-compare:main..feature/x and @git:HEAD~3.",
+                vec!["@git-compare:main..feature/x", "@git:HEAD~3"],
             ),
             (
-                "Multiple #urls https://example1.com https://example2.com",
-                vec!["#urls", "https://example1.com", "https://example2.com"],
+                "@folder/with/trailing/slash/ @file/without/slash",
+                vec!["@folder/with/trailing/slash/", "@file/without/slash"],
             ),
             (
-                "#folder/with/trailing/slash/ #file/without/slash",
-                vec!["#folder/with/trailing/slash/", "#file/without/slash"],
+                "Text with at@ but not mention, user@example.com or \\@escaped",
+                vec![],
             ),
             (
-                "Text with hash# but not mention",
+                "Legacy #src/main.rs syntax",
                 vec![],
             ),
         ];
@@ -163,13 +367,14 @@ mod tests {
     #[test]
     fn test_should_process_mentions() {
         let test_cases = vec![
-            ("Check #src/main.rs", true),
-            ("Visit https://example.com", true),
+            ("Check @src/main.rs", true),
+            ("Visit @https://example.com", true),
+            ("Visit https://example.com", false),
             ("No mentions here", false),
-            ("#git changes", true),
-            ("Multiple #mentions #here", true),
-            ("Text with hash# but not mention", false),
-            ("https://multiple.com http://urls.com", true),
+            ("@git changes", true),
+            ("Multiple @mentions @here", true),
+            ("Text with at@ but not mention", false),
+            ("\\@escaped", false),
             ("", false),
         ];
 
@@ -183,11 +388,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_compare_range() {
+        assert_eq!(
+            parse_compare_range("main..feature").unwrap(),
+            ("main", "feature")
+        );
+        assert_eq!(
+            parse_compare_range("main...feature").unwrap(),
+            ("main", "feature")
+        );
+        assert_eq!(parse_compare_range("main..").unwrap(), ("main", "HEAD"));
+        assert!(parse_compare_range("main").is_err());
+        assert!(parse_compare_range("..feature").is_err());
+    }
+
     #[tokio::test]
     async fn test_parse_mentions_with_git() {
         let workspace_path = PathBuf::from("/test/workspace");
         let mut browser_session = setup_test_browser();
-        let text = "Check #git and #git:abc123";
+        let text = "Check @git and @git:abc123";
 
         let result = parse_mentions(text, &mut browser_session, &workspace_path).await;
         assert!(
@@ -200,23 +420,84 @@ mod tests {
     async fn test_parse_mentions_with_problems() {
         let workspace_path = PathBuf::from("/test/workspace");
         let mut browser_session = setup_test_browser();
-        let text = "Check #problems";
+        let text = "Check @problems";
 
         let result = parse_mentions(text, &mut browser_session, &workspace_path).await;
         assert!(result.is_ok(), "Should succeed with empty diagnostics");
 
         let content = result.unwrap();
         assert!(
-            content.contains("Check #problems"),
+            content.contains("Check @problems"),
             "Should contain original text"
         );
     }
 
+    #[test]
+    fn test_parse_problems_filter() {
+        let filter = parse_problems_filter("").unwrap();
+        assert_eq!(filter.min_severity, DiagnosticSeverity::Error);
+        assert_eq!(filter.path_pattern, None);
+
+        let filter = parse_problems_filter("warnings:src/**").unwrap();
+        assert_eq!(filter.min_severity, DiagnosticSeverity::Warning);
+        assert_eq!(filter.path_pattern.as_deref(), Some("src/**"));
+
+        let filter = parse_problems_filter("src/**:errors").unwrap();
+        assert_eq!(filter.min_severity, DiagnosticSeverity::Error);
+        assert_eq!(filter.path_pattern.as_deref(), Some("src/**"));
+
+        assert!(parse_problems_filter("src:tests").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parse_mentions_with_filtered_problems() {
+        use crate::services::diagnostics::Diagnostic;
+
+        let workspace_path = PathBuf::from("/test/workspace");
+        let mut browser_session = setup_test_browser();
+        let mut diagnostics_provider = DiagnosticsProvider::new();
+        for (path, severity, message) in [
+            ("src/lib.rs", DiagnosticSeverity::Error, "mismatched types"),
+            ("src/lib.rs", DiagnosticSeverity::Warning, "unused import"),
+            (
+                "tests/it.rs",
+                DiagnosticSeverity::Error,
+                "unresolved import",
+            ),
+        ] {
+            diagnostics_provider.add_diagnostic(
+                workspace_path.join(path),
+                Diagnostic {
+                    severity,
+                    message: message.to_string(),
+                    source: None,
+                    line: 1,
+                },
+            );
+        }
+
+        let result = parse_mentions_with_context(
+            "Fix @problems:warnings:src/**",
+            &mut browser_session,
+            &workspace_path,
+            MentionContext {
+                diagnostics_provider: Some(&diagnostics_provider),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(result.contains("Found 1 error, 1 warning in 1 file"));
+        assert!(result.contains("## src/lib.rs"));
+        assert!(result.contains("unused import"));
+        assert!(!result.contains("unresolved import"));
+    }
+
     #[tokio::test]
     async fn test_parse_mentions_with_url() {
         let workspace_path = PathBuf::from("/test/workspace");
         let mut browser_session = setup_test_browser();
-        let text = "Check https://example.com";
+        let text = "Check @https://example.com";
 
         let result = parse_mentions(text, &mut browser_session, &workspace_path).await;
         assert!(result.is_err());
@@ -228,6 +509,277 @@ mod tests {
         );
     }
 
+    #[derive(Debug)]
+    struct FakeTerminalManager {
+        outputs: Vec<(TerminalInfo, Option<String>)>,
+    }
+
+    impl TerminalManager for FakeTerminalManager {
+        fn dispose_all(&mut self) {}
+        fn get_or_create_terminal(&mut self, _workspace_path: String) -> Result<TerminalInfo> {
+            unimplemented!()
+        }
+        fn run_command(
+            &mut self,
+            _terminal_info: TerminalInfo,
+            _command: String,
+        ) -> Result<Process> {
+            unimplemented!()
+        }
+        fn get_unretrieved_output(&mut self, terminal_id: u32) -> Option<String> {
+            self.outputs
+                .iter_mut()
+                .find(|(t, _)| t.id == terminal_id)
+                .and_then(|(_, output)| output.take())
+        }
+        fn is_process_hot(&self, _process_id: u32) -> bool {
+            false
+        }
+        fn get_terminals(&self, busy_only: bool) -> Vec<TerminalInfo> {
+            self.outputs
+                .iter()
+                .map(|(t, _)| t.clone())
+                .filter(|t| t.busy == busy_only)
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parse_mentions_with_terminal() {
+        let workspace_path = PathBuf::from("/test/workspace");
+        let mut browser_session = setup_test_browser();
+        let mut terminal_manager = FakeTerminalManager {
+            outputs: vec![
+                (
+                    TerminalInfo {
+                        id: 1,
+                        last_command: "cargo build".to_string(),
+                        busy: false,
+                    },
+                    Some("error[E0425]: cannot find value".to_string()),
+                ),
+                (
+                    TerminalInfo {
+                        id: 2,
+                        last_command: "npm run dev".to_string(),
+                        busy: true,
+                    },
+                    Some("ready on port 3000\n".to_string()),
+                ),
+            ],
+        };
+
+        let result = parse_mentions_with_context(
+            "Fix @terminal",
+            &mut browser_session,
+            &workspace_path,
+            MentionContext {
+                terminal_manager: Some(&mut terminal_manager),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(result.contains("Fix @terminal (see below for content)"));
+        assert!(result.contains("## Terminal 2 (`npm run dev`)\nready on port 3000"));
+        assert!(result.contains("## Terminal 1 (`cargo build`)\nerror[E0425]"));
+
+        // 取得済みの出力は再度含まれない
+        let result = parse_mentions_with_context(
+            "Fix @terminal",
+            &mut browser_session,
+            &workspace_path,
+            MentionContext {
+                terminal_manager: Some(&mut terminal_manager),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(result.contains("(No new terminal output)"));
+
+        // ターミナルがない場合はエラー
+        let result = parse_mentions("Fix @terminal", &mut browser_session, &workspace_path).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parse_mentions_replaces_in_place() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(temp_dir.path().join("a.txt"), "content of a").unwrap();
+        std::fs::write(temp_dir.path().join("a.txt.bak"), "backup of a").unwrap();
+        let mut browser_session = setup_test_browser();
+
+        let text = "Compare @a.txt with @a.txt.bak, then @a.txt again. Keep \\@a.txt literal.";
+        let result = parse_mentions(text, &mut browser_session, temp_dir.path())
+            .await
+            .unwrap();
+
+        assert!(result.starts_with(
+            "Compare @a.txt (see below for content) with @a.txt.bak (see below for content), then @a.txt (see below for content) again. Keep @a.txt literal."
+        ));
+        assert_eq!(result.matches("content of a").count(), 1);
+        assert_eq!(result.matches("backup of a").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_parse_mentions_outside_workspace() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let workspace_path = temp_dir.path().join("workspace");
+        std::fs::create_dir(&workspace_path).unwrap();
+        std::fs::write(temp_dir.path().join("secret.txt"), "secret").unwrap();
+        let mut browser_session = setup_test_browser();
+
+        let result =
+            parse_mentions("Read @../secret.txt", &mut browser_session, &workspace_path).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("is outside the workspace"));
+
+        let result = parse_mentions_with_context(
+            "Read @../secret.txt",
+            &mut browser_session,
+            &workspace_path,
+            MentionContext {
+                allow_outside_workspace: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(result.contains("secret"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_mentions_with_workspace_roots() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let workspace_path = temp_dir.path().join("frontend");
+        let backend_path = temp_dir.path().join("backend");
+        std::fs::create_dir_all(&workspace_path).unwrap();
+        std::fs::create_dir_all(backend_path.join("src")).unwrap();
+        std::fs::write(workspace_path.join("main.rs"), "frontend main").unwrap();
+        std::fs::write(backend_path.join("src/main.rs"), "backend main").unwrap();
+        let roots = [WorkspaceRoot::new("backend", &backend_path)];
+        let mut browser_session = setup_test_browser();
+
+        let result = parse_mentions_with_context(
+            "Compare @main.rs with @backend:/src/main.rs",
+            &mut browser_session,
+            &workspace_path,
+            MentionContext {
+                workspace_roots: &roots,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(result.contains("@backend:/src/main.rs (see below for content)"));
+        assert!(result.contains("frontend main"));
+        assert!(result.contains("backend main"));
+
+        // ルートの外には出られない
+        let result = parse_mentions_with_context(
+            "Read @backend:../frontend/main.rs",
+            &mut browser_session,
+            &workspace_path,
+            MentionContext {
+                workspace_roots: &roots,
+                ..Default::default()
+            },
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parse_mentions_with_custom_syntax() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(temp_dir.path().join("a.txt"), "content of a").unwrap();
+        let mut browser_session = setup_test_browser();
+
+        let result = parse_mentions_with_context(
+            "Check #a.txt and @a.txt",
+            &mut browser_session,
+            temp_dir.path(),
+            MentionContext {
+                syntax: MentionSyntax::new('#'),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(result.starts_with("Check #a.txt (see below for content) and @a.txt\n"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_mentions_with_cache() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(temp_dir.path().join("src")).unwrap();
+        std::fs::write(temp_dir.path().join("src/a.txt"), "content of a").unwrap();
+        let mut browser_session = setup_test_browser();
+        let cache = Mutex::new(MentionCache::default());
+
+        for _ in 0..2 {
+            let result = parse_mentions_with_context(
+                "Check @src/ and @src/a.txt",
+                &mut browser_session,
+                temp_dir.path(),
+                MentionContext {
+                    cache: Some(&cache),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+            assert_eq!(result.matches("content of a").count(), 2);
+        }
+        assert_eq!(cache.lock().unwrap().len(), 2);
+
+        // 新しいファイルが追加されたフォルダは再取得される
+        std::fs::write(temp_dir.path().join("src/b.txt"), "content of b").unwrap();
+        let result = parse_mentions_with_context(
+            "Check @src/",
+            &mut browser_session,
+            temp_dir.path(),
+            MentionContext {
+                cache: Some(&cache),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(result.contains("content of b"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_mentions_resolves_fuzzy_path() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("src/api")).unwrap();
+        std::fs::write(temp_dir.path().join("src/a.txt"), "content of a").unwrap();
+        std::fs::write(temp_dir.path().join("src/api/a.txt"), "content of api").unwrap();
+        std::fs::write(temp_dir.path().join("src/api/b.txt"), "content of b").unwrap();
+        let mut browser_session = setup_test_browser();
+        let file_list_cache = DirectoryCache::default();
+
+        let result = parse_mentions_with_context(
+            "Check @b.txt and @a.txt",
+            &mut browser_session,
+            temp_dir.path(),
+            MentionContext {
+                file_list_cache: Some(&file_list_cache),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(result.contains("(resolved to src/api/b.txt)\n"));
+        assert!(result.contains("content of b"));
+        assert!(result.contains(
+            "Error: a.txt does not exist. Did you mean one of these?\n- src/a.txt\n- src/api/a.txt"
+        ));
+    }
+
     #[tokio::test]
     async fn test_parse_mentions_without_mentions() {
         let workspace_path = PathBuf::from("/test/workspace");
@@ -0,0 +1,137 @@
+use regex::Regex;
+
+/// メンションの末尾から取り除く句読点・閉じ括弧
+const TRAILING_PUNCTUATION: &[char] =
+    &['.', ',', ';', ':', '!', '?', ')', ']', '}', '"', '\'', '`'];
+
+/// メンションの直前に置ける文字（行頭・空白・開き括弧・引用符）
+const LEADING_DELIMITERS: &str = r#"\s(\[{"'`"#;
+
+/// メンションの記法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MentionSyntax {
+    prefix: char,
+}
+
+impl Default for MentionSyntax {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_PREFIX)
+    }
+}
+
+/// テキスト中で見つかったメンション
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MentionMatch {
+    /// プレフィックスを含むメンションの開始位置（バイト）
+    pub start: usize,
+    /// メンションの終了位置（末尾の句読点は含まない）
+    pub end: usize,
+    /// プレフィックスを除いたメンションの値
+    pub value: String,
+}
+
+impl MentionSyntax {
+    pub const DEFAULT_PREFIX: char = '@';
+
+    pub fn new(prefix: char) -> Self {
+        Self { prefix }
+    }
+
+    pub fn prefix(&self) -> char {
+        self.prefix
+    }
+
+    /// メンション検出用の正規表現を作成する
+    pub fn regex(&self) -> Regex {
+        let prefix = regex::escape(&self.prefix.to_string());
+        Regex::new(&format!(r"(?:^|[{}])({}(\S+))", LEADING_DELIMITERS, prefix)).unwrap()
+    }
+
+    /// テキスト中のメンションを出現順に返す
+    ///
+    /// `\@` のようにエスケープされたもの、`user@example.com` のように
+    /// 単語の途中に現れるものはメンションとして扱わない。
+    pub fn find_mentions(&self, text: &str) -> Vec<MentionMatch> {
+        self.regex()
+            .captures_iter(text)
+            .filter_map(|captures| {
+                let mention = captures.get(1)?;
+                let value = captures
+                    .get(2)?
+                    .as_str()
+                    .trim_end_matches(TRAILING_PUNCTUATION);
+                if value.is_empty() {
+                    return None;
+                }
+                Some(MentionMatch {
+                    start: mention.start(),
+                    end: mention.start() + self.prefix.len_utf8() + value.len(),
+                    value: value.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// メンションを含むかどうかを判定する
+    pub fn has_mentions(&self, text: &str) -> bool {
+        !self.find_mentions(text).is_empty()
+    }
+
+    /// プレフィックスを付けたメンション文字列を作成する
+    pub fn format(&self, value: &str) -> String {
+        format!("{}{}", self.prefix, value)
+    }
+
+    /// エスケープされたプレフィックス（`\@`）を元に戻す
+    pub fn unescape(&self, text: &str) -> String {
+        text.replace(&format!("\\{}", self.prefix), &self.prefix.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(syntax: &MentionSyntax, text: &str) -> Vec<String> {
+        syntax
+            .find_mentions(text)
+            .into_iter()
+            .map(|m| m.value)
+            .collect()
+    }
+
+    #[test]
+    fn test_punctuation_terminates_mentions() {
+        let syntax = MentionSyntax::default();
+        assert_eq!(
+            values(
+                &syntax,
+                "See @src/lib.rs, then (@problems). Done: @git:HEAD~3!"
+            ),
+            vec!["src/lib.rs", "problems", "git:HEAD~3"]
+        );
+    }
+
+    #[test]
+    fn test_match_positions_exclude_punctuation() {
+        let syntax = MentionSyntax::default();
+        let text = "Open @test.txt.";
+        let mentions = syntax.find_mentions(text);
+        assert_eq!(mentions.len(), 1);
+        assert_eq!(&text[mentions[0].start..mentions[0].end], "@test.txt");
+    }
+
+    #[test]
+    fn test_escaped_and_embedded_prefixes_are_ignored() {
+        let syntax = MentionSyntax::default();
+        assert!(values(&syntax, r"Mail user@example.com or write \@literal").is_empty());
+        assert_eq!(syntax.unescape(r"write \@literal"), "write @literal");
+    }
+
+    #[test]
+    fn test_custom_prefix() {
+        let syntax = MentionSyntax::new('#');
+        assert_eq!(values(&syntax, "Check #git and @git"), vec!["git"]);
+        assert_eq!(syntax.format("git"), "#git");
+    }
+}
@@ -15,6 +15,12 @@ pub enum MentionType {
     GitChanges,
     /// Gitコミット
     GitCommit,
+    /// Gitブランチ比較
+    GitCompare,
+    /// ターミナルの出力
+    Terminal,
+    /// クレートのドキュメント
+    Docs,
 }
 
 /// メンションの内容
@@ -0,0 +1,163 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::Path;
+
+use crate::budget::TaskBudget;
+use crate::services::diagnostics::glob_to_regex;
+
+/// 管理者が配置するポリシーファイルの既定のパス
+pub const DEFAULT_POLICY_PATH: &str = "/etc/cline/policy.json";
+
+/// ポリシーファイルのパスを変更する環境変数
+pub const POLICY_PATH_ENV: &str = "CLINE_POLICY_FILE";
+
+/// 組織のポリシー（管理者が設定し、ユーザーの設定では緩められない）
+///
+/// 指定しない項目は制限しない。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Policy {
+    /// 使用を禁止するツール（`execute_command`・`browser_action` など）
+    pub disabled_tools: Vec<String>,
+    /// タスク全体のAPI料金の上限（USD）
+    pub max_cost: Option<f64>,
+    /// タスク全体のトークン数の上限
+    pub max_tokens: Option<u64>,
+    /// タスク全体のAPIリクエスト数の上限
+    pub max_requests: Option<u32>,
+    /// 使用を許可するAPIプロバイダ（`anthropic` など、`None` の場合はすべて）
+    pub allowed_providers: Option<Vec<String>>,
+    /// ファイルツールでアクセスを禁止するパスのglob（ワークスペースからの相対パスか絶対パス）
+    pub denied_paths: Vec<String>,
+    /// 確認なしで `fetch` を許可するホスト（`*.example.com` はサブドメインに一致する）
+    pub fetch_allowed_hosts: Vec<String>,
+}
+
+impl Policy {
+    /// `CLINE_POLICY_FILE` か既定のパスのポリシーを読み込む（ファイルがなければ制限しない）
+    pub fn load_default() -> Result<Self> {
+        match env::var(POLICY_PATH_ENV) {
+            Ok(path) if !path.is_empty() => Self::load(Path::new(&path)),
+            _ => Self::load(Path::new(DEFAULT_POLICY_PATH)),
+        }
+    }
+
+    /// ポリシーファイルを読み込む（ファイルがなければ制限しない）
+    ///
+    /// 解析できないファイルは制限を外さないようにエラーにする。
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read policy {}", path.display()))
+            }
+        };
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid policy file {}", path.display()))
+    }
+
+    pub fn is_tool_disabled(&self, tool: &str) -> bool {
+        self.disabled_tools.iter().any(|disabled| disabled == tool)
+    }
+
+    pub fn is_provider_allowed(&self, provider: &str) -> bool {
+        self.allowed_providers
+            .as_ref()
+            .is_none_or(|providers| providers.iter().any(|allowed| allowed == provider))
+    }
+
+    /// `url` のホストが確認なしで `fetch` を許可するホストに一致する
+    pub fn is_fetch_host_allowed(&self, url: &str) -> bool {
+        let Some(host) = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        else {
+            return false;
+        };
+        self.fetch_allowed_hosts.iter().any(|allowed| {
+            let allowed = allowed.to_ascii_lowercase();
+            match allowed.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|sub| sub.ends_with('.')),
+                None => host == allowed,
+            }
+        })
+    }
+
+    /// `rel_path`（ワークスペースからの相対パス）か `abs_path` が禁止されたパスに一致する
+    pub fn is_path_denied(&self, rel_path: Option<&str>, abs_path: &Path) -> bool {
+        let abs_path = abs_path.to_string_lossy().replace('\\', "/");
+        self.denied_paths.iter().any(|pattern| {
+            let regex = glob_to_regex(pattern);
+            if pattern.starts_with('/') {
+                regex.is_match(&abs_path)
+            } else {
+                rel_path.is_some_and(|rel_path| regex.is_match(rel_path))
+            }
+        })
+    }
+
+    /// タスク全体の使用量に対する予算（上限がなければ `None`）
+    pub fn budget(&self) -> Option<TaskBudget> {
+        let budget = TaskBudget {
+            max_cost: self.max_cost,
+            max_tokens: self.max_tokens,
+            max_requests: self.max_requests,
+        };
+        (budget != TaskBudget::default()).then_some(budget)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_load_policy() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("policy.json");
+        assert_eq!(Policy::load(&path).unwrap(), Policy::default());
+
+        std::fs::write(
+            &path,
+            r#"{
+                "disabledTools": ["execute_command", "browser_action"],
+                "maxCost": 5.0,
+                "allowedProviders": ["anthropic"],
+                "deniedPaths": [".env", "secrets/**", "/etc/**"],
+                "fetchAllowedHosts": ["api.example.com", "*.internal.test"]
+            }"#,
+        )
+        .unwrap();
+        let policy = Policy::load(&path).unwrap();
+        assert!(policy.is_tool_disabled("execute_command"));
+        assert!(!policy.is_tool_disabled("read_file"));
+        assert!(policy.is_provider_allowed("anthropic"));
+        assert!(!policy.is_provider_allowed("scripted"));
+        assert_eq!(
+            policy.budget(),
+            Some(TaskBudget {
+                max_cost: Some(5.0),
+                ..Default::default()
+            })
+        );
+        assert!(policy.is_path_denied(Some(".env"), Path::new("/work/.env")));
+        assert!(policy.is_path_denied(Some("secrets/prod/key.pem"), Path::new("/work/secrets")));
+        assert!(policy.is_path_denied(None, Path::new("/etc/passwd")));
+        assert!(!policy.is_path_denied(Some("src/main.rs"), Path::new("/work/src/main.rs")));
+        assert!(policy.is_fetch_host_allowed("https://API.example.com/v1/items"));
+        assert!(policy.is_fetch_host_allowed("http://docs.internal.test:8080/"));
+        assert!(!policy.is_fetch_host_allowed("http://internal.test/"));
+        assert!(!policy.is_fetch_host_allowed("https://evilinternal.test/"));
+        assert!(!policy.is_fetch_host_allowed("https://example.com/"));
+        assert!(!policy.is_fetch_host_allowed("not a url"));
+
+        std::fs::write(&path, "disabledTools = []").unwrap();
+        assert!(Policy::load(&path).is_err());
+        assert_eq!(Policy::default().budget(), None);
+    }
+}
@@ -1,6 +1,8 @@
 use std::path::Path;
 use tokio::fs;
 
+use crate::services::locale::Locale;
+
 #[allow(dead_code)]
 pub struct PreferredLanguage {
     pub preferred_language: Option<String>,
@@ -30,10 +32,7 @@ pub async fn add_custom_instructions(
 
     // Add language preference if provided
     if let Some(lang) = &options.preferred_language {
-        sections.push(format!(
-            "Language Preference:\nYou should always speak and think in the {} language.",
-            lang
-        ));
+        sections.push(Locale::from_preferred_language(lang).language_preference_section(lang));
     }
 
     // Add global instructions first
@@ -9,7 +9,9 @@ use crate::services::mcp::McpHub;
 use crate::shared::modes::{
     get_mode_by_slug, CustomModePrompts, Mode, ModeConfig, PromptComponent, MODES,
 };
+use crate::tools::{CustomTool, ToolSettings};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use std::path::Path;
 
@@ -21,6 +23,7 @@ pub async fn generate_prompt(
     context: &Path,
     cwd: &str,
     supports_computer_use: bool,
+    has_browser: bool,
     mode: Mode,
     mcp_hub: Option<&McpHub>,
     diff_strategy: Option<&dyn DiffStrategy>,
@@ -32,6 +35,8 @@ pub async fn generate_prompt(
     diff_enabled: Option<bool>,
     experiments: Option<&HashMap<String, bool>>,
     enable_mcp_server_creation: Option<bool>,
+    custom_tools: &[Arc<dyn CustomTool>],
+    tool_settings: Option<&ToolSettings>,
 ) -> Result<String, Box<dyn std::error::Error>> {
     if !context.exists() {
         return Err("Extension context is required for generating system prompt".into());
@@ -63,11 +68,14 @@ pub async fn generate_prompt(
             mode.clone(),
             cwd.to_string(),
             supports_computer_use,
+            has_browser,
             effective_diff_strategy,
             browser_viewport_size.map(|s| s.to_string()),
             mcp_hub,
             custom_mode_configs,
-            experiments
+            experiments,
+            custom_tools,
+            tool_settings
         ),
         get_tool_use_guidelines_section(),
         mcp_servers_section,
@@ -109,6 +117,7 @@ pub async fn system_prompt(
     context: &Path,
     cwd: &str,
     supports_computer_use: bool,
+    has_browser: bool,
     mcp_hub: Option<&McpHub>,
     diff_strategy: Option<&dyn DiffStrategy>,
     browser_viewport_size: Option<&str>,
@@ -120,6 +129,8 @@ pub async fn system_prompt(
     diff_enabled: Option<bool>,
     experiments: Option<&HashMap<String, bool>>,
     enable_mcp_server_creation: Option<bool>,
+    custom_tools: &[Arc<dyn CustomTool>],
+    tool_settings: Option<&ToolSettings>,
 ) -> Result<String, Box<dyn std::error::Error>> {
     if !context.exists() {
         return Err("Extension context is required for generating system prompt".into());
@@ -149,6 +160,7 @@ pub async fn system_prompt(
         context,
         cwd,
         supports_computer_use,
+        has_browser,
         current_mode.slug.clone(),
         mcp_hub,
         effective_diff_strategy,
@@ -160,6 +172,8 @@ pub async fn system_prompt(
         diff_enabled,
         experiments,
         enable_mcp_server_creation,
+        custom_tools,
+        tool_settings,
     )
     .await
 }
@@ -0,0 +1,7 @@
+use crate::prompts::tools::types::ToolArgs;
+
+/// `apply_diff` の説明（書式は差分の適用方法ごとに異なるため方法から取得する）
+pub fn get_apply_diff_description(args: &ToolArgs) -> Option<String> {
+    args.diff_strategy
+        .map(|strategy| strategy.get_tool_description(args))
+}
@@ -6,13 +6,21 @@ pub fn get_ask_followup_question_description(_args: &ToolArgs) -> String {
 Description: Ask the user a question to gather additional information needed to complete the task. This tool should be used when you encounter ambiguities, need clarification, or require more details to proceed effectively. It allows for interactive problem-solving by enabling direct communication with the user. Use this tool judiciously to maintain a balance between gathering necessary information and avoiding excessive back-and-forth.
 Parameters:
 - question: (required) The question to ask the user. This should be a clear, specific question that addresses the information you need.
+- follow_up: (optional) 2-4 suggested answers, each in its own <suggest> tag. Each suggestion must be a complete answer the user could send as is, without placeholders. The user can pick a suggestion or answer freely.
 Usage:
 <ask_followup_question>
 <question>Your question here</question>
+<follow_up>
+<suggest>Your suggested answer here</suggest>
+</follow_up>
 </ask_followup_question>
 
 Example: Requesting to ask the user for the path to the frontend-config.json file
 <ask_followup_question>
 <question>What is the path to the frontend-config.json file?</question>
+<follow_up>
+<suggest>./src/frontend-config.json</suggest>
+<suggest>./config/frontend-config.json</suggest>
+</follow_up>
 </ask_followup_question>"#.to_string()
 }
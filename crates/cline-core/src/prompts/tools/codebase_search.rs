@@ -0,0 +1,28 @@
+use crate::prompts::tools::types::ToolArgs;
+
+#[allow(dead_code)]
+pub fn get_codebase_search_description(args: &ToolArgs) -> Option<String> {
+    if !args.supports_codebase_search {
+        return None;
+    }
+
+    Some(format!(
+        r#"## codebase_search
+Description: Find code that is semantically relevant to a natural language query, even if it does not contain the exact words. Use it before search_files when you do not know where something is implemented or what it is called. Results are ranked by relevance and include the file path, line range and content of each snippet.
+Parameters:
+- query: (required) A natural language description of the code you are looking for. Prefer the user's wording.
+- path: (optional) Limit the search to a directory (relative to the current working directory {}).
+Usage:
+<codebase_search>
+<query>Your search query here</query>
+<path>Directory path here (optional)</path>
+</codebase_search>
+
+Example:
+<codebase_search>
+<query>where user authentication tokens are validated</query>
+<path>src</path>
+</codebase_search>"#,
+        args.cwd
+    ))
+}
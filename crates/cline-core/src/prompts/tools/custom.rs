@@ -0,0 +1,143 @@
+use serde_json::Value;
+
+use crate::services::anthropic::ToolDefinition;
+use crate::tools::CustomTool;
+
+/// ホストが追加したツールの説明（引数のJSON Schemaからパラメータと使い方を作る）
+pub fn get_custom_tool_description(tool: &dyn CustomTool) -> String {
+    let schema = tool.input_schema();
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .map(|required| required.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let properties = schema["properties"].as_object();
+
+    let name = tool.name();
+    let mut params = Vec::new();
+    let mut usage = vec![format!("<{}>", name)];
+    for (param, property) in properties.into_iter().flatten() {
+        let requirement = if required.contains(&param.as_str()) {
+            "required"
+        } else {
+            "optional"
+        };
+        let description = property["description"].as_str().unwrap_or_default();
+        params.push(
+            format!("- {}: ({}) {}", param, requirement, description)
+                .trim_end()
+                .to_string(),
+        );
+        let placeholder = match property["type"].as_str() {
+            Some("string") | None => format!("{} here", param),
+            Some(param_type) => format!("{} as JSON {}", param, param_type),
+        };
+        usage.push(format!("<{0}>{1}</{0}>", param, placeholder));
+    }
+
+    usage.push(format!("</{}>", name));
+    if params.is_empty() {
+        params.push("None".to_string());
+    }
+
+    format!(
+        "## {}\nDescription: {}\nParameters:\n{}\nUsage:\n{}",
+        name,
+        tool.description(),
+        params.join("\n"),
+        usage.join("\n"),
+    )
+}
+
+/// ホストが追加したツールのネイティブのツール定義
+pub fn get_custom_tool_definition(tool: &dyn CustomTool) -> ToolDefinition {
+    ToolDefinition {
+        name: tool.name().to_string(),
+        description: tool.description().to_string(),
+        input_schema: tool.input_schema(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompts::tools::types::ToolArgs;
+    use crate::prompts::tools::{get_native_tool_definitions, get_tool_descriptions_for_mode};
+    use async_trait::async_trait;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[derive(Debug)]
+    struct CreateTicket;
+
+    #[async_trait]
+    impl CustomTool for CreateTicket {
+        fn name(&self) -> &str {
+            "create_ticket"
+        }
+
+        fn description(&self) -> &str {
+            "Create a ticket in the issue tracker."
+        }
+
+        fn input_schema(&self) -> Value {
+            json!({
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string", "description": "The ticket title." },
+                    "priority": { "type": "integer" },
+                },
+                "required": ["title"],
+            })
+        }
+
+        async fn execute(&self, _input: Value) -> anyhow::Result<String> {
+            Ok("Created".to_string())
+        }
+    }
+
+    #[test]
+    fn test_get_custom_tool_description() {
+        assert_eq!(
+            get_custom_tool_description(&CreateTicket),
+            r#"## create_ticket
+Description: Create a ticket in the issue tracker.
+Parameters:
+- priority: (optional)
+- title: (required) The ticket title.
+Usage:
+<create_ticket>
+<priority>priority as JSON integer</priority>
+<title>title here</title>
+</create_ticket>"#
+        );
+    }
+
+    #[test]
+    fn test_custom_tools_in_native_definitions() {
+        let custom_tools: Vec<Arc<dyn CustomTool>> = vec![Arc::new(CreateTicket)];
+        let args = ToolArgs {
+            cwd: "/workspace".to_string(),
+            custom_tools: &custom_tools,
+            ..Default::default()
+        };
+        let tools = get_native_tool_definitions(&args);
+        let ticket = tools.last().unwrap();
+        assert_eq!(ticket.name, "create_ticket");
+        assert_eq!(ticket.input_schema["required"], json!(["title"]));
+        assert!(get_tool_descriptions_for_mode(
+            "code".to_string(),
+            "/workspace".to_string(),
+            false,
+            false,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &custom_tools,
+            None,
+        )
+        .contains("## create_ticket"));
+    }
+}
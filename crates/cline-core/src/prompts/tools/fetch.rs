@@ -0,0 +1,28 @@
+use crate::prompts::tools::types::ToolArgs;
+
+#[allow(dead_code)]
+pub fn get_fetch_description(_args: &ToolArgs) -> String {
+    r#"## fetch
+Description: Request to send an HTTP request without launching a browser, e.g. to call an API or read documentation. HTML responses are converted to Markdown and JSON responses are pretty-printed. Large responses are truncated.
+Parameters:
+- url: (required) The http or https URL to request.
+- method: (optional) GET (default) or POST.
+- headers: (optional) A JSON object of request headers, e.g. {"Authorization": "Bearer token"}.
+- body: (optional) The request body to send with a POST request.
+Usage:
+<fetch>
+<url>URL here</url>
+<method>GET or POST (optional)</method>
+<headers>JSON object of headers (optional)</headers>
+<body>Request body (optional)</body>
+</fetch>
+
+Example: Requesting to create an item through a local API
+<fetch>
+<url>http://localhost:3000/api/items</url>
+<method>POST</method>
+<headers>{"Content-Type": "application/json"}</headers>
+<body>{"name": "example"}</body>
+</fetch>"#
+        .to_string()
+}
@@ -0,0 +1,25 @@
+use crate::prompts::tools::types::ToolArgs;
+
+#[allow(dead_code)]
+pub fn get_get_page_text_description(args: &ToolArgs) -> Option<String> {
+    if !args.has_browser {
+        return None;
+    }
+
+    Some(
+        r#"## get_page_text
+Description: Request to read the main content of the browser's current page as Markdown, without a screenshot. Scripts, styles, navigation and page headers and footers are removed. Use this instead of browser_action screenshots when you only need the text of a page, e.g. to read documentation or check the output of a locally running development server. The page stays open in the browser, so you can read it again after interacting with it.
+Parameters:
+- url: (optional) A URL to open in the browser before reading, e.g. http://localhost:3000/page. Omit it to read the page that is currently open.
+Usage:
+<get_page_text>
+<url>URL to open (optional)</url>
+</get_page_text>
+
+Example: Requesting to read a page served by a local development server
+<get_page_text>
+<url>http://localhost:3000/docs</url>
+</get_page_text>"#
+            .to_string(),
+    )
+}
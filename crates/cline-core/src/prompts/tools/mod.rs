@@ -1,61 +1,86 @@
 pub mod access_mcp_resource;
+pub mod apply_diff;
 pub mod ask_followup_question;
 pub mod attempt_completion;
 pub mod browser_action;
+pub mod codebase_search;
+pub mod custom;
 pub mod execute_command;
+pub mod fetch;
+pub mod get_page_text;
 pub mod insert_content;
 pub mod list_code_definition_names;
 pub mod list_files;
+pub mod native;
 pub mod new_task;
 pub mod read_file;
 pub mod search_and_replace;
 pub mod search_files;
 pub mod switch_mode;
 pub mod types;
+pub mod update_todo_list;
 pub mod use_mcp_tool;
 pub mod write_to_file;
 
 pub use access_mcp_resource::get_access_mcp_resource_description;
+pub use apply_diff::get_apply_diff_description;
 pub use ask_followup_question::get_ask_followup_question_description;
 pub use attempt_completion::get_attempt_completion_description;
 pub use browser_action::get_browser_action_description;
+pub use codebase_search::get_codebase_search_description;
+pub use custom::get_custom_tool_description;
 pub use execute_command::get_execute_command_description;
+pub use fetch::get_fetch_description;
+pub use get_page_text::get_get_page_text_description;
 pub use insert_content::get_insert_content_description;
 pub use list_code_definition_names::get_list_code_definition_names_description;
 pub use list_files::get_list_files_description;
+pub use native::get_native_tool_definitions;
 pub use new_task::get_new_task_description;
 pub use read_file::get_read_file_description;
 pub use search_and_replace::get_search_and_replace_description;
 pub use search_files::get_search_files_description;
 pub use switch_mode::get_switch_mode_description;
-
+pub use update_todo_list::get_update_todo_list_description;
 pub use use_mcp_tool::get_use_mcp_tool_description;
 pub use write_to_file::get_write_to_file_description;
 
 use crate::prompts::tools::types::ToolArgs;
 use crate::services::diff::DiffStrategy;
 use crate::services::mcp::McpHub;
-use crate::shared::modes::{Mode, ModeConfig};
+use crate::shared::modes::{is_tool_allowed_for_mode, Mode, ModeConfig};
+use crate::tools::{CustomTool, ToolSettings};
+use std::sync::Arc;
 
 #[allow(clippy::too_many_arguments)]
 #[allow(dead_code)]
 pub fn get_tool_descriptions_for_mode(
-    _mode: Mode,
+    mode: Mode,
     cwd: String,
     supports_computer_use: bool,
+    has_browser: bool,
     diff_strategy: Option<&dyn DiffStrategy>,
     browser_viewport_size: Option<String>,
     mcp_hub: Option<&McpHub>,
-    _custom_modes: Option<&[ModeConfig]>,
-    _experiments: Option<&std::collections::HashMap<String, bool>>,
+    custom_modes: Option<&[ModeConfig]>,
+    experiments: Option<&std::collections::HashMap<String, bool>>,
+    custom_tools: &[Arc<dyn CustomTool>],
+    tool_settings: Option<&ToolSettings>,
 ) -> String {
     let args = ToolArgs {
         cwd,
         supports_computer_use,
+        has_browser,
         diff_strategy,
         browser_viewport_size,
         mcp_hub,
+        supports_codebase_search: *experiments
+            .and_then(|e| e.get("codebase_search"))
+            .unwrap_or(&false),
         tool_options: None,
+        compact: false,
+        custom_tools,
+        tool_settings,
     };
 
     let mut descriptions = Vec::new();
@@ -66,12 +91,22 @@ pub fn get_tool_descriptions_for_mode(
     }
     descriptions.push(get_read_file_description(&args));
     descriptions.push(get_write_to_file_description(&args));
+    if let Some(desc) = get_apply_diff_description(&args) {
+        descriptions.push(desc);
+    }
     descriptions.push(get_search_files_description(&args));
+    if let Some(desc) = get_codebase_search_description(&args) {
+        descriptions.push(desc);
+    }
     descriptions.push(get_list_files_description(&args));
     descriptions.push(get_list_code_definition_names_description(&args));
     if let Some(desc) = get_browser_action_description(&args) {
         descriptions.push(desc);
     }
+    if let Some(desc) = get_get_page_text_description(&args) {
+        descriptions.push(desc);
+    }
+    descriptions.push(get_fetch_description(&args));
     descriptions.push(get_ask_followup_question_description(&args));
     descriptions.push(get_attempt_completion_description(&args));
     if let Some(desc) = get_use_mcp_tool_description(&args) {
@@ -84,6 +119,62 @@ pub fn get_tool_descriptions_for_mode(
     descriptions.push(get_new_task_description(&args));
     descriptions.push(get_insert_content_description(&args));
     descriptions.push(get_search_and_replace_description(&args));
+    descriptions.push(get_update_todo_list_description(&args));
+    descriptions.extend(
+        args.custom_tools
+            .iter()
+            .map(|tool| get_custom_tool_description(tool.as_ref())),
+    );
+
+    // モードのグループにないツールは説明に含めない
+    descriptions.retain(|description| {
+        description
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix("## "))
+            .is_none_or(|name| is_tool_allowed_for_mode(name.trim(), &mode, custom_modes))
+    });
+    if let Some(settings) = tool_settings {
+        descriptions = descriptions
+            .into_iter()
+            .filter_map(|description| settings.apply_to_description(description))
+            .collect();
+    }
 
     format!("# Tools\n\n{}", descriptions.join("\n\n"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descriptions(mode: &str) -> String {
+        get_tool_descriptions_for_mode(
+            mode.to_string(),
+            "/workspace".to_string(),
+            false,
+            false,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &[],
+            None,
+        )
+    }
+
+    #[test]
+    fn test_tool_descriptions_follow_mode_groups() {
+        let code = descriptions("code");
+        assert!(code.contains("## write_to_file"));
+        assert!(code.contains("## execute_command"));
+
+        let architect = descriptions("architect");
+        assert!(architect.contains("## read_file"));
+        assert!(architect.contains("## attempt_completion"));
+        assert!(!architect.contains("## write_to_file"));
+        assert!(!architect.contains("## insert_content"));
+        assert!(!architect.contains("## execute_command"));
+    }
+}
@@ -0,0 +1,460 @@
+use serde_json::{json, Map, Value};
+
+use crate::prompts::tools::apply_diff::get_apply_diff_description;
+use crate::prompts::tools::custom::get_custom_tool_definition;
+use crate::prompts::tools::types::ToolArgs;
+use crate::services::anthropic::ToolDefinition;
+
+/// パラメータの定義（名前, JSONの型, 説明, 必須かどうか）
+type Param = (&'static str, &'static str, &'static str, bool);
+
+fn tool(name: &str, description: String, params: &[Param]) -> ToolDefinition {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for (param, param_type, param_description, is_required) in params {
+        properties.insert(
+            param.to_string(),
+            json!({ "type": param_type, "description": param_description }),
+        );
+        if *is_required {
+            required.push(param.to_string());
+        }
+    }
+
+    ToolDefinition {
+        name: name.to_string(),
+        description,
+        input_schema: json!({
+            "type": "object",
+            "properties": Value::Object(properties),
+            "required": required,
+        }),
+    }
+}
+
+/// ネイティブのツール呼び出しで送信するツール定義を取得する
+///
+/// XML形式の説明（`get_tool_descriptions_for_mode`）と同じツールを同じ条件で含める。
+/// ホストが追加したツールは組み込みのツールの後に続ける。
+/// `tool_settings` で無効にしたツールは含めず、説明の置き換えは短くした後に適用する。
+pub fn get_native_tool_definitions(args: &ToolArgs) -> Vec<ToolDefinition> {
+    let mut tools = vec![
+        tool(
+            "execute_command",
+            format!(
+                "Execute a CLI command on the system. Commands run in the current working directory: {}",
+                args.cwd
+            ),
+            &[("command", "string", "The CLI command to execute.", true)],
+        ),
+        tool(
+            "read_file",
+            "Read the contents of a file. The output includes line numbers.".to_string(),
+            &[(
+                "path",
+                "string",
+                "The path of the file to read, relative to the working directory.",
+                true,
+            )],
+        ),
+        tool(
+            "write_to_file",
+            "Write the complete content to a file, creating it and its directories if needed."
+                .to_string(),
+            &[
+                (
+                    "path",
+                    "string",
+                    "The path of the file to write to, relative to the working directory.",
+                    true,
+                ),
+                (
+                    "content",
+                    "string",
+                    "The COMPLETE intended content of the file, without line numbers.",
+                    true,
+                ),
+                (
+                    "line_count",
+                    "integer",
+                    "The number of lines in the file.",
+                    true,
+                ),
+            ],
+        ),
+    ];
+
+    // 戦略ごとの書式はXML形式の説明と同じものを渡す
+    if let Some(description) = get_apply_diff_description(args) {
+        tools.push(tool(
+            "apply_diff",
+            description,
+            &[
+                ("path", "string", "The path of the file to modify.", true),
+                ("diff", "string", "The diff defining the changes.", true),
+                (
+                    "start_line",
+                    "integer",
+                    "The line number where the diff starts.",
+                    false,
+                ),
+                (
+                    "end_line",
+                    "integer",
+                    "The line number where the diff ends.",
+                    false,
+                ),
+            ],
+        ));
+    }
+
+    tools.extend([
+        tool(
+            "search_files",
+            "Perform a recursive regex search across files in a directory.".to_string(),
+            &[
+                ("path", "string", "The directory to search in.", true),
+                (
+                    "regex",
+                    "string",
+                    "The regular expression pattern to search for (Rust regex syntax).",
+                    true,
+                ),
+                (
+                    "file_pattern",
+                    "string",
+                    "Glob pattern to filter files, e.g. '*.ts'.",
+                    false,
+                ),
+            ],
+        ),
+        tool(
+            "list_files",
+            "List files and directories within a directory.".to_string(),
+            &[
+                ("path", "string", "The directory to list.", true),
+                (
+                    "recursive",
+                    "boolean",
+                    "Whether to list files recursively.",
+                    false,
+                ),
+            ],
+        ),
+        tool(
+            "list_code_definition_names",
+            "List top level source code definitions in a directory.".to_string(),
+            &[(
+                "path",
+                "string",
+                "The directory to list definitions for.",
+                true,
+            )],
+        ),
+    ]);
+
+    if args.supports_codebase_search {
+        tools.push(tool(
+            "codebase_search",
+            "Find code semantically relevant to a natural language query.".to_string(),
+            &[
+                ("query", "string", "What to search for.", true),
+                ("path", "string", "Limit the search to a directory.", false),
+            ],
+        ));
+    }
+
+    if args.supports_computer_use {
+        tools.push(tool(
+            "browser_action",
+            format!(
+                "Interact with a browser. Every action except `close` responds with a screenshot and new console logs. The viewport is {} pixels.",
+                args.browser_viewport_size.as_deref().unwrap_or("900x600")
+            ),
+            &[
+                (
+                    "action",
+                    "string",
+                    "One of: launch, click, type, scroll_down, scroll_up, close.",
+                    true,
+                ),
+                ("url", "string", "The URL for the `launch` action.", false),
+                (
+                    "coordinate",
+                    "string",
+                    "The `x,y` coordinates for the `click` action.",
+                    false,
+                ),
+                ("text", "string", "The text for the `type` action.", false),
+            ],
+        ));
+    }
+
+    if args.has_browser {
+        tools.push(tool(
+            "get_page_text",
+            "Read the main content of the browser's current page as Markdown, without a screenshot. Scripts, navigation and page headers and footers are removed.".to_string(),
+            &[(
+                "url",
+                "string",
+                "A URL to open before reading. Omit it to read the current page.",
+                false,
+            )],
+        ));
+    }
+
+    tools.extend([
+        tool(
+            "fetch",
+            "Send an HTTP request without a browser. HTML responses are converted to Markdown and JSON responses are pretty-printed.".to_string(),
+            &[
+                ("url", "string", "The http or https URL to request.", true),
+                ("method", "string", "GET (default) or POST.", false),
+                (
+                    "headers",
+                    "string",
+                    "A JSON object of request headers.",
+                    false,
+                ),
+                ("body", "string", "The request body for POST.", false),
+            ],
+        ),
+        tool(
+            "ask_followup_question",
+            "Ask the user a question to gather information needed to complete the task."
+                .to_string(),
+            &[
+                ("question", "string", "The question to ask the user.", true),
+                (
+                    "follow_up",
+                    "string",
+                    "2-4 suggested answers, each in a <suggest></suggest> tag.",
+                    false,
+                ),
+            ],
+        ),
+        tool(
+            "attempt_completion",
+            "Present the result of the task to the user once it is complete.".to_string(),
+            &[
+                ("result", "string", "The final result of the task.", true),
+                (
+                    "command",
+                    "string",
+                    "A CLI command to show a live demo of the result.",
+                    false,
+                ),
+            ],
+        ),
+    ]);
+
+    if args.mcp_hub.is_some() {
+        tools.push(tool(
+            "use_mcp_tool",
+            "Use a tool provided by a connected MCP server.".to_string(),
+            &[
+                ("server_name", "string", "The name of the MCP server.", true),
+                (
+                    "tool_name",
+                    "string",
+                    "The name of the tool to execute.",
+                    true,
+                ),
+                (
+                    "arguments",
+                    "string",
+                    "A JSON object with the tool's input parameters.",
+                    true,
+                ),
+            ],
+        ));
+        tools.push(tool(
+            "access_mcp_resource",
+            "Access a resource provided by a connected MCP server.".to_string(),
+            &[
+                ("server_name", "string", "The name of the MCP server.", true),
+                ("uri", "string", "The URI of the resource.", true),
+            ],
+        ));
+    }
+
+    tools.extend([
+        tool(
+            "switch_mode",
+            "Request to switch to a different mode.".to_string(),
+            &[
+                ("mode_slug", "string", "The slug of the mode to switch to.", true),
+                ("reason", "string", "The reason for switching modes.", false),
+            ],
+        ),
+        tool(
+            "new_task",
+            "Create a new task in the given mode.".to_string(),
+            &[
+                ("mode", "string", "The slug of the mode to start the task in.", true),
+                ("message", "string", "The initial instructions for the task.", true),
+                (
+                    "subtasks",
+                    "string",
+                    "A JSON array of objects with `message` and `files` to run in parallel. The files of each subtask must not overlap.",
+                    false,
+                ),
+            ],
+        ),
+        tool(
+            "insert_content",
+            "Insert content at specific lines of a file.".to_string(),
+            &[
+                ("path", "string", "The path of the file.", true),
+                (
+                    "operations",
+                    "string",
+                    "A JSON array of objects with `start_line` and `content`.",
+                    true,
+                ),
+            ],
+        ),
+        tool(
+            "search_and_replace",
+            "Perform search and replace operations on a file.".to_string(),
+            &[
+                ("path", "string", "The path of the file.", true),
+                (
+                    "operations",
+                    "string",
+                    "A JSON array of objects with `search`, `replace` and optional `start_line`, `end_line`, `use_regex`, `ignore_case`, `regex_flags`.",
+                    true,
+                ),
+            ],
+        ),
+        tool(
+            "update_todo_list",
+            "Replace the todo list for the current task.".to_string(),
+            &[(
+                "todos",
+                "string",
+                "A markdown checklist with one item per line: `[ ]` pending, `[-]` in progress, `[x]` completed.",
+                true,
+            )],
+        ),
+    ]);
+    tools.extend(
+        args.custom_tools
+            .iter()
+            .map(|tool| get_custom_tool_definition(tool.as_ref())),
+    );
+
+    if args.compact {
+        tools.iter_mut().for_each(compact_tool);
+    }
+    if let Some(settings) = args.tool_settings {
+        tools.retain(|tool| !settings.is_disabled(&tool.name));
+        for tool in &mut tools {
+            if let Some(description) = settings.description_override(&tool.name) {
+                tool.description = description.to_string();
+            }
+        }
+    }
+    tools
+}
+
+/// 小さいコンテキストのモデル向けに、説明を最初の文だけにしてパラメータの説明を省く
+fn compact_tool(tool: &mut ToolDefinition) {
+    let first_sentence = tool
+        .description
+        .find(". ")
+        .map(|end| end + 1)
+        .or_else(|| tool.description.find('\n'))
+        .unwrap_or(tool.description.len());
+    tool.description.truncate(first_sentence);
+    if let Some(properties) = tool.input_schema["properties"].as_object_mut() {
+        for property in properties.values_mut() {
+            if let Some(property) = property.as_object_mut() {
+                property.remove("description");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolSettings;
+
+    #[test]
+    fn test_native_tool_definitions() {
+        let args = ToolArgs {
+            cwd: "/workspace".to_string(),
+            ..Default::default()
+        };
+        let tools = get_native_tool_definitions(&args);
+        let names: Vec<_> = tools.iter().map(|t| t.name.as_str()).collect();
+        assert!(names.contains(&"read_file"));
+        assert!(names.contains(&"attempt_completion"));
+        assert!(names.contains(&"fetch"));
+        assert!(!names.contains(&"browser_action"));
+        assert!(!names.contains(&"get_page_text"));
+        assert!(!names.contains(&"use_mcp_tool"));
+
+        let write = tools.iter().find(|t| t.name == "write_to_file").unwrap();
+        assert_eq!(
+            write.input_schema["required"],
+            json!(["path", "content", "line_count"])
+        );
+        assert_eq!(
+            write.input_schema["properties"]["line_count"]["type"],
+            "integer"
+        );
+    }
+
+    #[test]
+    fn test_get_page_text_without_computer_use() {
+        // 画像を扱えないモデルでも、ブラウザがあればページの本文を読める
+        let args = ToolArgs {
+            cwd: "/workspace".to_string(),
+            has_browser: true,
+            ..Default::default()
+        };
+        let tools = get_native_tool_definitions(&args);
+        let names: Vec<_> = tools.iter().map(|t| t.name.as_str()).collect();
+        assert!(names.contains(&"get_page_text"));
+        assert!(!names.contains(&"browser_action"));
+        let get_page_text = tools.iter().find(|t| t.name == "get_page_text").unwrap();
+        assert_eq!(get_page_text.input_schema["required"], json!([]));
+    }
+
+    #[test]
+    fn test_compact_native_tool_definitions() {
+        let args = ToolArgs {
+            cwd: "/workspace".to_string(),
+            compact: true,
+            ..Default::default()
+        };
+        let tools = get_native_tool_definitions(&args);
+        let read = tools.iter().find(|t| t.name == "read_file").unwrap();
+        assert_eq!(read.description, "Read the contents of a file.");
+        assert_eq!(
+            read.input_schema["properties"]["path"],
+            json!({ "type": "string" })
+        );
+        assert_eq!(read.input_schema["required"], json!(["path"]));
+    }
+
+    #[test]
+    fn test_tool_settings_in_native_definitions() {
+        let settings = ToolSettings {
+            disabled_tools: vec!["fetch".to_string()],
+            description_overrides: [("read_file".to_string(), "Read a file.".to_string())].into(),
+        };
+        let args = ToolArgs {
+            cwd: "/workspace".to_string(),
+            compact: true,
+            tool_settings: Some(&settings),
+            ..Default::default()
+        };
+        let tools = get_native_tool_definitions(&args);
+        assert!(!tools.iter().any(|t| t.name == "fetch"));
+        let read = tools.iter().find(|t| t.name == "read_file").unwrap();
+        assert_eq!(read.description, "Read a file.");
+    }
+}
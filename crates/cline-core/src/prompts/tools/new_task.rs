@@ -8,6 +8,7 @@ Description: Create a new task with a specified starting mode and initial messag
 Parameters:
 - mode: (required) The slug of the mode to start the new task in (e.g., "code", "ask", "architect").
 - message: (required) The initial user message or instructions for this new task.
+- subtasks: (optional) A JSON array of independent subtasks to run in parallel, each with a `message` and the `files` (files or directories relative to the workspace) it may edit. The files of different subtasks must not overlap, and each subtask can only access its own files. Use this to split large changes, such as a refactor per module.
 
 Usage:
 <new_task>
@@ -20,5 +21,12 @@ Example:
 <mode>code</mode>
 <message>Implement a new feature for the application.</message>
 </new_task>
+
+Example: Running subtasks in parallel
+<new_task>
+<mode>code</mode>
+<message>Migrate the API and UI modules to the new error type.</message>
+<subtasks>[{"message": "Migrate src/api to the new error type.", "files": ["src/api"]}, {"message": "Migrate src/ui to the new error type.", "files": ["src/ui"]}]</subtasks>
+</new_task>
 "#.to_string()
 }
@@ -1,16 +1,28 @@
 use crate::services::diff::DiffStrategy;
 use crate::services::mcp::McpHub;
+use crate::tools::{CustomTool, ToolSettings};
 use std::fmt;
+use std::sync::Arc;
 
 #[derive(Default)]
 #[allow(dead_code)]
 pub struct ToolArgs<'a> {
     pub cwd: String,
     pub supports_computer_use: bool,
+    /// ブラウザが設定されている（画像を扱えないモデルでも `get_page_text` を使える）
+    pub has_browser: bool,
     pub diff_strategy: Option<&'a dyn DiffStrategy>,
     pub browser_viewport_size: Option<String>,
     pub mcp_hub: Option<&'a McpHub>,
+    /// コードベースのインデックスがあり、`codebase_search` を使える
+    pub supports_codebase_search: bool,
     pub tool_options: Option<serde_json::Value>,
+    /// 小さいコンテキストのモデル向けに説明を短くする
+    pub compact: bool,
+    /// ホストが追加したツール
+    pub custom_tools: &'a [Arc<dyn CustomTool>],
+    /// 無効にするツールと置き換える説明
+    pub tool_settings: Option<&'a ToolSettings>,
 }
 
 impl fmt::Debug for ToolArgs<'_> {
@@ -18,10 +30,22 @@ impl fmt::Debug for ToolArgs<'_> {
         f.debug_struct("ToolArgs")
             .field("cwd", &self.cwd)
             .field("supports_computer_use", &self.supports_computer_use)
+            .field("has_browser", &self.has_browser)
             .field("diff_strategy", &"<DiffStrategy>")
             .field("browser_viewport_size", &self.browser_viewport_size)
             .field("mcp_hub", &self.mcp_hub)
+            .field("supports_codebase_search", &self.supports_codebase_search)
             .field("tool_options", &self.tool_options)
+            .field("compact", &self.compact)
+            .field(
+                "custom_tools",
+                &self
+                    .custom_tools
+                    .iter()
+                    .map(|tool| tool.name())
+                    .collect::<Vec<_>>(),
+            )
+            .field("tool_settings", &self.tool_settings)
             .finish()
     }
 }
@@ -0,0 +1,31 @@
+use crate::prompts::tools::types::ToolArgs;
+
+#[allow(dead_code)]
+pub fn get_update_todo_list_description(_args: &ToolArgs) -> String {
+    r#"## update_todo_list
+Description: Replace the todo list for the current task with an updated checklist. Use it to plan multi-step tasks and to keep track of progress: mark items completed as soon as they are done, mark the item you are working on as in progress, and add new items as they are discovered. Always provide the full list; it replaces the previous one.
+
+Parameters:
+- todos: (required) A markdown checklist with one item per line. Use `[ ]` for pending, `[-]` for in progress and `[x]` for completed items.
+
+Usage:
+<update_todo_list>
+<todos>
+[x] Completed item
+[-] Item in progress
+[ ] Pending item
+</todos>
+</update_todo_list>
+
+Example:
+<update_todo_list>
+<todos>
+[x] Analyze the existing error handling
+[-] Introduce the new error type
+[ ] Migrate the API module
+[ ] Update the tests
+</todos>
+</update_todo_list>
+"#
+    .to_string()
+}
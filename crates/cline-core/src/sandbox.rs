@@ -0,0 +1,389 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::fmt::Debug;
+use std::path::{Component, Path, PathBuf};
+
+use crate::cline::ToolApproval;
+use crate::error::ClineError;
+
+/// ワークスペース外へのアクセスを許可するか確認する（ヘッドレス実行時のホストが実装する）
+#[async_trait]
+pub trait OutsideWorkspaceApprover: Debug + Send + Sync {
+    async fn approve(&self, path: &Path) -> bool;
+
+    /// 拒否の理由をモデルに伝える場合に実装する（既定では `approve` の結果を使う）
+    async fn review(&self, path: &Path) -> ToolApproval {
+        self.approve(path).await.into()
+    }
+}
+
+/// メンションの種類と区別できないため、ワークスペースのルートに使えない名前
+const RESERVED_ROOT_NAMES: &[&str] = &[
+    "docs",
+    "git",
+    "git-compare",
+    "problems",
+    "scratch",
+    "terminal",
+    "http",
+    "https",
+];
+
+/// 追加のワークスペースのルート（`name:path` の形式のパスで参照する）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceRoot {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl WorkspaceRoot {
+    pub fn new(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+        }
+    }
+}
+
+/// ルートの名前が英数字・`-`・`_` のみで、重複や予約された名前がないことを確認する
+pub fn validate_workspace_roots(roots: &[WorkspaceRoot]) -> Result<()> {
+    for (index, root) in roots.iter().enumerate() {
+        let valid = !root.name.is_empty()
+            && root
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid || RESERVED_ROOT_NAMES.contains(&root.name.as_str()) {
+            anyhow::bail!("Invalid workspace root name: '{}'", root.name);
+        }
+        if roots[..index].iter().any(|other| other.name == root.name) {
+            anyhow::bail!("Duplicate workspace root name: '{}'", root.name);
+        }
+    }
+    Ok(())
+}
+
+/// ファイルツールとメンションのアクセスをワークスペース内に制限する
+///
+/// 追加のルートを指定した場合は `name:path`（`name:/path`）の形式のパスをそのルートで解決する。
+#[derive(Debug, Clone)]
+pub struct WorkspaceSandbox {
+    root: PathBuf,
+    /// 追加のルート（名前, 正規化したパス）
+    roots: Vec<(String, PathBuf)>,
+    allow_outside_workspace: bool,
+    /// 指定した場合はこれらのファイル・ディレクトリ以外へのアクセスを拒否する
+    allowed_paths: Option<Vec<PathBuf>>,
+}
+
+impl WorkspaceSandbox {
+    pub fn new(workspace_path: &Path) -> Self {
+        Self {
+            root: canonicalize_lenient(workspace_path),
+            roots: Vec::new(),
+            allow_outside_workspace: false,
+            allowed_paths: None,
+        }
+    }
+
+    /// 追加のルートを設定する
+    pub fn with_roots(mut self, roots: &[WorkspaceRoot]) -> Self {
+        self.roots = roots
+            .iter()
+            .map(|root| (root.name.clone(), canonicalize_lenient(&root.path)))
+            .collect();
+        self
+    }
+
+    /// ワークスペース外へのアクセスを許可する
+    pub fn with_allow_outside_workspace(mut self, allow: bool) -> Self {
+        self.allow_outside_workspace = allow;
+        self
+    }
+
+    /// アクセスできるファイル・ディレクトリを限定する（サブタスクの担当範囲など）
+    pub fn with_allowed_paths(mut self, paths: &[String]) -> Self {
+        self.allowed_paths = Some(paths.iter().map(|path| self.resolve(path)).collect());
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// パスを解決するルートとルートからのパスに分ける（`name:` がない場合は最初のルート）
+    pub fn split_root<'a>(&self, path: &'a str) -> (Option<&str>, &Path, &'a str) {
+        if let Some((name, rest)) = path.split_once(':') {
+            if let Some((name, root)) = self.roots.iter().find(|(root, _)| root == name) {
+                return (Some(name.as_str()), root, rest.trim_start_matches('/'));
+            }
+        }
+        (None, &self.root, path)
+    }
+
+    /// パスを正規化した絶対パスに変換する（シンボリックリンクは解決する）
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        let (_, root, path) = self.split_root(path);
+        canonicalize_lenient(&root.join(path))
+    }
+
+    pub fn is_inside(&self, abs_path: &Path) -> bool {
+        abs_path.starts_with(&self.root)
+            || self
+                .roots
+                .iter()
+                .any(|(_, root)| abs_path.starts_with(root))
+    }
+
+    /// 絶対パスをワークスペースからの相対パスにする（追加のルートの下は `name:path`）
+    ///
+    /// ルートが入れ子になっている場合は最も深いルートを使う。どのルートの下でもなければ `None`。
+    pub fn relative_path(&self, abs_path: &Path) -> Option<String> {
+        let primary = abs_path
+            .strip_prefix(&self.root)
+            .ok()
+            .map(|path| (self.root.as_os_str().len(), None, path));
+        self.roots
+            .iter()
+            .filter_map(|(name, root)| {
+                let path = abs_path.strip_prefix(root).ok()?;
+                Some((root.as_os_str().len(), Some(name), path))
+            })
+            .chain(primary)
+            .max_by_key(|(depth, _, _)| *depth)
+            .map(|(_, name, path)| {
+                let path = path.to_string_lossy().replace('\\', "/");
+                match name {
+                    Some(name) => format!("{}:{}", name, path),
+                    None => path,
+                }
+            })
+    }
+
+    /// 担当範囲が限定されていない、または範囲内のパスか
+    pub fn is_allowed(&self, abs_path: &Path) -> bool {
+        self.allowed_paths
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|allowed| abs_path.starts_with(allowed)))
+    }
+
+    /// パスを解決し、ワークスペース外の場合は許可されていなければエラーにする
+    pub fn check(&self, path: &str) -> Result<PathBuf> {
+        let abs_path = self.resolve(path);
+        if !self.is_allowed(&abs_path) {
+            return Err(outside_allowed_paths_error(path).into());
+        }
+        if !self.allow_outside_workspace && !self.is_inside(&abs_path) {
+            return Err(outside_workspace_error(path).into());
+        }
+        Ok(abs_path)
+    }
+}
+
+pub(crate) fn outside_workspace_error(path: &str) -> ClineError {
+    ClineError::AccessDenied(format!(
+        "Access denied: '{}' is outside the workspace",
+        path
+    ))
+}
+
+pub(crate) fn denied_by_policy_error(path: &str) -> ClineError {
+    ClineError::AccessDenied(format!("Access denied: '{}' is blocked by policy", path))
+}
+
+pub(crate) fn outside_allowed_paths_error(path: &str) -> ClineError {
+    ClineError::AccessDenied(format!(
+        "Access denied: '{}' is outside the files assigned to this task",
+        path
+    ))
+}
+
+/// 各グループのファイル・ディレクトリが互いに重ならないことを確認する（並列実行するサブタスク用）
+///
+/// 同じパスや、一方が他方のディレクトリに含まれるパスがある場合はエラーにする。
+pub fn validate_disjoint_paths(sandbox: &WorkspaceSandbox, groups: &[Vec<String>]) -> Result<()> {
+    let mut claimed: Vec<(usize, &str, PathBuf)> = Vec::new();
+    for (index, paths) in groups.iter().enumerate() {
+        if paths.is_empty() {
+            anyhow::bail!("Subtask {} has no files assigned", index + 1);
+        }
+        for path in paths {
+            let abs_path = sandbox.check(path)?;
+            if let Some((other, other_path, _)) = claimed.iter().find(|(other, _, other_abs)| {
+                *other != index
+                    && (abs_path.starts_with(other_abs) || other_abs.starts_with(&abs_path))
+            }) {
+                anyhow::bail!(
+                    "Subtasks {} and {} overlap: '{}' and '{}'",
+                    other + 1,
+                    index + 1,
+                    other_path,
+                    path
+                );
+            }
+            claimed.push((index, path, abs_path));
+        }
+    }
+    Ok(())
+}
+
+/// 存在する最も深い祖先までを正規化し、残りは字句的に解決する（未作成のファイル用）
+fn canonicalize_lenient(path: &Path) -> PathBuf {
+    let path = normalize_lexically(path);
+    let mut existing = path.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return rest
+                .iter()
+                .rev()
+                .fold(canonical, |path, component| path.join(component));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return path,
+        }
+    }
+}
+
+/// `.` と `..` を取り除く
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_workspace_sandbox() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let workspace = temp_dir.path().join("workspace");
+        std::fs::create_dir_all(workspace.join("src")).unwrap();
+        std::fs::write(temp_dir.path().join("secret.txt"), "secret").unwrap();
+
+        let sandbox = WorkspaceSandbox::new(&workspace);
+        let root = sandbox.root().to_path_buf();
+        assert_eq!(
+            sandbox.check("src/main.rs").unwrap(),
+            root.join("src/main.rs")
+        );
+        assert_eq!(
+            sandbox.check("src/../new/file.rs").unwrap(),
+            root.join("new/file.rs")
+        );
+        assert!(sandbox.check("../secret.txt").is_err());
+        assert!(sandbox.check("/etc/passwd").is_err());
+        assert!(sandbox.check("new/../../../etc/passwd").is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(temp_dir.path().join("secret.txt"), workspace.join("link"))
+                .unwrap();
+            assert!(sandbox.check("link").is_err());
+        }
+
+        let sandbox = sandbox.with_allow_outside_workspace(true);
+        assert_eq!(
+            sandbox.check("../secret.txt").unwrap(),
+            root.parent().unwrap().join("secret.txt")
+        );
+    }
+
+    #[test]
+    fn test_workspace_roots() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let frontend = temp_dir.path().join("frontend");
+        let backend = temp_dir.path().join("backend");
+        std::fs::create_dir_all(&frontend).unwrap();
+        std::fs::create_dir_all(&backend).unwrap();
+
+        let sandbox =
+            WorkspaceSandbox::new(&frontend).with_roots(&[WorkspaceRoot::new("backend", &backend)]);
+        let backend = backend.canonicalize().unwrap();
+        assert_eq!(
+            sandbox.check("backend:/src/main.rs").unwrap(),
+            backend.join("src/main.rs")
+        );
+        assert_eq!(
+            sandbox.check("backend:src/main.rs").unwrap(),
+            backend.join("src/main.rs")
+        );
+        assert_eq!(
+            sandbox
+                .relative_path(&backend.join("src/main.rs"))
+                .as_deref(),
+            Some("backend:src/main.rs")
+        );
+        assert_eq!(
+            sandbox
+                .relative_path(&sandbox.root().join("src/app.ts"))
+                .as_deref(),
+            Some("src/app.ts")
+        );
+        assert_eq!(sandbox.relative_path(temp_dir.path()), None);
+        // 登録していない名前はファイル名の一部として扱う
+        assert_eq!(
+            sandbox.check("other:file").unwrap(),
+            sandbox.root().join("other:file")
+        );
+        assert!(sandbox.check("backend:../secret.txt").is_err());
+
+        assert!(validate_workspace_roots(&[WorkspaceRoot::new("api-v2", "/a")]).is_ok());
+        for name in ["", "git", "has space", "a:b"] {
+            assert!(validate_workspace_roots(&[WorkspaceRoot::new(name, "/a")]).is_err());
+        }
+        assert!(validate_workspace_roots(&[
+            WorkspaceRoot::new("api", "/a"),
+            WorkspaceRoot::new("api", "/b")
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_allowed_paths() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let sandbox = WorkspaceSandbox::new(temp_dir.path());
+        let scoped = sandbox
+            .clone()
+            .with_allowed_paths(&["src/api".to_string(), "README.md".to_string()]);
+        assert!(scoped.check("src/api/mod.rs").is_ok());
+        assert!(scoped.check("./README.md").is_ok());
+        assert!(scoped.check("src/apis.rs").is_err());
+        assert!(scoped.check("src/lib.rs").is_err());
+
+        let groups = |groups: &[&[&str]]| -> Vec<Vec<String>> {
+            groups
+                .iter()
+                .map(|paths| paths.iter().map(|path| path.to_string()).collect())
+                .collect()
+        };
+        assert!(validate_disjoint_paths(
+            &sandbox,
+            &groups(&[&["src/api"], &["src/ui", "README.md"]])
+        )
+        .is_ok());
+        assert_eq!(
+            validate_disjoint_paths(&sandbox, &groups(&[&["src"], &["src/ui/mod.rs"]]))
+                .unwrap_err()
+                .to_string(),
+            "Subtasks 1 and 2 overlap: 'src' and 'src/ui/mod.rs'"
+        );
+        assert!(validate_disjoint_paths(&sandbox, &groups(&[&["src"], &[]])).is_err());
+        assert!(validate_disjoint_paths(&sandbox, &groups(&[&["../outside"]])).is_err());
+    }
+}
@@ -0,0 +1,454 @@
+use anyhow::Result;
+use futures_util::StreamExt;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{
+    AnthropicClient, AnthropicClientTrait, ApiMessage, ApiStreamChunk, ApiUsage, ClaudeRequest,
+    ContentBlock, Message, DEFAULT_MAX_TOKENS, DEFAULT_MODEL,
+};
+use crate::services::cost::calculate_api_cost;
+
+const BATCHES_URL: &str = "https://api.anthropic.com/v1/messages/batches";
+
+/// 同時に実行するリクエストの既定の数（バッチAPIを使わない場合）
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+/// バッチの状態を確認する既定の間隔
+pub const DEFAULT_BATCH_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// バッチAPIの料金の割合（通常の料金の半額）
+const BATCH_PRICE_RATIO: f64 = 0.5;
+
+/// 一括で送信するリクエスト
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchRequest {
+    /// 結果と対応付けるID（バッチ内で一意）
+    pub custom_id: String,
+    pub content: Vec<ContentBlock>,
+}
+
+impl BatchRequest {
+    pub fn new(custom_id: impl Into<String>, content: Vec<ContentBlock>) -> Self {
+        Self {
+            custom_id: custom_id.into(),
+            content,
+        }
+    }
+}
+
+/// 一括実行の設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// 同時に実行するリクエストの最大数（バッチAPIを使わない場合）
+    pub concurrency: usize,
+    /// バッチの状態を確認する間隔
+    pub poll_interval: Duration,
+    /// プロバイダーが対応していればバッチAPIを使う
+    pub use_batch_api: bool,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_BATCH_CONCURRENCY,
+            poll_interval: DEFAULT_BATCH_POLL_INTERVAL,
+            use_batch_api: true,
+        }
+    }
+}
+
+/// 1件のリクエストの結果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchResponse {
+    pub custom_id: String,
+    /// 成功した場合の応答のテキスト
+    pub text: Option<String>,
+    pub error: Option<String>,
+    pub usage: ApiUsage,
+    /// 料金（USD）
+    pub cost: f64,
+}
+
+/// 一括実行の結果（リクエストと同じ順に並ぶ）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchReport {
+    pub responses: Vec<BatchResponse>,
+    /// プロバイダーのバッチAPIで実行した
+    pub used_batch_api: bool,
+}
+
+impl BatchReport {
+    pub fn succeeded(&self) -> usize {
+        self.responses.iter().filter(|r| r.error.is_none()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.responses.len() - self.succeeded()
+    }
+
+    /// 全リクエストの料金の合計（USD）
+    pub fn total_cost(&self) -> f64 {
+        self.responses.iter().map(|r| r.cost).sum()
+    }
+
+    /// 全リクエストのトークン使用量の合計
+    pub fn total_usage(&self) -> ApiUsage {
+        let sum = |tokens: fn(&ApiUsage) -> Option<u32>| {
+            self.responses
+                .iter()
+                .filter_map(|r| tokens(&r.usage))
+                .reduce(|a, b| a + b)
+        };
+        ApiUsage {
+            input_tokens: sum(|u| u.input_tokens),
+            output_tokens: sum(|u| u.output_tokens),
+            cache_creation_input_tokens: sum(|u| u.cache_creation_input_tokens),
+            cache_read_input_tokens: sum(|u| u.cache_read_input_tokens),
+        }
+    }
+
+    /// 件数・トークン数・料金の要約
+    pub fn summary(&self) -> String {
+        let usage = self.total_usage();
+        let mut text = format!(
+            "{} requests ({} succeeded, {} failed{}), {} tokens in, {} tokens out, ${:.4}",
+            self.responses.len(),
+            self.succeeded(),
+            self.failed(),
+            if self.used_batch_api {
+                ", batch API"
+            } else {
+                ""
+            },
+            usage.input_tokens.unwrap_or(0),
+            usage.output_tokens.unwrap_or(0),
+            self.total_cost()
+        );
+        for response in self.responses.iter().filter(|r| r.error.is_some()) {
+            text.push_str(&format!(
+                "\n- {}: {}",
+                response.custom_id,
+                response.error.as_deref().unwrap_or_default()
+            ));
+        }
+        text
+    }
+}
+
+impl AnthropicClient {
+    /// 互いに独立したリクエストを一括で実行する（CIなどで多数のタスクを実行するためのもの）
+    ///
+    /// バッチAPIに対応したプロバイダーでは1つのバッチとして送信し、
+    /// それ以外は最大 `concurrency` 件ずつストリーミングで実行する。
+    pub async fn run_batch(
+        &self,
+        requests: Vec<BatchRequest>,
+        options: &BatchOptions,
+    ) -> Result<BatchReport> {
+        let mut ids = HashSet::new();
+        if let Some(request) = requests.iter().find(|r| !ids.insert(r.custom_id.as_str())) {
+            anyhow::bail!("Duplicate custom_id in batch: {}", request.custom_id);
+        }
+        if requests.is_empty() {
+            return Ok(BatchReport::default());
+        }
+        match self {
+            Self::Real {
+                client,
+                api_key,
+                tools,
+                ..
+            } if options.use_batch_api => {
+                let responses =
+                    run_message_batch(client, api_key, tools.clone(), requests, options).await?;
+                Ok(BatchReport {
+                    responses,
+                    used_batch_api: true,
+                })
+            }
+            _ => Ok(BatchReport {
+                responses: self.run_concurrently(requests, options.concurrency).await,
+                used_batch_api: false,
+            }),
+        }
+    }
+
+    async fn run_concurrently(
+        &self,
+        requests: Vec<BatchRequest>,
+        concurrency: usize,
+    ) -> Vec<BatchResponse> {
+        let model_id = self.model_id().to_string();
+        futures_util::stream::iter(requests)
+            .map(|request| {
+                let model_id = model_id.clone();
+                async move {
+                    let usage = Arc::new(Mutex::new(ApiUsage::default()));
+                    let received = Arc::clone(&usage);
+                    let result = self
+                        .attempt_api_request(
+                            vec![Message::new("user", request.content)],
+                            false,
+                            Box::new(move |chunk| {
+                                if let ApiStreamChunk::Usage(usage) = chunk {
+                                    received.lock().unwrap().merge(&usage);
+                                }
+                            }),
+                        )
+                        .await;
+                    let usage = usage.lock().unwrap().clone();
+                    BatchResponse {
+                        custom_id: request.custom_id,
+                        cost: calculate_api_cost(&model_id, &usage),
+                        usage,
+                        text: result.as_ref().ok().cloned(),
+                        error: result.err().map(|e| e.to_string()),
+                    }
+                }
+            })
+            // 完了順ではなくリクエストの順に結果を返す
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CreateBatchRequest {
+    requests: Vec<BatchRequestParams>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchRequestParams {
+    custom_id: String,
+    params: ClaudeRequest,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageBatch {
+    id: String,
+    /// `in_progress`・`canceling`・`ended`
+    processing_status: String,
+    results_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchResultLine {
+    custom_id: String,
+    result: BatchResult,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BatchResult {
+    Succeeded { message: BatchMessage },
+    Errored { error: serde_json::Value },
+    Canceled,
+    Expired,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchMessage {
+    content: Vec<BatchContent>,
+    #[serde(default)]
+    usage: ApiUsage,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchContent {
+    #[serde(rename = "type")]
+    block_type: String,
+    text: Option<String>,
+}
+
+async fn run_message_batch(
+    client: &Client,
+    api_key: &str,
+    tools: Option<Vec<super::ToolDefinition>>,
+    requests: Vec<BatchRequest>,
+    options: &BatchOptions,
+) -> Result<Vec<BatchResponse>> {
+    let order: Vec<String> = requests.iter().map(|r| r.custom_id.clone()).collect();
+    let body = CreateBatchRequest {
+        requests: requests
+            .into_iter()
+            .map(|request| BatchRequestParams {
+                custom_id: request.custom_id,
+                params: ClaudeRequest {
+                    model: DEFAULT_MODEL.to_string(),
+                    messages: vec![ApiMessage {
+                        role: "user".to_string(),
+                        content: request.content,
+                    }],
+                    max_tokens: DEFAULT_MAX_TOKENS,
+                    stream: false,
+                    tools: tools.clone(),
+                    thinking: None,
+                },
+            })
+            .collect(),
+    };
+    let send = |request: reqwest::RequestBuilder| {
+        request
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01")
+            .send()
+    };
+
+    let response = send(client.post(BATCHES_URL).json(&body)).await?;
+    if response.status() != StatusCode::OK {
+        anyhow::bail!("Batch request failed: {}", response.text().await?);
+    }
+    let mut batch: MessageBatch = response.json().await?;
+    while batch.processing_status != "ended" {
+        tokio::time::sleep(options.poll_interval).await;
+        let response = send(client.get(format!("{}/{}", BATCHES_URL, batch.id))).await?;
+        if response.status() != StatusCode::OK {
+            anyhow::bail!(
+                "Unable to check batch {}: {}",
+                batch.id,
+                response.text().await?
+            );
+        }
+        batch = response.json().await?;
+    }
+
+    let results_url = batch
+        .results_url
+        .ok_or_else(|| anyhow::anyhow!("Batch {} ended without results", batch.id))?;
+    let response = send(client.get(results_url)).await?;
+    if response.status() != StatusCode::OK {
+        anyhow::bail!(
+            "Unable to download results of batch {}: {}",
+            batch.id,
+            response.text().await?
+        );
+    }
+    parse_batch_results(&response.text().await?, &order)
+}
+
+/// 結果のJSONLをリクエストの順に並べる（結果がないリクエストはエラーとする）
+fn parse_batch_results(jsonl: &str, order: &[String]) -> Result<Vec<BatchResponse>> {
+    let mut results = HashMap::new();
+    for line in jsonl.lines().filter(|line| !line.trim().is_empty()) {
+        let line: BatchResultLine = serde_json::from_str(line)?;
+        let mut response = BatchResponse {
+            custom_id: line.custom_id.clone(),
+            ..Default::default()
+        };
+        match line.result {
+            BatchResult::Succeeded { message } => {
+                response.text = Some(
+                    message
+                        .content
+                        .into_iter()
+                        .filter(|block| block.block_type == "text")
+                        .filter_map(|block| block.text)
+                        .collect(),
+                );
+                response.cost =
+                    calculate_api_cost(DEFAULT_MODEL, &message.usage) * BATCH_PRICE_RATIO;
+                response.usage = message.usage;
+            }
+            BatchResult::Errored { error } => {
+                let message = error
+                    .pointer("/error/message")
+                    .or_else(|| error.get("message"))
+                    .and_then(|message| message.as_str())
+                    .map(String::from)
+                    .unwrap_or_else(|| error.to_string());
+                response.error = Some(message);
+            }
+            BatchResult::Canceled => response.error = Some("Request was canceled".to_string()),
+            BatchResult::Expired => response.error = Some("Request expired".to_string()),
+        }
+        results.insert(line.custom_id, response);
+    }
+    Ok(order
+        .iter()
+        .map(|custom_id| {
+            results.remove(custom_id).unwrap_or_else(|| BatchResponse {
+                custom_id: custom_id.clone(),
+                error: Some("No result returned".to_string()),
+                ..Default::default()
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::api::{ScriptedProvider, ScriptedTurn};
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_run_batch_falls_back_to_concurrent_requests() {
+        let provider = Arc::new(ScriptedProvider::new([
+            ScriptedTurn::text("first").with_usage(1_000_000, 100_000),
+            ScriptedTurn::error("overloaded"),
+            ScriptedTurn::text("third").with_usage(1_000_000, 0),
+        ]));
+        let client = AnthropicClient::scripted(Arc::clone(&provider));
+        let requests = ["a", "b", "c"]
+            .into_iter()
+            .map(|id| BatchRequest::new(id, vec![ContentBlock::text(format!("task {}", id))]))
+            .collect();
+        let report = client
+            .run_batch(
+                requests,
+                &BatchOptions {
+                    concurrency: 1,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        assert!(!report.used_batch_api);
+        let texts: Vec<_> = report.responses.iter().map(|r| r.text.as_deref()).collect();
+        assert_eq!(texts, vec![Some("first"), None, Some("third")]);
+        assert_eq!(report.responses[2].usage.input_tokens, Some(1_000_000));
+        assert_eq!(report.total_usage().input_tokens, Some(2_000_000));
+        assert!((report.total_cost() - (3.0 + 1.5 + 3.0)).abs() < 1e-9);
+        assert_eq!(
+            report.summary(),
+            "3 requests (2 succeeded, 1 failed), 2000000 tokens in, 100000 tokens out, $7.5000\n- b: API request failed: overloaded"
+        );
+
+        let duplicate = vec![
+            BatchRequest::new("a", Vec::new()),
+            BatchRequest::new("a", Vec::new()),
+        ];
+        assert!(client
+            .run_batch(duplicate, &BatchOptions::default())
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_batch_results() {
+        let jsonl = r#"{"custom_id":"b","result":{"type":"errored","error":{"type":"error","error":{"type":"invalid_request_error","message":"max_tokens too large"}}}}
+{"custom_id":"a","result":{"type":"succeeded","message":{"content":[{"type":"thinking","thinking":"..."},{"type":"text","text":"done"}],"usage":{"input_tokens":2000000,"output_tokens":0}}}}
+{"custom_id":"c","result":{"type":"expired"}}"#;
+        let order: Vec<String> = ["a", "b", "c", "d"].map(String::from).to_vec();
+        let responses = parse_batch_results(jsonl, &order).unwrap();
+        let summary: Vec<_> = responses
+            .iter()
+            .map(|r| (r.custom_id.as_str(), r.text.as_deref(), r.error.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a", Some("done"), None),
+                ("b", None, Some("max_tokens too large")),
+                ("c", None, Some("Request expired")),
+                ("d", None, Some("No result returned")),
+            ]
+        );
+        // バッチAPIは半額
+        assert!((responses[0].cost - 3.0).abs() < 1e-9);
+    }
+}
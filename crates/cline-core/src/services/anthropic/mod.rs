@@ -2,24 +2,197 @@ use anyhow::Result;
 use async_trait::async_trait;
 use futures_util::StreamExt;
 use reqwest::{Client, StatusCode};
-use serde::{Deserialize, Serialize};
-#[cfg(test)]
+use serde::{Deserialize, Deserializer, Serialize};
 use std::sync::Arc;
 use std::{env, fmt::Debug};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::error::ClineError;
+use crate::services::api::ScriptedProvider;
+
+mod batch;
+mod stream;
+mod tokens;
+
+pub use batch::{
+    BatchOptions, BatchReport, BatchRequest, BatchResponse, DEFAULT_BATCH_CONCURRENCY,
+    DEFAULT_BATCH_POLL_INTERVAL,
+};
+pub use stream::{ApiStreamAccumulator, ApiStreamChunk, ApiUsage, SseParser, StreamedToolUse};
+pub use tokens::{estimate_tokens, HeuristicTokenCounter, TokenCounter};
+
+/// メッセージを構成するコンテンツブロック
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text {
+        text: String,
+    },
+    Image {
+        source: ImageSource,
+    },
+    /// ネイティブのツール呼び出し
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    /// ネイティブのツール呼び出しに対する結果
+    ToolResult {
+        tool_use_id: String,
+        content: Vec<ContentBlock>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        is_error: Option<bool>,
+    },
+}
+
+/// 画像ブロックのデータ（base64）
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ImageSource {
+    #[serde(rename = "type")]
+    pub source_type: String,
+    pub media_type: String,
+    pub data: String,
+}
+
+impl ContentBlock {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text { text: text.into() }
+    }
+
+    pub fn image(media_type: impl Into<String>, data: impl Into<String>) -> Self {
+        Self::Image {
+            source: ImageSource {
+                source_type: "base64".to_string(),
+                media_type: media_type.into(),
+                data: data.into(),
+            },
+        }
+    }
+
+    /// `data:image/png;base64,...` 形式のデータURLから画像ブロックを作成する
+    pub fn from_data_url(url: &str) -> Option<Self> {
+        let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+        let media_type = header.strip_suffix(";base64")?;
+        if !media_type.starts_with("image/") || data.is_empty() {
+            return None;
+        }
+        Some(Self::image(media_type, data))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Message {
     pub role: String,
-    pub content: String,
+    /// 以前の形式（文字列）で保存された履歴も読み込める
+    #[serde(deserialize_with = "deserialize_content")]
+    pub content: Vec<ContentBlock>,
     pub ts: Option<i64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ClaudeRequest {
+impl Message {
+    pub fn new(role: impl Into<String>, content: Vec<ContentBlock>) -> Self {
+        Self {
+            role: role.into(),
+            content,
+            ts: None,
+        }
+    }
+
+    /// テキストブロックを連結した文字列
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn deserialize_content<'de, D>(deserializer: D) -> Result<Vec<ContentBlock>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawContent {
+        Text(String),
+        Blocks(Vec<ContentBlock>),
+    }
+
+    Ok(match RawContent::deserialize(deserializer)? {
+        RawContent::Text(text) => vec![ContentBlock::text(text)],
+        RawContent::Blocks(blocks) => blocks,
+    })
+}
+
+/// ネイティブのツール呼び出しで送信するツール定義
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    /// 入力のJSON Schema
+    pub input_schema: serde_json::Value,
+}
+
+impl ToolDefinition {
+    /// OpenAI互換APIの `tools` 要素に変換する
+    pub fn to_openai_function(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": self.input_schema,
+            }
+        })
+    }
+}
+
+/// APIに送信するメッセージ（タイムスタンプは含めない）
+#[derive(Debug, Serialize)]
+struct ApiMessage {
+    role: String,
+    content: Vec<ContentBlock>,
+}
+
+impl From<Message> for ApiMessage {
+    fn from(message: Message) -> Self {
+        Self {
+            role: message.role,
+            content: message.content,
+        }
+    }
+}
+
+/// リクエストに使用するモデル
+const DEFAULT_MODEL: &str = "claude-3-sonnet-20240229";
+
+/// 応答の最大トークン数（拡張思考の予算はこれに加算する）
+const DEFAULT_MAX_TOKENS: u32 = 1000;
+
+/// 拡張思考の予算として指定できる最小トークン数
+pub const MIN_THINKING_BUDGET: u32 = 1024;
+
+/// 拡張思考の設定
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ThinkingConfig {
+    Enabled { budget_tokens: u32 },
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ClaudeRequest {
     model: String,
-    messages: Vec<Message>,
+    messages: Vec<ApiMessage>,
     max_tokens: u32,
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<ToolDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking: Option<ThinkingConfig>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -32,31 +205,34 @@ struct Content {
     text: String,
 }
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct StreamResponse {
-    #[serde(rename = "type")]
-    response_type: String,
-    index: Option<i32>,
-    delta: Option<Delta>,
-}
-
-#[derive(Debug, Deserialize)]
-struct Delta {
-    #[serde(rename = "type")]
-    delta_type: String,
-    text: String,
+/// ストリーミングで応答を受け取るリクエスト（`messages` は会話履歴全体）
+fn streaming_request(
+    messages: Vec<Message>,
+    tools: &Option<Vec<ToolDefinition>>,
+    thinking_budget: Option<u32>,
+) -> ClaudeRequest {
+    ClaudeRequest {
+        model: DEFAULT_MODEL.to_string(),
+        messages: messages.into_iter().map(ApiMessage::from).collect(),
+        // 思考のトークンも max_tokens に含まれるため、予算分を加算する
+        max_tokens: DEFAULT_MAX_TOKENS + thinking_budget.unwrap_or(0),
+        stream: true,
+        tools: tools.clone(),
+        thinking: thinking_budget.map(|budget_tokens| ThinkingConfig::Enabled { budget_tokens }),
+    }
 }
 
-pub type MessageCallback = Box<dyn FnMut(String) + Send + 'static>;
+/// ストリームのイベントを受け取るコールバック
+pub type MessageCallback = Box<dyn FnMut(ApiStreamChunk) + Send + 'static>;
 
 #[cfg_attr(test, mockall::automock)]
 #[async_trait]
 pub trait AnthropicClientTrait: Send + Sync + std::fmt::Debug {
     async fn send_message(&self, message: &str) -> Result<String>;
+    /// 会話履歴（最後はユーザーのメッセージ）を送り、アシスタントの応答をストリームで受け取る
     async fn attempt_api_request(
         &self,
-        user_content: String,
+        messages: Vec<Message>,
         include_file_details: bool,
         on_chunk: MessageCallback,
     ) -> Result<String>;
@@ -67,7 +243,13 @@ pub enum AnthropicClient {
     Real {
         client: Client,
         api_key: String,
+        /// 指定するとネイティブのツール呼び出しを使う
+        tools: Option<Vec<ToolDefinition>>,
+        /// 指定すると拡張思考を有効にする
+        thinking_budget: Option<u32>,
     },
+    /// 用意した応答を返す（APIを呼ばずにタスクを実行する）
+    Scripted(Arc<ScriptedProvider>),
     #[cfg(test)]
     Mock(Arc<MockAnthropicClientTrait>),
 }
@@ -80,9 +262,67 @@ impl AnthropicClient {
         Ok(Self::Real {
             client: Client::new(),
             api_key,
+            tools: None,
+            thinking_budget: None,
         })
     }
 
+    /// リクエストに使用するモデルのID
+    pub fn model_id(&self) -> &str {
+        DEFAULT_MODEL
+    }
+
+    /// ポリシーで許可を確認するAPIプロバイダの名前
+    pub fn provider_name(&self) -> &'static str {
+        match self {
+            Self::Real { .. } => "anthropic",
+            Self::Scripted(_) => "scripted",
+            #[cfg(test)]
+            Self::Mock(_) => "mock",
+        }
+    }
+
+    /// 拡張思考の予算を設定する（`None` で無効にする）
+    ///
+    /// APIの下限に満たない値は `MIN_THINKING_BUDGET` に切り上げる。
+    pub fn set_thinking_budget(&mut self, budget: Option<u32>) {
+        match self {
+            Self::Real {
+                thinking_budget, ..
+            } => *thinking_budget = budget.map(|b| b.max(MIN_THINKING_BUDGET)),
+            Self::Scripted(_) => {}
+            #[cfg(test)]
+            Self::Mock(_) => {}
+        }
+    }
+
+    /// `attempt_api_request` で送るリクエスト（APIを呼ばないクライアントは `None`）
+    #[cfg(test)]
+    pub(crate) fn streaming_request(&self, messages: Vec<Message>) -> Option<ClaudeRequest> {
+        match self {
+            Self::Real {
+                tools,
+                thinking_budget,
+                ..
+            } => Some(streaming_request(messages, tools, *thinking_budget)),
+            _ => None,
+        }
+    }
+
+    /// リクエストに含めるツール定義を設定する（`None` でXML形式に戻す）
+    pub fn set_tools(&mut self, new_tools: Option<Vec<ToolDefinition>>) {
+        match self {
+            Self::Real { tools, .. } => *tools = new_tools,
+            Self::Scripted(_) => {}
+            #[cfg(test)]
+            Self::Mock(_) => {}
+        }
+    }
+
+    pub fn scripted(provider: Arc<ScriptedProvider>) -> Self {
+        Self::Scripted(provider)
+    }
+
     #[cfg(test)]
     pub fn mock(mock: MockAnthropicClientTrait) -> Self {
         Self::Mock(Arc::new(mock))
@@ -93,16 +333,19 @@ impl AnthropicClient {
 impl AnthropicClientTrait for AnthropicClient {
     async fn send_message(&self, message: &str) -> Result<String> {
         match self {
-            Self::Real { client, api_key } => {
+            Self::Real {
+                client, api_key, ..
+            } => {
                 let request_body = ClaudeRequest {
-                    model: "claude-3-sonnet-20240229".to_string(),
-                    messages: vec![Message {
+                    model: DEFAULT_MODEL.to_string(),
+                    messages: vec![ApiMessage {
                         role: "user".to_string(),
-                        content: message.to_string(),
-                        ts: None,
+                        content: vec![ContentBlock::text(message)],
                     }],
-                    max_tokens: 1000,
+                    max_tokens: DEFAULT_MAX_TOKENS,
                     stream: false,
+                    tools: None,
+                    thinking: None,
                 };
 
                 let response = client
@@ -113,15 +356,19 @@ impl AnthropicClientTrait for AnthropicClient {
                     .header("anthropic-version", "2023-06-01")
                     .json(&request_body)
                     .send()
-                    .await?;
+                    .await
+                    .map_err(ClineError::from_request)?;
 
-                if response.status() != StatusCode::OK {
-                    anyhow::bail!("API request failed: {}", response.text().await?);
+                let status = response.status();
+                if status != StatusCode::OK {
+                    let error_text = response.text().await?;
+                    return Err(ClineError::from_status(status.as_u16(), &error_text).into());
                 }
 
                 let claude_response: ClaudeResponse = response.json().await?;
                 Ok(claude_response.content[0].text.clone())
             }
+            Self::Scripted(provider) => provider.send_message(message).await,
             #[cfg(test)]
             Self::Mock(mock) => mock.as_ref().send_message(message).await,
         }
@@ -129,22 +376,18 @@ impl AnthropicClientTrait for AnthropicClient {
 
     async fn attempt_api_request(
         &self,
-        user_content: String,
+        messages: Vec<Message>,
         _include_file_details: bool,
         mut on_chunk: MessageCallback,
     ) -> Result<String> {
         match self {
-            Self::Real { client, api_key } => {
-                let request_body = ClaudeRequest {
-                    model: "claude-3-sonnet-20240229".to_string(),
-                    messages: vec![Message {
-                        role: "user".to_string(),
-                        content: user_content,
-                        ts: None,
-                    }],
-                    max_tokens: 1000,
-                    stream: true,
-                };
+            Self::Real {
+                client,
+                api_key,
+                tools,
+                thinking_budget,
+            } => {
+                let request_body = streaming_request(messages, tools, *thinking_budget);
 
                 let response = client
                     .post("https://api.anthropic.com/v1/messages")
@@ -154,47 +397,138 @@ impl AnthropicClientTrait for AnthropicClient {
                     .header("anthropic-version", "2023-06-01")
                     .json(&request_body)
                     .send()
-                    .await?;
+                    .await
+                    .map_err(ClineError::from_request)?;
 
-                if response.status() != StatusCode::OK {
+                let status = response.status();
+                if status != StatusCode::OK {
                     let error_text = response.text().await?;
-                    tracing::error!("API request failed: {}", error_text);
-                    anyhow::bail!("API request failed: {}", error_text);
+                    return Err(ClineError::from_status(status.as_u16(), &error_text).into());
                 }
 
                 let mut stream = response.bytes_stream();
+                let mut parser = SseParser::new();
                 let mut assistant_message = String::new();
+                let mut emit = |chunk: ApiStreamChunk| {
+                    if let ApiStreamChunk::Text(text) = &chunk {
+                        assistant_message.push_str(text);
+                    }
+                    on_chunk(chunk);
+                };
 
-                while let Some(chunk) = stream.next().await {
-                    let chunk = chunk?.to_vec();
-                    let text = String::from_utf8_lossy(&chunk);
-
-                    for line in text.lines() {
-                        if let Some(data) = line.strip_prefix("data: ") {
-                            if data == "[DONE]" {
-                                continue;
-                            }
-
-                            if let Ok(response) = serde_json::from_str::<StreamResponse>(data) {
-                                if let Some(delta) = response.delta {
-                                    if delta.delta_type == "text_delta" {
-                                        assistant_message.push_str(&delta.text);
-                                        on_chunk(assistant_message.clone());
-                                    }
-                                }
-                            }
-                        }
+                while let Some(bytes) = stream.next().await {
+                    for chunk in parser.push(&bytes?)? {
+                        emit(chunk);
                     }
                 }
+                for chunk in parser.finish()? {
+                    emit(chunk);
+                }
 
                 Ok(assistant_message)
             }
+            Self::Scripted(provider) => {
+                provider
+                    .attempt_api_request(messages, _include_file_details, on_chunk)
+                    .await
+            }
             #[cfg(test)]
             Self::Mock(mock) => {
                 mock.as_ref()
-                    .attempt_api_request(user_content, _include_file_details, on_chunk)
+                    .attempt_api_request(messages, _include_file_details, on_chunk)
                     .await
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_image_block_from_data_url() {
+        assert_eq!(
+            ContentBlock::from_data_url("data:image/png;base64,iVBORw0KGgo="),
+            Some(ContentBlock::image("image/png", "iVBORw0KGgo="))
+        );
+        assert_eq!(
+            ContentBlock::from_data_url("data:text/plain;base64,aGk="),
+            None
+        );
+        assert_eq!(
+            ContentBlock::from_data_url("https://example.com/a.png"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_message_content_serialization() {
+        let message = Message::new(
+            "user",
+            vec![
+                ContentBlock::text("Describe this"),
+                ContentBlock::image("image/jpeg", "/9j/4AAQ"),
+            ],
+        );
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(
+            json["content"],
+            serde_json::json!([
+                { "type": "text", "text": "Describe this" },
+                {
+                    "type": "image",
+                    "source": { "type": "base64", "media_type": "image/jpeg", "data": "/9j/4AAQ" }
+                }
+            ])
+        );
+
+        let restored: Message = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.content, message.content);
+        assert_eq!(restored.text(), "Describe this");
+    }
+
+    #[test]
+    fn test_thinking_request_serialization() {
+        let request = ClaudeRequest {
+            model: "claude".to_string(),
+            messages: Vec::new(),
+            max_tokens: DEFAULT_MAX_TOKENS + 2048,
+            stream: true,
+            tools: None,
+            thinking: Some(ThinkingConfig::Enabled {
+                budget_tokens: 2048,
+            }),
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json["thinking"],
+            serde_json::json!({ "type": "enabled", "budget_tokens": 2048 })
+        );
+        assert_eq!(json["max_tokens"], 3048);
+        assert!(json.get("tools").is_none());
+
+        let mut client = AnthropicClient::Real {
+            client: Client::new(),
+            api_key: String::new(),
+            tools: None,
+            thinking_budget: None,
+        };
+        client.set_thinking_budget(Some(100));
+        assert!(matches!(
+            client,
+            AnthropicClient::Real {
+                thinking_budget: Some(MIN_THINKING_BUDGET),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_message_accepts_legacy_string_content() {
+        let message: Message =
+            serde_json::from_str(r#"{"role":"assistant","content":"hello","ts":1}"#).unwrap();
+        assert_eq!(message.content, vec![ContentBlock::text("hello")]);
+    }
+}
@@ -0,0 +1,393 @@
+use anyhow::Result;
+use cline_sse::{SseDecoder, SseEvent};
+use serde::Deserialize;
+
+use crate::error::ClineError;
+
+/// プロバイダーのストリームから得られるイベント
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiStreamChunk {
+    /// テキストの差分
+    Text(String),
+    /// 拡張思考の差分
+    Reasoning(String),
+    /// ツール使用ブロックの開始
+    ToolUseStart {
+        index: usize,
+        id: String,
+        name: String,
+    },
+    /// ツール入力（JSON）の差分
+    ToolUseInputDelta { index: usize, partial_json: String },
+    /// コンテンツブロックの終了
+    ContentBlockStop { index: usize },
+    /// トークン使用量（含まれる項目のみ）
+    Usage(ApiUsage),
+    /// メッセージの終了理由
+    MessageStop { stop_reason: Option<String> },
+}
+
+/// トークン使用量
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ApiUsage {
+    pub input_tokens: Option<u32>,
+    pub output_tokens: Option<u32>,
+    pub cache_creation_input_tokens: Option<u32>,
+    pub cache_read_input_tokens: Option<u32>,
+}
+
+impl ApiUsage {
+    /// 値を持つ項目で上書きする（出力トークン数は累計で届く）
+    pub fn merge(&mut self, other: &ApiUsage) {
+        self.input_tokens = other.input_tokens.or(self.input_tokens);
+        self.output_tokens = other.output_tokens.or(self.output_tokens);
+        self.cache_creation_input_tokens = other
+            .cache_creation_input_tokens
+            .or(self.cache_creation_input_tokens);
+        self.cache_read_input_tokens = other
+            .cache_read_input_tokens
+            .or(self.cache_read_input_tokens);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    MessageStart {
+        message: MessageStartBody,
+    },
+    ContentBlockStart {
+        index: usize,
+        content_block: StartBlock,
+    },
+    ContentBlockDelta {
+        index: usize,
+        delta: BlockDelta,
+    },
+    ContentBlockStop {
+        index: usize,
+    },
+    MessageDelta {
+        delta: MessageDeltaBody,
+        usage: Option<ApiUsage>,
+    },
+    MessageStop,
+    Ping,
+    Error {
+        error: ErrorBody,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageStartBody {
+    usage: Option<ApiUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StartBlock {
+    Text {
+        text: String,
+    },
+    Thinking {
+        thinking: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BlockDelta {
+    TextDelta {
+        text: String,
+    },
+    ThinkingDelta {
+        thinking: String,
+    },
+    InputJsonDelta {
+        partial_json: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDeltaBody {
+    stop_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    #[serde(rename = "type")]
+    error_type: String,
+    message: String,
+}
+
+impl StreamEvent {
+    fn into_chunks(self) -> Result<Vec<ApiStreamChunk>> {
+        let chunks = match self {
+            StreamEvent::MessageStart { message } => message
+                .usage
+                .map(ApiStreamChunk::Usage)
+                .into_iter()
+                .collect(),
+            StreamEvent::ContentBlockStart {
+                index,
+                content_block,
+            } => match content_block {
+                StartBlock::Text { text } if !text.is_empty() => vec![ApiStreamChunk::Text(text)],
+                StartBlock::Thinking { thinking } if !thinking.is_empty() => {
+                    vec![ApiStreamChunk::Reasoning(thinking)]
+                }
+                StartBlock::ToolUse { id, name } => {
+                    vec![ApiStreamChunk::ToolUseStart { index, id, name }]
+                }
+                _ => Vec::new(),
+            },
+            StreamEvent::ContentBlockDelta { index, delta } => match delta {
+                BlockDelta::TextDelta { text } => vec![ApiStreamChunk::Text(text)],
+                BlockDelta::ThinkingDelta { thinking } => vec![ApiStreamChunk::Reasoning(thinking)],
+                BlockDelta::InputJsonDelta { partial_json } => {
+                    vec![ApiStreamChunk::ToolUseInputDelta {
+                        index,
+                        partial_json,
+                    }]
+                }
+                BlockDelta::Other => Vec::new(),
+            },
+            StreamEvent::ContentBlockStop { index } => {
+                vec![ApiStreamChunk::ContentBlockStop { index }]
+            }
+            StreamEvent::MessageDelta { delta, usage } => {
+                let mut chunks: Vec<_> = usage.map(ApiStreamChunk::Usage).into_iter().collect();
+                chunks.push(ApiStreamChunk::MessageStop {
+                    stop_reason: delta.stop_reason,
+                });
+                chunks
+            }
+            StreamEvent::Error { error } => {
+                return Err(ClineError::Provider {
+                    status: None,
+                    retryable: matches!(
+                        error.error_type.as_str(),
+                        "overloaded_error" | "rate_limit_error" | "api_error"
+                    ),
+                    message: format!("API stream error ({}): {}", error.error_type, error.message),
+                }
+                .into())
+            }
+            StreamEvent::MessageStop | StreamEvent::Ping | StreamEvent::Unknown => Vec::new(),
+        };
+        Ok(chunks)
+    }
+}
+
+/// SSEのバイト列を `ApiStreamChunk` に変換する
+///
+/// ネットワークのチャンク境界で分割されたイベントは `SseDecoder` が組み立てる。
+#[derive(Debug, Default)]
+pub struct SseParser {
+    decoder: SseDecoder,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) -> Result<Vec<ApiStreamChunk>> {
+        let mut chunks = Vec::new();
+        for event in self.decoder.push(bytes) {
+            chunks.extend(parse_event(&event)?);
+        }
+        Ok(chunks)
+    }
+
+    /// 空行で終わらなかった最後のイベントを処理する
+    pub fn finish(&mut self) -> Result<Vec<ApiStreamChunk>> {
+        match self.decoder.finish() {
+            Some(event) => parse_event(&event),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+fn parse_event(event: &SseEvent) -> Result<Vec<ApiStreamChunk>> {
+    let data = event.data.trim();
+    if data.is_empty() || data == "[DONE]" {
+        return Ok(Vec::new());
+    }
+    match serde_json::from_str::<StreamEvent>(data) {
+        Ok(stream_event) => stream_event.into_chunks(),
+        // 本文を解析できないエラーイベントも失敗として扱う
+        Err(_) if event.event_type() == "error" => Err(ClineError::Provider {
+            status: None,
+            retryable: false,
+            message: format!("API stream error: {}", data),
+        }
+        .into()),
+        Err(e) => {
+            tracing::debug!("Skipping unparsable stream event: {} ({})", data, e);
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// ストリーム中のツール使用
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamedToolUse {
+    pub id: String,
+    pub name: String,
+    /// 受信済みの入力JSON（ブロックの終了までは不完全）
+    pub input_json: String,
+    pub complete: bool,
+}
+
+impl StreamedToolUse {
+    /// 入力JSONを解析する（空または不正な場合は空のオブジェクト）
+    pub fn input(&self) -> serde_json::Value {
+        serde_json::from_str(&self.input_json)
+            .ok()
+            .filter(serde_json::Value::is_object)
+            .unwrap_or_else(|| serde_json::json!({}))
+    }
+}
+
+/// `ApiStreamChunk` を順に適用して応答全体を組み立てる
+#[derive(Debug, Clone, Default)]
+pub struct ApiStreamAccumulator {
+    pub text: String,
+    pub reasoning: String,
+    pub tool_uses: Vec<(usize, StreamedToolUse)>,
+    pub usage: ApiUsage,
+    pub stop_reason: Option<String>,
+}
+
+impl ApiStreamAccumulator {
+    pub fn apply(&mut self, chunk: &ApiStreamChunk) {
+        match chunk {
+            ApiStreamChunk::Text(text) => self.text.push_str(text),
+            ApiStreamChunk::Reasoning(reasoning) => self.reasoning.push_str(reasoning),
+            ApiStreamChunk::ToolUseStart { index, id, name } => self.tool_uses.push((
+                *index,
+                StreamedToolUse {
+                    id: id.clone(),
+                    name: name.clone(),
+                    ..Default::default()
+                },
+            )),
+            ApiStreamChunk::ToolUseInputDelta {
+                index,
+                partial_json,
+            } => {
+                if let Some(tool_use) = self.tool_use_mut(*index) {
+                    tool_use.input_json.push_str(partial_json);
+                }
+            }
+            ApiStreamChunk::ContentBlockStop { index } => {
+                if let Some(tool_use) = self.tool_use_mut(*index) {
+                    tool_use.complete = true;
+                }
+            }
+            ApiStreamChunk::Usage(usage) => self.usage.merge(usage),
+            ApiStreamChunk::MessageStop { stop_reason } => {
+                self.stop_reason = stop_reason.clone();
+            }
+        }
+    }
+
+    fn tool_use_mut(&mut self, index: usize) -> Option<&mut StreamedToolUse> {
+        self.tool_uses
+            .iter_mut()
+            .find(|(i, _)| *i == index)
+            .map(|(_, tool_use)| tool_use)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const EVENTS: &str = concat!(
+        "event: message_start\n",
+        "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"usage\":{\"input_tokens\":25,\"output_tokens\":1,\"cache_read_input_tokens\":10}}}\n\n",
+        "event: content_block_start\n",
+        "data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"thinking\",\"thinking\":\"\"}}\n\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"thinking_delta\",\"thinking\":\"Let me think\"}}\n\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"signature_delta\",\"signature\":\"abc\"}}\n\n",
+        "data: {\"type\":\"content_block_stop\",\"index\":0}\n\n",
+        "event: ping\ndata: {\"type\":\"ping\"}\n\n",
+        "data: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n",
+        "data: {\"type\":\"content_block_start\",\"index\":2,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_1\",\"name\":\"read_file\",\"input\":{}}}\n\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":2,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"path\\\":\"}}\n\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":2,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"\\\"a.rs\\\"}\"}}\n\n",
+        "data: {\"type\":\"content_block_stop\",\"index\":2}\n\n",
+        "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"tool_use\"},\"usage\":{\"output_tokens\":42}}\n\n",
+        "data: {\"type\":\"message_stop\"}\n\n",
+    );
+
+    #[test]
+    fn test_parse_full_event_set() {
+        let mut parser = SseParser::new();
+        let mut accumulator = ApiStreamAccumulator::default();
+        // 任意の位置で分割されたチャンクでも同じ結果になる
+        for piece in EVENTS.as_bytes().chunks(7) {
+            for chunk in parser.push(piece).unwrap() {
+                accumulator.apply(&chunk);
+            }
+        }
+        assert!(parser.finish().unwrap().is_empty());
+
+        assert_eq!(accumulator.text, "Hello");
+        assert_eq!(accumulator.reasoning, "Let me think");
+        assert_eq!(
+            accumulator.tool_uses,
+            vec![(
+                2,
+                StreamedToolUse {
+                    id: "toolu_1".to_string(),
+                    name: "read_file".to_string(),
+                    input_json: "{\"path\":\"a.rs\"}".to_string(),
+                    complete: true,
+                }
+            )]
+        );
+        assert_eq!(
+            accumulator.usage,
+            ApiUsage {
+                input_tokens: Some(25),
+                output_tokens: Some(42),
+                cache_creation_input_tokens: None,
+                cache_read_input_tokens: Some(10),
+            }
+        );
+        assert_eq!(accumulator.stop_reason.as_deref(), Some("tool_use"));
+    }
+
+    #[test]
+    fn test_error_event_fails_stream() {
+        let mut parser = SseParser::new();
+        let err = parser
+            .push(b"event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n")
+            .unwrap_err();
+        assert!(err.to_string().contains("overloaded_error"));
+        assert!(ClineError::from(err).is_retryable());
+
+        let mut parser = SseParser::new();
+        assert!(parser
+            .push(b"event: error\r\ndata: upstream timeout")
+            .unwrap()
+            .is_empty());
+        let err = parser.finish().unwrap_err();
+        assert_eq!(err.to_string(), "API stream error: upstream timeout");
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+use super::{AnthropicClient, ApiMessage, ContentBlock, Message, ToolDefinition, DEFAULT_MODEL};
+
+const COUNT_TOKENS_URL: &str = "https://api.anthropic.com/v1/messages/count_tokens";
+
+/// 1トークンに当たるおおよその文字数
+const CHARS_PER_TOKEN: usize = 4;
+/// 画像1枚の推定トークン数（大きさが分からないため、APIが縮小する上限の大きさとみなす）
+const IMAGE_TOKENS: u32 = 1_600;
+/// メッセージごとの役割や区切りの推定トークン数
+const MESSAGE_OVERHEAD_TOKENS: u32 = 4;
+
+/// リクエストの入力トークン数を数える
+#[async_trait]
+pub trait TokenCounter: Debug + Send + Sync {
+    /// `messages` を送信した場合の入力トークン数
+    async fn count_tokens(&self, messages: &[Message]) -> Result<u32>;
+}
+
+/// 文字数からトークン数を推定する（APIを呼ばないため、数える手段のないプロバイダーで使う）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeuristicTokenCounter {
+    /// リクエストに含めるツール定義
+    pub tools: Option<Vec<ToolDefinition>>,
+}
+
+impl HeuristicTokenCounter {
+    pub fn new(tools: Option<Vec<ToolDefinition>>) -> Self {
+        Self { tools }
+    }
+}
+
+#[async_trait]
+impl TokenCounter for HeuristicTokenCounter {
+    async fn count_tokens(&self, messages: &[Message]) -> Result<u32> {
+        Ok(estimate_tokens(messages, self.tools.as_deref()))
+    }
+}
+
+/// `messages` と `tools` の入力トークン数を文字数から推定する
+pub fn estimate_tokens(messages: &[Message], tools: Option<&[ToolDefinition]>) -> u32 {
+    let messages: u32 = messages
+        .iter()
+        .map(|message| MESSAGE_OVERHEAD_TOKENS + blocks_tokens(&message.content))
+        .sum();
+    let tools: u32 = tools
+        .unwrap_or_default()
+        .iter()
+        .map(|tool| {
+            text_tokens(&tool.name)
+                + text_tokens(&tool.description)
+                + text_tokens(&tool.input_schema.to_string())
+        })
+        .sum();
+    messages + tools
+}
+
+fn blocks_tokens(blocks: &[ContentBlock]) -> u32 {
+    blocks
+        .iter()
+        .map(|block| match block {
+            ContentBlock::Text { text } => text_tokens(text),
+            ContentBlock::Image { .. } => IMAGE_TOKENS,
+            ContentBlock::ToolUse { name, input, .. } => {
+                text_tokens(name) + text_tokens(&input.to_string())
+            }
+            ContentBlock::ToolResult { content, .. } => blocks_tokens(content),
+        })
+        .sum()
+}
+
+fn text_tokens(text: &str) -> u32 {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN) as u32
+}
+
+#[derive(Debug, Serialize)]
+struct CountTokensRequest<'a> {
+    model: &'a str,
+    messages: Vec<ApiMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<&'a [ToolDefinition]>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CountTokensResponse {
+    input_tokens: u32,
+}
+
+/// Anthropicでは `/v1/messages/count_tokens` で数え、それ以外は文字数から推定する
+#[async_trait]
+impl TokenCounter for AnthropicClient {
+    async fn count_tokens(&self, messages: &[Message]) -> Result<u32> {
+        match self {
+            Self::Real {
+                client,
+                api_key,
+                tools,
+                ..
+            } => {
+                let request_body = CountTokensRequest {
+                    model: DEFAULT_MODEL,
+                    messages: messages
+                        .iter()
+                        .map(|message| ApiMessage {
+                            role: message.role.clone(),
+                            content: message.content.clone(),
+                        })
+                        .collect(),
+                    tools: tools.as_deref(),
+                };
+                let response = client
+                    .post(COUNT_TOKENS_URL)
+                    .header("accept", "application/json")
+                    .header("content-type", "application/json")
+                    .header("x-api-key", api_key)
+                    .header("anthropic-version", "2023-06-01")
+                    .json(&request_body)
+                    .send()
+                    .await?;
+                if response.status() != StatusCode::OK {
+                    anyhow::bail!("Token count request failed: {}", response.text().await?);
+                }
+                let count: CountTokensResponse = response.json().await?;
+                Ok(count.input_tokens)
+            }
+            _ => Ok(estimate_tokens(messages, None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_heuristic_token_counter() {
+        let messages = vec![
+            Message::new("user", vec![ContentBlock::text("a".repeat(40))]),
+            Message::new(
+                "assistant",
+                vec![
+                    ContentBlock::text("abcde"),
+                    ContentBlock::ToolUse {
+                        id: "toolu_1".to_string(),
+                        name: "read_file".to_string(),
+                        input: json!({"path": "a.rs"}),
+                    },
+                ],
+            ),
+            Message::new(
+                "user",
+                vec![ContentBlock::ToolResult {
+                    tool_use_id: "toolu_1".to_string(),
+                    content: vec![ContentBlock::image("image/png", "AAAA")],
+                    is_error: None,
+                }],
+            ),
+        ];
+        // 10 + (2 + 3 + 4) + 1600 + メッセージごとに4
+        assert_eq!(estimate_tokens(&messages, None), 1631);
+        assert_eq!(
+            HeuristicTokenCounter::default()
+                .count_tokens(&messages)
+                .await
+                .unwrap(),
+            1631
+        );
+
+        let tools = vec![ToolDefinition {
+            name: "read_file".to_string(),
+            description: "Read a file".to_string(),
+            input_schema: json!({"type": "object"}),
+        }];
+        let counter = HeuristicTokenCounter::new(Some(tools));
+        // 3 + 3 + 5
+        assert_eq!(counter.count_tokens(&messages).await.unwrap(), 1642);
+        assert_eq!(estimate_tokens(&[], None), 0);
+    }
+
+    #[test]
+    fn test_count_tokens_request_serialization() {
+        let messages = vec![ApiMessage {
+            role: "user".to_string(),
+            content: vec![ContentBlock::text("Hello")],
+        }];
+        let request = CountTokensRequest {
+            model: DEFAULT_MODEL,
+            messages,
+            tools: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "model": DEFAULT_MODEL,
+                "messages": [{"role": "user", "content": [{"type": "text", "text": "Hello"}]}],
+            })
+        );
+        let response: CountTokensResponse =
+            serde_json::from_str(r#"{"input_tokens": 2095}"#).unwrap();
+        assert_eq!(response.input_tokens, 2095);
+    }
+}
@@ -0,0 +1,69 @@
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+use super::{ScriptedProvider, ScriptedTurn};
+use crate::error::Result;
+use crate::services::anthropic::AnthropicClient;
+use crate::storage::StoragePaths;
+use crate::Cline;
+
+/// 一時ワークスペースで `ScriptedProvider` を使ってタスクを最後まで実行するためのハーネス
+///
+/// タスクデータは一時ワークスペース内の `.cline` に保存し、破棄時にまとめて削除される。
+pub struct TaskHarness {
+    workspace: TempDir,
+    provider: Arc<ScriptedProvider>,
+    cline: Cline,
+}
+
+impl TaskHarness {
+    pub fn new(turns: impl IntoIterator<Item = ScriptedTurn>) -> Result<Self> {
+        let workspace = tempfile::tempdir()?;
+        let provider = Arc::new(ScriptedProvider::new(turns));
+        let mut cline = Cline::with_client(
+            AnthropicClient::scripted(Arc::clone(&provider)),
+            workspace.path().to_path_buf(),
+            None,
+            None,
+            None,
+        )?;
+        cline.set_storage_paths(StoragePaths::workspace(workspace.path()));
+        Ok(Self {
+            workspace,
+            provider,
+            cline,
+        })
+    }
+
+    /// ワークスペースにファイルを作成する
+    pub fn write_file(&self, rel_path: &str, content: &str) -> Result<()> {
+        let path = self.workspace.path().join(rel_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// `start_task` でタスクを開始し、応答が尽きるかツール使用で止まるまで実行する
+    pub async fn run(&mut self, task: &str) -> Result<()> {
+        self.cline.start_task(Some(task.to_string()), None).await
+    }
+
+    pub fn workspace_path(&self) -> &Path {
+        self.workspace.path()
+    }
+
+    pub fn provider(&self) -> &ScriptedProvider {
+        &self.provider
+    }
+
+    pub fn cline(&self) -> &Cline {
+        &self.cline
+    }
+
+    pub fn cline_mut(&mut self) -> &mut Cline {
+        &mut self.cline
+    }
+}
@@ -0,0 +1,5 @@
+mod harness;
+mod scripted;
+
+pub use harness::TaskHarness;
+pub use scripted::{ScriptedProvider, ScriptedTurn};
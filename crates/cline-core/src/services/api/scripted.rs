@@ -0,0 +1,262 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::error::ClineError;
+use crate::services::anthropic::{
+    AnthropicClientTrait, ApiStreamChunk, ApiUsage, ContentBlock, Message, MessageCallback,
+};
+
+/// `ScriptedProvider` が返す1回分の応答
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptedTurn {
+    /// ストリームで順に送るイベント
+    pub chunks: Vec<ApiStreamChunk>,
+    /// 指定するとイベントを送った後にエラーを返す
+    pub error: Option<String>,
+    /// イベントを送った後、応答を返さずに待ち続ける（中断のテスト用）
+    pub hang: bool,
+}
+
+impl ScriptedTurn {
+    pub fn new(chunks: Vec<ApiStreamChunk>) -> Self {
+        Self {
+            chunks,
+            error: None,
+            hang: false,
+        }
+    }
+
+    /// テキストのみの応答
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::new(vec![ApiStreamChunk::Text(text.into())])
+    }
+
+    /// ネイティブのツール使用を追加する
+    pub fn with_tool_use(
+        mut self,
+        id: impl Into<String>,
+        name: impl Into<String>,
+        input: serde_json::Value,
+    ) -> Self {
+        let index = self.chunks.len();
+        self.chunks.extend([
+            ApiStreamChunk::ToolUseStart {
+                index,
+                id: id.into(),
+                name: name.into(),
+            },
+            ApiStreamChunk::ToolUseInputDelta {
+                index,
+                partial_json: input.to_string(),
+            },
+            ApiStreamChunk::ContentBlockStop { index },
+        ]);
+        self
+    }
+
+    /// 拡張思考を先頭に追加する
+    pub fn with_reasoning(mut self, reasoning: impl Into<String>) -> Self {
+        self.chunks
+            .insert(0, ApiStreamChunk::Reasoning(reasoning.into()));
+        self
+    }
+
+    /// トークン使用量を追加する
+    pub fn with_usage(mut self, input_tokens: u32, output_tokens: u32) -> Self {
+        self.chunks.push(ApiStreamChunk::Usage(ApiUsage {
+            input_tokens: Some(input_tokens),
+            output_tokens: Some(output_tokens),
+            ..Default::default()
+        }));
+        self
+    }
+
+    /// APIエラーとして失敗させる
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            chunks: Vec::new(),
+            error: Some(message.into()),
+            hang: false,
+        }
+    }
+
+    /// イベントを送った後、応答を返さずに待ち続ける
+    pub fn with_hang(mut self) -> Self {
+        self.hang = true;
+        self
+    }
+}
+
+/// あらかじめ用意した応答を順に返すプロバイダー（APIを呼ばずにタスクを実行するためのもの）
+#[derive(Debug, Default)]
+pub struct ScriptedProvider {
+    turns: Mutex<VecDeque<ScriptedTurn>>,
+    requests: Mutex<Vec<Vec<Message>>>,
+}
+
+impl ScriptedProvider {
+    pub fn new(turns: impl IntoIterator<Item = ScriptedTurn>) -> Self {
+        Self {
+            turns: Mutex::new(turns.into_iter().collect()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 応答を末尾に追加する
+    pub fn push(&self, turn: ScriptedTurn) {
+        self.turns.lock().unwrap().push_back(turn);
+    }
+
+    /// 受け取ったリクエストごとの最後のメッセージ（そのリクエストで追加したユーザーの内容）
+    pub fn requests(&self) -> Vec<Vec<ContentBlock>> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|messages| {
+                messages
+                    .last()
+                    .map(|message| message.content.clone())
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// 受け取ったリクエストごとの会話履歴全体
+    pub fn request_messages(&self) -> Vec<Vec<Message>> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// まだ返していない応答の数
+    pub fn remaining(&self) -> usize {
+        self.turns.lock().unwrap().len()
+    }
+
+    fn next_turn(&self) -> Result<ScriptedTurn> {
+        self.turns
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| anyhow::anyhow!("ScriptedProvider has no more responses"))
+    }
+}
+
+/// 台本のエラーを、再試行しないAPIリクエストの失敗として返す
+fn scripted_error(error: &str) -> ClineError {
+    ClineError::Provider {
+        status: None,
+        retryable: false,
+        message: format!("API request failed: {}", error),
+    }
+}
+
+#[async_trait]
+impl AnthropicClientTrait for ScriptedProvider {
+    async fn send_message(&self, message: &str) -> Result<String> {
+        self.requests.lock().unwrap().push(vec![Message::new(
+            "user",
+            vec![ContentBlock::text(message)],
+        )]);
+        let turn = self.next_turn()?;
+        if let Some(error) = turn.error {
+            return Err(scripted_error(&error).into());
+        }
+        Ok(turn
+            .chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                ApiStreamChunk::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn attempt_api_request(
+        &self,
+        messages: Vec<Message>,
+        _include_file_details: bool,
+        mut on_chunk: MessageCallback,
+    ) -> Result<String> {
+        self.requests.lock().unwrap().push(messages);
+        let turn = self.next_turn()?;
+
+        let mut assistant_message = String::new();
+        for chunk in turn.chunks {
+            if let ApiStreamChunk::Text(text) = &chunk {
+                assistant_message.push_str(text);
+            }
+            on_chunk(chunk);
+        }
+        if turn.hang {
+            std::future::pending::<()>().await;
+        }
+        if let Some(error) = turn.error {
+            return Err(scripted_error(&error).into());
+        }
+        Ok(assistant_message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_scripted_provider_replays_turns() {
+        let provider = ScriptedProvider::new([
+            ScriptedTurn::text("Hello")
+                .with_reasoning("Greet")
+                .with_tool_use(
+                    "toolu_1",
+                    "read_file",
+                    serde_json::json!({ "path": "a.rs" }),
+                ),
+            ScriptedTurn::error("overloaded"),
+        ]);
+
+        let chunks = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&chunks);
+        let text = provider
+            .attempt_api_request(
+                vec![Message::new("user", vec![ContentBlock::text("Hi")])],
+                false,
+                Box::new(move |chunk| received.lock().unwrap().push(chunk)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(text, "Hello");
+        assert_eq!(
+            chunks.lock().unwrap().clone(),
+            vec![
+                ApiStreamChunk::Reasoning("Greet".to_string()),
+                ApiStreamChunk::Text("Hello".to_string()),
+                ApiStreamChunk::ToolUseStart {
+                    index: 2,
+                    id: "toolu_1".to_string(),
+                    name: "read_file".to_string(),
+                },
+                ApiStreamChunk::ToolUseInputDelta {
+                    index: 2,
+                    partial_json: r#"{"path":"a.rs"}"#.to_string(),
+                },
+                ApiStreamChunk::ContentBlockStop { index: 2 },
+            ]
+        );
+
+        let error = provider.send_message("again").await.unwrap_err();
+        assert_eq!(error.to_string(), "API request failed: overloaded");
+        assert!(provider.send_message("more").await.is_err());
+        assert_eq!(
+            provider.requests(),
+            vec![
+                vec![ContentBlock::text("Hi")],
+                vec![ContentBlock::text("again")],
+                vec![ContentBlock::text("more")],
+            ]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::services::logging::redact;
+
+/// タスクディレクトリ内の操作ログのファイル名
+pub const AUDIT_LOG_FILE_NAME: &str = "audit_log.jsonl";
+
+/// 操作ログに記録する操作の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    FileRead,
+    FileWrite,
+    FileDelete,
+    /// ワークスペース外のファイルへのアクセスの確認
+    FileAccess,
+    Command,
+    BrowserNavigate,
+    Fetch,
+    McpCall,
+    /// MCPサーバーからのモデルの呼び出し
+    McpSampling,
+}
+
+/// 操作を実行するまでの承認の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    /// 確認せずに実行した
+    AutoApproved,
+    /// 確認して承認された
+    Approved,
+    /// 確認して拒否された（実行していない）
+    Denied,
+}
+
+/// JSON Lines形式で保存する操作ログの1行
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub ts: i64,
+    pub operation: AuditOperation,
+    /// 操作の対象（ファイルのパス、コマンド、URL、`server/tool` など）
+    pub target: String,
+    pub approval: ApprovalStatus,
+    pub success: bool,
+}
+
+#[derive(Debug, Default)]
+struct AuditFile {
+    path: Option<PathBuf>,
+    file: Option<File>,
+}
+
+/// タスク中のファイル・コマンド・ブラウザ・MCPの操作をタスクディレクトリに記録する
+///
+/// 無人で実行したタスクの監査に使う。ファイルは最初に記録する際に作成する。
+#[derive(Debug, Clone, Default)]
+pub struct AuditLog {
+    inner: Arc<Mutex<AuditFile>>,
+}
+
+impl AuditLog {
+    /// ファイルに書き出さない
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub fn new(task_dir: &Path) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(AuditFile {
+                path: Some(task_dir.join(AUDIT_LOG_FILE_NAME)),
+                file: None,
+            })),
+        }
+    }
+
+    pub fn path(&self) -> Option<PathBuf> {
+        self.inner.lock().unwrap().path.clone()
+    }
+
+    /// 操作を記録する（対象に含まれる秘密情報は伏せ字にする）
+    pub fn record(
+        &self,
+        operation: AuditOperation,
+        target: &str,
+        approval: ApprovalStatus,
+        success: bool,
+    ) {
+        let entry = AuditEntry {
+            ts: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64,
+            operation,
+            target: redact(target),
+            approval,
+            success,
+        };
+        // 記録に失敗してもタスクは続行する
+        if let Err(e) = self.write_entry(&entry) {
+            tracing::warn!("Failed to write audit log: {}", e);
+        }
+    }
+
+    fn write_entry(&self, entry: &AuditEntry) -> Result<()> {
+        let mut audit_file = self.inner.lock().unwrap();
+        let Some(path) = audit_file.path.clone() else {
+            return Ok(());
+        };
+        if audit_file.file.is_none() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            audit_file.file = Some(OpenOptions::new().create(true).append(true).open(&path)?);
+        }
+        let file = audit_file.file.as_mut().unwrap();
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// 記録したすべての操作（解析できない行は読み飛ばす）
+    pub fn entries(&self) -> Result<Vec<AuditEntry>> {
+        let Some(path) = self.path() else {
+            return Ok(Vec::new());
+        };
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut entries = Vec::new();
+        for line in BufReader::new(File::open(&path)?).lines() {
+            if let Ok(entry) = serde_json::from_str::<AuditEntry>(&line?) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_audit_log_entries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let task_dir = temp_dir.path().join("task-1");
+        let audit_log = AuditLog::new(&task_dir);
+        assert!(audit_log.entries().unwrap().is_empty());
+
+        audit_log.record(
+            AuditOperation::Command,
+            "curl -H 'Authorization: Bearer abc123' example.com",
+            ApprovalStatus::AutoApproved,
+            true,
+        );
+        audit_log.record(
+            AuditOperation::FileAccess,
+            "/etc/passwd",
+            ApprovalStatus::Denied,
+            false,
+        );
+
+        let entries = audit_log.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].target,
+            "curl -H 'Authorization: Bearer [REDACTED]' example.com"
+        );
+        assert_eq!(entries[1].operation, AuditOperation::FileAccess);
+        assert_eq!(entries[1].approval, ApprovalStatus::Denied);
+        let line = std::fs::read_to_string(task_dir.join(AUDIT_LOG_FILE_NAME)).unwrap();
+        assert!(line.contains(r#""operation":"command","#));
+        assert!(line.contains(r#""approval":"auto_approved","#));
+
+        let disabled = AuditLog::disabled();
+        disabled.record(
+            AuditOperation::FileRead,
+            "main.rs",
+            ApprovalStatus::AutoApproved,
+            true,
+        );
+        assert!(disabled.entries().unwrap().is_empty());
+    }
+}
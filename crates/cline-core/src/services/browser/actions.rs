@@ -0,0 +1,71 @@
+use super::BrowserSession;
+use anyhow::Result;
+use async_trait::async_trait;
+use headless_chrome::browser::tab::point::Point;
+use std::fmt;
+
+/// `browser_action` ツールで行うブラウザの操作
+///
+/// `BrowserSession` が実装する。テストではブラウザを起動しない実装に差し替える。
+#[async_trait]
+pub trait BrowserActions: fmt::Debug + Send {
+    /// ブラウザを起動して（起動済みの場合はそのまま）`url` を開く
+    async fn launch(&mut self, url: &str) -> Result<()>;
+
+    /// 表示範囲の `x`,`y` の位置をクリックする
+    async fn click(&mut self, x: f64, y: f64) -> Result<()>;
+
+    /// キーボードから `text` を入力する
+    async fn type_text(&mut self, text: &str) -> Result<()>;
+
+    /// 表示範囲の高さの `pages` 倍だけスクロールする（負の値で上へ）
+    async fn scroll(&mut self, pages: i32) -> Result<()>;
+
+    /// ブラウザを閉じる
+    async fn close(&mut self) -> Result<()>;
+
+    /// 表示範囲のスクリーンショット（`data:image/png;base64,...`）
+    async fn screenshot(&self) -> Result<String>;
+}
+
+#[async_trait]
+impl BrowserActions for BrowserSession {
+    async fn launch(&mut self, url: &str) -> Result<()> {
+        if !self.is_initialized() {
+            self.launch_browser().await?;
+        }
+        self.navigate(url).await
+    }
+
+    async fn click(&mut self, x: f64, y: f64) -> Result<()> {
+        self.current_tab()?
+            .tab
+            .click_point(Point { x, y })
+            .map_err(|e| anyhow::anyhow!("Failed to click at {},{}: {}", x, y, e))?;
+        Ok(())
+    }
+
+    async fn type_text(&mut self, text: &str) -> Result<()> {
+        self.current_tab()?
+            .tab
+            .type_str(text)
+            .map_err(|e| anyhow::anyhow!("Failed to type text: {}", e))?;
+        Ok(())
+    }
+
+    async fn scroll(&mut self, pages: i32) -> Result<()> {
+        self.current_tab()?.tab.evaluate(
+            &format!("window.scrollBy(0, {} * window.innerHeight)", pages),
+            false,
+        )?;
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        self.close_browser().await
+    }
+
+    async fn screenshot(&self) -> Result<String> {
+        self.capture_screenshot().await
+    }
+}
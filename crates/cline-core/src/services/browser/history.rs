@@ -0,0 +1,78 @@
+/// タブのナビゲーション履歴
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NavigationHistory {
+    entries: Vec<String>,
+    /// 表示中のページ（`entries` が空の場合は0）
+    position: usize,
+}
+
+impl NavigationHistory {
+    /// 新しいページに移動する（戻った後に移動した場合は先の履歴を捨てる）
+    pub fn push(&mut self, url: &str) {
+        if !self.entries.is_empty() {
+            self.entries.truncate(self.position + 1);
+        }
+        self.entries.push(url.to_string());
+        self.position = self.entries.len() - 1;
+    }
+
+    /// 前のページ（なければ `None`）
+    pub fn back(&mut self) -> Option<&str> {
+        if self.position == 0 {
+            return None;
+        }
+        self.position -= 1;
+        self.current()
+    }
+
+    /// 次のページ（なければ `None`）
+    pub fn forward(&mut self) -> Option<&str> {
+        if self.position + 1 >= self.entries.len() {
+            return None;
+        }
+        self.position += 1;
+        self.current()
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.entries.get(self.position).map(String::as_str)
+    }
+
+    /// 古い順のURL
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_navigation_history() {
+        let mut history = NavigationHistory::default();
+        assert_eq!(history.current(), None);
+        assert_eq!(history.back(), None);
+
+        history.push("http://localhost/a");
+        history.push("http://localhost/b");
+        history.push("http://localhost/c");
+        assert_eq!(history.back(), Some("http://localhost/b"));
+        assert_eq!(history.back(), Some("http://localhost/a"));
+        assert_eq!(history.back(), None);
+        assert_eq!(history.forward(), Some("http://localhost/b"));
+
+        // 戻った位置から移動すると先の履歴は消える
+        history.push("http://localhost/d");
+        assert_eq!(history.forward(), None);
+        assert_eq!(
+            history.entries(),
+            [
+                "http://localhost/a",
+                "http://localhost/b",
+                "http://localhost/d"
+            ]
+        );
+    }
+}
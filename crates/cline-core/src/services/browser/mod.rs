@@ -0,0 +1,424 @@
+mod actions;
+mod history;
+mod orphan;
+mod readability;
+mod screenshot;
+
+pub use actions::BrowserActions;
+pub use history::NavigationHistory;
+pub use orphan::{cleanup_orphaned_browsers, default_pid_dir};
+pub use readability::extract_readable_markdown;
+pub use screenshot::{ScreenshotDeduper, PAGE_UNCHANGED};
+
+use anyhow::Result;
+use base64::Engine;
+use headless_chrome::protocol::cdp::Page::CaptureScreenshotFormatOption;
+use headless_chrome::{Browser, LaunchOptionsBuilder, Tab};
+use html2md::parse_html;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// ページの読み込みを待つ間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// ページの読み込みを待つ方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WaitStrategy {
+    /// `load` イベントまで待つ
+    #[default]
+    Load,
+    /// DOMの構築（`document.readyState` が `loading` でなくなる）まで待つ
+    DomReady,
+    /// `load` の後、リソースの読み込みが `idle` の間途切れるまで待つ
+    NetworkIdle { idle: Duration },
+}
+
+/// ブラウザのタブ
+struct BrowserTab {
+    tab: Arc<Tab>,
+    history: NavigationHistory,
+}
+
+pub struct BrowserSession {
+    browser: Option<Browser>,
+    tabs: Vec<BrowserTab>,
+    /// 操作するタブ
+    active_tab: usize,
+    chrome_args: Vec<String>,
+    /// 最初に使うときに起動する
+    launch_on_demand: bool,
+    wait_strategy: WaitStrategy,
+    /// ページの読み込みを待つ時間の上限
+    navigation_timeout: Duration,
+    /// 起動したブラウザのプロセスIDを記録するディレクトリ（`None` で記録しない）
+    pid_dir: Option<PathBuf>,
+}
+
+impl fmt::Debug for BrowserSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BrowserSession")
+            .field("browser", &self.browser.is_some())
+            .field("tabs", &self.tabs.len())
+            .field("active_tab", &self.active_tab)
+            .field("launch_on_demand", &self.launch_on_demand)
+            .field("wait_strategy", &self.wait_strategy)
+            .field("navigation_timeout", &self.navigation_timeout)
+            .finish()
+    }
+}
+
+impl Default for BrowserSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BrowserSession {
+    pub fn new() -> Self {
+        Self {
+            browser: None,
+            tabs: Vec::new(),
+            active_tab: 0,
+            chrome_args: Vec::new(),
+            launch_on_demand: false,
+            wait_strategy: WaitStrategy::default(),
+            navigation_timeout: Duration::from_secs(30),
+            pid_dir: Some(default_pid_dir()),
+        }
+    }
+
+    /// 最初に使うときに起動するセッション（起動したブラウザはタスクの終了まで使い回す）
+    pub fn on_demand() -> Self {
+        let mut session = Self::new();
+        session.launch_on_demand = true;
+        session
+    }
+
+    pub fn launches_on_demand(&self) -> bool {
+        self.launch_on_demand
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.browser.is_some() && !self.tabs.is_empty()
+    }
+
+    pub fn set_chrome_args(&mut self, args: Vec<&str>) {
+        self.chrome_args = args.into_iter().map(String::from).collect();
+    }
+
+    pub fn set_wait_strategy(&mut self, strategy: WaitStrategy) {
+        self.wait_strategy = strategy;
+    }
+
+    pub fn set_navigation_timeout(&mut self, timeout: Duration) {
+        self.navigation_timeout = timeout;
+    }
+
+    /// 起動したブラウザを記録するディレクトリを変更する（`None` で記録せず、残ったブラウザも終了しない）
+    pub fn set_pid_dir(&mut self, pid_dir: Option<PathBuf>) {
+        self.pid_dir = pid_dir;
+    }
+
+    /// ブラウザを起動して1つ目のタブを開く
+    ///
+    /// 起動する前に、異常終了したプロセスが残したブラウザを終了する。
+    pub async fn launch_browser(&mut self) -> Result<()> {
+        if let Some(pid_dir) = &self.pid_dir {
+            cleanup_orphaned_browsers(pid_dir);
+        }
+        let args: Vec<&OsStr> = self.chrome_args.iter().map(OsStr::new).collect();
+        let mut builder = LaunchOptionsBuilder::default();
+        builder.headless(true);
+        builder.args(args);
+        let options = builder.build()?;
+
+        let browser = Browser::new(options)?;
+        if let (Some(pid_dir), Some(pid)) = (&self.pid_dir, browser.get_process_id()) {
+            if let Err(e) = orphan::register_browser(pid_dir, pid) {
+                tracing::warn!("Failed to record browser process {}: {}", pid, e);
+            }
+        }
+        let tab = browser.new_tab()?;
+
+        self.browser = Some(browser);
+        self.tabs = vec![self.browser_tab(tab)];
+        self.active_tab = 0;
+
+        Ok(())
+    }
+
+    /// 起動していなければ起動する（`on_demand` のセッションのみ）
+    ///
+    /// ブラウザが応答しない場合（クラッシュした場合など）は起動し直す。
+    pub async fn ensure_launched(&mut self) -> Result<()> {
+        if !self.launch_on_demand {
+            return Ok(());
+        }
+        if self.is_initialized() && !self.is_responsive() {
+            tracing::warn!("Browser is not responding, relaunching");
+            self.close_browser().await?;
+        }
+        if !self.is_initialized() {
+            self.launch_browser().await?;
+        }
+        Ok(())
+    }
+
+    fn is_responsive(&self) -> bool {
+        self.browser
+            .as_ref()
+            .is_some_and(|browser| browser.get_version().is_ok())
+    }
+
+    pub async fn close_browser(&mut self) -> Result<()> {
+        for tab in self.tabs.drain(..) {
+            let _ = tab.tab.close(false);
+        }
+        self.active_tab = 0;
+        if let Some(browser) = self.browser.take() {
+            if let (Some(pid_dir), Some(pid)) = (&self.pid_dir, browser.get_process_id()) {
+                orphan::unregister_browser(pid_dir, pid);
+            }
+        }
+        Ok(())
+    }
+
+    fn browser_tab(&self, tab: Arc<Tab>) -> BrowserTab {
+        tab.set_default_timeout(self.navigation_timeout);
+        BrowserTab {
+            tab,
+            history: NavigationHistory::default(),
+        }
+    }
+
+    /// 新しいタブを開いて操作するタブにし、その番号を返す
+    pub async fn open_tab(&mut self) -> Result<usize> {
+        let browser = self
+            .browser
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Browser not initialized"))?;
+        let tab = browser.new_tab()?;
+        let tab = self.browser_tab(tab);
+        self.tabs.push(tab);
+        self.active_tab = self.tabs.len() - 1;
+        Ok(self.active_tab)
+    }
+
+    /// 操作するタブを切り替える
+    pub fn switch_tab(&mut self, index: usize) -> Result<()> {
+        let tab = self
+            .tabs
+            .get(index)
+            .ok_or_else(|| anyhow::anyhow!("No browser tab {}", index))?;
+        tab.tab.activate()?;
+        self.active_tab = index;
+        Ok(())
+    }
+
+    /// タブを閉じる（操作するタブは直前のタブになる）
+    pub async fn close_tab(&mut self, index: usize) -> Result<()> {
+        if index >= self.tabs.len() {
+            anyhow::bail!("No browser tab {}", index);
+        }
+        let tab = self.tabs.remove(index);
+        tab.tab.close(false)?;
+        if self.tabs.is_empty() {
+            return self.close_browser().await;
+        }
+        if self.active_tab >= index && self.active_tab > 0 {
+            self.active_tab -= 1;
+        }
+        Ok(())
+    }
+
+    pub fn tab_count(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub fn active_tab(&self) -> usize {
+        self.active_tab
+    }
+
+    /// 操作するタブのナビゲーション履歴
+    pub fn history(&self) -> Option<&NavigationHistory> {
+        self.tabs.get(self.active_tab).map(|tab| &tab.history)
+    }
+
+    fn current_tab(&self) -> Result<&BrowserTab> {
+        self.tabs
+            .get(self.active_tab)
+            .ok_or_else(|| anyhow::anyhow!("Browser not initialized"))
+    }
+
+    /// 操作するタブで `url` に移動し、読み込みを待つ
+    pub async fn navigate(&mut self, url: &str) -> Result<()> {
+        self.load(url).await?;
+        if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+            tab.history.push(url);
+        }
+        Ok(())
+    }
+
+    /// 前のページに戻る（前のページがない場合は `false`）
+    pub async fn go_back(&mut self) -> Result<bool> {
+        let url = match self.tabs.get_mut(self.active_tab) {
+            Some(tab) => tab.history.back().map(String::from),
+            None => anyhow::bail!("Browser not initialized"),
+        };
+        match url {
+            Some(url) => self.load(&url).await.map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// 次のページに進む（次のページがない場合は `false`）
+    pub async fn go_forward(&mut self) -> Result<bool> {
+        let url = match self.tabs.get_mut(self.active_tab) {
+            Some(tab) => tab.history.forward().map(String::from),
+            None => anyhow::bail!("Browser not initialized"),
+        };
+        match url {
+            Some(url) => self.load(&url).await.map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    async fn load(&self, url: &str) -> Result<()> {
+        let tab = &self.current_tab()?.tab;
+        tab.navigate_to(url)
+            .map_err(|e| anyhow::anyhow!("Failed to navigate to URL: {}", e))?;
+        self.wait_for_page(tab).await
+    }
+
+    /// `wait_strategy` に従ってページの読み込みを待つ
+    async fn wait_for_page(&self, tab: &Tab) -> Result<()> {
+        let deadline = Instant::now() + self.navigation_timeout;
+        match self.wait_strategy {
+            WaitStrategy::Load => {
+                tab.wait_until_navigated()
+                    .map_err(|e| anyhow::anyhow!("Failed to wait for navigation: {}", e))?;
+            }
+            WaitStrategy::DomReady => loop {
+                let state = tab.evaluate("document.readyState", false)?;
+                if state.value.as_ref().and_then(|value| value.as_str()) != Some("loading") {
+                    break;
+                }
+                if Instant::now() >= deadline {
+                    anyhow::bail!("Timed out waiting for the page to load: {}", tab.get_url());
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            },
+            WaitStrategy::NetworkIdle { idle } => {
+                tab.wait_until_navigated()
+                    .map_err(|e| anyhow::anyhow!("Failed to wait for navigation: {}", e))?;
+                let mut resources = None;
+                let mut idle_since = Instant::now();
+                while idle_since.elapsed() < idle {
+                    // 長いポーリングなどで途切れないページは、時間切れの時点の内容を使う
+                    if Instant::now() >= deadline {
+                        tracing::warn!("Network did not become idle: {}", tab.get_url());
+                        break;
+                    }
+                    let count = tab
+                        .evaluate("performance.getEntriesByType('resource').length", false)?
+                        .value
+                        .and_then(|value| value.as_u64());
+                    if count != resources {
+                        resources = count;
+                        idle_since = Instant::now();
+                    }
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 操作するタブの表示範囲のスクリーンショット（`data:image/png;base64,...`）
+    ///
+    /// `ScreenshotDeduper` で前回と比べられるようPNGで取得する。
+    pub async fn capture_screenshot(&self) -> Result<String> {
+        let png = self.current_tab()?.tab.capture_screenshot(
+            CaptureScreenshotFormatOption::Png,
+            None,
+            None,
+            true,
+        )?;
+        Ok(format!(
+            "data:image/png;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(png)
+        ))
+    }
+
+    /// 操作するタブのページの本文をMarkdownで返す（スクリーンショットを撮らない）
+    pub async fn page_text(&self) -> Result<String> {
+        let content = self
+            .current_tab()?
+            .tab
+            .get_content()
+            .map_err(|e| anyhow::anyhow!("Failed to get page content: {}", e))?;
+        Ok(extract_readable_markdown(&content))
+    }
+
+    /// 操作するタブで `url` を開き、内容をMarkdownに変換する
+    pub async fn url_to_markdown(&mut self, url: &str) -> Result<String> {
+        self.navigate(url).await?;
+        let tab = &self.current_tab()?.tab;
+
+        // DOMが完全に読み込まれるまで待機
+        tab.wait_for_element_with_custom_timeout("body", self.navigation_timeout)
+            .map_err(|e| anyhow::anyhow!("Failed to wait for body element: {}", e))?;
+
+        // HTMLコンテンツを取得
+        let content = tab
+            .get_content()
+            .map_err(|e| anyhow::anyhow!("Failed to get page content: {}", e))?;
+
+        // HTMLをMarkdownに変換
+        let markdown = parse_html(&content);
+        Ok(markdown)
+    }
+}
+
+impl Drop for BrowserSession {
+    fn drop(&mut self) {
+        // ブラウザのプロセスは `Browser` のドロップで終了するため、記録だけ削除する
+        if let (Some(pid_dir), Some(pid)) = (
+            &self.pid_dir,
+            self.browser.as_ref().and_then(Browser::get_process_id),
+        ) {
+            orphan::unregister_browser(pid_dir, pid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_session_without_browser() {
+        let mut session = BrowserSession::new();
+        assert!(!session.is_initialized());
+        assert_eq!(session.tab_count(), 0);
+        assert!(session.history().is_none());
+        assert!(session.open_tab().await.is_err());
+        assert!(session.switch_tab(0).is_err());
+        assert!(session.close_tab(0).await.is_err());
+        assert!(session.go_back().await.is_err());
+        assert!(session.url_to_markdown("http://localhost").await.is_err());
+        assert!(session.page_text().await.is_err());
+        assert!(session.click(10.0, 10.0).await.is_err());
+        assert!(session.type_text("a").await.is_err());
+        assert!(session.scroll(1).await.is_err());
+        assert!(session.screenshot().await.is_err());
+
+        // 起動しないセッションでは何もしない
+        session.ensure_launched().await.unwrap();
+        assert!(!session.is_initialized());
+        session.close_browser().await.unwrap();
+    }
+}
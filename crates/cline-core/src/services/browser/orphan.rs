@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+
+/// 起動したブラウザのプロセスIDを記録するディレクトリの既定値
+pub fn default_pid_dir() -> PathBuf {
+    std::env::temp_dir().join("headless-cline-browsers")
+}
+
+fn pid_file(pid_dir: &Path, browser_pid: u32) -> PathBuf {
+    pid_dir.join(format!("chrome-{}.pid", browser_pid))
+}
+
+/// 起動したブラウザを記録する（ファイルの内容は起動したプロセスのID）
+pub fn register_browser(pid_dir: &Path, browser_pid: u32) -> std::io::Result<()> {
+    std::fs::create_dir_all(pid_dir)?;
+    std::fs::write(
+        pid_file(pid_dir, browser_pid),
+        std::process::id().to_string(),
+    )
+}
+
+/// 終了したブラウザの記録を削除する
+pub fn unregister_browser(pid_dir: &Path, browser_pid: u32) {
+    let _ = std::fs::remove_file(pid_file(pid_dir, browser_pid));
+}
+
+/// 起動したプロセスが異常終了して残ったブラウザを終了し、終了した数を返す
+///
+/// 起動したプロセスが動いている記録はそのまま残す。
+/// プロセスの状態を確認できない環境では何もしない。
+pub fn cleanup_orphaned_browsers(pid_dir: &Path) -> usize {
+    let Ok(entries) = std::fs::read_dir(pid_dir) else {
+        return 0;
+    };
+    let mut killed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(browser_pid) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("chrome-"))
+            .and_then(|name| name.strip_suffix(".pid"))
+            .and_then(|pid| pid.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let owner = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|owner| owner.trim().parse::<u32>().ok());
+        match owner.map(process_state) {
+            Some(ProcessState::Running) | Some(ProcessState::Unknown) => continue,
+            _ => {}
+        }
+        if process_state(browser_pid) == ProcessState::Running && is_browser(browser_pid) {
+            if kill(browser_pid) {
+                tracing::info!("Killed orphaned browser process {}", browser_pid);
+                killed += 1;
+            } else {
+                continue;
+            }
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+    killed
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProcessState {
+    Running,
+    Exited,
+    Unknown,
+}
+
+#[cfg(unix)]
+fn process_state(pid: u32) -> ProcessState {
+    match std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+    {
+        Ok(status) if status.success() => ProcessState::Running,
+        Ok(_) => ProcessState::Exited,
+        Err(_) => ProcessState::Unknown,
+    }
+}
+
+#[cfg(not(unix))]
+fn process_state(_pid: u32) -> ProcessState {
+    ProcessState::Unknown
+}
+
+/// 再利用された別のプロセスを終了しないよう、コマンドラインでChromeか確認する
+fn is_browser(pid: u32) -> bool {
+    std::fs::read(format!("/proc/{}/cmdline", pid))
+        .map(|cmdline| {
+            let cmdline = String::from_utf8_lossy(&cmdline).to_lowercase();
+            cmdline.contains("chrome") || cmdline.contains("chromium")
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(unix)]
+fn kill(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .args(["-9", &pid.to_string()])
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(not(unix))]
+fn kill(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cleanup_orphaned_browsers() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let pid_dir = temp_dir.path();
+        // 動いているプロセスが起動したブラウザ
+        register_browser(pid_dir, 4_000_001).unwrap();
+        // 終了したプロセスが起動し、すでに終了したブラウザ
+        std::fs::write(pid_file(pid_dir, 4_000_002), "4000003").unwrap();
+        std::fs::write(pid_dir.join("unrelated.txt"), "").unwrap();
+
+        assert_eq!(cleanup_orphaned_browsers(pid_dir), 0);
+        assert!(pid_file(pid_dir, 4_000_001).exists());
+        assert!(!pid_file(pid_dir, 4_000_002).exists());
+        assert!(pid_dir.join("unrelated.txt").exists());
+
+        unregister_browser(pid_dir, 4_000_001);
+        assert!(!pid_file(pid_dir, 4_000_001).exists());
+        assert_eq!(cleanup_orphaned_browsers(&pid_dir.join("missing")), 0);
+    }
+}
@@ -0,0 +1,164 @@
+use html2md::parse_html;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// 本文に含めない要素（中身ごと削除する）
+const REMOVED_ELEMENTS: &[&str] = &[
+    "head", "title", "script", "style", "noscript", "template", "svg", "canvas", "iframe", "nav",
+    "aside",
+];
+
+/// 本文の要素が見つからない場合に `body` から削除する要素
+const PAGE_CHROME_ELEMENTS: &[&str] = &["header", "footer"];
+
+lazy_static! {
+    static ref COMMENT: Regex = Regex::new(r"(?s)<!--.*?-->").unwrap();
+    static ref TITLE: Regex = Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap();
+    static ref BLANK_LINES: Regex = Regex::new(r"\n[ \t]*(\n[ \t]*)+").unwrap();
+}
+
+/// ページのHTMLから本文を取り出してMarkdownにする（スクリーンショットの代わりに使う）
+///
+/// スクリプトやナビゲーションを除き、`article`、`main`、`body` の順に最初に見つかった要素を本文とする。
+/// `<title>` があれば見出しとして先頭に付ける。
+pub fn extract_readable_markdown(html: &str) -> String {
+    let mut html = COMMENT.replace_all(html, "").into_owned();
+    let title = TITLE
+        .captures(&html)
+        .map(|captures| collapse_whitespace(&captures[1]))
+        .filter(|title| !title.is_empty());
+    for tag in REMOVED_ELEMENTS {
+        html = remove_elements(&html, tag);
+    }
+
+    let content = match element_inner(&html, "article").or_else(|| element_inner(&html, "main")) {
+        Some(content) => content.to_string(),
+        None => {
+            let mut body = element_inner(&html, "body").unwrap_or(&html).to_string();
+            for tag in PAGE_CHROME_ELEMENTS {
+                body = remove_elements(&body, tag);
+            }
+            body
+        }
+    };
+
+    let markdown = parse_html(&content);
+    let markdown = BLANK_LINES.replace_all(markdown.trim(), "\n\n");
+    match title {
+        Some(title) => format!("# {}\n\n{}", title, markdown),
+        _ => markdown.into_owned(),
+    }
+}
+
+/// `tag` の要素を中身ごと削除する（閉じタグのない要素は開始タグから最後までを削除する）
+fn remove_elements(html: &str, tag: &str) -> String {
+    let open = open_tag(tag);
+    let close = close_tag(tag);
+    let mut result = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = open.find(rest) {
+        result.push_str(&rest[..start.start()]);
+        rest = &rest[start.end()..];
+        // 同じ要素の入れ子を数えて対応する閉じタグを探す
+        let mut depth = 1;
+        let mut end = rest.len();
+        let mut position = 0;
+        while let Some(next_close) = close.find_at(rest, position) {
+            depth += open.find_iter(&rest[position..next_close.start()]).count();
+            depth -= 1;
+            position = next_close.end();
+            if depth == 0 {
+                end = position;
+                break;
+            }
+        }
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// 最初の `tag` の要素の中身（最後の閉じタグまで）
+fn element_inner<'a>(html: &'a str, tag: &str) -> Option<&'a str> {
+    let start = open_tag(tag).find(html)?.end();
+    let end = close_tag(tag)
+        .find_iter(&html[start..])
+        .last()
+        .map_or(html.len(), |close| start + close.start());
+    Some(&html[start..end])
+}
+
+fn open_tag(tag: &str) -> Regex {
+    Regex::new(&format!(r"(?i)<{}(\s[^>]*)?>", tag)).unwrap()
+}
+
+fn close_tag(tag: &str) -> Regex {
+    Regex::new(&format!(r"(?i)</{}\s*>", tag)).unwrap()
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_extract_readable_markdown() {
+        let html = r#"<html><head><title>
+            Release notes
+        </title><style>body { color: red; }</style></head>
+        <body>
+            <header><a href="/">Home</a></header>
+            <nav><ul><li>Docs</li></ul></nav>
+            <main>
+                <h1>Version 2.0</h1>
+                <!-- hidden -->
+                <p>Adds <strong>tabs</strong>.</p>
+                <script>track("view")</script>
+                <aside><div>Sponsored <div>nested</div></div></aside>
+                <p>Fixes bugs.</p>
+            </main>
+            <footer>Copyright</footer>
+        </body></html>"#;
+        let markdown = extract_readable_markdown(html);
+        assert!(markdown.starts_with("# Release notes\n\nVersion 2.0\n"));
+        assert!(markdown.contains("Adds **tabs**."));
+        assert!(markdown.contains("Fixes bugs."));
+        for removed in [
+            "Home",
+            "Docs",
+            "hidden",
+            "track",
+            "Sponsored",
+            "nested",
+            "Copyright",
+        ] {
+            assert!(!markdown.contains(removed), "{}", removed);
+        }
+    }
+
+    #[test]
+    fn test_extract_readable_markdown_from_body() {
+        let html = "<title>Login</title><header>Site</header><p>Sign in</p><p>to continue</p><footer>Help</footer>";
+        assert_eq!(
+            extract_readable_markdown(html),
+            "# Login\n\nSign in\n\nto continue"
+        );
+    }
+
+    #[test]
+    fn test_remove_elements() {
+        assert_eq!(
+            remove_elements("a<div>b<div>c</div>d</div>e<DIV class=\"x\">f", "div"),
+            "ae"
+        );
+        // 名前が前方一致する別の要素は残す
+        assert_eq!(
+            remove_elements("<nav>x</nav><navbar>y</navbar>", "nav"),
+            "<navbar>y</navbar>"
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use base64::Engine;
+
+/// 前回と同じ画面のスクリーンショットの代わりに返すテキスト
+pub const PAGE_UNCHANGED: &str = "(page unchanged)";
+
+/// 同じ画面とみなすハッシュの差（異なるビットの数）
+const UNCHANGED_DISTANCE: u32 = 3;
+
+/// 連続する同じ画面のスクリーンショットを省く（画像のトークンを節約する）
+///
+/// 知覚ハッシュ（dHash）で比べるため、カーソルの点滅などのわずかな違いは同じ画面とみなす。
+/// ハッシュを計算できない形式（PNG以外）のスクリーンショットは常に送る。
+#[derive(Debug, Clone, Default)]
+pub struct ScreenshotDeduper {
+    last_hash: Option<u64>,
+    /// 同じ画面でもスクリーンショットを送る
+    force_full: bool,
+}
+
+impl ScreenshotDeduper {
+    pub fn set_force_full(&mut self, force_full: bool) {
+        self.force_full = force_full;
+    }
+
+    pub fn force_full(&self) -> bool {
+        self.force_full
+    }
+
+    /// `data_url` のスクリーンショットが前回と同じ画面か（前回のスクリーンショットとして記録する）
+    pub fn is_unchanged(&mut self, data_url: &str) -> bool {
+        let hash = screenshot_hash(data_url);
+        let previous = std::mem::replace(&mut self.last_hash, hash);
+        if self.force_full {
+            return false;
+        }
+        match (previous, hash) {
+            (Some(previous), Some(hash)) => (previous ^ hash).count_ones() <= UNCHANGED_DISTANCE,
+            _ => false,
+        }
+    }
+
+    /// 前回のスクリーンショットを忘れる（ブラウザを閉じた場合など）
+    pub fn reset(&mut self) {
+        self.last_hash = None;
+    }
+}
+
+/// `data:image/png;base64,...` のスクリーンショットの知覚ハッシュ
+fn screenshot_hash(data_url: &str) -> Option<u64> {
+    let data = data_url.strip_prefix("data:image/png;base64,")?;
+    let png = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .ok()?;
+    let (width, height, luma) = decode_png_luma(&png)?;
+    Some(difference_hash(width, height, &luma))
+}
+
+/// 9x8に縮小したグレースケール画像で、横に隣り合う画素の明暗から64ビットのハッシュを作る
+fn difference_hash(width: usize, height: usize, luma: &[u8]) -> u64 {
+    const COLUMNS: usize = 9;
+    const ROWS: usize = 8;
+    let mut cells = [[0u64; COLUMNS]; ROWS];
+    let mut counts = [[0u64; COLUMNS]; ROWS];
+    for y in 0..height {
+        let row = y * ROWS / height;
+        for x in 0..width {
+            let column = x * COLUMNS / width;
+            cells[row][column] += u64::from(luma[y * width + x]);
+            counts[row][column] += 1;
+        }
+    }
+    let mut hash = 0u64;
+    for row in 0..ROWS {
+        for column in 0..COLUMNS - 1 {
+            let left = cells[row][column] / counts[row][column].max(1);
+            let right = cells[row][column + 1] / counts[row][column + 1].max(1);
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    hash
+}
+
+/// 8ビットのインターレースなしのPNGをグレースケールの画素にする（それ以外は `None`）
+#[cfg(feature = "screenshot-diff")]
+fn decode_png_luma(png: &[u8]) -> Option<(usize, usize, Vec<u8>)> {
+    use std::io::Read;
+
+    let mut rest = png.strip_prefix(b"\x89PNG\r\n\x1a\n")?;
+    let mut header = None;
+    let mut compressed = Vec::new();
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+        let kind = &rest[4..8];
+        let data = rest.get(8..8 + length)?;
+        match kind {
+            b"IHDR" if data.len() >= 13 => header = Some(data.to_vec()),
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = rest.get(12 + length..)?;
+    }
+    let header = header?;
+    let width = u32::from_be_bytes(header[..4].try_into().ok()?) as usize;
+    let height = u32::from_be_bytes(header[4..8].try_into().ok()?) as usize;
+    let (bit_depth, color_type, interlace) = (header[8], header[9], header[12]);
+    let channels = match color_type {
+        0 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return None,
+    };
+    if bit_depth != 8 || interlace != 0 || width == 0 || height == 0 {
+        return None;
+    }
+
+    let stride = width * channels;
+    let mut raw = Vec::with_capacity((stride + 1) * height);
+    flate2::read::ZlibDecoder::new(compressed.as_slice())
+        .read_to_end(&mut raw)
+        .ok()?;
+    if raw.len() < (stride + 1) * height {
+        return None;
+    }
+
+    let mut previous = vec![0u8; stride];
+    let mut current = vec![0u8; stride];
+    let mut luma = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let line = &raw[y * (stride + 1)..(y + 1) * (stride + 1)];
+        let filter = line[0];
+        for i in 0..stride {
+            let a = if i >= channels {
+                current[i - channels]
+            } else {
+                0
+            };
+            let b = previous[i];
+            let c = if i >= channels {
+                previous[i - channels]
+            } else {
+                0
+            };
+            let predictor = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((u16::from(a) + u16::from(b)) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return None,
+            };
+            current[i] = line[i + 1].wrapping_add(predictor);
+        }
+        for pixel in current.chunks(channels) {
+            let value = match channels {
+                1 | 2 => u32::from(pixel[0]),
+                _ => {
+                    (299 * u32::from(pixel[0])
+                        + 587 * u32::from(pixel[1])
+                        + 114 * u32::from(pixel[2]))
+                        / 1000
+                }
+            };
+            luma.push(value as u8);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    Some((width, height, luma))
+}
+
+#[cfg(not(feature = "screenshot-diff"))]
+fn decode_png_luma(_png: &[u8]) -> Option<(usize, usize, Vec<u8>)> {
+    None
+}
+
+#[cfg(feature = "screenshot-diff")]
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let pa = (p - i16::from(a)).abs();
+    let pb = (p - i16::from(b)).abs();
+    let pc = (p - i16::from(c)).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(all(test, feature = "screenshot-diff"))]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    /// グレースケールのPNG（CRCは検証しないため0にする）
+    fn png_data_url(width: usize, height: usize, pixel: impl Fn(usize, usize) -> u8) -> String {
+        let mut raw = Vec::new();
+        for y in 0..height {
+            // 行ごとにフィルタを変えて復元を確かめる（1行目はフィルタなし、以降は上との差）
+            raw.push(if y == 0 { 0 } else { 2 });
+            for x in 0..width {
+                let value = pixel(x, y);
+                raw.push(if y == 0 {
+                    value
+                } else {
+                    value.wrapping_sub(pixel(x, y - 1))
+                });
+            }
+        }
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&raw).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut chunk = |kind: &[u8], data: &[u8]| {
+            png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            png.extend_from_slice(kind);
+            png.extend_from_slice(data);
+            png.extend_from_slice(&[0; 4]);
+        };
+        let mut header = Vec::new();
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(height as u32).to_be_bytes());
+        header.extend_from_slice(&[8, 0, 0, 0, 0]);
+        chunk(b"IHDR", &header);
+        chunk(b"IDAT", &compressed);
+        chunk(b"IEND", &[]);
+        format!(
+            "data:image/png;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(png)
+        )
+    }
+
+    #[test]
+    fn test_screenshot_deduper() {
+        let page = png_data_url(64, 48, |x, y| ((x * 3 + y * 2) % 256) as u8);
+        // 1画素だけ異なる（カーソルの点滅など）
+        let blinked = png_data_url(64, 48, |x, y| {
+            if (x, y) == (10, 10) {
+                255
+            } else {
+                ((x * 3 + y * 2) % 256) as u8
+            }
+        });
+        let other = png_data_url(64, 48, |x, _| if x % 16 < 8 { 0 } else { 255 });
+
+        let mut deduper = ScreenshotDeduper::default();
+        assert!(!deduper.is_unchanged(&page));
+        assert!(deduper.is_unchanged(&page));
+        assert!(deduper.is_unchanged(&blinked));
+        assert!(!deduper.is_unchanged(&other));
+        // ハッシュを計算できない形式は常に送る
+        assert!(!deduper.is_unchanged("data:image/webp;base64,UklGR"));
+        assert!(!deduper.is_unchanged(&other));
+
+        deduper.set_force_full(true);
+        assert!(!deduper.is_unchanged(&other));
+        deduper.set_force_full(false);
+        deduper.reset();
+        assert!(!deduper.is_unchanged(&other));
+    }
+}
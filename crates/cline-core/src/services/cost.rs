@@ -0,0 +1,87 @@
+use crate::services::anthropic::ApiUsage;
+
+/// モデルの料金（100万トークンあたりのUSD）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+    pub input_price: f64,
+    pub output_price: f64,
+    pub cache_writes_price: f64,
+    pub cache_reads_price: f64,
+}
+
+impl ModelPricing {
+    const fn new(input: f64, output: f64, cache_writes: f64, cache_reads: f64) -> Self {
+        Self {
+            input_price: input,
+            output_price: output,
+            cache_writes_price: cache_writes,
+            cache_reads_price: cache_reads,
+        }
+    }
+
+    /// 使用量から料金を計算する
+    ///
+    /// Anthropicの `input_tokens` にはキャッシュの読み書き分が含まれないため、それぞれ加算する。
+    pub fn calculate(&self, usage: &ApiUsage) -> f64 {
+        let cost =
+            |tokens: Option<u32>, price: f64| tokens.unwrap_or(0) as f64 * price / 1_000_000.0;
+        cost(usage.input_tokens, self.input_price)
+            + cost(usage.output_tokens, self.output_price)
+            + cost(usage.cache_creation_input_tokens, self.cache_writes_price)
+            + cost(usage.cache_read_input_tokens, self.cache_reads_price)
+    }
+}
+
+/// モデルIDの接頭辞ごとの料金表（より具体的な接頭辞を先に並べる）
+const PRICING_TABLE: &[(&str, ModelPricing)] = &[
+    ("claude-opus-4", ModelPricing::new(15.0, 75.0, 18.75, 1.5)),
+    ("claude-sonnet-4", ModelPricing::new(3.0, 15.0, 3.75, 0.3)),
+    ("claude-3-7-sonnet", ModelPricing::new(3.0, 15.0, 3.75, 0.3)),
+    ("claude-3-5-sonnet", ModelPricing::new(3.0, 15.0, 3.75, 0.3)),
+    ("claude-3-5-haiku", ModelPricing::new(0.8, 4.0, 1.0, 0.08)),
+    ("claude-3-opus", ModelPricing::new(15.0, 75.0, 18.75, 1.5)),
+    ("claude-3-sonnet", ModelPricing::new(3.0, 15.0, 3.75, 0.3)),
+    ("claude-3-haiku", ModelPricing::new(0.25, 1.25, 0.3, 0.03)),
+];
+
+/// モデルIDに対応する料金を取得する
+pub fn get_model_pricing(model_id: &str) -> Option<ModelPricing> {
+    PRICING_TABLE
+        .iter()
+        .find(|(prefix, _)| model_id.starts_with(prefix))
+        .map(|(_, pricing)| *pricing)
+}
+
+/// APIリクエスト1回分の料金を計算する（料金が不明なモデルは0）
+pub fn calculate_api_cost(model_id: &str, usage: &ApiUsage) -> f64 {
+    get_model_pricing(model_id)
+        .map(|pricing| pricing.calculate(usage))
+        .unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calculate_api_cost() {
+        let usage = ApiUsage {
+            input_tokens: Some(1_000_000),
+            output_tokens: Some(100_000),
+            cache_creation_input_tokens: Some(200_000),
+            cache_read_input_tokens: Some(1_000_000),
+        };
+        let cost = calculate_api_cost("claude-3-5-sonnet-20241022", &usage);
+        assert!((cost - (3.0 + 1.5 + 0.75 + 0.3)).abs() < 1e-9);
+
+        assert_eq!(
+            get_model_pricing("claude-3-5-haiku-20241022"),
+            Some(ModelPricing::new(0.8, 4.0, 1.0, 0.08))
+        );
+        assert_eq!(calculate_api_cost("unknown-model", &usage), 0.0);
+        assert_eq!(
+            calculate_api_cost("claude-3-haiku-20240307", &ApiUsage::default()),
+            0.0
+        );
+    }
+}
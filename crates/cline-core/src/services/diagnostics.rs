@@ -1,3 +1,4 @@
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -10,7 +11,7 @@ pub struct Diagnostic {
     pub line: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DiagnosticSeverity {
     Error,
     Warning,
@@ -18,6 +19,105 @@ pub enum DiagnosticSeverity {
     Hint,
 }
 
+impl DiagnosticSeverity {
+    /// 重大度の順位（小さいほど重大）
+    fn rank(self) -> u8 {
+        match self {
+            DiagnosticSeverity::Error => 0,
+            DiagnosticSeverity::Warning => 1,
+            DiagnosticSeverity::Information => 2,
+            DiagnosticSeverity::Hint => 3,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            DiagnosticSeverity::Error => "error",
+            DiagnosticSeverity::Warning => "warning",
+            DiagnosticSeverity::Information => "info",
+            DiagnosticSeverity::Hint => "hint",
+        }
+    }
+}
+
+/// 表示する診断の絞り込み条件
+#[derive(Debug, Clone)]
+pub struct DiagnosticsFilter {
+    /// この重大度以上の診断のみを含める
+    pub min_severity: DiagnosticSeverity,
+    /// ワークスペースからの相対パスに対するglob（`src/**`, `**/*.rs` など）
+    pub path_pattern: Option<String>,
+}
+
+impl Default for DiagnosticsFilter {
+    fn default() -> Self {
+        Self {
+            min_severity: DiagnosticSeverity::Error,
+            path_pattern: None,
+        }
+    }
+}
+
+impl DiagnosticsFilter {
+    /// 警告も含める
+    pub fn with_warnings(mut self) -> Self {
+        self.min_severity = DiagnosticSeverity::Warning;
+        self
+    }
+
+    pub fn with_path_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.path_pattern = Some(pattern.into());
+        self
+    }
+
+    fn includes(&self, severity: DiagnosticSeverity) -> bool {
+        severity.rank() <= self.min_severity.rank()
+    }
+
+    fn path_matcher(&self) -> Option<Regex> {
+        self.path_pattern.as_deref().map(glob_to_regex)
+    }
+}
+
+/// globをパス全体に一致する正規表現に変換する
+///
+/// ワイルドカードを含まないパターンは、そのパス自体と配下に一致する。
+pub(crate) fn glob_to_regex(pattern: &str) -> Regex {
+    let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+    if !pattern.contains(['*', '?']) {
+        return Regex::new(&format!("^{}(?:/.*)?$", regex::escape(pattern))).unwrap();
+    }
+
+    let mut regex = String::from("^");
+    let mut rest = pattern;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("**/") {
+            regex.push_str("(?:.*/)?");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("**") {
+            regex.push_str(".*");
+            rest = after;
+        } else {
+            match c {
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                _ => regex.push_str(&regex::escape(&c.to_string())),
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).unwrap()
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        format!("{} {}", count, word)
+    } else {
+        format!("{} {}s", count, word)
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct DiagnosticsProvider {
     diagnostics: HashMap<PathBuf, Vec<Diagnostic>>,
@@ -49,35 +149,177 @@ impl DiagnosticsProvider {
         self.diagnostics.clear();
     }
 
+    /// エラーのみを表示する
     pub fn format_diagnostics(&self) -> String {
-        let mut result = String::new();
+        self.format_filtered_diagnostics(&DiagnosticsFilter::default(), None)
+    }
 
-        for (path, diagnostics) in &self.diagnostics {
-            let errors: Vec<_> = diagnostics
-                .iter()
-                .filter(|d| matches!(d.severity, DiagnosticSeverity::Error))
-                .collect();
-
-            if !errors.is_empty() {
-                result.push_str(&format!("\n## {}", path.display()));
-                for diagnostic in errors {
-                    let source = diagnostic
-                        .source
-                        .as_ref()
-                        .map(|s| format!("[{}] ", s))
-                        .unwrap_or_default();
-                    result.push_str(&format!(
-                        "\n- {}Line {}: {}",
-                        source, diagnostic.line, diagnostic.message
-                    ));
+    /// 条件に一致する診断を件数の概要付きで表示する
+    ///
+    /// `workspace_path` を指定した場合、パスはワークスペースからの相対パスとして
+    /// 表示・照合する。
+    pub fn format_filtered_diagnostics(
+        &self,
+        filter: &DiagnosticsFilter,
+        workspace_path: Option<&Path>,
+    ) -> String {
+        let path_matcher = filter.path_matcher();
+        let mut files: Vec<(String, Vec<&Diagnostic>)> = self
+            .diagnostics
+            .iter()
+            .filter_map(|(path, diagnostics)| {
+                let display_path = workspace_path
+                    .and_then(|workspace| path.strip_prefix(workspace).ok())
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                if let Some(matcher) = &path_matcher {
+                    if !matcher.is_match(&display_path) {
+                        return None;
+                    }
+                }
+                let mut matched: Vec<_> = diagnostics
+                    .iter()
+                    .filter(|d| filter.includes(d.severity))
+                    .collect();
+                if matched.is_empty() {
+                    return None;
                 }
+                matched.sort_by_key(|d| (d.severity.rank(), d.line));
+                Some((display_path, matched))
+            })
+            .collect();
+
+        if files.is_empty() {
+            return if filter.min_severity == DiagnosticSeverity::Error {
+                "(No errors detected)".to_string()
+            } else {
+                "(No problems detected)".to_string()
+            };
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let count = |severity: DiagnosticSeverity| {
+            files
+                .iter()
+                .flat_map(|(_, diagnostics)| diagnostics.iter())
+                .filter(|d| d.severity == severity)
+                .count()
+        };
+        let mut counts = vec![plural(count(DiagnosticSeverity::Error), "error")];
+        if filter.includes(DiagnosticSeverity::Warning) {
+            counts.push(plural(count(DiagnosticSeverity::Warning), "warning"));
+        }
+        if filter.includes(DiagnosticSeverity::Information) {
+            let others = count(DiagnosticSeverity::Information) + count(DiagnosticSeverity::Hint);
+            counts.push(format!("{} other", others));
+        }
+        let mut result = format!(
+            "Found {} in {}",
+            counts.join(", "),
+            plural(files.len(), "file")
+        );
+
+        for (path, diagnostics) in files {
+            result.push_str(&format!("\n\n## {}", path));
+            for diagnostic in diagnostics {
+                let source = diagnostic
+                    .source
+                    .as_ref()
+                    .map(|s| format!("[{}] ", s))
+                    .unwrap_or_default();
+                result.push_str(&format!(
+                    "\n- {}Line {} ({}): {}",
+                    source,
+                    diagnostic.line,
+                    diagnostic.severity.label(),
+                    diagnostic.message
+                ));
             }
         }
 
-        if result.is_empty() {
-            "(No errors detected)".to_string()
-        } else {
-            result.trim().to_string()
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn diagnostic(severity: DiagnosticSeverity, line: u32, message: &str) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message: message.to_string(),
+            source: Some("rustc".to_string()),
+            line,
         }
     }
+
+    fn setup_provider() -> DiagnosticsProvider {
+        let mut provider = DiagnosticsProvider::new();
+        provider.add_diagnostic(
+            PathBuf::from("/w/src/lib.rs"),
+            diagnostic(DiagnosticSeverity::Warning, 3, "unused import"),
+        );
+        provider.add_diagnostic(
+            PathBuf::from("/w/src/lib.rs"),
+            diagnostic(DiagnosticSeverity::Error, 10, "mismatched types"),
+        );
+        provider.add_diagnostic(
+            PathBuf::from("/w/tests/it.rs"),
+            diagnostic(DiagnosticSeverity::Error, 1, "unresolved import"),
+        );
+        provider
+    }
+
+    #[test]
+    fn test_format_diagnostics_errors_only() {
+        let provider = setup_provider();
+        assert_eq!(
+            provider
+                .format_filtered_diagnostics(&DiagnosticsFilter::default(), Some(Path::new("/w"))),
+            "Found 2 errors in 2 files\n\n\
+             ## src/lib.rs\n\
+             - [rustc] Line 10 (error): mismatched types\n\n\
+             ## tests/it.rs\n\
+             - [rustc] Line 1 (error): unresolved import"
+        );
+        assert_eq!(
+            DiagnosticsProvider::new().format_diagnostics(),
+            "(No errors detected)"
+        );
+    }
+
+    #[test]
+    fn test_format_diagnostics_with_warnings_and_path() {
+        let provider = setup_provider();
+        let filter = DiagnosticsFilter::default()
+            .with_warnings()
+            .with_path_pattern("src/**");
+        assert_eq!(
+            provider.format_filtered_diagnostics(&filter, Some(Path::new("/w"))),
+            "Found 1 error, 1 warning in 1 file\n\n\
+             ## src/lib.rs\n\
+             - [rustc] Line 10 (error): mismatched types\n\
+             - [rustc] Line 3 (warning): unused import"
+        );
+
+        let filter = DiagnosticsFilter::default().with_path_pattern("docs");
+        assert_eq!(
+            provider.format_filtered_diagnostics(&filter, Some(Path::new("/w"))),
+            "(No errors detected)"
+        );
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        assert!(glob_to_regex("src/**").is_match("src/a/b.rs"));
+        assert!(!glob_to_regex("src/**").is_match("tests/a.rs"));
+        assert!(glob_to_regex("**/*.rs").is_match("lib.rs"));
+        assert!(glob_to_regex("**/*.rs").is_match("src/lib.rs"));
+        assert!(!glob_to_regex("src/*.rs").is_match("src/a/b.rs"));
+        assert!(glob_to_regex("src").is_match("src/lib.rs"));
+        assert!(!glob_to_regex("src").is_match("srcs/lib.rs"));
+    }
 }
@@ -1,4 +1,14 @@
+pub mod normalize;
+pub mod recovery;
+pub mod registry;
 pub mod strategies;
 pub mod types;
 
+pub use normalize::{
+    match_line_endings, normalize, split_indent, LineEnding, StreamingNormalizer, WhitespacePolicy,
+};
+pub use recovery::{diff_recovery_prompt, whole_file_fallback_prompt};
+pub use registry::{
+    DiffStrategyContext, DiffStrategyRegistry, SharedDiffStrategy, WHOLE_FILE_STRATEGY_ID,
+};
 pub use types::*;
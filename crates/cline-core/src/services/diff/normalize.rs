@@ -0,0 +1,331 @@
+use std::borrow::Cow;
+
+/// ファイルの改行コード
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// 最初の改行の種類（改行がなければLF）
+    pub fn detect(text: &str) -> Self {
+        match text.find('\n') {
+            Some(index) if text[..index].ends_with('\r') => Self::CrLf,
+            _ => Self::Lf,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+
+    /// 改行をこの改行コードに揃える
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let text = normalize_line_endings(text);
+        match self {
+            Self::Lf => text,
+            Self::CrLf => Cow::Owned(text.replace('\n', "\r\n")),
+        }
+    }
+}
+
+/// 比較・適用の前にテキストの空白をどこまで揃えるか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WhitespacePolicy {
+    /// CRLF・CRをLFにする
+    pub line_endings: bool,
+    /// 行末の空白を除く
+    pub trailing_whitespace: bool,
+    /// 行頭のタブをこの幅の空白にする（`None` でタブのまま）
+    pub tab_width: Option<usize>,
+    /// 改行を含む連続した空白を1つの空白にし、前後の空白を除く（類似度の計算に使う）
+    pub collapse: bool,
+}
+
+impl WhitespacePolicy {
+    /// 改行コードだけを揃える
+    pub const EXACT: Self = Self {
+        line_endings: true,
+        trailing_whitespace: false,
+        tab_width: None,
+        collapse: false,
+    };
+
+    /// 行末の空白の違いも無視する
+    pub const IGNORE_TRAILING: Self = Self {
+        trailing_whitespace: true,
+        ..Self::EXACT
+    };
+
+    /// 空白の量と位置の違いを無視する
+    pub const SIMILARITY: Self = Self {
+        collapse: true,
+        ..Self::EXACT
+    };
+}
+
+impl Default for WhitespacePolicy {
+    fn default() -> Self {
+        Self::EXACT
+    }
+}
+
+/// CRLF・CRをLFにする
+pub fn normalize_line_endings(text: &str) -> Cow<'_, str> {
+    if text.contains('\r') {
+        Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// `policy` に従ってテキストの空白を揃える
+pub fn normalize<'a>(text: &'a str, policy: &WhitespacePolicy) -> Cow<'a, str> {
+    if policy.collapse {
+        return Cow::Owned(text.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+    let text = if policy.line_endings {
+        normalize_line_endings(text)
+    } else {
+        Cow::Borrowed(text)
+    };
+    if !policy.trailing_whitespace && policy.tab_width.is_none() {
+        return text;
+    }
+    let mut normalized = String::with_capacity(text.len());
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            normalized.push('\n');
+        }
+        normalized.push_str(&normalize_line(line, policy));
+    }
+    Cow::Owned(normalized)
+}
+
+/// 1行（改行を含まない）の空白を揃える
+pub fn normalize_line<'a>(line: &'a str, policy: &WhitespacePolicy) -> Cow<'a, str> {
+    if policy.collapse {
+        return Cow::Owned(line.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+    let line = if policy.trailing_whitespace {
+        line.trim_end()
+    } else {
+        line
+    };
+    match policy.tab_width {
+        Some(width) if split_indent(line).0.contains('\t') => {
+            let (indent, rest) = split_indent(line);
+            Cow::Owned(expand_tabs(indent, width) + rest)
+        }
+        _ => Cow::Borrowed(line),
+    }
+}
+
+/// 行頭の空白と残りに分ける
+pub fn split_indent(line: &str) -> (&str, &str) {
+    let rest = line.trim_start();
+    line.split_at(line.len() - rest.len())
+}
+
+/// インデントのタブを次のタブ位置までの空白にする
+fn expand_tabs(indent: &str, width: usize) -> String {
+    let mut expanded = String::with_capacity(indent.len());
+    for c in indent.chars() {
+        if c == '\t' {
+            let column = expanded.chars().count();
+            let spaces = if width == 0 {
+                0
+            } else {
+                width - column % width
+            };
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+        } else {
+            expanded.push(c);
+        }
+    }
+    expanded
+}
+
+/// 行に分けて編集した内容を、元のファイルの改行コードと末尾の改行に合わせる
+pub fn match_line_endings(original: &str, content: &str) -> String {
+    let ending = LineEnding::detect(original);
+    let mut content = ending.apply(content).into_owned();
+    let original_has_newline = original.ends_with('\n');
+    let content_has_newline = content.ends_with('\n');
+    if original_has_newline && !content_has_newline && !content.is_empty() {
+        content.push_str(ending.as_str());
+    }
+    content
+}
+
+/// チャンクに分かれて届くテキストの空白を揃える
+///
+/// 完成した行だけを返し、途中の行はチャンクの境界で分かれたCRLFや行末の空白を正しく扱うために
+/// 次のチャンクか `finish` まで保持する。
+#[derive(Debug, Clone, Default)]
+pub struct StreamingNormalizer {
+    policy: WhitespacePolicy,
+    pending: String,
+    /// `collapse` で最初の単語を出力した
+    started: bool,
+}
+
+impl StreamingNormalizer {
+    pub fn new(policy: WhitespacePolicy) -> Self {
+        Self {
+            policy,
+            pending: String::new(),
+            started: false,
+        }
+    }
+
+    /// チャンクを追加し、揃えた完成した行を返す
+    pub fn push(&mut self, chunk: &str) -> String {
+        self.pending.push_str(chunk);
+        // 末尾のCRは次のチャンクのLFと合わせて1つの改行になる場合がある
+        let complete = match self.pending.trim_end_matches('\r').rfind(['\n', '\r']) {
+            Some(index) => index + 1,
+            None => return String::new(),
+        };
+        let rest = self.pending.split_off(complete);
+        let complete = std::mem::replace(&mut self.pending, rest);
+        self.emit(&complete)
+    }
+
+    /// 残りのテキストを揃えて返す
+    pub fn finish(mut self) -> String {
+        let rest = std::mem::take(&mut self.pending);
+        self.emit(&rest)
+    }
+
+    fn emit(&mut self, text: &str) -> String {
+        if !self.policy.collapse {
+            return normalize(text, &self.policy).into_owned();
+        }
+        let mut output = String::new();
+        for word in text.split_whitespace() {
+            if self.started {
+                output.push(' ');
+            }
+            output.push_str(word);
+            self.started = true;
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_line_endings() {
+        assert_eq!(LineEnding::detect("a\r\nb\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("a\nb\r\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a"), LineEnding::Lf);
+        assert_eq!(normalize_line_endings("a\r\nb\rc\n"), "a\nb\nc\n");
+        assert!(matches!(normalize_line_endings("a\nb"), Cow::Borrowed(_)));
+        assert_eq!(LineEnding::CrLf.apply("a\nb\r\nc"), "a\r\nb\r\nc");
+        assert_eq!(LineEnding::Lf.apply("a\r\nb"), "a\nb");
+    }
+
+    #[test]
+    fn test_normalize() {
+        let text = "fn main() {\r\n\tlet x = 1;  \r\n  \tx\t\n}";
+        assert_eq!(
+            normalize(text, &WhitespacePolicy::EXACT),
+            "fn main() {\n\tlet x = 1;  \n  \tx\t\n}"
+        );
+        assert_eq!(
+            normalize(text, &WhitespacePolicy::IGNORE_TRAILING),
+            "fn main() {\n\tlet x = 1;\n  \tx\n}"
+        );
+        let tabs = WhitespacePolicy {
+            tab_width: Some(4),
+            ..WhitespacePolicy::IGNORE_TRAILING
+        };
+        assert_eq!(
+            normalize(text, &tabs),
+            "fn main() {\n    let x = 1;\n    x\n}"
+        );
+        assert_eq!(
+            normalize(text, &WhitespacePolicy::SIMILARITY),
+            "fn main() { let x = 1; x }"
+        );
+        let raw = WhitespacePolicy {
+            line_endings: false,
+            ..WhitespacePolicy::EXACT
+        };
+        assert_eq!(normalize(text, &raw), text);
+        assert_eq!(normalize("", &WhitespacePolicy::SIMILARITY), "");
+        assert_eq!(
+            normalize("  \n\t ", &WhitespacePolicy::IGNORE_TRAILING),
+            "\n"
+        );
+    }
+
+    #[test]
+    fn test_normalize_line() {
+        assert_eq!(split_indent("\t  foo bar "), ("\t  ", "foo bar "));
+        assert_eq!(split_indent("foo"), ("", "foo"));
+        assert_eq!(split_indent("   "), ("   ", ""));
+
+        let tabs = WhitespacePolicy {
+            tab_width: Some(4),
+            ..WhitespacePolicy::EXACT
+        };
+        // タブは次のタブ位置まで進める
+        assert_eq!(normalize_line("  \tx", &tabs), "    x");
+        assert_eq!(normalize_line("\t\tx\ty", &tabs), "        x\ty");
+        assert!(matches!(normalize_line("    x", &tabs), Cow::Borrowed(_)));
+        assert_eq!(
+            normalize_line(" a   b ", &WhitespacePolicy::SIMILARITY),
+            "a b"
+        );
+    }
+
+    #[test]
+    fn test_match_line_endings() {
+        assert_eq!(match_line_endings("a\nb\n", "a\nc"), "a\nc\n");
+        assert_eq!(match_line_endings("a\nb\n", "a\nc\n"), "a\nc\n");
+        assert_eq!(match_line_endings("a\nb", "a\nc\n"), "a\nc\n");
+        assert_eq!(match_line_endings("a\nb", "a\nc"), "a\nc");
+        assert_eq!(match_line_endings("a\r\nb\r\n", "a\nc"), "a\r\nc\r\n");
+        assert_eq!(match_line_endings("a\r\nb\r\n", "a\r\nc"), "a\r\nc\r\n");
+        assert_eq!(match_line_endings("a\n", ""), "");
+    }
+
+    #[test]
+    fn test_streaming_normalizer() {
+        let text = "one  \r\ntwo\t\r\n\r\nthree \nfour";
+        let expected = normalize(text, &WhitespacePolicy::IGNORE_TRAILING);
+        // どこで分割しても一度に揃えた場合と同じになる
+        for split in 0..=text.len() {
+            let mut normalizer = StreamingNormalizer::new(WhitespacePolicy::IGNORE_TRAILING);
+            let mut output = normalizer.push(&text[..split]);
+            output.push_str(&normalizer.push(&text[split..]));
+            output.push_str(&normalizer.finish());
+            assert_eq!(output, expected, "split at {}", split);
+        }
+
+        let mut normalizer = StreamingNormalizer::new(WhitespacePolicy::IGNORE_TRAILING);
+        assert_eq!(normalizer.push("line "), "");
+        assert_eq!(normalizer.push("one \r"), "");
+        assert_eq!(normalizer.push("\nline two"), "line one\n");
+        assert_eq!(normalizer.finish(), "line two");
+
+        for split in 0..=text.len() {
+            let mut normalizer = StreamingNormalizer::new(WhitespacePolicy::SIMILARITY);
+            let mut output = normalizer.push(&text[..split]);
+            output.push_str(&normalizer.push(&text[split..]));
+            output.push_str(&normalizer.finish());
+            assert_eq!(output, "one two three four", "split at {}", split);
+        }
+    }
+}
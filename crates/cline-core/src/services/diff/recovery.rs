@@ -0,0 +1,132 @@
+use crate::services::diff::types::DiffResultDetails;
+
+/// 差分を適用できなかったときにモデルに返す、修正を促すメッセージを作成する
+///
+/// 詳細があれば、hunkごとの類似度と最も一致した箇所の現在の内容（行番号付き）を含める。
+pub fn diff_recovery_prompt(
+    rel_path: &str,
+    error: &str,
+    details: Option<&DiffResultDetails>,
+) -> String {
+    let mut prompt = format!("Unable to apply diff to {}: {}", rel_path, error.trim_end());
+    let Some(details) = details else {
+        prompt.push_str(&format!(
+            "\n\nUse read_file to get the current content of {} and retry with a corrected diff.",
+            rel_path
+        ));
+        return prompt;
+    };
+    let threshold = details.threshold.unwrap_or(1.0);
+
+    if !details.hunks.is_empty() {
+        prompt.push_str("\n\nHunk matches:");
+        for hunk in &details.hunks {
+            let location = hunk
+                .matched_range
+                .map(|range| format!("lines {}-{}", range.start, range.end))
+                .unwrap_or_else(|| "no location".to_string());
+            if hunk.matched {
+                prompt.push_str(&format!(
+                    "\n- Hunk {}: matched {} ({}%)",
+                    hunk.hunk,
+                    location,
+                    percent(hunk.similarity)
+                ));
+            } else {
+                prompt.push_str(&format!(
+                    "\n- Hunk {}: best match {} ({}%, needs {}%)",
+                    hunk.hunk,
+                    location,
+                    percent(hunk.similarity),
+                    percent(threshold)
+                ));
+            }
+        }
+    }
+
+    if let (Some(range), Some(best_match)) = (details.matched_range, &details.best_match) {
+        prompt.push_str(&format!(
+            "\n\nClosest match in the current file (lines {}-{}{}):\n```\n",
+            range.start,
+            range.end,
+            details
+                .similarity
+                .map(|similarity| format!(", {}% similar", percent(similarity)))
+                .unwrap_or_default()
+        ));
+        for (offset, line) in best_match.lines().enumerate() {
+            prompt.push_str(&format!("{} | {}\n", range.start + offset, line));
+        }
+        prompt.push_str("```");
+    }
+
+    prompt.push_str(&format!(
+        "\n\nNext steps:\n\
+         1. Compare the closest match with the lines your diff expects and copy them exactly, including whitespace.\n\
+         2. If {} may have changed, use read_file to get its current content.\n\
+         3. Retry apply_diff with the corrected diff.",
+        rel_path
+    ));
+    prompt
+}
+
+/// 差分の適用が続けて失敗し、次はファイル全体を置き換える方法になることを伝える
+pub fn whole_file_fallback_prompt(rel_path: &str, failures: u32) -> String {
+    format!(
+        "apply_diff has failed {} times in a row for {}. On the next apply_diff for this file, \
+         pass the complete new content of the file as the diff (without SEARCH/REPLACE markers, \
+         diff headers, or line numbers); it will replace the entire file.",
+        failures, rel_path
+    )
+}
+
+fn percent(value: f64) -> i32 {
+    (value * 100.0).floor() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::diff::types::{HunkMatch, MatchedRange};
+
+    #[test]
+    fn test_diff_recovery_prompt() {
+        let details = DiffResultDetails {
+            similarity: Some(0.85),
+            threshold: Some(1.0),
+            matched_range: Some(MatchedRange { start: 10, end: 11 }),
+            search_content: Some("fn a() {\n    b();".to_string()),
+            best_match: Some("fn a() {\n    c();".to_string()),
+            hunks: vec![
+                HunkMatch {
+                    hunk: 1,
+                    similarity: 1.0,
+                    matched_range: Some(MatchedRange { start: 2, end: 4 }),
+                    matched: true,
+                },
+                HunkMatch {
+                    hunk: 2,
+                    similarity: 0.85,
+                    matched_range: Some(MatchedRange { start: 10, end: 11 }),
+                    matched: false,
+                },
+            ],
+        };
+        let prompt = diff_recovery_prompt("src/a.rs", "No match\n", Some(&details));
+        assert!(prompt.starts_with("Unable to apply diff to src/a.rs: No match\n\nHunk matches:"));
+        assert!(prompt.contains("- Hunk 1: matched lines 2-4 (100%)"));
+        assert!(prompt.contains("- Hunk 2: best match lines 10-11 (85%, needs 100%)"));
+        assert!(prompt.contains(
+            "Closest match in the current file (lines 10-11, 85% similar):\n```\n10 | fn a() {\n11 |     c();\n```"
+        ));
+        assert!(prompt.contains("3. Retry apply_diff"));
+
+        let prompt = diff_recovery_prompt("src/a.rs", "Invalid diff format", None);
+        assert!(prompt.ends_with(
+            "Use read_file to get the current content of src/a.rs and retry with a corrected diff."
+        ));
+
+        assert!(whole_file_fallback_prompt("src/a.rs", 2)
+            .starts_with("apply_diff has failed 2 times in a row for src/a.rs."));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use super::strategies::{
+    FastApplyConfig, FastApplyStrategy, NewUnifiedDiffStrategy, SearchReplaceDiffStrategy,
+    UnifiedDiffStrategy, WholeFileStrategy, WHOLE_FILE_MAX_LINES,
+};
+use super::types::DiffStrategy;
+
+/// 新しい統合差分形式を有効にする実験フラグ
+pub const EXPERIMENT_NEW_UNIFIED_DIFF: &str = "experimental_diff_strategy";
+/// 通常の統合差分形式を有効にする実験フラグ
+pub const EXPERIMENT_UNIFIED_DIFF: &str = "unified_diff_strategy";
+/// ファイル全体を置き換える方法の識別子
+pub const WHOLE_FILE_STRATEGY_ID: &str = "whole_file";
+/// 高速適用モデルで編集する方法の識別子
+pub const FAST_APPLY_STRATEGY_ID: &str = "fast_apply";
+/// 同じファイルへの差分の適用がこの回数続けて失敗したら、ファイル全体を置き換える方法にする
+pub const WHOLE_FILE_AFTER_FAILURES: u32 = 2;
+
+pub type SharedDiffStrategy = Arc<dyn DiffStrategy + Send + Sync>;
+
+type Matcher = Arc<dyn Fn(&DiffStrategyContext) -> bool + Send + Sync>;
+type Factory = Arc<dyn Fn(&DiffStrategyContext) -> SharedDiffStrategy + Send + Sync>;
+
+/// 差分の適用方法を選ぶ条件
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiffStrategyContext<'a> {
+    pub model: &'a str,
+    pub fuzzy_match_threshold: Option<f64>,
+    pub experiments: Option<&'a HashMap<String, bool>>,
+    /// 編集するファイルへの差分の適用が続けて失敗した回数
+    pub consecutive_failures: u32,
+    /// 編集するファイルの行数（システムプロンプトのように特定のファイルがなければ `None`）
+    pub file_lines: Option<usize>,
+}
+
+impl DiffStrategyContext<'_> {
+    pub fn experiment_enabled(&self, experiment: &str) -> bool {
+        self.experiments
+            .and_then(|experiments| experiments.get(experiment))
+            .copied()
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Clone)]
+struct DiffStrategyEntry {
+    id: String,
+    matches: Matcher,
+    create: Factory,
+}
+
+/// `apply_diff` の差分の適用方法の一覧
+///
+/// プロンプトのツールの説明と `apply_diff` の実行はどちらもここで選んだ方法を使う。
+/// 先に登録した方法から順に条件を調べ、最初に一致したものを使う。
+#[derive(Clone)]
+pub struct DiffStrategyRegistry {
+    entries: Vec<DiffStrategyEntry>,
+}
+
+impl DiffStrategyRegistry {
+    /// 方法を登録しない空の一覧
+    pub fn empty() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// 条件に一致した場合に使う方法を登録する（既存の方法より優先する）
+    pub fn register(
+        &mut self,
+        id: &str,
+        matches: impl Fn(&DiffStrategyContext) -> bool + Send + Sync + 'static,
+        create: impl Fn(&DiffStrategyContext) -> SharedDiffStrategy + Send + Sync + 'static,
+    ) {
+        self.entries.retain(|entry| entry.id != id);
+        self.entries.insert(
+            0,
+            DiffStrategyEntry {
+                id: id.to_string(),
+                matches: Arc::new(matches),
+                create: Arc::new(create),
+            },
+        );
+    }
+
+    /// 登録した方法の識別子（優先する順）
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.id.as_str())
+    }
+
+    /// 条件に一致する方法の識別子
+    pub fn select_id(&self, context: &DiffStrategyContext) -> Option<&str> {
+        self.select(context).map(|entry| entry.id.as_str())
+    }
+
+    pub fn create(&self, context: &DiffStrategyContext) -> Option<SharedDiffStrategy> {
+        self.select(context).map(|entry| (entry.create)(context))
+    }
+
+    /// 差分の適用が `after_failures` 回続けて失敗した `max_lines` 行以下のファイルでは、
+    /// ファイル全体を置き換える方法を使う（既存の登録を置き換える）
+    pub fn register_whole_file(&mut self, max_lines: usize, after_failures: u32) {
+        self.register(
+            WHOLE_FILE_STRATEGY_ID,
+            move |context| {
+                context.consecutive_failures >= after_failures
+                    && context.file_lines.is_some_and(|lines| lines <= max_lines)
+            },
+            move |_| Arc::new(WholeFileStrategy::new(Some(max_lines))),
+        );
+    }
+
+    /// `config.models` に一致するモデルでは、編集内容を高速適用モデルで統合する方法を使う
+    /// （既存の登録を置き換える）
+    pub fn register_fast_apply(&mut self, config: FastApplyConfig) {
+        let models = config.clone();
+        let strategy: SharedDiffStrategy = Arc::new(FastApplyStrategy::new(config));
+        self.register(
+            FAST_APPLY_STRATEGY_ID,
+            move |context| models.applies_to(context.model),
+            move |_| strategy.clone(),
+        );
+    }
+
+    fn select(&self, context: &DiffStrategyContext) -> Option<&DiffStrategyEntry> {
+        self.entries.iter().find(|entry| (entry.matches)(context))
+    }
+}
+
+impl Default for DiffStrategyRegistry {
+    /// 組み込みの方法（実験フラグがなければ検索・置換ブロック、失敗が続いた小さいファイルは全体の置き換え）
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(
+            "search_replace",
+            |_| true,
+            |context| {
+                Arc::new(SearchReplaceDiffStrategy::new(
+                    context.fuzzy_match_threshold,
+                    None,
+                ))
+            },
+        );
+        registry.register(
+            "unified",
+            |context| context.experiment_enabled(EXPERIMENT_UNIFIED_DIFF),
+            |_| Arc::new(UnifiedDiffStrategy::new()),
+        );
+        registry.register(
+            "new_unified",
+            |context| context.experiment_enabled(EXPERIMENT_NEW_UNIFIED_DIFF),
+            |context| {
+                Arc::new(NewUnifiedDiffStrategy::new(
+                    context.fuzzy_match_threshold,
+                    None,
+                ))
+            },
+        );
+        registry.register_whole_file(WHOLE_FILE_MAX_LINES, WHOLE_FILE_AFTER_FAILURES);
+        registry
+    }
+}
+
+impl fmt::Debug for DiffStrategyRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiffStrategyRegistry")
+            .field("ids", &self.ids().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_select_diff_strategy() {
+        let mut registry = DiffStrategyRegistry::default();
+        assert_eq!(
+            registry.ids().collect::<Vec<_>>(),
+            vec!["whole_file", "new_unified", "unified", "search_replace"]
+        );
+        assert_eq!(
+            registry.select_id(&DiffStrategyContext::default()),
+            Some("search_replace")
+        );
+
+        let experiments = HashMap::from([(EXPERIMENT_NEW_UNIFIED_DIFF.to_string(), true)]);
+        let context = DiffStrategyContext {
+            model: "claude-3-5-sonnet",
+            fuzzy_match_threshold: Some(0.9),
+            experiments: Some(&experiments),
+            ..Default::default()
+        };
+        assert_eq!(registry.select_id(&context), Some("new_unified"));
+
+        // 失敗が続いた小さいファイルだけ全体を置き換える
+        let failing = DiffStrategyContext {
+            consecutive_failures: WHOLE_FILE_AFTER_FAILURES,
+            file_lines: Some(100),
+            ..context
+        };
+        assert_eq!(registry.select_id(&failing), Some(WHOLE_FILE_STRATEGY_ID));
+        let large = DiffStrategyContext {
+            file_lines: Some(10_000),
+            ..failing
+        };
+        assert_eq!(registry.select_id(&large), Some("new_unified"));
+        registry.register_whole_file(20_000, 1);
+        assert_eq!(registry.select_id(&large), Some(WHOLE_FILE_STRATEGY_ID));
+
+        let mut fast_apply = FastApplyConfig::new("http://localhost:8000/v1", None, "morph");
+        fast_apply.models = vec!["qwen".to_string()];
+        registry.register_fast_apply(fast_apply);
+        assert_eq!(registry.select_id(&context), Some("new_unified"));
+        let local = DiffStrategyContext {
+            model: "qwen2.5-coder:7b",
+            ..context
+        };
+        assert_eq!(registry.select_id(&local), Some(FAST_APPLY_STRATEGY_ID));
+
+        // モデルごとに方法を追加できる
+        registry.register(
+            "claude_unified",
+            |context| context.model.starts_with("claude-3-5"),
+            |_| Arc::new(UnifiedDiffStrategy::new()),
+        );
+        assert_eq!(registry.select_id(&context), Some("claude_unified"));
+        assert!(registry.create(&context).is_some());
+        assert_eq!(DiffStrategyRegistry::empty().select_id(&context), None);
+    }
+}
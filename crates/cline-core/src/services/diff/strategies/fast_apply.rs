@@ -0,0 +1,242 @@
+use crate::prompts::tools::types::ToolArgs;
+use crate::services::diff::normalize::match_line_endings;
+use crate::services::diff::types::{DiffResult, DiffStrategy};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// 高速適用モデルの接続先
+#[derive(Debug, Clone, PartialEq)]
+pub struct FastApplyConfig {
+    /// OpenAI互換のAPIのURL（`https://api.morphllm.com/v1` など）
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub model: String,
+    /// この方法を使うモデルIDの接頭辞（空ならすべてのモデル）
+    pub models: Vec<String>,
+}
+
+impl FastApplyConfig {
+    pub fn new(base_url: &str, api_key: Option<String>, model: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            model: model.to_string(),
+            models: Vec::new(),
+        }
+    }
+
+    pub fn applies_to(&self, model_id: &str) -> bool {
+        self.models.is_empty()
+            || self
+                .models
+                .iter()
+                .any(|prefix| model_id.starts_with(prefix.as_str()))
+    }
+}
+
+/// 元のファイルとモデルの大まかな編集内容を高速適用モデル（Morphなど）に送り、
+/// 統合したファイル全体を受け取る方法
+///
+/// 正確な差分を書けないモデルでも、変更箇所だけを書けば編集できる。
+#[derive(Debug, Clone)]
+pub struct FastApplyStrategy {
+    client: Client,
+    config: FastApplyConfig,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionResponse {
+    choices: Vec<ChatCompletionChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionChoice {
+    message: ChatCompletionMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionMessage {
+    content: Option<String>,
+}
+
+impl FastApplyStrategy {
+    pub fn new(config: FastApplyConfig) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+        }
+    }
+
+    fn request_body(&self, original_content: &str, edit: &str) -> Value {
+        json!({
+            "model": self.config.model,
+            "messages": [{
+                "role": "user",
+                "content": format!(
+                    "<code>{}</code>\n<update>{}</update>",
+                    original_content, edit
+                ),
+            }],
+        })
+    }
+
+    async fn merge(&self, original_content: &str, edit: &str) -> Result<String> {
+        let mut request = self
+            .client
+            .post(format!("{}/chat/completions", self.config.base_url))
+            .json(&self.request_body(original_content, edit));
+        if let Some(api_key) = &self.config.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!(
+                "Fast apply request failed ({}): {}",
+                status,
+                response.text().await.unwrap_or_default()
+            );
+        }
+        parse_merged_content(&response.text().await?)
+    }
+}
+
+/// 応答から統合したファイルの内容を取り出す（コードブロックで囲まれていれば外す）
+fn parse_merged_content(body: &str) -> Result<String> {
+    let content = serde_json::from_str::<ChatCompletionResponse>(body)?
+        .choices
+        .into_iter()
+        .next()
+        .and_then(|choice| choice.message.content)
+        .ok_or_else(|| anyhow::anyhow!("Fast apply response has no content"))?;
+    let trimmed = content.trim();
+    if let Some(fenced) = trimmed.strip_prefix("```") {
+        if let (Some(start), Some(inner)) = (fenced.find('\n'), fenced.strip_suffix("```")) {
+            if start < inner.len() {
+                return Ok(inner[start + 1..].to_string());
+            }
+        }
+    }
+    Ok(content)
+}
+
+#[async_trait]
+impl DiffStrategy for FastApplyStrategy {
+    fn get_tool_description(&self, args: &ToolArgs) -> String {
+        format!(
+            r#"## apply_diff
+Description: Request to edit an existing file by describing only the changed parts. The edit is merged into the current file automatically.
+Write each changed section with a few unchanged lines around it so its location is unambiguous, and replace unchanged code in between with a comment such as `// ... existing code ...`.
+Do not rewrite the whole file, and do not use diff markers or line numbers.
+
+Parameters:
+- path: (required) The path of the file to modify (relative to the current working directory {})
+- diff: (required) The changed parts of the file, separated by `// ... existing code ...` comments.
+
+Example:
+```
+fn main() {{
+    // ... existing code ...
+    println!("done");
+}}
+```"#,
+            args.cwd
+        )
+    }
+
+    async fn apply_diff(
+        &self,
+        original_content: &str,
+        diff_content: &str,
+        _start_line: Option<usize>,
+        _end_line: Option<usize>,
+    ) -> DiffResult {
+        match self.merge(original_content, diff_content).await {
+            Ok(content) if content.trim().is_empty() && !original_content.trim().is_empty() => {
+                DiffResult::Failure {
+                    error: "Fast apply returned an empty file".to_string(),
+                    details: None,
+                }
+            }
+            Ok(content) => DiffResult::Success {
+                content: match_line_endings(original_content, &content),
+            },
+            Err(e) => DiffResult::Failure {
+                error: e.to_string(),
+                details: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_fast_apply_strategy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}/v1/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0; 8192];
+            let len = stream.read(&mut request).unwrap();
+            let body = json!({
+                "choices": [{ "message": { "content": "```rust\nfn main() {\n    run();\n}\n```" } }]
+            })
+            .to_string();
+            stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                    .as_bytes(),
+                )
+                .unwrap();
+            String::from_utf8_lossy(&request[..len]).into_owned()
+        });
+
+        let strategy = FastApplyStrategy::new(FastApplyConfig::new(
+            &base_url,
+            Some("key".to_string()),
+            "morph-v3-large",
+        ));
+        let result = strategy
+            .apply_diff(
+                "fn main() {\n    start();\n}\n",
+                "fn main() {\n    run();\n}",
+                None,
+                None,
+            )
+            .await;
+        match result {
+            DiffResult::Success { content } => {
+                assert_eq!(content, "fn main() {\n    run();\n}\n")
+            }
+            DiffResult::Failure { error, .. } => panic!("{}", error),
+        }
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /v1/chat/completions HTTP/1.1"));
+        assert!(request.contains("authorization: Bearer key"));
+
+        assert_eq!(
+            strategy.request_body("a", "b")["messages"][0]["content"],
+            "<code>a</code>\n<update>b</update>"
+        );
+        assert!(parse_merged_content(r#"{"choices":[]}"#).is_err());
+
+        let mut config = FastApplyConfig::new("http://unused", None, "morph");
+        assert!(config.applies_to("qwen2.5-coder:7b"));
+        config.models = vec!["qwen".to_string()];
+        assert!(config.applies_to("qwen2.5-coder:7b"));
+        assert!(!config.applies_to("claude-3-5-sonnet"));
+    }
+}
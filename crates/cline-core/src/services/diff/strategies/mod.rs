@@ -1,21 +1,37 @@
+mod fast_apply;
 mod new_unified;
 mod search_replace;
 mod unified;
+mod whole_file;
 
+pub use fast_apply::{FastApplyConfig, FastApplyStrategy};
 pub use new_unified::NewUnifiedDiffStrategy;
 pub use search_replace::SearchReplaceDiffStrategy;
+pub use unified::UnifiedDiffStrategy;
+pub use whole_file::{WholeFileStrategy, WHOLE_FILE_MAX_LINES};
 
-use crate::services::diff::types::*;
+use std::collections::HashMap;
 
-#[allow(dead_code)]
+use crate::services::diff::registry::{
+    DiffStrategyContext, DiffStrategyRegistry, SharedDiffStrategy, EXPERIMENT_NEW_UNIFIED_DIFF,
+};
+
+/// 組み込みの方法から選ぶ（`DiffStrategyRegistry::default` と同じ）
 pub fn get_diff_strategy(
-    _model: &str,
+    model: &str,
     fuzzy_match_threshold: Option<f64>,
     experimental_diff_strategy: bool,
-) -> Box<dyn DiffStrategy> {
-    if experimental_diff_strategy {
-        Box::new(NewUnifiedDiffStrategy::new(fuzzy_match_threshold))
-    } else {
-        Box::new(SearchReplaceDiffStrategy::new(fuzzy_match_threshold, None))
-    }
+) -> SharedDiffStrategy {
+    let experiments = HashMap::from([(
+        EXPERIMENT_NEW_UNIFIED_DIFF.to_string(),
+        experimental_diff_strategy,
+    )]);
+    DiffStrategyRegistry::default()
+        .create(&DiffStrategyContext {
+            model,
+            fuzzy_match_threshold,
+            experiments: Some(&experiments),
+            ..Default::default()
+        })
+        .expect("the default registry always has a fallback strategy")
 }
@@ -3,25 +3,52 @@ mod search_strategies;
 mod types;
 
 use crate::prompts::tools::types::ToolArgs;
-use crate::services::diff::types::{DiffResult, DiffStrategy};
+use crate::services::diff::normalize::split_indent;
+use crate::services::diff::types::{
+    DiffResult, DiffResultDetails, DiffStrategy, HunkMatch, MatchedRange,
+};
+use crate::services::fs::Rope;
 use async_trait::async_trait;
 use edit_strategies::apply_edit;
-use search_strategies::{find_best_match, prepare_search_string};
-use types::{Change, ChangeType, Diff, Hunk};
+use search_strategies::{find_best_match_in_range, prepare_search_string};
+use std::ops::Range;
+use types::{Change, ChangeType, Diff, Hunk, HunkFailure, LocatedHunk};
+
+/// 指定された行範囲の前後に含めて探す行数の既定値
+const BUFFER_LINES: usize = 20;
 
 #[derive(Debug)]
 pub struct NewUnifiedDiffStrategy {
     confidence_threshold: f64,
+    buffer_lines: usize,
 }
 
 #[allow(dead_code)]
 impl NewUnifiedDiffStrategy {
-    pub fn new(confidence_threshold: Option<f64>) -> Self {
+    pub fn new(confidence_threshold: Option<f64>, buffer_lines: Option<usize>) -> Self {
         Self {
             confidence_threshold: confidence_threshold.unwrap_or(1.0).max(0.8),
+            buffer_lines: buffer_lines.unwrap_or(BUFFER_LINES),
         }
     }
 
+    /// hunkを探す行の範囲（`start_line`・`end_line` が指定されていれば前後の余裕を含めてその周辺に限る）
+    fn search_range(
+        &self,
+        start_line: Option<usize>,
+        end_line: Option<usize>,
+        line_count: usize,
+    ) -> Range<usize> {
+        let start = start_line
+            .map(|line| line.saturating_sub(1).saturating_sub(self.buffer_lines))
+            .unwrap_or(0);
+        let end = end_line
+            .map(|line| line.saturating_add(self.buffer_lines))
+            .unwrap_or(line_count)
+            .min(line_count);
+        start.min(end)..end
+    }
+
     fn parse_unified_diff(&self, diff: &str) -> Diff {
         const MAX_CONTEXT_LINES: usize = 6;
         let mut hunks = Vec::new();
@@ -72,9 +99,8 @@ impl NewUnifiedDiffStrategy {
 
             if let Some(ref mut hunk) = current_hunk {
                 let content = &line[1..];
-                let indent_match = content.matches(|c: char| c.is_whitespace()).next();
-                let indent = indent_match.unwrap_or("").to_string();
-                let trimmed_content = &content[indent.len()..];
+                let (indent, trimmed_content) = split_indent(content);
+                let indent = indent.to_string();
 
                 let change = match line.chars().next() {
                     Some(' ') => Change {
@@ -178,6 +204,121 @@ impl NewUnifiedDiffStrategy {
 
         result
     }
+
+    /// hunkの位置を探す（見つからない場合は分割したhunkごとに探す）
+    ///
+    /// 見つからなければモデルに返すエラーメッセージと最も一致した箇所を返す。
+    fn locate_hunk(
+        &self,
+        hunk: &Hunk,
+        hunk_number: usize,
+        content: &[String],
+        range: &Range<usize>,
+    ) -> Result<Vec<LocatedHunk>, HunkFailure> {
+        let context_str = prepare_search_string(&hunk.changes);
+        let search_result = find_best_match_in_range(
+            &context_str,
+            content,
+            range.clone(),
+            self.confidence_threshold,
+        );
+        if search_result.confidence >= self.confidence_threshold {
+            return Ok(vec![LocatedHunk {
+                hunk: hunk.clone(),
+                hunk_number,
+                index: search_result.index as usize,
+                len: context_str.lines().count(),
+                confidence: search_result.confidence,
+            }]);
+        }
+
+        let sub_hunks = self.split_hunk(hunk);
+        let located: Option<Vec<_>> = sub_hunks
+            .iter()
+            .map(|sub_hunk| {
+                let sub_context_str = prepare_search_string(&sub_hunk.changes);
+                let sub_search_result = find_best_match_in_range(
+                    &sub_context_str,
+                    content,
+                    range.clone(),
+                    self.confidence_threshold,
+                );
+                (sub_search_result.confidence >= self.confidence_threshold).then(|| LocatedHunk {
+                    hunk: sub_hunk.clone(),
+                    hunk_number,
+                    index: sub_search_result.index as usize,
+                    len: sub_context_str.lines().count(),
+                    confidence: sub_search_result.confidence,
+                })
+            })
+            .collect();
+        if let Some(located) = located.filter(|located| !located.is_empty()) {
+            return Ok(located);
+        }
+
+        let context_lines = hunk
+            .changes
+            .iter()
+            .filter(|c| matches!(c.change_type, ChangeType::Context))
+            .count();
+        let total_lines = hunk.changes.len();
+        let context_ratio = context_lines as f64 / total_lines as f64;
+
+        let mut error_msg = format!(
+            "Failed to find a matching location in the file ({}% confidence, needs {}%)\n\n",
+            (search_result.confidence * 100.0).floor(),
+            (self.confidence_threshold * 100.0).floor()
+        );
+
+        error_msg.push_str("Debug Info:\n");
+        error_msg.push_str(&format!(
+            "- Search Strategy Used: {}\n",
+            search_result.strategy
+        ));
+        error_msg.push_str(&format!(
+            "- Context Lines: {} out of {} total lines ({}%)\n",
+            context_lines,
+            total_lines,
+            (context_ratio * 100.0).floor()
+        ));
+        error_msg.push_str(&format!(
+            "- Attempted to split into {} sub-hunks but still failed\n",
+            sub_hunks.len()
+        ));
+
+        if context_ratio < 0.2 {
+            error_msg.push_str("\nPossible Issues:\n");
+            error_msg.push_str("- Not enough context lines to uniquely identify the location\n");
+            error_msg.push_str("- Add a few more lines of unchanged code around your changes\n");
+        } else if context_ratio > 0.5 {
+            error_msg.push_str("\nPossible Issues:\n");
+            error_msg.push_str("- Too many context lines may reduce search accuracy\n");
+            error_msg
+                .push_str("- Try to keep only 2-3 lines of context before and after changes\n");
+        } else {
+            error_msg.push_str("\nPossible Issues:\n");
+            error_msg.push_str("- The diff may be targeting a different version of the file\n");
+            error_msg.push_str("- There may be too many changes in a single hunk, try splitting the changes into multiple hunks\n");
+        }
+
+        // 類似度が足りない箇所も含めて最も一致した箇所を探す
+        let best = find_best_match_in_range(&context_str, content, range.clone(), 0.0);
+        let matched_range = (best.index >= 0).then(|| MatchedRange {
+            start: best.index as usize + 1,
+            end: (best.index as usize + context_str.lines().count()).min(content.len()),
+        });
+        Err(HunkFailure {
+            error: error_msg,
+            hunk_match: HunkMatch {
+                hunk: hunk_number,
+                similarity: best.confidence.min(1.0),
+                matched_range,
+                matched: false,
+            },
+            search_content: context_str,
+            best_match: matched_range.map(|range| content[range.start - 1..range.end].join("\n")),
+        })
+    }
 }
 
 #[async_trait]
@@ -231,7 +372,6 @@ Parameters:
     ) -> DiffResult {
         let parsed_diff = self.parse_unified_diff(diff_content);
         let original_lines: Vec<String> = original_content.lines().map(String::from).collect();
-        let mut result = original_lines.clone();
 
         if parsed_diff.hunks.is_empty() {
             return DiffResult::Failure {
@@ -240,124 +380,100 @@ Parameters:
             };
         }
 
-        for hunk in &parsed_diff.hunks {
-            let context_str = prepare_search_string(&hunk.changes);
-            let search_result =
-                find_best_match(&context_str, &result, 0, self.confidence_threshold);
-
-            if search_result.confidence < self.confidence_threshold {
-                let sub_hunks = self.split_hunk(hunk);
-                let mut sub_hunk_success = true;
-                let mut sub_hunk_result = result.clone();
-
-                for sub_hunk in &sub_hunks {
-                    let sub_context_str = prepare_search_string(&sub_hunk.changes);
-                    let sub_search_result = find_best_match(
-                        &sub_context_str,
-                        &sub_hunk_result,
-                        0,
-                        self.confidence_threshold,
-                    );
-
-                    if sub_search_result.confidence >= self.confidence_threshold {
-                        let sub_edit_result = apply_edit(
-                            sub_hunk,
-                            &sub_hunk_result,
-                            sub_search_result.index,
-                            sub_search_result.confidence,
-                            Some(self.confidence_threshold),
-                        )
-                        .await;
-
-                        if sub_edit_result.confidence >= self.confidence_threshold {
-                            sub_hunk_result = sub_edit_result.result;
-                            continue;
-                        }
-                    }
-                    sub_hunk_success = false;
-                    break;
+        // すべてのhunkの位置を元の内容で探してから、位置の順に適用する
+        let search_range = self.search_range(start_line, end_line, original_lines.len());
+        let mut located = Vec::new();
+        let mut hunk_matches = Vec::new();
+        let mut failure = None;
+        for (hunk_number, hunk) in parsed_diff.hunks.iter().enumerate() {
+            match self.locate_hunk(hunk, hunk_number + 1, &original_lines, &search_range) {
+                Ok(hunks) => {
+                    hunk_matches.push(HunkMatch {
+                        hunk: hunk_number + 1,
+                        similarity: hunks
+                            .iter()
+                            .map(|hunk| hunk.confidence.min(1.0))
+                            .fold(1.0, f64::min),
+                        matched_range: Some(MatchedRange {
+                            start: hunks.iter().map(|hunk| hunk.index).min().unwrap_or(0) + 1,
+                            end: hunks
+                                .iter()
+                                .map(|hunk| hunk.index + hunk.len)
+                                .max()
+                                .unwrap_or(0),
+                        }),
+                        matched: true,
+                    });
+                    located.extend(hunks);
                 }
-
-                if sub_hunk_success {
-                    result = sub_hunk_result;
-                    continue;
+                Err(hunk_failure) => {
+                    hunk_matches.push(hunk_failure.hunk_match.clone());
+                    failure.get_or_insert(hunk_failure);
                 }
-
-                let context_lines = hunk
-                    .changes
-                    .iter()
-                    .filter(|c| matches!(c.change_type, ChangeType::Context))
-                    .count();
-                let total_lines = hunk.changes.len();
-                let context_ratio = context_lines as f64 / total_lines as f64;
-
-                let mut error_msg = format!(
-                    "Failed to find a matching location in the file ({}% confidence, needs {}%)\n\n",
-                    (search_result.confidence * 100.0).floor(),
-                    (self.confidence_threshold * 100.0).floor()
-                );
-
-                error_msg.push_str("Debug Info:\n");
-                error_msg.push_str(&format!(
-                    "- Search Strategy Used: {}\n",
-                    search_result.strategy
-                ));
-                error_msg.push_str(&format!(
-                    "- Context Lines: {} out of {} total lines ({}%)\n",
-                    context_lines,
-                    total_lines,
-                    (context_ratio * 100.0).floor()
-                ));
+            }
+        }
+        // 最初に見つからなかったhunkのエラーと、すべてのhunkの一致の状況を返す
+        if let Some(failure) = failure {
+            let mut error_msg = failure.error;
+            if start_line.is_some() || end_line.is_some() {
                 error_msg.push_str(&format!(
-                    "- Attempted to split into {} sub-hunks but still failed\n",
-                    sub_hunks.len()
+                    "\nSearch Range: lines {}-{} (including {} buffer lines)\n",
+                    search_range.start + 1,
+                    search_range.end,
+                    self.buffer_lines
                 ));
+            }
+            return DiffResult::Failure {
+                error: error_msg,
+                details: Some(DiffResultDetails {
+                    similarity: Some(failure.hunk_match.similarity),
+                    threshold: Some(self.confidence_threshold),
+                    matched_range: failure.hunk_match.matched_range,
+                    search_content: Some(failure.search_content),
+                    best_match: failure.best_match,
+                    hunks: hunk_matches,
+                }),
+            };
+        }
 
-                if context_ratio < 0.2 {
-                    error_msg.push_str("\nPossible Issues:\n");
-                    error_msg
-                        .push_str("- Not enough context lines to uniquely identify the location\n");
-                    error_msg
-                        .push_str("- Add a few more lines of unchanged code around your changes\n");
-                } else if context_ratio > 0.5 {
-                    error_msg.push_str("\nPossible Issues:\n");
-                    error_msg.push_str("- Too many context lines may reduce search accuracy\n");
-                    error_msg.push_str(
-                        "- Try to keep only 2-3 lines of context before and after changes\n",
-                    );
-                } else {
-                    error_msg.push_str("\nPossible Issues:\n");
-                    error_msg
-                        .push_str("- The diff may be targeting a different version of the file\n");
-                    error_msg.push_str("- There may be too many changes in a single hunk, try splitting the changes into multiple hunks\n");
-                }
-
-                if start_line.is_some() && end_line.is_some() {
-                    error_msg.push_str(&format!(
-                        "\nSearch Range: lines {}-{}\n",
-                        start_line.unwrap(),
-                        end_line.unwrap()
-                    ));
-                }
-
+        located.sort_by_key(|hunk| hunk.index);
+        for pair in located.windows(2) {
+            let (previous, next) = (&pair[0], &pair[1]);
+            if previous.index + previous.len > next.index {
                 return DiffResult::Failure {
-                    error: error_msg,
+                    error: format!(
+                        "Hunk {} (lines {}-{}) overlaps hunk {} (lines {}-{})\n\nMerge overlapping changes into a single hunk, or make sure each hunk targets a different part of the file.",
+                        previous.hunk_number,
+                        previous.index + 1,
+                        previous.index + previous.len,
+                        next.hunk_number,
+                        next.index + 1,
+                        next.index + next.len
+                    ),
                     details: None,
                 };
             }
+        }
 
+        // 先に適用したhunkで増減した行数だけ後のhunkの位置をずらし、一致した行だけを置き換える
+        let mut result = Rope::new(original_content);
+        let mut offset: isize = 0;
+        for located_hunk in &located {
+            let position = (located_hunk.index as isize + offset) as usize;
+            let window: Vec<String> = result
+                .lines_in(position..position + located_hunk.len)
+                .map(String::from)
+                .collect();
             let edit_result = apply_edit(
-                hunk,
-                &result,
-                search_result.index,
-                search_result.confidence,
+                &located_hunk.hunk,
+                &window,
+                0,
+                located_hunk.confidence,
                 Some(self.confidence_threshold),
             )
             .await;
 
-            if edit_result.confidence >= self.confidence_threshold {
-                result = edit_result.result;
-            } else {
+            if edit_result.confidence < self.confidence_threshold {
                 let mut error_msg = format!(
                     "Failed to apply the edit using {} strategy ({}% confidence)\n\n",
                     edit_result.strategy,
@@ -383,10 +499,80 @@ Parameters:
                     details: None,
                 };
             }
+            offset += edit_result.result.len() as isize - window.len() as isize;
+            result.splice(position..position + window.len(), edit_result.result);
         }
 
         DiffResult::Success {
-            content: result.join("\n"),
+            content: result.to_string(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_apply_diff_within_line_range() {
+        let block = "fn handler() {\n    let value = load();\n    process(value);\n}";
+        let filler: Vec<String> = (0..40).map(|i| format!("// line {}", i)).collect();
+        let original = format!("{}\n{}\n{}", block, filler.join("\n"), block);
+        let diff = "--- a.rs\n+++ a.rs\n@@ ... @@\n fn handler() {\n     let value = load();\n-    process(value);\n+    process_twice(value);\n }";
+
+        let strategy = NewUnifiedDiffStrategy::new(None, Some(5));
+        let DiffResult::Success { content } = strategy
+            .apply_diff(&original, diff, Some(45), Some(48))
+            .await
+        else {
+            panic!("diff should apply in the range");
+        };
+        let expected = format!(
+            "{}\n{}\n{}",
+            block,
+            filler.join("\n"),
+            block.replace("process(", "process_twice(")
+        );
+        assert_eq!(content, expected);
+
+        // 範囲の外にしかない内容は探さない
+        let result = strategy
+            .apply_diff(&original, diff, Some(20), Some(24))
+            .await;
+        let DiffResult::Failure { error, details } = result else {
+            panic!("diff should not apply outside the range");
+        };
+        assert!(error.contains("Search Range: lines 15-29"));
+        let details = details.unwrap();
+        assert_eq!(details.hunks.len(), 1);
+        assert!(!details.hunks[0].matched);
+        assert!(details.similarity.unwrap() < 1.0);
+        let range = details.matched_range.unwrap();
+        assert!(range.start >= 15 && range.end <= 29);
+    }
+
+    #[tokio::test]
+    async fn test_apply_diff_orders_hunks_and_rejects_overlaps() {
+        let original = "fn first() {\n    one();\n}\n\nfn second() {\n    two();\n}";
+        let strategy = NewUnifiedDiffStrategy::new(None, None);
+
+        // 後ろのhunkが先に書かれていても位置の順に適用する
+        let diff = "--- a.rs\n+++ a.rs\n@@ ... @@\n fn second() {\n-    two();\n+    two_a();\n+    two_b();\n }\n@@ ... @@\n fn first() {\n-    one();\n+    one_a();\n+    one_b();\n }";
+        let DiffResult::Success { content } = strategy.apply_diff(original, diff, None, None).await
+        else {
+            panic!("diff should apply");
+        };
+        assert_eq!(
+            content,
+            "fn first() {\n    one_a();\n    one_b();\n}\n\nfn second() {\n    two_a();\n    two_b();\n}"
+        );
+
+        let overlapping = "--- a.rs\n+++ a.rs\n@@ ... @@\n fn first() {\n-    one();\n+    one_a();\n }\n@@ ... @@\n-    one();\n+    one_b();\n }";
+        let result = strategy.apply_diff(original, overlapping, None, None).await;
+        assert!(matches!(
+            result,
+            DiffResult::Failure { error, .. } if error.starts_with("Hunk 1 (lines 1-3) overlaps hunk 2 (lines 2-3)")
+        ));
+    }
+}
@@ -1,4 +1,5 @@
 use super::types::{Change, ChangeType, Hunk};
+use std::ops::Range;
 use strsim::normalized_levenshtein;
 
 const LARGE_FILE_THRESHOLD: usize = 1000;
@@ -117,8 +118,14 @@ pub fn find_exact_match(
     };
 
     for (window, window_index) in windows {
-        let window_str = window.join("\n");
-        if let Some(exact_match) = window_str.find(search_str) {
+        // 一致した位置は行で数える
+        let exact_match = window.windows(search_lines.len().max(1)).position(|lines| {
+            lines
+                .iter()
+                .map(String::as_str)
+                .eq(search_lines.iter().copied())
+        });
+        if let Some(exact_match) = exact_match {
             let matched_content = window[exact_match..exact_match + search_lines.len()].join("\n");
             let similarity = evaluate_similarity(search_str, &matched_content);
             let context_similarity =
@@ -196,6 +203,21 @@ pub fn find_best_match(
     best_result
 }
 
+/// `range` の行の中だけを探す（見つかった位置は `content` 全体の行番号で返す）
+pub fn find_best_match_in_range(
+    search_str: &str,
+    content: &[String],
+    range: Range<usize>,
+    confidence_threshold: f64,
+) -> SearchResult {
+    let range = range.start.min(content.len())..range.end.min(content.len());
+    let mut result = find_best_match(search_str, &content[range.clone()], 0, confidence_threshold);
+    if result.index >= 0 {
+        result.index += range.start as i32;
+    }
+    result
+}
+
 pub fn validate_edit_result(hunk: &Hunk, result: &str) -> f64 {
     let expected_text = hunk
         .changes
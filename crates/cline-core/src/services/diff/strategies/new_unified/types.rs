@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::services::diff::types::HunkMatch;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ChangeType {
     Context,
@@ -20,6 +22,30 @@ pub struct Hunk {
     pub changes: Vec<Change>,
 }
 
+/// 元の内容で位置が見つかったhunk
+#[derive(Debug, Clone)]
+pub struct LocatedHunk {
+    pub hunk: Hunk,
+    /// 差分の中での番号（1から、分割したhunkは元のhunkの番号）
+    pub hunk_number: usize,
+    /// 一致した最初の行（0から）
+    pub index: usize,
+    /// 一致した行数（コンテキストと削除する行）
+    pub len: usize,
+    pub confidence: f64,
+}
+
+/// 位置が見つからなかったhunk
+#[derive(Debug, Clone)]
+pub struct HunkFailure {
+    /// モデルに返すエラーメッセージ
+    pub error: String,
+    pub hunk_match: HunkMatch,
+    pub search_content: String,
+    /// 最も一致した箇所の現在の内容
+    pub best_match: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Diff {
     pub hunks: Vec<Hunk>,
@@ -1,5 +1,7 @@
 use crate::prompts::tools::types::ToolArgs;
-use crate::services::diff::types::{DiffResult, DiffResultDetails, DiffStrategy};
+use crate::services::diff::normalize::{normalize, WhitespacePolicy};
+use crate::services::diff::types::{DiffResult, DiffResultDetails, DiffStrategy, MatchedRange};
+use crate::services::fs::Rope;
 use async_trait::async_trait;
 use strsim::normalized_levenshtein;
 
@@ -25,11 +27,8 @@ impl SearchReplaceDiffStrategy {
             return 1.0;
         }
 
-        let normalized_original = original
-            .replace(char::is_whitespace, " ")
-            .trim()
-            .to_string();
-        let normalized_search = search.replace(char::is_whitespace, " ").trim().to_string();
+        let normalized_original = normalize(original, &WhitespacePolicy::SIMILARITY);
+        let normalized_search = normalize(search, &WhitespacePolicy::SIMILARITY);
 
         if normalized_original == normalized_search {
             return 1.0;
@@ -89,7 +88,9 @@ Diff format:
             }
         };
 
-        let captures = match re.captures(diff_content) {
+        // CRLFで送られた差分も同じ形式で解析する
+        let diff_content = normalize(diff_content, &WhitespacePolicy::EXACT);
+        let captures = match re.captures(&diff_content) {
             Some(captures) => captures,
             None => {
                 return DiffResult::Failure {
@@ -182,21 +183,27 @@ Diff format:
                 details: Some(DiffResultDetails {
                     similarity: Some(best_match_score),
                     threshold: Some(self.fuzzy_threshold),
-                    matched_range: None,
+                    matched_range: best_match_index.map(|index| MatchedRange {
+                        start: index + 1,
+                        end: index + search_lines.len(),
+                    }),
                     search_content: Some(search_content.to_string()),
-                    best_match: None,
+                    best_match: best_match_index
+                        .map(|index| original_lines[index..index + search_lines.len()].join("\n")),
+                    hunks: Vec::new(),
                 }),
             };
         }
 
         let match_index = best_match_index.unwrap();
-        let mut result = Vec::new();
-        result.extend_from_slice(&original_lines[..match_index]);
-        result.extend(replace_content.lines());
-        result.extend_from_slice(&original_lines[match_index + search_lines.len()..]);
+        let mut result = Rope::new(original_content);
+        result.splice(
+            match_index..match_index + search_lines.len(),
+            replace_content.lines().map(String::from),
+        );
 
         DiffResult::Success {
-            content: result.join("\n"),
+            content: result.to_string(),
         }
     }
 }
@@ -0,0 +1,111 @@
+use crate::prompts::tools::types::ToolArgs;
+use crate::services::diff::normalize::match_line_endings;
+use crate::services::diff::types::{DiffResult, DiffStrategy};
+use async_trait::async_trait;
+
+/// 全体を置き換えられるファイルの行数の上限
+pub const WHOLE_FILE_MAX_LINES: usize = 300;
+
+/// `diff` にファイルの新しい内容全体を受け取り、そのまま置き換える方法
+///
+/// 差分を何度も適用できなかった小さいファイルで、同じ失敗を繰り返さないために使う。
+#[derive(Debug)]
+pub struct WholeFileStrategy {
+    max_lines: usize,
+}
+
+impl WholeFileStrategy {
+    pub fn new(max_lines: Option<usize>) -> Self {
+        Self {
+            max_lines: max_lines.unwrap_or(WHOLE_FILE_MAX_LINES),
+        }
+    }
+}
+
+/// 全体の内容ではなく差分の形式で送られた内容
+fn looks_like_diff(content: &str) -> bool {
+    content.contains("<<<<<<< SEARCH")
+        || content.contains(">>>>>>> REPLACE")
+        || (content.starts_with("--- ") && content.contains("\n+++ "))
+        || content.starts_with("@@ ")
+}
+
+#[async_trait]
+impl DiffStrategy for WholeFileStrategy {
+    fn get_tool_description(&self, args: &ToolArgs) -> String {
+        format!(
+            r#"## apply_diff
+Description: Request to replace the entire content of an existing file with new content.
+Use this when a file is small (at most {} lines) and a targeted diff could not be applied.
+ALWAYS provide the COMPLETE new content of the file, without truncation or omissions, and without line numbers.
+
+Parameters:
+- path: (required) The path of the file to modify (relative to the current working directory {})
+- diff: (required) The complete new content of the file."#,
+            self.max_lines, args.cwd
+        )
+    }
+
+    async fn apply_diff(
+        &self,
+        original_content: &str,
+        diff_content: &str,
+        _start_line: Option<usize>,
+        _end_line: Option<usize>,
+    ) -> DiffResult {
+        let lines = original_content.lines().count();
+        if lines > self.max_lines {
+            return DiffResult::Failure {
+                error: format!(
+                    "The file has {} lines, which is more than the {} lines that can be replaced as a whole. Use a targeted diff instead.",
+                    lines, self.max_lines
+                ),
+                details: None,
+            };
+        }
+        if looks_like_diff(diff_content) {
+            return DiffResult::Failure {
+                error: "Expected the complete new content of the file, but received a diff. Provide the entire file content instead.".to_string(),
+                details: None,
+            };
+        }
+
+        DiffResult::Success {
+            content: match_line_endings(original_content, diff_content),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_whole_file_strategy() {
+        let strategy = WholeFileStrategy::new(Some(3));
+        match strategy.apply_diff("a\nb\n", "a\nc", None, None).await {
+            DiffResult::Success { content } => assert_eq!(content, "a\nc\n"),
+            DiffResult::Failure { error, .. } => panic!("{}", error),
+        }
+        match strategy.apply_diff("a\r\nb\r\n", "a\nc", None, None).await {
+            DiffResult::Success { content } => assert_eq!(content, "a\r\nc\r\n"),
+            DiffResult::Failure { error, .. } => panic!("{}", error),
+        }
+
+        let diff = "<<<<<<< SEARCH\nb\n=======\nc\n>>>>>>> REPLACE";
+        assert!(matches!(
+            strategy
+                .apply_diff("a\nb\n", diff, None, None)
+                .await
+                .into_result(),
+            Err(crate::ClineError::Diff(_))
+        ));
+        match strategy.apply_diff("1\n2\n3\n4\n", "1", None, None).await {
+            DiffResult::Failure { error, .. } => {
+                assert!(error.starts_with("The file has 4 lines"))
+            }
+            DiffResult::Success { .. } => panic!("expected the line limit to apply"),
+        }
+    }
+}
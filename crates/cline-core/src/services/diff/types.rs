@@ -2,21 +2,44 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 
+use crate::error::ClineError;
+
+/// 差分を適用できなかった理由の詳細（修正を促すプロンプトの作成に使う）
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct DiffResultDetails {
+    /// 最も一致した箇所の類似度（0.0〜1.0）
     pub similarity: Option<f64>,
     pub threshold: Option<f64>,
+    /// 最も一致した箇所の行
     pub matched_range: Option<MatchedRange>,
+    /// 探した内容
     pub search_content: Option<String>,
+    /// 最も一致した箇所の現在の内容
     pub best_match: Option<String>,
+    /// hunkごとの一致の状況（hunkに分かれない形式では空）
+    #[serde(default)]
+    pub hunks: Vec<HunkMatch>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 行の範囲（1から数え、`end` を含む）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MatchedRange {
     pub start: usize,
     pub end: usize,
 }
 
+/// 差分のhunkごとの一致の状況
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HunkMatch {
+    /// 差分の中での番号（1から）
+    pub hunk: usize,
+    pub similarity: f64,
+    /// 最も一致した箇所の行（見つからなければ `None`）
+    pub matched_range: Option<MatchedRange>,
+    /// 適用できる類似度で見つかった
+    pub matched: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DiffResult {
     Success {
@@ -28,6 +51,16 @@ pub enum DiffResult {
     },
 }
 
+impl DiffResult {
+    /// 適用後の内容（失敗した場合は `ClineError::Diff`）
+    pub fn into_result(self) -> Result<String, ClineError> {
+        match self {
+            Self::Success { content } => Ok(content),
+            Self::Failure { error, .. } => Err(ClineError::Diff(error)),
+        }
+    }
+}
+
 #[async_trait]
 #[allow(dead_code)]
 pub trait DiffStrategy: Debug {
@@ -0,0 +1,190 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use git2::{Repository, Status, StatusOptions};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::cline::EditorInfoProvider;
+
+/// 走査しないディレクトリ
+pub(crate) const SKIPPED_DIRS: &[&str] = &[".git", ".cline", "node_modules", "target"];
+
+/// 一覧に表示する既定の最大ファイル数
+pub const DEFAULT_MAX_FILES: usize = 20;
+
+/// 最近更新されたファイルを探す際に走査する最大エントリ数
+const MAX_SCANNED_ENTRIES: usize = 10_000;
+
+/// エディタのないヘッドレス実行用の `EditorInfoProvider`
+///
+/// 表示中のファイルとしてgitで変更のあるファイルを、開いているタブとして最近更新されたファイルを返す。
+#[derive(Debug, Clone)]
+pub struct FilesystemEditorInfoProvider {
+    workspace_path: PathBuf,
+    max_files: usize,
+}
+
+impl FilesystemEditorInfoProvider {
+    pub fn new(workspace_path: impl Into<PathBuf>) -> Self {
+        Self {
+            workspace_path: workspace_path.into(),
+            max_files: DEFAULT_MAX_FILES,
+        }
+    }
+
+    /// 一覧に表示する最大ファイル数を変更する
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    async fn run_blocking<F>(&self, f: F) -> Result<Vec<String>>
+    where
+        F: FnOnce(&Path, usize) -> Result<Vec<String>> + Send + 'static,
+    {
+        let workspace_path = self.workspace_path.clone();
+        let max_files = self.max_files;
+        tokio::task::spawn_blocking(move || f(&workspace_path, max_files))
+            .await
+            .map_err(|e| anyhow::anyhow!("Editor info task failed: {}", e))?
+    }
+}
+
+#[async_trait]
+impl EditorInfoProvider for FilesystemEditorInfoProvider {
+    async fn get_visible_files(&self) -> Result<Vec<String>> {
+        self.run_blocking(git_dirty_files).await
+    }
+
+    async fn get_open_tabs(&self) -> Result<Vec<String>> {
+        self.run_blocking(recently_modified_files).await
+    }
+}
+
+/// gitで変更のあるファイル（gitリポジトリでない場合は空）を更新日時の新しい順に返す
+fn git_dirty_files(workspace_path: &Path, max_files: usize) -> Result<Vec<String>> {
+    let Ok(repo) = Repository::open(workspace_path) else {
+        return Ok(Vec::new());
+    };
+    let mut status_options = StatusOptions::new();
+    status_options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true);
+    let statuses = repo.statuses(Some(&mut status_options))?;
+
+    let mut files: Vec<(SystemTime, String)> = statuses
+        .iter()
+        .filter(|entry| {
+            !entry
+                .status()
+                .intersects(Status::WT_DELETED | Status::INDEX_DELETED)
+        })
+        .filter_map(|entry| {
+            let path = entry.path()?.to_string();
+            let modified = modified_time(&workspace_path.join(&path))?;
+            Some((modified, path))
+        })
+        .filter(|(_, path)| !path.split('/').any(|c| SKIPPED_DIRS.contains(&c)))
+        .collect();
+    Ok(newest_first(&mut files, max_files))
+}
+
+/// ワークスペース内で最近更新されたファイルを新しい順に返す
+fn recently_modified_files(workspace_path: &Path, max_files: usize) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut pending = vec![workspace_path.to_path_buf()];
+    let mut scanned = 0;
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)?.flatten() {
+            scanned += 1;
+            if scanned > MAX_SCANNED_ENTRIES {
+                return Ok(newest_first(&mut files, max_files));
+            }
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                let name = entry.file_name();
+                if !SKIPPED_DIRS.contains(&name.to_string_lossy().as_ref()) {
+                    pending.push(path);
+                }
+            } else if file_type.is_file() {
+                let Some(modified) = modified_time(&path) else {
+                    continue;
+                };
+                let Ok(rel_path) = path.strip_prefix(workspace_path) else {
+                    continue;
+                };
+                files.push((modified, rel_path.to_string_lossy().replace('\\', "/")));
+            }
+        }
+    }
+    Ok(newest_first(&mut files, max_files))
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn newest_first(files: &mut [(SystemTime, String)], max_files: usize) -> Vec<String> {
+    files.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    files
+        .iter()
+        .take(max_files)
+        .map(|(_, path)| path.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    fn write_with_mtime(path: &Path, content: &str, secs_ago: u64) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(secs_ago))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_filesystem_editor_info() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        write_with_mtime(&root.join("old.rs"), "old", 300);
+        write_with_mtime(&root.join("src/new.rs"), "new", 10);
+        write_with_mtime(&root.join("src/mid.rs"), "mid", 100);
+        write_with_mtime(&root.join("target/debug/build.rs"), "build", 0);
+
+        let provider = FilesystemEditorInfoProvider::new(root).with_max_files(2);
+        assert_eq!(
+            provider.get_open_tabs().await.unwrap(),
+            vec!["src/new.rs", "src/mid.rs"]
+        );
+        // gitリポジトリでない場合は変更ファイルなし
+        assert!(provider.get_visible_files().await.unwrap().is_empty());
+
+        let repo = Repository::init(root).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("old.rs")).unwrap();
+        index.add_path(Path::new("src/mid.rs")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+        write_with_mtime(&root.join("old.rs"), "changed", 50);
+
+        let provider = FilesystemEditorInfoProvider::new(root);
+        assert_eq!(
+            provider.get_visible_files().await.unwrap(),
+            vec!["src/new.rs", "old.rs"]
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::Result;
+
+use crate::services::anthropic::AnthropicClientTrait;
+
+/// 既定のプロンプト改善用のサポートプロンプト（`${userInput}` をユーザーの入力に置き換える）
+pub const ENHANCE_PROMPT_TEMPLATE: &str = "Generate an enhanced version of this prompt (reply with only the enhanced prompt - no conversation, explanations, lead-in, bullet points, placeholders, or surrounding quotes):\n\n${userInput}";
+
+/// サポートプロンプトの `${userInput}` をユーザーの入力に置き換える
+pub fn create_enhance_prompt(template: Option<&str>, user_input: &str) -> String {
+    template
+        .unwrap_or(ENHANCE_PROMPT_TEMPLATE)
+        .replace("${userInput}", user_input)
+}
+
+/// 大まかな依頼をモデルに渡し、改善したタスクの説明を返す
+pub async fn enhance_prompt(
+    client: &dyn AnthropicClientTrait,
+    template: Option<&str>,
+    text: &str,
+) -> Result<String> {
+    if text.trim().is_empty() {
+        anyhow::bail!("No prompt to enhance");
+    }
+    let response = client
+        .send_message(&create_enhance_prompt(template, text))
+        .await?;
+    // 指示に反して引用符で囲まれることがあるため取り除く
+    let enhanced = response.trim().trim_matches('"').trim();
+    if enhanced.is_empty() {
+        anyhow::bail!("The model returned an empty prompt");
+    }
+    Ok(enhanced.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::anthropic::ContentBlock;
+    use crate::services::api::{ScriptedProvider, ScriptedTurn};
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_enhance_prompt() {
+        let provider = ScriptedProvider::new([
+            ScriptedTurn::text("\n\"Add input validation to the signup form.\"\n"),
+            ScriptedTurn::text("  "),
+        ]);
+        assert_eq!(
+            enhance_prompt(&provider, Some("Improve: ${userInput}"), "validate signup")
+                .await
+                .unwrap(),
+            "Add input validation to the signup form."
+        );
+        assert_eq!(
+            provider.requests()[0],
+            vec![ContentBlock::text("Improve: validate signup")]
+        );
+        assert!(enhance_prompt(&provider, None, "validate signup")
+            .await
+            .is_err());
+        assert!(enhance_prompt(&provider, None, " ").await.is_err());
+        assert!(create_enhance_prompt(None, "fix it").ends_with("\n\nfix it"));
+    }
+}
@@ -0,0 +1,293 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::services::editor::SKIPPED_DIRS;
+use crate::shared::modes::Mode;
+
+/// `environment_details` に含める既定の最大ファイル数
+pub const DEFAULT_FILE_LIST_LIMIT: usize = 200;
+/// ファイル一覧の作成にかける既定の時間の上限（超えた分は省略する）
+pub const DEFAULT_FILE_LIST_TIME_LIMIT: Duration = Duration::from_millis(100);
+/// ファイル一覧を並列に読み込むスレッド数の上限
+const MAX_WALK_THREADS: usize = 8;
+
+/// `environment_details` に含めるセクションとファイル一覧の件数
+///
+/// 設計だけを行うモードなど、大きな `environment_details` が不要な場合にトークンを節約する。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvironmentDetailsOptions {
+    /// 実行中・終了したターミナルの出力
+    pub terminals: bool,
+    pub time: bool,
+    /// 最初のリクエストに含めるワークスペースのファイル一覧
+    pub file_list: bool,
+    /// コンテキストのトークン数とコスト
+    pub context_size: bool,
+    /// ファイル一覧の最大件数（モードごとの指定がない場合）
+    pub file_list_limit: usize,
+    /// モードごとのファイル一覧の最大件数（0で一覧を含めない）
+    pub mode_file_list_limits: HashMap<Mode, usize>,
+    /// ファイル一覧の作成にかける時間の上限
+    pub file_list_time_limit: Duration,
+}
+
+impl Default for EnvironmentDetailsOptions {
+    fn default() -> Self {
+        Self {
+            terminals: true,
+            time: true,
+            file_list: true,
+            context_size: true,
+            file_list_limit: DEFAULT_FILE_LIST_LIMIT,
+            mode_file_list_limits: HashMap::new(),
+            file_list_time_limit: DEFAULT_FILE_LIST_TIME_LIMIT,
+        }
+    }
+}
+
+impl EnvironmentDetailsOptions {
+    /// モードのファイル一覧の最大件数を指定する
+    pub fn with_mode_file_list_limit(mut self, mode: &str, limit: usize) -> Self {
+        self.mode_file_list_limits.insert(mode.to_string(), limit);
+        self
+    }
+
+    pub fn file_list_limit(&self, mode: &str) -> usize {
+        self.mode_file_list_limits
+            .get(mode)
+            .copied()
+            .unwrap_or(self.file_list_limit)
+    }
+}
+
+/// `environment_details` のセクションを作成するときに参照できるタスクの情報
+#[derive(Debug, Clone, Copy)]
+pub struct EnvDetailContext<'a> {
+    pub task_id: &'a str,
+    pub workspace_path: &'a Path,
+    pub mode: &'a str,
+    /// 最初のリクエストなど、ファイル一覧を含めるリクエスト
+    pub include_file_details: bool,
+}
+
+/// `environment_details` に追加するセクション
+///
+/// ホストがKubernetesのコンテキストやデータベースのスキーマの要約など、独自の情報を含めるために実装する。
+#[async_trait]
+pub trait EnvDetailSection: Debug + Send + Sync {
+    /// 見出し（`# ` は付けない）
+    fn title(&self) -> String;
+
+    /// 本文（`None` ならこのリクエストには含めない）
+    async fn content(&self, context: &EnvDetailContext<'_>) -> Result<Option<String>>;
+}
+
+#[derive(Debug)]
+struct DirEntryInfo {
+    name: String,
+    is_dir: bool,
+}
+
+/// 名前順のディレクトリの内容
+type DirListing = Arc<Vec<DirEntryInfo>>;
+
+/// ディレクトリの内容を、更新日時が変わるまで再利用するキャッシュ
+///
+/// ディレクトリの更新日時は直下のエントリの追加・削除・名前の変更で変わるため、
+/// ディレクトリごとに保持すれば変更のない部分の読み込みを省ける。
+#[derive(Debug, Default)]
+pub struct DirectoryCache {
+    dirs: Mutex<HashMap<PathBuf, (SystemTime, DirListing)>>,
+}
+
+impl DirectoryCache {
+    /// ディレクトリの内容（読み込めなければ `None`）
+    fn read(&self, dir: &Path) -> Option<DirListing> {
+        let modified = std::fs::metadata(dir).and_then(|m| m.modified()).ok();
+        if let Some(modified) = modified {
+            if let Some((cached_at, entries)) = self.dirs.lock().unwrap().get(dir) {
+                if *cached_at == modified {
+                    return Some(Arc::clone(entries));
+                }
+            }
+        }
+
+        let mut entries: Vec<_> = std::fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .filter_map(|entry| {
+                let file_type = entry.file_type().ok()?;
+                // シンボリックリンクなどは含めない
+                (file_type.is_dir() || file_type.is_file()).then(|| DirEntryInfo {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    is_dir: file_type.is_dir(),
+                })
+            })
+            .collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        let entries = Arc::new(entries);
+        if let Some(modified) = modified {
+            self.dirs
+                .lock()
+                .unwrap()
+                .insert(dir.to_path_buf(), (modified, Arc::clone(&entries)));
+        }
+        Some(entries)
+    }
+}
+
+/// ワークスペースのファイルを浅い階層から順に最大 `limit` 件返す（ディレクトリは末尾に `/`）
+///
+/// 件数を超えた場合は `true` も返す。読み込めないディレクトリは飛ばす。
+pub fn list_workspace_files(workspace_path: &Path, limit: usize) -> (Vec<String>, bool) {
+    list_workspace_files_cached(workspace_path, limit, &DirectoryCache::default(), None)
+}
+
+/// `cache` を使い、同じ階層のディレクトリを並列に読み込んでファイル一覧を作成する
+///
+/// `deadline` を過ぎた場合はそこまでの一覧を返し、省略したことを示す `true` も返す。
+pub fn list_workspace_files_cached(
+    workspace_path: &Path,
+    limit: usize,
+    cache: &DirectoryCache,
+    deadline: Option<Instant>,
+) -> (Vec<String>, bool) {
+    let mut files = Vec::new();
+    // (ディレクトリ, ワークスペースからの相対パス)
+    let mut level = vec![(workspace_path.to_path_buf(), String::new())];
+    while !level.is_empty() {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return (files, true);
+        }
+        let listings = read_dirs_parallel(cache, &level);
+        let mut next_level = Vec::new();
+        for ((dir, rel_dir), entries) in level.iter().zip(listings) {
+            for entry in entries.iter().flat_map(|entries| entries.iter()) {
+                let rel_path = format!("{}{}", rel_dir, entry.name);
+                if entry.is_dir {
+                    if SKIPPED_DIRS.contains(&entry.name.as_str()) {
+                        continue;
+                    }
+                    next_level.push((dir.join(&entry.name), format!("{}/", rel_path)));
+                    files.push(format!("{}/", rel_path));
+                } else {
+                    files.push(rel_path);
+                }
+                if files.len() > limit {
+                    files.truncate(limit);
+                    return (files, true);
+                }
+            }
+        }
+        level = next_level;
+    }
+    (files, false)
+}
+
+/// 同じ階層のディレクトリの内容を、順序を保ったまま複数のスレッドで読み込む
+fn read_dirs_parallel(
+    cache: &DirectoryCache,
+    dirs: &[(PathBuf, String)],
+) -> Vec<Option<DirListing>> {
+    let threads = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_WALK_THREADS)
+        .min(dirs.len());
+    if threads <= 1 {
+        return dirs.iter().map(|(dir, _)| cache.read(dir)).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<DirListing>>> = dirs.iter().map(|_| Mutex::new(None)).collect();
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some((dir, _)) = dirs.get(index) else {
+                    break;
+                };
+                *results[index].lock().unwrap() = cache.read(dir);
+            });
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.into_inner().unwrap())
+        .collect()
+}
+
+/// ファイル一覧を `environment_details` に含めるテキストにする
+pub fn format_file_list(files: &[String], truncated: bool) -> String {
+    if files.is_empty() {
+        return "(No files found)".to_string();
+    }
+    let mut text = files.join("\n");
+    if truncated {
+        text.push_str(
+            "\n\n(File list truncated. Use list_files on specific subdirectories if you need to explore further.)",
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_list_workspace_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("src/api")).unwrap();
+        std::fs::create_dir_all(root.join("node_modules/pkg")).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "").unwrap();
+        std::fs::write(root.join("src/lib.rs"), "").unwrap();
+        std::fs::write(root.join("src/api/mod.rs"), "").unwrap();
+
+        let (files, truncated) = list_workspace_files(root, 10);
+        assert_eq!(
+            files,
+            vec![
+                "Cargo.toml",
+                "src/",
+                "src/api/",
+                "src/lib.rs",
+                "src/api/mod.rs"
+            ]
+        );
+        assert!(!truncated);
+
+        let (files, truncated) = list_workspace_files(root, 2);
+        assert_eq!(files, vec!["Cargo.toml", "src/"]);
+        assert!(truncated);
+
+        // 変更のないディレクトリはキャッシュを使い、変更したディレクトリだけを読み直す
+        let cache = DirectoryCache::default();
+        let (cached, _) = list_workspace_files_cached(root, 10, &cache, None);
+        assert_eq!(cached.len(), 5);
+        assert_eq!(cache.dirs.lock().unwrap().len(), 3);
+        std::fs::write(root.join("src/api/routes.rs"), "").unwrap();
+        let (cached, _) = list_workspace_files_cached(root, 10, &cache, None);
+        assert_eq!(cached.last().unwrap(), "src/api/routes.rs");
+        let (cached, truncated) =
+            list_workspace_files_cached(root, 10, &cache, Some(Instant::now()));
+        assert!(cached.is_empty() && truncated);
+        assert!(format_file_list(&files, truncated).ends_with("explore further.)"));
+        assert_eq!(format_file_list(&[], false), "(No files found)");
+    }
+
+    #[test]
+    fn test_mode_file_list_limit() {
+        let options =
+            EnvironmentDetailsOptions::default().with_mode_file_list_limit("architect", 0);
+        assert_eq!(options.file_list_limit("architect"), 0);
+        assert_eq!(options.file_list_limit("code"), DEFAULT_FILE_LIST_LIMIT);
+    }
+}
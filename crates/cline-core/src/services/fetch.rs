@@ -0,0 +1,243 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Client, Method};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::time::Duration;
+
+use crate::cline::ToolApproval;
+
+/// `fetch` の既定のタイムアウト
+pub const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// `fetch` で読み込む応答の既定の最大サイズ
+pub const DEFAULT_FETCH_MAX_BYTES: usize = 1024 * 1024;
+
+/// `fetch` ツールの制限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FetchOptions {
+    /// 接続から応答の読み込みまでのタイムアウト
+    pub timeout: Duration,
+    /// 読み込む応答の本文の最大サイズ（超えた分は切り捨てる）
+    pub max_bytes: usize,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_FETCH_TIMEOUT,
+            max_bytes: DEFAULT_FETCH_MAX_BYTES,
+        }
+    }
+}
+
+/// `fetch` ツールのリクエスト
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FetchRequest {
+    pub url: String,
+    /// `GET`（既定）または `POST`
+    pub method: Option<String>,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<String>,
+}
+
+/// `fetch` のリクエストの送信を許可するか確認する（ヘッドレス実行時のホストが実装する）
+#[async_trait]
+pub trait FetchApprover: Debug + Send + Sync {
+    async fn approve(&self, request: &FetchRequest) -> bool;
+
+    /// 拒否の理由をモデルに伝える場合に実装する（既定では `approve` の結果を使う）
+    async fn review(&self, request: &FetchRequest) -> ToolApproval {
+        self.approve(request).await.into()
+    }
+}
+
+/// `fetch` ツールの応答
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: String,
+    /// 最大サイズを超えたため本文を切り捨てた
+    pub truncated: bool,
+}
+
+impl FetchResponse {
+    /// モデルに返す形式（HTMLはMarkdown、JSONは整形する）
+    pub fn format(&self) -> String {
+        let content_type = self.content_type.as_deref().unwrap_or_default();
+        let body = if content_type.contains("html") {
+            html2md::parse_html(&self.body).trim().to_string()
+        } else if content_type.contains("json") && !self.truncated {
+            serde_json::from_str::<serde_json::Value>(&self.body)
+                .and_then(|value| serde_json::to_string_pretty(&value))
+                .unwrap_or_else(|_| self.body.clone())
+        } else {
+            self.body.clone()
+        };
+        let mut text = format!(
+            "HTTP {}{}\n\n{}",
+            self.status,
+            self.content_type
+                .as_deref()
+                .map(|content_type| format!(" ({})", content_type))
+                .unwrap_or_default(),
+            body
+        );
+        if self.truncated {
+            text.push_str("\n\n(Response truncated)");
+        }
+        text
+    }
+}
+
+/// HTTPリクエストを送り、応答を最大サイズまで読み込む（ブラウザは使わない）
+pub async fn fetch(request: &FetchRequest, options: &FetchOptions) -> Result<FetchResponse> {
+    let url = reqwest::Url::parse(&request.url)
+        .map_err(|e| anyhow::anyhow!("Invalid URL '{}': {}", request.url, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        anyhow::bail!("Only http and https URLs can be fetched: {}", request.url);
+    }
+    let method = match request
+        .method
+        .as_deref()
+        .map(str::to_ascii_uppercase)
+        .as_deref()
+    {
+        None | Some("GET") => Method::GET,
+        Some("POST") => Method::POST,
+        Some(method) => anyhow::bail!("Unsupported method '{}': use GET or POST", method),
+    };
+    let mut headers = HeaderMap::new();
+    for (name, value) in &request.headers {
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| anyhow::anyhow!("Invalid header name '{}'", name))?,
+            HeaderValue::from_str(value)
+                .map_err(|_| anyhow::anyhow!("Invalid value for header '{}'", name))?,
+        );
+    }
+
+    let client = Client::builder().timeout(options.timeout).build()?;
+    let mut builder = client.request(method, url).headers(headers);
+    if let Some(body) = &request.body {
+        builder = builder.body(body.clone());
+    }
+    let mut response = builder
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("Request to {} failed: {}", request.url, e))?;
+
+    let status = response.status().as_u16();
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    let mut body = Vec::new();
+    let mut truncated = false;
+    while let Some(chunk) = response.chunk().await? {
+        let remaining = options.max_bytes - body.len();
+        if chunk.len() > remaining {
+            body.extend_from_slice(&chunk[..remaining]);
+            truncated = true;
+            break;
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(FetchResponse {
+        status,
+        content_type,
+        body: String::from_utf8_lossy(&body).into_owned(),
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    /// 1件のリクエストに用意した応答を返し、受け取ったリクエストを返すサーバー
+    fn serve_once(response: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).unwrap();
+            stream.write_all(response.as_bytes()).unwrap();
+            String::from_utf8_lossy(&request[..len]).into_owned()
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_formats_json_and_html() {
+        let (url, server) = serve_once(
+            "HTTP/1.1 201 Created\r\nContent-Type: application/json\r\nContent-Length: 18\r\nConnection: close\r\n\r\n{\"id\":1,\"ok\":true}",
+        );
+        let response = fetch(
+            &FetchRequest {
+                url: format!("{}/items", url),
+                method: Some("post".to_string()),
+                headers: BTreeMap::from([("X-Token".to_string(), "secret".to_string())]),
+                body: Some("{\"name\":\"a\"}".to_string()),
+            },
+            &FetchOptions::default(),
+        )
+        .await
+        .unwrap();
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /items HTTP/1.1"));
+        assert!(request.to_ascii_lowercase().contains("x-token: secret"));
+        assert_eq!(
+            response.format(),
+            "HTTP 201 (application/json)\n\n{\n  \"id\": 1,\n  \"ok\": true\n}"
+        );
+
+        let (url, _server) = serve_once(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nConnection: close\r\n\r\n<h1>Title</h1><p>Hello world, this page is long</p>",
+        );
+        let response = fetch(
+            &FetchRequest {
+                url,
+                ..Default::default()
+            },
+            &FetchOptions {
+                max_bytes: 30,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(response.truncated);
+        assert_eq!(
+            response.format(),
+            "HTTP 200 (text/html)\n\nTitle\n==========\n\nHello world,\n\n(Response truncated)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_rejects_invalid_requests() {
+        let options = FetchOptions::default();
+        let request = |url: &str, method: Option<&str>| FetchRequest {
+            url: url.to_string(),
+            method: method.map(String::from),
+            ..Default::default()
+        };
+        assert!(fetch(&request("file:///etc/passwd", None), &options)
+            .await
+            .is_err());
+        assert!(fetch(&request("not a url", None), &options).await.is_err());
+        assert!(
+            fetch(&request("http://127.0.0.1:9", Some("DELETE")), &options)
+                .await
+                .unwrap_err()
+                .to_string()
+                .contains("Unsupported method")
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use anyhow::Result;
+use flate2::read::DeflateDecoder;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::io::Read;
+
+/// 展開するエントリの最大サイズ
+const MAX_ENTRY_BYTES: u64 = 64 * 1024 * 1024;
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+const CENTRAL_DIRECTORY_ENTRY: u32 = 0x02014b50;
+const LOCAL_FILE_HEADER: u32 = 0x04034b50;
+
+lazy_static! {
+    static ref XML_TOKEN: Regex = Regex::new(r"<[^>]*>|[^<]+").unwrap();
+    static ref STYLE_VALUE: Regex = Regex::new(r#"w:val="([^"]*)""#).unwrap();
+}
+
+/// DOCXの本文をテキストにする（見出しは `#` の付いた行にする）
+pub fn extract_docx_text(data: &[u8]) -> Result<String> {
+    let document = read_zip_entry(data, "word/document.xml")?;
+    let document = String::from_utf8(document)
+        .map_err(|_| anyhow::anyhow!("word/document.xml is not valid UTF-8"))?;
+    Ok(document_text(&document))
+}
+
+fn document_text(xml: &str) -> String {
+    let mut text = String::new();
+    let mut paragraph = String::new();
+    let mut heading_level = 0;
+    let mut in_text = false;
+    for token in XML_TOKEN.find_iter(xml).map(|m| m.as_str()) {
+        let Some(tag) = token.strip_prefix('<') else {
+            if in_text {
+                paragraph.push_str(&decode_entities(token));
+            }
+            continue;
+        };
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .next()
+            .unwrap_or_default();
+        match (name, closing) {
+            // 空の段落
+            ("w:p", false) if tag.ends_with("/>") => text.push('\n'),
+            ("w:p", false) => {
+                paragraph.clear();
+                heading_level = 0;
+            }
+            ("w:p", true) => {
+                if heading_level > 0 && !paragraph.trim().is_empty() {
+                    text.push_str(&format!("{} ", "#".repeat(heading_level)));
+                }
+                text.push_str(paragraph.trim_end());
+                text.push('\n');
+                paragraph.clear();
+            }
+            ("w:pStyle", false) => {
+                heading_level = STYLE_VALUE
+                    .captures(tag)
+                    .map_or(0, |captures| heading_style_level(&captures[1]));
+            }
+            ("w:t", _) => in_text = !closing && !tag.ends_with("/>"),
+            ("w:tab", false) => paragraph.push('\t'),
+            ("w:br" | "w:cr", false) => paragraph.push('\n'),
+            _ => {}
+        }
+    }
+    text.trim_end().to_string()
+}
+
+/// `Title` は1、`Heading2` は2（見出しでない場合は0）
+fn heading_style_level(style: &str) -> usize {
+    if style == "Title" {
+        return 1;
+    }
+    style
+        .strip_prefix("Heading")
+        .and_then(|level| level.parse::<usize>().ok())
+        .map_or(0, |level| level.clamp(1, 6))
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse::<u32>()))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// ZIPから指定したエントリを取り出す（無圧縮とDeflateのみ対応）
+fn read_zip_entry(data: &[u8], entry_name: &str) -> Result<Vec<u8>> {
+    let not_docx = || anyhow::anyhow!("Not a valid DOCX file");
+    // コメントの最大長を含めて末尾からEnd of central directoryを探す
+    let search_start = data.len().saturating_sub(22 + u16::MAX as usize);
+    let eocd = (search_start..data.len().saturating_sub(21))
+        .rev()
+        .find(|&offset| read_u32(data, offset) == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(not_docx)?;
+    let entries = read_u16(data, eocd + 10).ok_or_else(not_docx)?;
+    let mut offset = read_u32(data, eocd + 16).ok_or_else(not_docx)? as usize;
+
+    for _ in 0..entries {
+        if read_u32(data, offset) != Some(CENTRAL_DIRECTORY_ENTRY) {
+            return Err(not_docx());
+        }
+        let field = |at: usize| read_u16(data, offset + at).ok_or_else(not_docx);
+        let method = field(10)?;
+        let compressed_size = read_u32(data, offset + 20).ok_or_else(not_docx)? as usize;
+        let name_len = field(28)? as usize;
+        let extra_len = field(30)? as usize;
+        let comment_len = field(32)? as usize;
+        let local_offset = read_u32(data, offset + 42).ok_or_else(not_docx)? as usize;
+        let name = data
+            .get(offset + 46..offset + 46 + name_len)
+            .ok_or_else(not_docx)?;
+        offset += 46 + name_len + extra_len + comment_len;
+        if name != entry_name.as_bytes() {
+            continue;
+        }
+
+        if read_u32(data, local_offset) != Some(LOCAL_FILE_HEADER) {
+            return Err(not_docx());
+        }
+        let local_name_len = read_u16(data, local_offset + 26).ok_or_else(not_docx)? as usize;
+        let local_extra_len = read_u16(data, local_offset + 28).ok_or_else(not_docx)? as usize;
+        let start = local_offset + 30 + local_name_len + local_extra_len;
+        let compressed = data
+            .get(start..start + compressed_size)
+            .ok_or_else(not_docx)?;
+        return match method {
+            0 => Ok(compressed.to_vec()),
+            8 => {
+                let mut content = Vec::new();
+                DeflateDecoder::new(compressed)
+                    .take(MAX_ENTRY_BYTES)
+                    .read_to_end(&mut content)?;
+                Ok(content)
+            }
+            _ => anyhow::bail!("Unsupported compression method {} in DOCX", method),
+        };
+    }
+    anyhow::bail!("{} not found in DOCX", entry_name)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    /// テスト用のZIPを作る（CRCは検証しないため0にする）
+    fn zip(entries: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut central = Vec::new();
+        for (name, content, deflate) in entries {
+            let compressed = if *deflate {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(content).unwrap();
+                encoder.finish().unwrap()
+            } else {
+                content.to_vec()
+            };
+            let method: u16 = if *deflate { 8 } else { 0 };
+            let local_offset = data.len() as u32;
+
+            data.extend(LOCAL_FILE_HEADER.to_le_bytes());
+            data.extend([0u8; 4]);
+            data.extend(method.to_le_bytes());
+            data.extend([0u8; 8]);
+            data.extend((compressed.len() as u32).to_le_bytes());
+            data.extend((content.len() as u32).to_le_bytes());
+            data.extend((name.len() as u16).to_le_bytes());
+            data.extend(0u16.to_le_bytes());
+            data.extend(name.as_bytes());
+            data.extend(&compressed);
+
+            central.extend(CENTRAL_DIRECTORY_ENTRY.to_le_bytes());
+            central.extend([0u8; 6]);
+            central.extend(method.to_le_bytes());
+            central.extend([0u8; 8]);
+            central.extend((compressed.len() as u32).to_le_bytes());
+            central.extend((content.len() as u32).to_le_bytes());
+            central.extend((name.len() as u16).to_le_bytes());
+            central.extend([0u8; 12]);
+            central.extend(local_offset.to_le_bytes());
+            central.extend(name.as_bytes());
+        }
+        let central_offset = data.len() as u32;
+        let central_len = central.len() as u32;
+        data.extend(central);
+        data.extend(END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        data.extend([0u8; 4]);
+        data.extend((entries.len() as u16).to_le_bytes());
+        data.extend((entries.len() as u16).to_le_bytes());
+        data.extend(central_len.to_le_bytes());
+        data.extend(central_offset.to_le_bytes());
+        data.extend(0u16.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_extract_docx_text() {
+        let document = r#"<?xml version="1.0" encoding="UTF-8"?>
+<w:document><w:body>
+<w:p><w:pPr><w:pStyle w:val="Heading1"/></w:pPr><w:r><w:t>Overview</w:t></w:r></w:p>
+<w:p><w:r><w:t xml:space="preserve">Fish &amp; chips </w:t></w:r><w:r><w:t>cost &#163;5</w:t></w:r></w:p>
+<w:p><w:r><w:t>a</w:t><w:tab/><w:t>b</w:t><w:br/><w:t>c</w:t></w:r></w:p>
+<w:p><w:pPr><w:pStyle w:val="Heading2"/></w:pPr><w:r><w:t>Details</w:t></w:r></w:p>
+<w:sectPr/></w:body></w:document>"#;
+        let data = zip(&[
+            ("[Content_Types].xml", b"<Types/>", false),
+            ("word/document.xml", document.as_bytes(), true),
+        ]);
+        assert_eq!(
+            extract_docx_text(&data).unwrap(),
+            "# Overview\nFish & chips cost £5\na\tb\nc\n## Details"
+        );
+
+        let data = zip(&[("word/styles.xml", b"<w:styles/>", false)]);
+        assert!(extract_docx_text(&data).is_err());
+        assert!(extract_docx_text(b"not a zip").is_err());
+    }
+}
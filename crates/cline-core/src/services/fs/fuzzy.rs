@@ -0,0 +1,184 @@
+use async_trait::async_trait;
+use std::fmt::Debug;
+
+/// 確認を求める候補の最大数
+pub const MAX_FUZZY_CANDIDATES: usize = 5;
+
+/// パスを推測するために一覧にするワークスペースのファイルの最大数
+pub const MAX_FUZZY_INDEX_FILES: usize = 20_000;
+
+/// 存在しないパスをワークスペースのファイルから探した結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FuzzyPathMatch {
+    /// パスの末尾が一致するファイルが1つだけある
+    Unique(String),
+    /// 確認が必要な候補（一致度の高い順）
+    Ambiguous(Vec<String>),
+    NotFound,
+}
+
+/// 複数の候補から使うファイルを選ぶ（ヘッドレス実行時のホストが実装する）
+///
+/// 選ぶ相手がいない場合は候補をモデルに返し、パスを指定し直させる。
+#[async_trait]
+pub trait PathChooser: Debug + Send + Sync {
+    /// `query` の代わりに使うパス（`None` でどれも使わない）
+    async fn choose(&self, query: &str, candidates: &[String]) -> Option<String>;
+}
+
+/// ワークスペースのファイル一覧（`list_workspace_files` の結果）から `query` に当たるファイルを探す
+///
+/// パスの末尾がディレクトリ単位で一致するファイルを優先し、なければfzfのように
+/// 文字の順序だけが一致するファイルを候補にする。
+pub fn match_path(files: &[String], query: &str) -> FuzzyPathMatch {
+    let query = query
+        .trim_start_matches("./")
+        .trim_start_matches('/')
+        .replace('\\', "/");
+    if query.is_empty() {
+        return FuzzyPathMatch::NotFound;
+    }
+    let want_dir = query.ends_with('/');
+    let candidates = files.iter().filter(|file| file.ends_with('/') == want_dir);
+
+    let suffix = format!("/{}", query);
+    let mut suffix_matches: Vec<&String> = candidates
+        .clone()
+        .filter(|file| **file == query || file.ends_with(&suffix))
+        .collect();
+    match suffix_matches.len() {
+        0 => {}
+        1 => return FuzzyPathMatch::Unique(suffix_matches[0].clone()),
+        _ => {
+            suffix_matches.sort_by_key(|file| (file.matches('/').count(), file.len()));
+            return FuzzyPathMatch::Ambiguous(
+                suffix_matches
+                    .into_iter()
+                    .take(MAX_FUZZY_CANDIDATES)
+                    .cloned()
+                    .collect(),
+            );
+        }
+    }
+
+    // ディレクトリを含まない指定はファイル名だけと比べる
+    let by_name = !query.trim_end_matches('/').contains('/');
+    let mut scored: Vec<(i64, &String)> = candidates
+        .filter_map(|file| {
+            let target = if by_name {
+                file_name(file)
+            } else {
+                file.as_str()
+            };
+            fuzzy_score(query.trim_end_matches('/'), target).map(|score| (score, file))
+        })
+        .collect();
+    if scored.is_empty() {
+        return FuzzyPathMatch::NotFound;
+    }
+    scored.sort_by(|(a_score, a), (b_score, b)| {
+        b_score.cmp(a_score).then_with(|| a.len().cmp(&b.len()))
+    });
+    FuzzyPathMatch::Ambiguous(
+        scored
+            .into_iter()
+            .take(MAX_FUZZY_CANDIDATES)
+            .map(|(_, file)| file.clone())
+            .collect(),
+    )
+}
+
+fn file_name(path: &str) -> &str {
+    let path = path.trim_end_matches('/');
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// `query` の文字が順に `candidate` に含まれる場合の一致度（大文字・小文字は区別しない）
+///
+/// 区切りの直後と連続した一致を高く、飛ばした文字を低く評価する。
+/// 一致度が低すぎる（半分以上の文字がばらばらに一致する）場合は `None` にする。
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    if query.is_empty() || query.len() > candidate.len() {
+        return None;
+    }
+
+    let mut score = 0;
+    let mut scattered = 0;
+    let mut last_match: Option<usize> = None;
+    let mut position = 0;
+    for &c in &query {
+        let found = candidate[position..].iter().position(|&x| x == c)? + position;
+        score += 16;
+        let at_boundary = found == 0 || matches!(candidate[found - 1], '/' | '_' | '-' | '.' | ' ');
+        match last_match {
+            Some(last) if last + 1 == found => score += 12,
+            _ if at_boundary => score += 8,
+            Some(last) => {
+                score -= (found - last - 1) as i64;
+                scattered += 1;
+            }
+            None => {
+                score -= found as i64;
+                scattered += 1;
+            }
+        }
+        last_match = Some(found);
+        position = found + 1;
+    }
+    // 余分な文字の多い候補を下げる
+    score -= (candidate.len() - query.len()) as i64 / 4;
+    (scattered * 2 <= query.len()).then_some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_match_path() {
+        let files: Vec<String> = [
+            "Cargo.toml",
+            "src/",
+            "src/lib.rs",
+            "src/utils.rs",
+            "src/api/",
+            "src/api/utils.rs",
+            "src/api/handlers.rs",
+            "tests/integration.rs",
+        ]
+        .iter()
+        .map(|file| file.to_string())
+        .collect();
+
+        assert_eq!(
+            match_path(&files, "api/utils.rs"),
+            FuzzyPathMatch::Unique("src/api/utils.rs".to_string())
+        );
+        assert_eq!(
+            match_path(&files, "handlers.rs"),
+            FuzzyPathMatch::Unique("src/api/handlers.rs".to_string())
+        );
+        assert_eq!(
+            match_path(&files, "utils.rs"),
+            FuzzyPathMatch::Ambiguous(vec![
+                "src/utils.rs".to_string(),
+                "src/api/utils.rs".to_string()
+            ])
+        );
+        assert_eq!(
+            match_path(&files, "handler.rs"),
+            FuzzyPathMatch::Ambiguous(vec!["src/api/handlers.rs".to_string()])
+        );
+        assert_eq!(
+            match_path(&files, "api/"),
+            FuzzyPathMatch::Unique("src/api/".to_string())
+        );
+        assert_eq!(match_path(&files, "README.md"), FuzzyPathMatch::NotFound);
+
+        assert!(fuzzy_score("utl", "utils.rs") > fuzzy_score("utl", "mutable.rs"));
+        assert_eq!(fuzzy_score("xyz", "utils.rs"), None);
+    }
+}
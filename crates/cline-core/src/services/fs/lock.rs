@@ -0,0 +1,70 @@
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use tokio::sync::OwnedMutexGuard;
+
+lazy_static! {
+    /// パスごとの編集ロック（使われなくなったロックは次に取得するときに取り除く）
+    static ref FILE_LOCKS: Mutex<HashMap<PathBuf, Weak<tokio::sync::Mutex<()>>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// ファイルの編集ロック
+///
+/// 破棄するまで同じパスへの他の編集を待たせる。
+#[derive(Debug)]
+pub struct FileLock {
+    _guard: OwnedMutexGuard<()>,
+}
+
+/// ファイルの編集ロックを取得する
+///
+/// 同じプロセス内（並列のサブタスクを含む）で同じパスを編集する場合は、取得した順に1つずつ実行される。
+/// 読み込みから書き込みまでロックを保持することで、差分のプレビューと書き込む内容が一致する。
+pub async fn lock_file(path: &Path) -> FileLock {
+    let lock = {
+        let mut locks = FILE_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+        locks.retain(|_, lock| lock.strong_count() > 0);
+        match locks.get(path).and_then(Weak::upgrade) {
+            Some(lock) => lock,
+            None => {
+                let lock = Arc::new(tokio::sync::Mutex::new(()));
+                locks.insert(path.to_path_buf(), Arc::downgrade(&lock));
+                lock
+            }
+        }
+    };
+    FileLock {
+        _guard: lock.lock_owned().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_lock_file_serializes_same_path() {
+        let path = Path::new("/tmp/cline-lock-test/a.rs");
+        let lock = lock_file(path).await;
+
+        // 別のパスはすぐに取得できる
+        let other = tokio::time::timeout(
+            Duration::from_millis(100),
+            lock_file(Path::new("/tmp/cline-lock-test/b.rs")),
+        )
+        .await;
+        assert!(other.is_ok());
+
+        let waiting = tokio::spawn(lock_file(path));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+        drop(lock);
+        tokio::time::timeout(Duration::from_millis(100), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
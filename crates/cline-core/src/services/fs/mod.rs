@@ -0,0 +1,228 @@
+use anyhow::Result;
+use std::path::Path;
+
+#[cfg(feature = "document-extraction")]
+mod docx;
+mod fuzzy;
+mod lock;
+#[cfg(feature = "document-extraction")]
+mod pdf;
+mod rope;
+
+#[cfg(feature = "document-extraction")]
+pub use docx::extract_docx_text;
+pub use fuzzy::{
+    fuzzy_score, match_path, FuzzyPathMatch, PathChooser, MAX_FUZZY_CANDIDATES,
+    MAX_FUZZY_INDEX_FILES,
+};
+pub use lock::{lock_file, FileLock};
+#[cfg(feature = "document-extraction")]
+pub use pdf::extract_pdf_text;
+pub use rope::Rope;
+
+/// バイナリ判定のために先頭から調べるバイト数
+const BINARY_SNIFF_BYTES: usize = 8000;
+
+/// テキストとして読み込むファイルの既定の最大サイズ
+pub const DEFAULT_MAX_READ_BYTES: u64 = 1024 * 1024;
+
+/// テキストを抽出する文書（PDF・DOCX）の最大サイズ
+#[cfg(feature = "document-extraction")]
+const MAX_DOCUMENT_BYTES: u64 = 50 * 1024 * 1024;
+
+/// 内容を調べずにバイナリとみなす拡張子
+pub const BINARY_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "webp", "tif", "tiff", "psd", "mp3", "mp4", "wav",
+    "ogg", "flac", "mov", "avi", "mkv", "webm", "zip", "gz", "tgz", "bz2", "xz", "7z", "rar",
+    "tar", "jar", "war", "exe", "dll", "so", "dylib", "o", "a", "lib", "bin", "class", "pyc",
+    "wasm", "woff", "woff2", "ttf", "otf", "eot", "sqlite", "db", "pdf", "doc", "docx", "xls",
+    "xlsx", "ppt", "pptx",
+];
+
+/// 拡張子がバイナリの形式
+pub fn is_binary_extension(path: &Path) -> bool {
+    file_extension(path).is_some_and(|extension| BINARY_EXTENSIONS.contains(&extension.as_str()))
+}
+
+/// 先頭にNULを含む内容はバイナリとみなす
+pub fn is_binary_content(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0)
+}
+
+/// `512 B`・`4.2 MB` の形式のサイズ
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// 内容の代わりに返すバイナリファイルの注記
+pub fn binary_placeholder(size: u64) -> String {
+    format!("(binary file, {})", format_size(size))
+}
+
+/// 内容の代わりに返す大きすぎるファイルの注記
+pub fn too_large_placeholder(size: u64) -> String {
+    format!("(file too large, {})", format_size(size))
+}
+
+/// `read_file_contents` で読み込んだファイル
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileContents {
+    /// モデルに渡すテキスト（PDF・DOCXは抽出したテキスト、読めないファイルは注記）
+    pub text: String,
+    /// テキストの元にしたファイルの内容（読み込まずに注記を返した場合は `None`）
+    pub bytes: Option<Vec<u8>>,
+}
+
+impl FileContents {
+    fn placeholder(text: String) -> Self {
+        Self { text, bytes: None }
+    }
+}
+
+/// ファイルをテキストとして読み込む（PDF・DOCXはテキストを抽出する）
+///
+/// バイナリファイルや `max_bytes` を超えるファイルは、内容の代わりに注記を返す。
+pub async fn read_file_text(path: &Path, max_bytes: u64) -> Result<String> {
+    Ok(read_file_contents(path, max_bytes).await?.text)
+}
+
+/// `read_file_text` と同じテキストを、読み込んだファイルの内容と合わせて返す
+///
+/// 読んだ内容のハッシュを記録する場合に使う（読み直すと間に変更された内容になり得る）。
+pub async fn read_file_contents(path: &Path, max_bytes: u64) -> Result<FileContents> {
+    let size = tokio::fs::metadata(path).await?.len();
+    #[cfg(feature = "document-extraction")]
+    if let Some(extract) = document_extractor(path) {
+        if size > MAX_DOCUMENT_BYTES {
+            return Ok(FileContents::placeholder(too_large_placeholder(size)));
+        }
+        let bytes = tokio::fs::read(path).await?;
+        let (text, bytes) =
+            tokio::task::spawn_blocking(move || extract(&bytes).map(|text| (text, bytes)))
+                .await
+                .map_err(|e| anyhow::anyhow!("Text extraction task failed: {}", e))??;
+        if text.len() as u64 > max_bytes {
+            return Ok(FileContents::placeholder(too_large_placeholder(
+                text.len() as u64
+            )));
+        }
+        return Ok(FileContents {
+            text,
+            bytes: Some(bytes),
+        });
+    }
+    if is_binary_extension(path) {
+        return Ok(FileContents::placeholder(binary_placeholder(size)));
+    }
+    if size > max_bytes {
+        return Ok(FileContents::placeholder(too_large_placeholder(size)));
+    }
+    let bytes = tokio::fs::read(path).await?;
+    let text = if is_binary_content(&bytes) {
+        binary_placeholder(bytes.len() as u64)
+    } else {
+        String::from_utf8_lossy(&bytes).into_owned()
+    };
+    Ok(FileContents {
+        text,
+        bytes: Some(bytes),
+    })
+}
+
+/// 文書からテキストを抽出する関数
+#[cfg(feature = "document-extraction")]
+type TextExtractor = fn(&[u8]) -> Result<String>;
+
+#[cfg(feature = "document-extraction")]
+fn document_extractor(path: &Path) -> Option<TextExtractor> {
+    match file_extension(path)?.as_str() {
+        "pdf" => Some(extract_pdf_text),
+        "docx" => Some(extract_docx_text),
+        _ => None,
+    }
+}
+
+fn file_extension(path: &Path) -> Option<String> {
+    path.extension()?
+        .to_str()
+        .map(|extension| extension.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_read_file_text() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let text_path = temp_dir.path().join("notes.txt");
+        std::fs::write(&text_path, "hello\n").unwrap();
+        assert_eq!(read_file_text(&text_path, 100).await.unwrap(), "hello\n");
+        assert_eq!(
+            read_file_text(&text_path, 3).await.unwrap(),
+            "(file too large, 6 B)"
+        );
+
+        let latin1_path = temp_dir.path().join("latin1.txt");
+        std::fs::write(&latin1_path, b"caf\xe9").unwrap();
+        assert_eq!(
+            read_file_text(&latin1_path, 100).await.unwrap(),
+            "caf\u{fffd}"
+        );
+
+        let binary_path = temp_dir.path().join("data.dat");
+        std::fs::write(&binary_path, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\xff").unwrap();
+        assert_eq!(
+            read_file_text(&binary_path, 100).await.unwrap(),
+            "(binary file, 17 B)"
+        );
+        let image_path = temp_dir.path().join("logo.PNG");
+        std::fs::write(&image_path, "not really an image").unwrap();
+        assert_eq!(
+            read_file_text(&image_path, 100).await.unwrap(),
+            "(binary file, 19 B)"
+        );
+
+        assert!(read_file_text(&temp_dir.path().join("missing.txt"), 100)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_file_contents_returns_read_bytes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("latin1.txt");
+        std::fs::write(&path, b"caf\xe9").unwrap();
+        assert_eq!(
+            read_file_contents(&path, 100).await.unwrap(),
+            FileContents {
+                text: "caf\u{fffd}".to_string(),
+                bytes: Some(b"caf\xe9".to_vec()),
+            }
+        );
+        // 読み込まずに注記を返した場合は内容がない
+        assert_eq!(read_file_contents(&path, 2).await.unwrap().bytes, None);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(4_404_019), "4.2 MB");
+        assert_eq!(
+            binary_placeholder(3 * 1024 * 1024 * 1024),
+            "(binary file, 3.0 GB)"
+        );
+    }
+}
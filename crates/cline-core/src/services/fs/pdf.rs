@@ -0,0 +1,649 @@
+use anyhow::Result;
+use flate2::read::ZlibDecoder;
+use lazy_static::lazy_static;
+use regex::bytes::Regex;
+use std::collections::{BTreeMap, HashSet};
+use std::io::Read;
+
+/// 展開するストリームの最大サイズ
+const MAX_STREAM_BYTES: u64 = 64 * 1024 * 1024;
+
+/// ページツリーをたどる最大の深さ
+const MAX_PAGE_TREE_DEPTH: usize = 32;
+
+/// TJ配列の文字間隔がこれより大きい場合は単語の区切りとみなす（1/1000 em）
+const WORD_SPACING: f32 = 200.0;
+
+lazy_static! {
+    static ref OBJECT_START: Regex = Regex::new(r"(\d+)\s+\d+\s+obj\b").unwrap();
+    static ref REFERENCE: Regex = Regex::new(r"(\d+)\s+\d+\s+R\b").unwrap();
+    static ref LENGTH: Regex = Regex::new(r"/Length\s+(\d+)(\s+\d+\s+R)?").unwrap();
+    static ref PAGES: Regex = Regex::new(r"/Pages\s+(\d+)\s+\d+\s+R").unwrap();
+    static ref KIDS: Regex = Regex::new(r"(?s)/Kids\s*\[([^\]]*)\]").unwrap();
+    static ref CONTENTS: Regex =
+        Regex::new(r"(?s)/Contents\s*(?:\[([^\]]*)\]|(\d+)\s+\d+\s+R)").unwrap();
+    static ref CATALOG: Regex = Regex::new(r"/Type\s*/Catalog\b").unwrap();
+    static ref PAGE: Regex = Regex::new(r"/Type\s*/Page\b").unwrap();
+    static ref OBJECT_STREAM: Regex = Regex::new(r"/Type\s*/ObjStm\b").unwrap();
+    static ref FIRST: Regex = Regex::new(r"/First\s+(\d+)").unwrap();
+}
+
+/// PDFのオブジェクト（辞書とストリーム）
+#[derive(Debug, Default)]
+struct PdfObject {
+    dict: Vec<u8>,
+    stream: Option<Vec<u8>>,
+}
+
+impl PdfObject {
+    /// 展開したストリーム（FlateDecode以外のフィルタは対応しない）
+    fn decoded_stream(&self) -> Option<Vec<u8>> {
+        let stream = self.stream.as_ref()?;
+        if !contains(&self.dict, b"/Filter") {
+            return Some(stream.clone());
+        }
+        if !contains(&self.dict, b"/FlateDecode") {
+            return None;
+        }
+        let mut decoded = Vec::new();
+        // 末尾が壊れていても展開できた部分は使う
+        let _ = ZlibDecoder::new(stream.as_slice())
+            .take(MAX_STREAM_BYTES)
+            .read_to_end(&mut decoded);
+        (!decoded.is_empty()).then_some(decoded)
+    }
+}
+
+/// PDFのテキストをページごとに抽出する（ページの前に `--- Page N ---` を付ける）
+///
+/// 画像だけのページや独自のエンコーディングのフォントは抽出できない。
+pub fn extract_pdf_text(data: &[u8]) -> Result<String> {
+    if !data.starts_with(b"%PDF") {
+        anyhow::bail!("Not a valid PDF file");
+    }
+    let objects = parse_objects(data);
+    let pages = page_order(&objects);
+    if pages.is_empty() {
+        anyhow::bail!("No pages found in PDF");
+    }
+
+    let mut text = String::new();
+    let mut has_text = false;
+    for (index, page) in pages.iter().enumerate() {
+        let page = objects
+            .get(page)
+            .ok_or_else(|| anyhow::anyhow!("Missing page object {} in PDF", page))?;
+        let content: Vec<u8> = page_contents(page)
+            .iter()
+            .filter_map(|id| objects.get(id)?.decoded_stream())
+            .flat_map(|mut stream| {
+                stream.push(b'\n');
+                stream
+            })
+            .collect();
+        let page_text = content_text(&content);
+        has_text |= !page_text.is_empty();
+        if index > 0 {
+            text.push_str("\n\n");
+        }
+        text.push_str(&format!("--- Page {} ---\n{}", index + 1, page_text));
+    }
+    if !has_text {
+        anyhow::bail!("No extractable text found in PDF (it may contain only scanned images)");
+    }
+    Ok(text)
+}
+
+fn parse_objects(data: &[u8]) -> BTreeMap<u32, PdfObject> {
+    let mut objects = BTreeMap::new();
+    let mut pos = 0;
+    // ストリームのデータに `N 0 obj` と同じバイト列があっても区切らないよう、
+    // 前のオブジェクトの終わりから次のオブジェクトを探す
+    while let Some(captures) = OBJECT_START.captures_at(data, pos) {
+        let Some(start) = captures.get(0).map(|m| m.end()) else {
+            break;
+        };
+        let (object, end) = parse_object(data, start);
+        if let Some(id) = std::str::from_utf8(&captures[1])
+            .ok()
+            .and_then(|id| id.parse().ok())
+        {
+            objects.insert(id, object);
+        }
+        pos = end.max(start);
+    }
+
+    // PDF 1.5以降はページ辞書がオブジェクトストリームに圧縮されていることがある
+    let compressed: Vec<(u32, Vec<u8>)> = objects
+        .values()
+        .filter(|object| OBJECT_STREAM.is_match(&object.dict))
+        .flat_map(object_stream_entries)
+        .collect();
+    for (id, dict) in compressed {
+        objects
+            .entry(id)
+            .or_insert(PdfObject { dict, stream: None });
+    }
+    objects
+}
+
+/// `start` から始まるオブジェクトを読み、オブジェクトと終わりの位置を返す
+///
+/// ストリームのデータは `/Length`（間接参照の場合は `endstream`）で読み飛ばす。
+fn parse_object(data: &[u8], start: usize) -> (PdfObject, usize) {
+    let body = &data[start..];
+    // 辞書の部分はストリームのデータより前にあるため、次のオブジェクトの前までで探せる
+    let header_end = OBJECT_START
+        .find(body)
+        .map_or(body.len(), |next| next.start());
+    let header = &body[..header_end];
+    let endobj = find(header, b"endobj");
+    let stream_start =
+        find(header, b"stream").filter(|&stream| endobj.is_none_or(|endobj| stream < endobj));
+    let Some(stream_start) = stream_start else {
+        let end = endobj.unwrap_or(header_end);
+        let object = PdfObject {
+            dict: body[..end].to_vec(),
+            stream: None,
+        };
+        return (
+            object,
+            start + endobj.map_or(end, |end| end + b"endobj".len()),
+        );
+    };
+    let dict = body[..stream_start].to_vec();
+    let mut data_start = stream_start + b"stream".len();
+    if body[data_start..].starts_with(b"\r\n") {
+        data_start += 2;
+    } else if body[data_start..].starts_with(b"\n") {
+        data_start += 1;
+    }
+    let direct_length = LENGTH
+        .captures(&dict)
+        .filter(|captures| captures.get(2).is_none())
+        .and_then(|captures| {
+            std::str::from_utf8(&captures[1])
+                .ok()?
+                .parse::<usize>()
+                .ok()
+        })
+        .filter(|length| data_start + length <= body.len());
+    let data_end = direct_length
+        .map(|length| data_start + length)
+        .or_else(|| find(&body[data_start..], b"endstream").map(|end| data_start + end))
+        .unwrap_or(body.len());
+    let end =
+        find(&body[data_end..], b"endobj").map_or(data_end, |end| data_end + end + b"endobj".len());
+    let object = PdfObject {
+        dict,
+        stream: Some(body[data_start..data_end].to_vec()),
+    };
+    (object, start + end)
+}
+
+/// オブジェクトストリームに含まれるオブジェクトの番号と辞書
+fn object_stream_entries(object: &PdfObject) -> Vec<(u32, Vec<u8>)> {
+    let (Some(stream), Some(first)) = (
+        object.decoded_stream(),
+        FIRST.captures(&object.dict).and_then(|captures| {
+            std::str::from_utf8(&captures[1])
+                .ok()?
+                .parse::<usize>()
+                .ok()
+        }),
+    ) else {
+        return Vec::new();
+    };
+    let Some(header) = stream.get(..first) else {
+        return Vec::new();
+    };
+    let numbers: Vec<usize> = String::from_utf8_lossy(header)
+        .split_whitespace()
+        .filter_map(|number| number.parse().ok())
+        .collect();
+    let offsets: Vec<(u32, usize)> = numbers
+        .chunks_exact(2)
+        .map(|pair| (pair[0] as u32, first + pair[1]))
+        .collect();
+    offsets
+        .iter()
+        .enumerate()
+        .filter_map(|(index, &(id, start))| {
+            let end = offsets
+                .get(index + 1)
+                .map_or(stream.len(), |&(_, next)| next);
+            Some((id, stream.get(start..end)?.to_vec()))
+        })
+        .collect()
+}
+
+/// ページツリーの順にページのオブジェクト番号を返す（ツリーが見つからない場合は番号順）
+fn page_order(objects: &BTreeMap<u32, PdfObject>) -> Vec<u32> {
+    let root = objects
+        .values()
+        .find(|object| CATALOG.is_match(&object.dict))
+        .and_then(|catalog| first_number(&PAGES, &catalog.dict));
+    let mut pages = Vec::new();
+    if let Some(root) = root {
+        collect_pages(objects, root, 0, &mut HashSet::new(), &mut pages);
+    }
+    if pages.is_empty() {
+        pages = objects
+            .iter()
+            .filter(|(_, object)| PAGE.is_match(&object.dict))
+            .map(|(&id, _)| id)
+            .collect();
+    }
+    pages
+}
+
+fn collect_pages(
+    objects: &BTreeMap<u32, PdfObject>,
+    id: u32,
+    depth: usize,
+    visited: &mut HashSet<u32>,
+    pages: &mut Vec<u32>,
+) {
+    if depth > MAX_PAGE_TREE_DEPTH || !visited.insert(id) {
+        return;
+    }
+    let Some(object) = objects.get(&id) else {
+        return;
+    };
+    if PAGE.is_match(&object.dict) {
+        pages.push(id);
+        return;
+    }
+    if let Some(kids) = KIDS.captures(&object.dict) {
+        for kid in references(&kids[1]) {
+            collect_pages(objects, kid, depth + 1, visited, pages);
+        }
+    }
+}
+
+fn page_contents(page: &PdfObject) -> Vec<u32> {
+    let Some(captures) = CONTENTS.captures(&page.dict) else {
+        return Vec::new();
+    };
+    match (captures.get(1), captures.get(2)) {
+        (Some(array), _) => references(array.as_bytes()),
+        (None, Some(id)) => references(&[id.as_bytes(), b" 0 R"].concat()),
+        _ => Vec::new(),
+    }
+}
+
+fn references(text: &[u8]) -> Vec<u32> {
+    REFERENCE
+        .captures_iter(text)
+        .filter_map(|captures| std::str::from_utf8(&captures[1]).ok()?.parse().ok())
+        .collect()
+}
+
+fn first_number(regex: &Regex, text: &[u8]) -> Option<u32> {
+    let captures = regex.captures(text)?;
+    std::str::from_utf8(&captures[1]).ok()?.parse().ok()
+}
+
+/// コンテンツストリームの演算子の対象
+#[derive(Debug)]
+enum Operand {
+    Number(f32),
+    Text(Vec<u8>),
+    Array(Vec<Operand>),
+    Other,
+}
+
+/// コンテンツストリームのテキスト演算子から文字列を取り出す
+fn content_text(content: &[u8]) -> String {
+    let mut text = String::new();
+    let mut operands: Vec<Operand> = Vec::new();
+    let mut arrays: Vec<Vec<Operand>> = Vec::new();
+    let mut pos = 0;
+    let mut line_y: Option<f32> = None;
+
+    while pos < content.len() {
+        let byte = content[pos];
+        let operand = match byte {
+            b'%' => {
+                pos = find(&content[pos..], b"\n").map_or(content.len(), |end| pos + end);
+                continue;
+            }
+            b'(' => {
+                let (string, end) = literal_string(content, pos + 1);
+                pos = end;
+                Operand::Text(string)
+            }
+            b'<' if content.get(pos + 1) == Some(&b'<') => {
+                pos += 2;
+                Operand::Other
+            }
+            b'>' if content.get(pos + 1) == Some(&b'>') => {
+                pos += 2;
+                Operand::Other
+            }
+            b'<' => {
+                let end = find(&content[pos..], b">").map_or(content.len(), |end| pos + end);
+                let string = hex_string(&content[pos + 1..end]);
+                pos = end + 1;
+                Operand::Text(string)
+            }
+            b'[' => {
+                arrays.push(Vec::new());
+                pos += 1;
+                continue;
+            }
+            b']' => {
+                pos += 1;
+                match arrays.pop() {
+                    Some(array) => Operand::Array(array),
+                    None => continue,
+                }
+            }
+            b'/' => {
+                pos = token_end(content, pos + 1);
+                Operand::Other
+            }
+            b'0'..=b'9' | b'-' | b'+' | b'.' => {
+                let end = token_end(content, pos + 1);
+                let number = std::str::from_utf8(&content[pos..end])
+                    .ok()
+                    .and_then(|number| number.parse().ok());
+                pos = end;
+                number.map_or(Operand::Other, Operand::Number)
+            }
+            byte if byte.is_ascii_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            _ => {
+                let end = token_end(content, pos + 1);
+                let operator = &content[pos..end];
+                pos = end;
+                if operator == b"BI" {
+                    // インライン画像のデータは読み飛ばす
+                    pos = find(&content[pos..], b"EI").map_or(content.len(), |end| pos + end + 2);
+                } else if arrays.is_empty() {
+                    apply_operator(operator, &operands, &mut text, &mut line_y);
+                    operands.clear();
+                }
+                continue;
+            }
+        };
+        match arrays.last_mut() {
+            Some(array) => array.push(operand),
+            None => operands.push(operand),
+        }
+    }
+    clean_text(&text)
+}
+
+fn apply_operator(
+    operator: &[u8],
+    operands: &[Operand],
+    text: &mut String,
+    line_y: &mut Option<f32>,
+) {
+    let number = |index_from_end: usize| match operands.iter().rev().nth(index_from_end) {
+        Some(Operand::Number(number)) => Some(*number),
+        _ => None,
+    };
+    match operator {
+        b"Tj" => push_strings(operands, text),
+        b"'" | b"\"" => {
+            text.push('\n');
+            push_strings(operands, text);
+        }
+        b"TJ" => {
+            for operand in operands {
+                if let Operand::Array(items) = operand {
+                    for item in items {
+                        match item {
+                            Operand::Text(string) => text.push_str(&decode_string(string)),
+                            Operand::Number(spacing) if -spacing > WORD_SPACING => push_space(text),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        b"T*" => text.push('\n'),
+        b"Td" | b"TD" => match number(0) {
+            Some(ty) if ty != 0.0 => text.push('\n'),
+            _ => push_space(text),
+        },
+        b"Tm" => {
+            let y = number(0);
+            if line_y.is_some() && y != *line_y {
+                text.push('\n');
+            } else {
+                push_space(text);
+            }
+            *line_y = y;
+        }
+        _ => {}
+    }
+}
+
+fn push_strings(operands: &[Operand], text: &mut String) {
+    for operand in operands {
+        if let Operand::Text(string) = operand {
+            text.push_str(&decode_string(string));
+        }
+    }
+}
+
+fn push_space(text: &mut String) {
+    if !text.is_empty() && !text.ends_with(char::is_whitespace) {
+        text.push(' ');
+    }
+}
+
+/// UTF-16BE（BOM付き）またはPDFDocEncoding（Latin-1として扱う）の文字列を変換する
+fn decode_string(bytes: &[u8]) -> String {
+    let text = if let Some(utf16) = bytes.strip_prefix(&[0xfe, 0xff]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        bytes.iter().map(|&byte| byte as char).collect()
+    };
+    text.chars()
+        .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
+        .collect()
+}
+
+/// `(` の次から `)` までのリテラル文字列を読み、文字列と次の位置を返す
+fn literal_string(content: &[u8], start: usize) -> (Vec<u8>, usize) {
+    let mut string = Vec::new();
+    let mut depth = 0;
+    let mut pos = start;
+    while pos < content.len() {
+        let byte = content[pos];
+        pos += 1;
+        match byte {
+            b'\\' => {
+                let Some(&escaped) = content.get(pos) else {
+                    break;
+                };
+                pos += 1;
+                match escaped {
+                    b'n' => string.push(b'\n'),
+                    b'r' => string.push(b'\r'),
+                    b't' => string.push(b'\t'),
+                    b'b' => string.push(0x08),
+                    b'f' => string.push(0x0c),
+                    b'0'..=b'7' => {
+                        let mut value = (escaped - b'0') as u32;
+                        for _ in 0..2 {
+                            match content.get(pos) {
+                                Some(&digit @ b'0'..=b'7') => {
+                                    value = value * 8 + (digit - b'0') as u32;
+                                    pos += 1;
+                                }
+                                _ => break,
+                            }
+                        }
+                        string.push(value as u8);
+                    }
+                    // 行末の `\` は改行を無視する
+                    b'\r' | b'\n' => {
+                        if escaped == b'\r' && content.get(pos) == Some(&b'\n') {
+                            pos += 1;
+                        }
+                    }
+                    other => string.push(other),
+                }
+            }
+            b'(' => {
+                depth += 1;
+                string.push(byte);
+            }
+            b')' if depth == 0 => break,
+            b')' => {
+                depth -= 1;
+                string.push(byte);
+            }
+            _ => string.push(byte),
+        }
+    }
+    (string, pos)
+}
+
+fn hex_string(hex: &[u8]) -> Vec<u8> {
+    let digits: Vec<u8> = hex
+        .iter()
+        .filter_map(|&c| (c as char).to_digit(16).map(|d| d as u8))
+        .collect();
+    digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
+/// 区切り文字の位置
+fn token_end(content: &[u8], start: usize) -> usize {
+    content[start..]
+        .iter()
+        .position(|&c| c.is_ascii_whitespace() || b"()<>[]{}/%".contains(&c))
+        .map_or(content.len(), |end| start + end)
+}
+
+/// 行末の空白と連続する空行を取り除く
+fn clean_text(text: &str) -> String {
+    let mut cleaned = String::new();
+    let mut blank_lines = 0;
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() {
+            blank_lines += 1;
+            if blank_lines > 1 {
+                continue;
+            }
+        } else {
+            blank_lines = 0;
+        }
+        cleaned.push_str(line);
+        cleaned.push('\n');
+    }
+    cleaned.trim().to_string()
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    find(haystack, needle).is_some()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    fn stream_object(id: u32, content: &[u8], compress: bool) -> Vec<u8> {
+        let (data, filter) = if compress {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(content).unwrap();
+            (encoder.finish().unwrap(), " /Filter /FlateDecode")
+        } else {
+            (content.to_vec(), "")
+        };
+        [
+            format!(
+                "{} 0 obj\n<< /Length {}{} >>\nstream\n",
+                id,
+                data.len(),
+                filter
+            )
+            .as_bytes(),
+            &data,
+            b"\nendstream\nendobj\n",
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_extract_pdf_text() {
+        let pdf = [
+            b"%PDF-1.4\n".as_slice(),
+            b"1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n",
+            b"2 0 obj\n<< /Type /Pages /Kids [5 0 R 3 0 R] /Count 2 >>\nendobj\n",
+            b"3 0 obj\n<< /Type /Page /Parent 2 0 R /Contents [4 0 R] >>\nendobj\n",
+            &stream_object(4, b"BT /F1 12 Tf 72 720 Td (Second page) Tj ET", false),
+            b"5 0 obj\n<< /Type /Page /Parent 2 0 R /Contents 6 0 R >>\nendobj\n",
+            &stream_object(
+                6,
+                b"BT /F1 12 Tf 72 720 Td [(Hel) 20 (lo) -300 (world)] TJ 0 -14 Td (Caf\\351 \\(menu\\)) Tj T* <FEFF00E9> Tj ET",
+                true,
+            ),
+            b"trailer\n<< /Root 1 0 R >>\n%%EOF\n",
+        ]
+        .concat();
+        assert_eq!(
+            extract_pdf_text(&pdf).unwrap(),
+            "--- Page 1 ---\nHello world\nCafé (menu)\né\n\n--- Page 2 ---\nSecond page"
+        );
+
+        let image_only = [
+            b"%PDF-1.4\n".as_slice(),
+            b"1 0 obj\n<< /Type /Page /Contents 2 0 R >>\nendobj\n",
+            &stream_object(2, b"q 100 0 0 100 0 0 cm /Im1 Do Q", false),
+        ]
+        .concat();
+        assert!(extract_pdf_text(&image_only).is_err());
+        assert!(extract_pdf_text(b"PK\x03\x04").is_err());
+    }
+
+    #[test]
+    fn test_object_markers_inside_streams() {
+        // ストリームのデータに含まれる `N 0 obj` ではオブジェクトを区切らない
+        let pdf = [
+            b"%PDF-1.4\n".as_slice(),
+            b"1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n",
+            b"2 0 obj\n<< /Type /Pages /Kids [3 0 R] /Count 1 >>\nendobj\n",
+            b"3 0 obj\n<< /Type /Page /Parent 2 0 R /Contents 4 0 R >>\nendobj\n",
+            &stream_object(
+                4,
+                b"BT 72 720 Td (See 3 0 obj and 9 0 obj) Tj ET\n3 0 obj << /Type /Page >>",
+                false,
+            ),
+        ]
+        .concat();
+        let objects = parse_objects(&pdf);
+        assert_eq!(
+            objects.keys().copied().collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(
+            extract_pdf_text(&pdf).unwrap(),
+            "--- Page 1 ---\nSee 3 0 obj and 9 0 obj"
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::services::diff::normalize::LineEnding;
+
+/// 1つのチャンクに入れる行数の目安
+const CHUNK_LINES: usize = 1024;
+
+/// 行単位で編集するテキスト（行をチャンクに分けて保持する）
+///
+/// 行の置き換えは該当するチャンクだけを更新するため、大きなファイルに何度も編集を適用しても
+/// 全体を分割し直さない。改行コードと末尾の改行は元の内容に合わせて出力する。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rope {
+    /// 空のチャンクは持たない
+    chunks: Vec<Vec<String>>,
+    len: usize,
+    line_ending: LineEnding,
+    trailing_newline: bool,
+}
+
+impl Rope {
+    pub fn new(text: &str) -> Self {
+        let mut rope = Self {
+            line_ending: LineEnding::detect(text),
+            trailing_newline: text.ends_with('\n'),
+            ..Default::default()
+        };
+        rope.splice(0..0, text.lines().map(String::from));
+        rope
+    }
+
+    /// 行数
+    pub fn len_lines(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// 最後の行の後に改行を出力するか
+    pub fn set_trailing_newline(&mut self, trailing_newline: bool) {
+        self.trailing_newline = trailing_newline;
+    }
+
+    /// `index` 行目（0から）
+    pub fn line(&self, index: usize) -> Option<&str> {
+        if index >= self.len {
+            return None;
+        }
+        let (chunk, offset) = self.locate(index);
+        Some(&self.chunks[chunk][offset])
+    }
+
+    /// すべての行
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.chunks.iter().flatten().map(String::as_str)
+    }
+
+    /// `range` の行（範囲の外は含まない）
+    pub fn lines_in(&self, range: Range<usize>) -> impl Iterator<Item = &str> {
+        let start = range.start.min(self.len);
+        let end = range.end.clamp(start, self.len);
+        let (chunk, offset) = self.locate(start);
+        self.chunks[chunk.min(self.chunks.len())..]
+            .iter()
+            .flatten()
+            .skip(offset)
+            .take(end - start)
+            .map(String::as_str)
+    }
+
+    /// `range` の行を `lines` に置き換える
+    ///
+    /// # Panics
+    ///
+    /// `range` が行数を超える場合
+    pub fn splice(&mut self, range: Range<usize>, lines: impl IntoIterator<Item = String>) {
+        assert!(
+            range.start <= range.end && range.end <= self.len,
+            "line range {:?} is out of bounds for {} lines",
+            range,
+            self.len
+        );
+        let lines: Vec<String> = lines.into_iter().collect();
+        if self.chunks.is_empty() {
+            self.chunks.push(Vec::new());
+        }
+        let (start_chunk, start_offset) = self.locate(range.start);
+        let (end_chunk, end_offset) = self.locate(range.end);
+        self.len = self.len - range.len() + lines.len();
+        if start_chunk == end_chunk {
+            self.chunks[start_chunk].splice(start_offset..end_offset, lines);
+        } else {
+            let tail = self.chunks[end_chunk].split_off(end_offset);
+            let chunk = &mut self.chunks[start_chunk];
+            chunk.truncate(start_offset);
+            chunk.extend(lines);
+            chunk.extend(tail);
+            self.chunks.drain(start_chunk + 1..=end_chunk);
+        }
+        self.rebalance(start_chunk);
+    }
+
+    /// `index` 行目の前に `lines` を挿入する（`index` が行数なら末尾に追加する）
+    pub fn insert(&mut self, index: usize, lines: impl IntoIterator<Item = String>) {
+        self.splice(index..index, lines);
+    }
+
+    /// 行を含むチャンクとその中の位置（行数と同じ位置は最後のチャンクの末尾）
+    fn locate(&self, mut index: usize) -> (usize, usize) {
+        for (chunk_index, chunk) in self.chunks.iter().enumerate() {
+            if index < chunk.len() {
+                return (chunk_index, index);
+            }
+            index -= chunk.len();
+        }
+        match self.chunks.len() {
+            0 => (0, 0),
+            len => (len - 1, self.chunks[len - 1].len()),
+        }
+    }
+
+    /// 編集したチャンクを分割・結合して大きさを揃える
+    fn rebalance(&mut self, chunk_index: usize) {
+        let chunk_len = self.chunks[chunk_index].len();
+        if chunk_len > CHUNK_LINES * 2 {
+            let chunk = std::mem::take(&mut self.chunks[chunk_index]);
+            let pieces: Vec<Vec<String>> =
+                chunk.chunks(CHUNK_LINES).map(<[String]>::to_vec).collect();
+            self.chunks.splice(chunk_index..=chunk_index, pieces);
+        } else if chunk_len == 0 {
+            self.chunks.remove(chunk_index);
+        } else if chunk_len < CHUNK_LINES / 4 {
+            let next = chunk_index + 1;
+            if next < self.chunks.len() && chunk_len + self.chunks[next].len() <= CHUNK_LINES * 2 {
+                let next_chunk = self.chunks.remove(next);
+                self.chunks[chunk_index].extend(next_chunk);
+            }
+        }
+    }
+}
+
+impl From<&str> for Rope {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ending = self.line_ending.as_str();
+        for (index, line) in self.lines().enumerate() {
+            if index > 0 {
+                f.write_str(ending)?;
+            }
+            f.write_str(line)?;
+        }
+        if self.trailing_newline && !self.is_empty() {
+            f.write_str(ending)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn numbered(range: Range<usize>) -> Vec<String> {
+        range.map(|i| format!("line {}", i)).collect()
+    }
+
+    #[test]
+    fn test_rope_round_trip() {
+        for text in ["", "\n", "a", "a\nb\n", "a\r\nb\r\n", "a\n\nb"] {
+            assert_eq!(Rope::new(text).to_string(), text);
+        }
+        let rope = Rope::new("a\r\nb");
+        assert_eq!(rope.line_ending(), LineEnding::CrLf);
+        assert_eq!(rope.lines().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(rope.line(1), Some("b"));
+        assert_eq!(rope.line(2), None);
+    }
+
+    #[test]
+    fn test_rope_splice() {
+        let mut rope = Rope::new("a\nb\nc\n");
+        rope.splice(1..2, vec!["B1".to_string(), "B2".to_string()]);
+        assert_eq!(rope.to_string(), "a\nB1\nB2\nc\n");
+        rope.insert(0, vec!["start".to_string()]);
+        rope.insert(rope.len_lines(), vec!["end".to_string()]);
+        assert_eq!(rope.to_string(), "start\na\nB1\nB2\nc\nend\n");
+        rope.splice(0..rope.len_lines(), Vec::new());
+        assert!(rope.is_empty());
+        assert_eq!(rope.to_string(), "");
+
+        let mut rope = Rope::new("");
+        rope.set_trailing_newline(true);
+        rope.insert(0, vec!["new".to_string()]);
+        assert_eq!(rope.to_string(), "new\n");
+    }
+
+    #[test]
+    fn test_rope_large_edits() {
+        let count = CHUNK_LINES * 5 + 7;
+        let mut expected = numbered(0..count);
+        let mut rope = Rope::new(&expected.join("\n"));
+        assert_eq!(rope.len_lines(), count);
+        assert!(rope.chunks.len() > 1);
+
+        // チャンクの境界をまたぐ置き換え・大きな挿入・削除
+        let edits = [
+            (CHUNK_LINES - 2..CHUNK_LINES + 3, numbered(9000..9004)),
+            (10..10, numbered(10_000..10_000 + CHUNK_LINES * 3)),
+            (100..CHUNK_LINES * 3, Vec::new()),
+            (0..1, numbered(20_000..20_001)),
+        ];
+        for (range, lines) in edits {
+            let end = range.end.min(expected.len());
+            expected.splice(range.start..end, lines.clone());
+            rope.splice(range.start..end, lines);
+            assert_eq!(rope.len_lines(), expected.len());
+            assert!(rope.chunks.iter().all(|chunk| !chunk.is_empty()));
+            assert!(rope
+                .chunks
+                .iter()
+                .all(|chunk| chunk.len() <= CHUNK_LINES * 2));
+        }
+        assert_eq!(rope.to_string(), expected.join("\n"));
+        assert_eq!(
+            rope.lines_in(CHUNK_LINES - 1..CHUNK_LINES + 2)
+                .collect::<Vec<_>>(),
+            expected[CHUNK_LINES - 1..CHUNK_LINES + 2]
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            rope.lines_in(expected.len() - 1..expected.len() + 5)
+                .count(),
+            1
+        );
+        assert_eq!(
+            rope.line(CHUNK_LINES * 2).unwrap(),
+            expected[CHUNK_LINES * 2]
+        );
+    }
+}
@@ -1,9 +1,16 @@
 use anyhow::Result;
-use std::path::Path;
-use std::process::Output;
-use tokio::process::Command;
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use git2::{Commit, Diff, DiffFormat, DiffOptions, DiffStatsFormat, Repository, Status};
+use git2::{Index, IndexEntry, IndexTime, Signature, StatusOptions, Time};
+use std::path::{Path, PathBuf};
 
-pub struct GitService;
+/// プロンプトに含める差分の既定の最大バイト数
+pub const DEFAULT_MAX_DIFF_BYTES: usize = 100_000;
+
+#[derive(Debug, Clone)]
+pub struct GitService {
+    max_diff_bytes: usize,
+}
 
 impl Default for GitService {
     fn default() -> Self {
@@ -13,128 +20,152 @@ impl Default for GitService {
 
 impl GitService {
     pub fn new() -> Self {
-        Self
+        Self {
+            max_diff_bytes: DEFAULT_MAX_DIFF_BYTES,
+        }
     }
 
-    /// Gitコマンドを実行する
-    async fn execute_git(&self, args: &[&str], cwd: &Path) -> Result<Output> {
-        Command::new("git")
-            .args(args)
-            .current_dir(cwd)
-            .output()
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to execute git command: {}", e))
+    /// 差分の最大バイト数を指定して作成する
+    pub fn with_max_diff_bytes(max_diff_bytes: usize) -> Self {
+        Self { max_diff_bytes }
+    }
+
+    pub fn max_diff_bytes(&self) -> usize {
+        self.max_diff_bytes
+    }
+
+    /// ブロッキングなgit2の処理を専用スレッドで実行する
+    async fn run_blocking<T, F>(&self, workspace_path: &Path, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&GitService, &Repository) -> Result<T> + Send + 'static,
+    {
+        let service = self.clone();
+        let workspace_path = workspace_path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let repo = open_repository(&workspace_path)?;
+            f(&service, &repo)
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Git task failed: {}", e))?
     }
 
     /// ワーキングディレクトリの変更状態を取得
     pub async fn get_working_state(&self, workspace_path: &Path) -> Result<String> {
-        // Gitリポジトリかどうかを確認
-        let git_dir = workspace_path.join(".git");
-        if !git_dir.exists() {
-            return Err(anyhow::anyhow!("Not a git repository"));
-        }
+        self.run_blocking(workspace_path, |service, repo| service.working_state(repo))
+            .await
+    }
+
+    /// コミット情報を取得
+    pub async fn get_commit_info(
+        &self,
+        workspace_path: &Path,
+        commit_hash: &str,
+    ) -> Result<String> {
+        let commit_hash = commit_hash.to_string();
+        self.run_blocking(workspace_path, move |service, repo| {
+            service.commit_info(repo, &commit_hash)
+        })
+        .await
+    }
 
-        // git statusで変更状態を取得
-        let status_output = self
-            .execute_git(&["status", "--porcelain"], workspace_path)
-            .await?;
-        let status = String::from_utf8_lossy(&status_output.stdout);
+    /// ブランチ間の差分（コミット一覧と変更統計）を取得
+    pub async fn get_branch_comparison(
+        &self,
+        workspace_path: &Path,
+        base: &str,
+        head: &str,
+    ) -> Result<String> {
+        let base = base.to_string();
+        let head = head.to_string();
+        self.run_blocking(workspace_path, move |_, repo| {
+            branch_comparison(repo, &base, &head)
+        })
+        .await
+    }
 
-        if status.is_empty() {
+    /// 作業ツリーを変更せずにファイルの内容をブランチにコミットする
+    ///
+    /// ブランチがない場合はHEADから作成する。`files` はワークスペースからの相対パスと内容の組。
+    pub async fn commit_files_to_branch(
+        &self,
+        workspace_path: &Path,
+        branch: &str,
+        files: Vec<(String, String)>,
+        message: &str,
+        author: &CommitAuthor,
+    ) -> Result<String> {
+        let branch = branch.to_string();
+        let message = message.to_string();
+        let author = author.clone();
+        self.run_blocking(workspace_path, move |_, repo| {
+            commit_files_to_branch(repo, &branch, &files, &message, &author)
+        })
+        .await
+    }
+
+    fn working_state(&self, repo: &Repository) -> Result<String> {
+        let mut status_options = StatusOptions::new();
+        status_options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true);
+        let statuses = repo.statuses(Some(&mut status_options))?;
+
+        if statuses.is_empty() {
             return Ok("No git changes".to_string());
         }
 
-        // git diffで詳細な差分を取得
-        let diff_output = self.execute_git(&["diff"], workspace_path).await?;
-        let diff = String::from_utf8_lossy(&diff_output.stdout);
-
         let mut result = String::new();
         result.push_str("git changes:\n\n");
 
         // 変更ファイルの一覧
         result.push_str("# Changed files\n");
-        for line in status.lines() {
-            if line.is_empty() {
-                continue;
-            }
-            let status_code = &line[0..2];
-            let file_path = &line[3..];
-
-            let status_text = match status_code.trim() {
-                "M" => "Modified",
-                "A" => "Added",
-                "D" => "Deleted",
-                "R" => "Renamed",
-                "C" => "Copied",
-                "U" => "Updated but unmerged",
-                "??" => "Untracked",
-                _ => "Unknown status",
-            };
-
-            result.push_str(&format!("- {} ({})\n", file_path, status_text));
+        for entry in statuses.iter() {
+            let file_path = entry.path().unwrap_or_default();
+            result.push_str(&format!(
+                "- {} ({})\n",
+                file_path,
+                status_text(entry.status())
+            ));
         }
 
-        // 詳細な差分
-        if !diff.is_empty() {
+        // インデックスとワーキングツリーの差分（git diff相当）
+        let diff = repo.diff_index_to_workdir(None, Some(&mut DiffOptions::new()))?;
+        let diff_text = self.render_diff(&diff)?;
+        if !diff_text.is_empty() {
             result.push_str("\n# Detailed changes\n");
-            result.push_str(&diff);
+            result.push_str(&diff_text);
         }
 
         Ok(result)
     }
 
-    /// コミット情報を取得
-    pub async fn get_commit_info(
-        &self,
-        workspace_path: &Path,
-        commit_hash: &str,
-    ) -> Result<String> {
-        // Gitリポジトリかどうかを確認
-        let git_dir = workspace_path.join(".git");
-        if !git_dir.exists() {
-            return Err(anyhow::anyhow!("Not a git repository"));
-        }
+    fn commit_info(&self, repo: &Repository, commit_hash: &str) -> Result<String> {
+        let commit = resolve_commit(repo, commit_hash)?;
+
+        let full_hash = commit.id().to_string();
+        let short_hash = commit
+            .as_object()
+            .short_id()?
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let subject = commit.summary().unwrap_or_default();
+        let author = commit.author();
+        let date = format_time(&commit.author().when());
+        let body = commit.body().unwrap_or_default().trim();
 
-        // コミット情報を取得
-        let show_output = self
-            .execute_git(
-                &[
-                    "show",
-                    "--format=%H%n%h%n%s%n%an%n%ad%n%b",
-                    "--no-patch",
-                    commit_hash,
-                ],
-                workspace_path,
-            )
-            .await?;
-        let show = String::from_utf8_lossy(&show_output.stdout);
-        let mut lines = show.lines();
-
-        let full_hash = lines.next().unwrap_or_default();
-        let short_hash = lines.next().unwrap_or_default();
-        let subject = lines.next().unwrap_or_default();
-        let author = lines.next().unwrap_or_default();
-        let date = lines.next().unwrap_or_default();
-        let body = lines.collect::<Vec<_>>().join("\n");
-
-        // 変更統計を取得
-        let stat_output = self
-            .execute_git(
-                &["show", "--stat", "--format=", commit_hash],
-                workspace_path,
-            )
-            .await?;
-        let stat = String::from_utf8_lossy(&stat_output.stdout);
-
-        // 詳細な差分を取得
-        let diff_output = self
-            .execute_git(&["show", "--format=", commit_hash], workspace_path)
-            .await?;
-        let diff = String::from_utf8_lossy(&diff_output.stdout);
+        let diff = commit_diff(repo, &commit)?;
+        let stat = diff
+            .stats()?
+            .to_buf(DiffStatsFormat::FULL, 80)?
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
 
         let mut result = String::new();
         result.push_str(&format!("Commit: {} ({})\n", short_hash, full_hash));
-        result.push_str(&format!("Author: {}\n", author));
+        result.push_str(&format!("Author: {}\n", author.name().unwrap_or_default()));
         result.push_str(&format!("Date: {}\n\n", date));
         result.push_str(&format!("Message: {}\n", subject));
         if !body.is_empty() {
@@ -143,8 +174,445 @@ impl GitService {
         result.push_str("\nFiles Changed:\n");
         result.push_str(&stat);
         result.push_str("\nFull Changes:\n");
-        result.push_str(&diff);
+        result.push_str(&self.render_diff(&diff)?);
 
         Ok(result)
     }
+
+    /// 差分をパッチ形式で描画する（バイナリは省略し、上限を超えたら切り詰める）
+    fn render_diff(&self, diff: &Diff) -> Result<String> {
+        let mut output = String::new();
+        let mut truncated = false;
+        let mut skipped_binary: Option<PathBuf> = None;
+
+        let print_result = diff.print(DiffFormat::Patch, |delta, _hunk, line| {
+            let path = delta
+                .new_file()
+                .path()
+                .or_else(|| delta.old_file().path())
+                .map(Path::to_path_buf);
+
+            // バイナリファイルは内容を含めずに一行だけ記録する
+            if delta.flags().is_binary() {
+                if skipped_binary != path {
+                    skipped_binary = path.clone();
+                    if let Some(path) = &path {
+                        output.push_str(&format!(
+                            "diff --git a/{0} b/{0}\n(binary file, diff skipped)\n",
+                            path.display()
+                        ));
+                    }
+                }
+                return true;
+            }
+
+            let content = String::from_utf8_lossy(line.content());
+            let rendered = match line.origin() {
+                origin @ ('+' | '-' | ' ') => format!("{}{}", origin, content),
+                _ => content.into_owned(),
+            };
+
+            if output.len() + rendered.len() > self.max_diff_bytes {
+                truncated = true;
+                return false;
+            }
+            output.push_str(&rendered);
+            true
+        });
+
+        // 切り詰めによる中断はエラーとして扱わない
+        if let Err(e) = print_result {
+            if !truncated {
+                return Err(e.into());
+            }
+        }
+
+        if truncated {
+            output.push_str(&format!(
+                "\n(Diff truncated at {} bytes)\n",
+                self.max_diff_bytes
+            ));
+        }
+
+        Ok(output)
+    }
+}
+
+/// エージェントが作成するコミットの作成者
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitAuthor {
+    pub name: String,
+    pub email: String,
+}
+
+impl Default for CommitAuthor {
+    fn default() -> Self {
+        Self {
+            name: "headless-cline".to_string(),
+            email: "headless-cline@localhost".to_string(),
+        }
+    }
+}
+
+/// 自動コミットのタイミング
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AutoCommitTrigger {
+    /// `attempt_completion` を使った応答ごと
+    #[default]
+    Completion,
+    /// ツールを使った応答ごと
+    ToolBatch,
+}
+
+/// エージェントの変更を専用のブランチに自動でコミットする設定
+///
+/// ブランチ名とメッセージでは `{task_id}`、`{checkpoint}`（1始まりの連番）、`{files}`（変更したファイルの一覧）を置き換える。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoCommitConfig {
+    pub branch: String,
+    pub message_template: String,
+    pub author: CommitAuthor,
+    pub trigger: AutoCommitTrigger,
+}
+
+impl Default for AutoCommitConfig {
+    fn default() -> Self {
+        Self {
+            branch: "cline/{task_id}".to_string(),
+            message_template: "cline: checkpoint {checkpoint} for task {task_id}\n\n{files}"
+                .to_string(),
+            author: CommitAuthor::default(),
+            trigger: AutoCommitTrigger::default(),
+        }
+    }
+}
+
+impl AutoCommitConfig {
+    fn render(template: &str, task_id: &str, checkpoint: usize, files: &[String]) -> String {
+        let files = files
+            .iter()
+            .map(|file| format!("- {}", file))
+            .collect::<Vec<_>>()
+            .join("\n");
+        template
+            .replace("{task_id}", task_id)
+            .replace("{checkpoint}", &checkpoint.to_string())
+            .replace("{files}", &files)
+    }
+
+    pub fn branch_name(&self, task_id: &str) -> String {
+        Self::render(&self.branch, task_id, 0, &[])
+    }
+
+    pub fn message(&self, task_id: &str, checkpoint: usize, files: &[String]) -> String {
+        Self::render(&self.message_template, task_id, checkpoint, files)
+            .trim_end()
+            .to_string()
+    }
+}
+
+fn commit_files_to_branch(
+    repo: &Repository,
+    branch: &str,
+    files: &[(String, String)],
+    message: &str,
+    author: &CommitAuthor,
+) -> Result<String> {
+    let refname = format!("refs/heads/{}", branch);
+    let parent = match repo.find_reference(&refname) {
+        Ok(reference) => Some(reference.peel_to_commit()?),
+        Err(_) => repo.head().ok().and_then(|head| head.peel_to_commit().ok()),
+    };
+
+    // 作業ツリーのインデックスを使わず、親コミットのツリーに変更を重ねる
+    let mut index = Index::new()?;
+    if let Some(parent) = &parent {
+        index.read_tree(&parent.tree()?)?;
+    }
+    for (path, content) in files {
+        let mode = index
+            .get_path(Path::new(path), 0)
+            .map_or(0o100644, |entry| entry.mode);
+        index.add(&IndexEntry {
+            ctime: IndexTime::new(0, 0),
+            mtime: IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode,
+            uid: 0,
+            gid: 0,
+            file_size: content.len() as u32,
+            id: repo.blob(content.as_bytes())?,
+            flags: 0,
+            flags_extended: 0,
+            path: path.as_bytes().to_vec(),
+        })?;
+    }
+    let tree = repo.find_tree(index.write_tree_to(repo)?)?;
+
+    let signature = Signature::now(&author.name, &author.email)?;
+    let parents: Vec<&Commit> = parent.iter().collect();
+    let oid = repo.commit(
+        Some(&refname),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )?;
+    Ok(oid.to_string())
+}
+
+/// リポジトリを開く
+fn open_repository(workspace_path: &Path) -> Result<Repository> {
+    Repository::open(workspace_path).map_err(|_| anyhow::anyhow!("Not a git repository"))
+}
+
+/// リビジョン指定（ハッシュ、ブランチ名、HEAD~3など）をコミットに解決する
+fn resolve_commit<'r>(repo: &'r Repository, revision: &str) -> Result<Commit<'r>> {
+    repo.revparse_single(revision)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| anyhow::anyhow!("Unknown git revision: {}", revision))
+}
+
+/// コミットとその親との差分を取得する
+fn commit_diff<'r>(repo: &'r Repository, commit: &Commit<'r>) -> Result<Diff<'r>> {
+    let tree = commit.tree()?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    Ok(repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?)
+}
+
+fn branch_comparison(repo: &Repository, base: &str, head: &str) -> Result<String> {
+    let base_commit = resolve_commit(repo, base)?;
+    let head_commit = resolve_commit(repo, head)?;
+
+    // base..head のコミット一覧
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(head_commit.id())?;
+    revwalk.hide(base_commit.id())?;
+
+    let mut commits = Vec::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        let short_hash = commit
+            .as_object()
+            .short_id()?
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        commits.push(format!(
+            "{} {} ({}, {})",
+            short_hash,
+            commit.summary().unwrap_or_default(),
+            commit.author().name().unwrap_or_default(),
+            format_date(&commit.author().when())
+        ));
+    }
+
+    // マージベースからの変更統計（git diff base...head 相当）
+    let merge_base = repo.merge_base(base_commit.id(), head_commit.id())?;
+    let base_tree = repo.find_commit(merge_base)?.tree()?;
+    let head_tree = head_commit.tree()?;
+    let diff = repo.diff_tree_to_tree(Some(&base_tree), Some(&head_tree), None)?;
+    let stat = diff
+        .stats()?
+        .to_buf(DiffStatsFormat::FULL, 80)?
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+
+    let mut result = String::new();
+    result.push_str(&format!("Comparing {} with {}\n", base, head));
+    result.push_str("\nCommits:\n");
+    if commits.is_empty() {
+        result.push_str("(No commits)\n");
+    } else {
+        for line in commits {
+            result.push_str(&format!("- {}\n", line));
+        }
+    }
+    result.push_str("\nFiles Changed:\n");
+    if stat.trim().is_empty() {
+        result.push_str("(No changes)\n");
+    } else {
+        result.push_str(&stat);
+    }
+
+    Ok(result)
+}
+
+fn status_text(status: Status) -> &'static str {
+    if status.is_conflicted() {
+        "Updated but unmerged"
+    } else if status.is_wt_new() {
+        "Untracked"
+    } else if status.is_index_new() {
+        "Added"
+    } else if status.is_index_deleted() || status.is_wt_deleted() {
+        "Deleted"
+    } else if status.is_index_renamed() || status.is_wt_renamed() {
+        "Renamed"
+    } else if status.is_index_modified()
+        || status.is_wt_modified()
+        || status.is_index_typechange()
+        || status.is_wt_typechange()
+    {
+        "Modified"
+    } else {
+        "Unknown status"
+    }
+}
+
+fn to_datetime(time: &Time) -> Option<DateTime<FixedOffset>> {
+    let offset = FixedOffset::east_opt(time.offset_minutes() * 60)?;
+    Utc.timestamp_opt(time.seconds(), 0)
+        .single()
+        .map(|t| t.with_timezone(&offset))
+}
+
+fn format_time(time: &Time) -> String {
+    to_datetime(time)
+        .map(|t| t.format("%a %b %e %H:%M:%S %Y %z").to_string())
+        .unwrap_or_default()
+}
+
+fn format_date(time: &Time) -> String {
+    to_datetime(time)
+        .map(|t| t.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn init_repo() -> (tempfile::TempDir, Repository) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        (temp_dir, repo)
+    }
+
+    fn commit_all(repo: &Repository, message: &str) {
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let parents = repo
+            .head()
+            .ok()
+            .and_then(|h| h.peel_to_commit().ok())
+            .into_iter()
+            .collect::<Vec<_>>();
+        let parents = parents.iter().collect::<Vec<_>>();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_working_state_truncates_large_diff() {
+        let (temp_dir, repo) = init_repo();
+        fs::write(temp_dir.path().join("large.txt"), "line\n".repeat(10)).unwrap();
+        commit_all(&repo, "Initial commit");
+
+        fs::write(temp_dir.path().join("large.txt"), "changed\n".repeat(1000)).unwrap();
+
+        let service = GitService::with_max_diff_bytes(200);
+        let result = service.get_working_state(temp_dir.path()).await.unwrap();
+        assert!(result.contains("large.txt (Modified)"));
+        assert!(result.contains("(Diff truncated at 200 bytes)"));
+        assert!(result.len() < 600, "Diff should be truncated: {}", result);
+    }
+
+    #[tokio::test]
+    async fn test_commit_info_skips_binary_files() {
+        let (temp_dir, repo) = init_repo();
+        fs::write(temp_dir.path().join("image.bin"), [0u8, 1, 2, 0, 255, 0]).unwrap();
+        fs::write(temp_dir.path().join("readme.txt"), "hello\n").unwrap();
+        commit_all(&repo, "Add files");
+
+        let service = GitService::new();
+        let result = service
+            .get_commit_info(temp_dir.path(), "HEAD")
+            .await
+            .unwrap();
+        assert!(result.contains("Message: Add files"));
+        assert!(result.contains("image.bin\n(binary file, diff skipped)"));
+        assert!(result.contains("+hello"));
+    }
+
+    #[tokio::test]
+    async fn test_commit_files_to_branch() {
+        let (temp_dir, repo) = init_repo();
+        fs::write(temp_dir.path().join("a.txt"), "one\n").unwrap();
+        commit_all(&repo, "Initial commit");
+
+        let service = GitService::new();
+        let author = CommitAuthor {
+            name: "Agent".to_string(),
+            email: "agent@example.com".to_string(),
+        };
+        for (content, message) in [("two\n", "First edit"), ("three\n", "Second edit")] {
+            service
+                .commit_files_to_branch(
+                    temp_dir.path(),
+                    "cline/task",
+                    vec![
+                        ("a.txt".to_string(), content.to_string()),
+                        ("src/new.txt".to_string(), "new\n".to_string()),
+                    ],
+                    message,
+                    &author,
+                )
+                .await
+                .unwrap();
+        }
+
+        let commit = resolve_commit(&repo, "cline/task").unwrap();
+        assert_eq!(commit.summary(), Some("Second edit"));
+        assert_eq!(commit.author().name(), Some("Agent"));
+        assert_eq!(commit.parent(0).unwrap().summary(), Some("First edit"));
+        let tree = commit.tree().unwrap();
+        let blob = tree
+            .get_path(Path::new("a.txt"))
+            .unwrap()
+            .to_object(&repo)
+            .unwrap()
+            .peel_to_blob()
+            .unwrap();
+        assert_eq!(blob.content(), b"three\n");
+        assert!(tree.get_path(Path::new("src/new.txt")).is_ok());
+        // 作業ツリーとHEADは変更しない
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("a.txt")).unwrap(),
+            "one\n"
+        );
+        assert_eq!(
+            repo.head().unwrap().peel_to_commit().unwrap().summary(),
+            Some("Initial commit")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_not_a_git_repository() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let err = GitService::new()
+            .get_working_state(temp_dir.path())
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Not a git repository");
+    }
 }
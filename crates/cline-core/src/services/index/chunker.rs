@@ -0,0 +1,129 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::services::editor::SKIPPED_DIRS;
+
+/// 1つのチャンクの最大行数
+pub const CHUNK_LINES: usize = 40;
+/// 前のチャンクと重ねる行数（境界をまたぐコードも検索できるようにする）
+pub const CHUNK_OVERLAP_LINES: usize = 5;
+/// これより大きいファイルはインデックスしない
+pub const MAX_INDEXED_FILE_BYTES: u64 = 1024 * 1024;
+
+/// インデックスするファイルの一部
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// ワークスペースからの相対パス
+    pub path: String,
+    /// 1始まりの行番号（終了行を含む）
+    pub start_line: usize,
+    pub end_line: usize,
+    pub content: String,
+}
+
+/// ファイルの内容を重なりのある行単位のチャンクに分ける（空白のみのチャンクは除く）
+pub fn chunk_file(path: &str, content: &str) -> Vec<Chunk> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let end = (start + CHUNK_LINES).min(lines.len());
+        let content = lines[start..end].join("\n");
+        if !content.trim().is_empty() {
+            chunks.push(Chunk {
+                path: path.to_string(),
+                start_line: start + 1,
+                end_line: end,
+                content,
+            });
+        }
+        if end == lines.len() {
+            break;
+        }
+        start = end - CHUNK_OVERLAP_LINES;
+    }
+    chunks
+}
+
+/// インデックス対象のテキストファイルか（バイナリ・大きすぎるファイルは除く）
+pub fn read_indexable_file(abs_path: &Path) -> Option<String> {
+    let metadata = std::fs::metadata(abs_path).ok()?;
+    if !metadata.is_file() || metadata.len() > MAX_INDEXED_FILE_BYTES {
+        return None;
+    }
+    let bytes = std::fs::read(abs_path).ok()?;
+    if bytes[..bytes.len().min(8000)].contains(&0) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// ワークスペースのファイル（相対パス）を列挙する（隠しディレクトリと依存関係・ビルド成果物は除く）
+pub fn workspace_files(workspace_path: &Path) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![workspace_path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_str()) {
+                    dirs.push(entry.path());
+                }
+            } else if file_type.is_file() {
+                if let Ok(rel_path) = entry.path().strip_prefix(workspace_path) {
+                    files.push(rel_path.to_string_lossy().replace('\\', "/"));
+                }
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_chunk_file() {
+        let content: String = (1..=80).map(|i| format!("line {}\n", i)).collect();
+        let chunks = chunk_file("src/lib.rs", &content);
+        let ranges: Vec<_> = chunks
+            .iter()
+            .map(|chunk| (chunk.start_line, chunk.end_line))
+            .collect();
+        assert_eq!(ranges, vec![(1, 40), (36, 75), (71, 80)]);
+        assert!(chunks[2].content.starts_with("line 71\n"));
+        assert!(chunks[2].content.ends_with("line 80"));
+        assert!(chunk_file("empty.rs", "\n  \n").is_empty());
+    }
+
+    #[test]
+    fn test_workspace_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        for path in [
+            "src/lib.rs",
+            "README.md",
+            ".git/HEAD",
+            "target/debug/x",
+            "node_modules/a.js",
+        ] {
+            let path = temp_dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "content").unwrap();
+        }
+        std::fs::write(temp_dir.path().join("image.bin"), [0u8, 1, 2]).unwrap();
+
+        let files = workspace_files(temp_dir.path()).unwrap();
+        assert_eq!(files, vec!["README.md", "image.bin", "src/lib.rs"]);
+        assert!(read_indexable_file(&temp_dir.path().join("image.bin")).is_none());
+        assert_eq!(
+            read_indexable_file(&temp_dir.path().join("README.md")).as_deref(),
+            Some("content")
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::fmt::Debug;
+
+/// 1回のリクエストで埋め込みを計算するテキストの最大数
+pub const EMBEDDING_BATCH_SIZE: usize = 64;
+
+/// テキストの埋め込みを計算する
+#[async_trait]
+pub trait Embedder: Debug + Send + Sync {
+    /// モデルの識別子（変わった場合はインデックスを作り直す）
+    fn model_id(&self) -> String;
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+}
+
+/// 外部のモデルを使わずにトークンの特徴量ハッシュから埋め込みを計算する
+///
+/// 識別子は `camelCase` や `snake_case` の単語にも分けるため、部分的な名前でも検索できる。
+#[derive(Debug, Clone)]
+pub struct HashEmbedder {
+    dimensions: usize,
+}
+
+impl HashEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+        }
+    }
+
+    fn embed_text(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimensions];
+        for token in tokenize(text) {
+            let hash = fnv1a(token.as_bytes());
+            let index = (hash % self.dimensions as u64) as usize;
+            // 衝突の影響を打ち消し合うように符号もハッシュで決める
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[index] += sign;
+        }
+        normalize(&mut vector);
+        vector
+    }
+}
+
+impl Default for HashEmbedder {
+    fn default() -> Self {
+        Self::new(512)
+    }
+}
+
+#[async_trait]
+impl Embedder for HashEmbedder {
+    fn model_id(&self) -> String {
+        format!("hash-{}", self.dimensions)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| self.embed_text(text)).collect())
+    }
+}
+
+/// OpenAI互換の `/embeddings` APIで埋め込みを計算する（OpenAI・Ollama・LM Studioなど）
+#[derive(Debug, Clone)]
+pub struct OpenAiEmbedder {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl OpenAiEmbedder {
+    /// `base_url` は `https://api.openai.com/v1` や `http://localhost:11434/v1` など
+    pub fn new(base_url: &str, api_key: Option<String>, model: &str) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            model: model.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Embedder for OpenAiEmbedder {
+    fn model_id(&self) -> String {
+        format!("{}@{}", self.model, self.base_url)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(EMBEDDING_BATCH_SIZE) {
+            let mut request = self
+                .client
+                .post(format!("{}/embeddings", self.base_url))
+                .json(&json!({ "model": self.model, "input": batch }));
+            if let Some(api_key) = &self.api_key {
+                request = request.bearer_auth(api_key);
+            }
+            let response = request.send().await?;
+            let status = response.status();
+            if !status.is_success() {
+                anyhow::bail!(
+                    "Embedding request failed ({}): {}",
+                    status,
+                    response.text().await.unwrap_or_default()
+                );
+            }
+            embeddings.extend(parse_embedding_response(
+                &response.text().await?,
+                batch.len(),
+            )?);
+        }
+        Ok(embeddings)
+    }
+}
+
+fn parse_embedding_response(body: &str, expected: usize) -> Result<Vec<Vec<f32>>> {
+    let mut data = serde_json::from_str::<EmbeddingResponse>(body)?.data;
+    if data.len() != expected {
+        anyhow::bail!(
+            "Expected {} embeddings but received {}",
+            expected,
+            data.len()
+        );
+    }
+    data.sort_by_key(|data| data.index);
+    Ok(data.into_iter().map(|data| data.embedding).collect())
+}
+
+/// 英数字の並びを小文字の単語に分ける（識別子は元の単語と構成する単語の両方を返す）
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+    {
+        let parts = split_identifier(word);
+        if parts.len() > 1 {
+            tokens.extend(parts);
+        }
+        tokens.push(word.to_lowercase());
+    }
+    tokens
+}
+
+fn split_identifier(word: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut previous_lowercase = false;
+    for c in word.chars() {
+        if (c == '_' || (c.is_uppercase() && previous_lowercase)) && !current.is_empty() {
+            parts.push(std::mem::take(&mut current));
+        }
+        if c != '_' {
+            current.extend(c.to_lowercase());
+        }
+        previous_lowercase = c.is_lowercase() || c.is_ascii_digit();
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+/// コサイン類似度
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm_a = a.iter().map(|v| v * v).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_tokenize_identifiers() {
+        assert_eq!(
+            tokenize("fn parseHttpRequest(raw_input)"),
+            vec![
+                "fn",
+                "parse",
+                "http",
+                "request",
+                "parsehttprequest",
+                "raw",
+                "input",
+                "raw_input"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hash_embedder_similarity() {
+        let embedder = HashEmbedder::default();
+        let texts = [
+            "parse the http request headers".to_string(),
+            "fn parse_http_request(headers: &str)".to_string(),
+            "render the sidebar component".to_string(),
+        ];
+        let embeddings = embedder.embed(&texts).await.unwrap();
+        assert_eq!(embeddings[0].len(), 512);
+        assert!(
+            cosine_similarity(&embeddings[0], &embeddings[1])
+                > cosine_similarity(&embeddings[0], &embeddings[2])
+        );
+    }
+
+    #[test]
+    fn test_parse_embedding_response() {
+        let body =
+            r#"{"data": [{"index": 1, "embedding": [0.5]}, {"index": 0, "embedding": [0.1]}]}"#;
+        assert_eq!(
+            parse_embedding_response(body, 2).unwrap(),
+            vec![vec![0.1], vec![0.5]]
+        );
+        assert!(parse_embedding_response(body, 3).is_err());
+    }
+}
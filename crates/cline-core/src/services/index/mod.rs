@@ -0,0 +1,463 @@
+mod chunker;
+mod embedding;
+mod store;
+
+pub use chunker::{
+    chunk_file, workspace_files, Chunk, CHUNK_LINES, CHUNK_OVERLAP_LINES, MAX_INDEXED_FILE_BYTES,
+};
+pub use embedding::{cosine_similarity, Embedder, HashEmbedder, OpenAiEmbedder};
+pub use store::{content_hash, manifest_hash, IndexedChunk, SearchResult, VectorStore};
+
+use anyhow::Result;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::services::editor::SKIPPED_DIRS;
+use chunker::read_indexable_file;
+
+/// `codebase_search` が返す結果の数
+pub const DEFAULT_SEARCH_RESULTS: usize = 10;
+
+/// インデックスの更新で処理したファイルの数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexSyncStats {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+impl IndexSyncStats {
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.updated == 0 && self.removed == 0
+    }
+}
+
+/// ワークスペースの意味検索用のインデックス
+///
+/// ファイルをチャンクに分けて埋め込みを計算し、`codebase_search` ツールから検索する。
+/// 内部で排他制御するため、`Arc` で共有したまま更新・検索できる。
+/// ファイルごとに内容のハッシュを記録し、変更のあったファイルだけ埋め込みを計算し直す。
+#[derive(Debug)]
+pub struct CodebaseIndex {
+    workspace_path: PathBuf,
+    embedder: Arc<dyn Embedder>,
+    store: Mutex<VectorStore>,
+    /// 変更を検知したが、まだインデックスし直していないファイル
+    pending: Mutex<BTreeSet<String>>,
+    /// 指定した場合は `save` でここに保存する
+    index_path: Option<PathBuf>,
+}
+
+impl CodebaseIndex {
+    pub fn new(workspace_path: PathBuf, embedder: Arc<dyn Embedder>) -> Self {
+        Self {
+            store: Mutex::new(VectorStore::new(embedder.model_id())),
+            pending: Mutex::new(BTreeSet::new()),
+            workspace_path,
+            embedder,
+            index_path: None,
+        }
+    }
+
+    /// 保存したインデックスを読み込む（ないか、モデルが異なる場合は空のインデックスにする）
+    ///
+    /// 読み込んだ後に `sync` を呼ぶと、前回からの変更だけをインデックスし直す。
+    pub async fn open(
+        workspace_path: PathBuf,
+        embedder: Arc<dyn Embedder>,
+        index_path: PathBuf,
+    ) -> Result<Self> {
+        let mut index = Self::new(workspace_path, embedder);
+        if index_path.exists() {
+            let store = VectorStore::load(&index_path).await?;
+            if store.model_id == index.embedder.model_id() {
+                index.store = Mutex::new(store);
+            }
+        }
+        index.index_path = Some(index_path);
+        Ok(index)
+    }
+
+    pub fn workspace_path(&self) -> &Path {
+        &self.workspace_path
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.store.lock().unwrap().chunk_count()
+    }
+
+    pub fn indexed_files(&self) -> Vec<String> {
+        self.store
+            .lock()
+            .unwrap()
+            .files()
+            .map(String::from)
+            .collect()
+    }
+
+    /// ワークスペースの全てのファイルをインデックスし直し、チャンク数を返す
+    pub async fn build(&self) -> Result<usize> {
+        let files = self.read_workspace_files().await?;
+        let mut store = VectorStore::new(self.embedder.model_id());
+        for (path, content) in &files {
+            let chunks = self.embed_content(path, content).await?;
+            store.replace_file(path, &content_hash(content.as_bytes()), chunks);
+        }
+        let count = store.chunk_count();
+        *self.store.lock().unwrap() = store;
+        self.pending.lock().unwrap().clear();
+        Ok(count)
+    }
+
+    /// ワークスペースとハッシュを比べ、追加・変更・削除されたファイルだけインデックスし直す
+    ///
+    /// ルートハッシュが一致する場合は埋め込みを計算しない。
+    pub async fn sync(&self) -> Result<IndexSyncStats> {
+        let files = self.read_workspace_files().await?;
+        let hashes: Vec<(String, String)> = files
+            .iter()
+            .map(|(path, content)| (path.clone(), content_hash(content.as_bytes())))
+            .collect();
+        let root = manifest_hash(
+            hashes
+                .iter()
+                .map(|(path, hash)| (path.as_str(), hash.as_str())),
+        );
+        if root == self.store.lock().unwrap().manifest_hash() {
+            self.pending.lock().unwrap().clear();
+            return Ok(IndexSyncStats::default());
+        }
+
+        let mut stats = IndexSyncStats::default();
+        for ((path, content), (_, hash)) in files.iter().zip(&hashes) {
+            let previous = self.store.lock().unwrap().file_hash(path).map(String::from);
+            match previous {
+                Some(previous) if previous == *hash => continue,
+                Some(_) => stats.updated += 1,
+                None => stats.added += 1,
+            }
+            let chunks = self.embed_content(path, content).await?;
+            self.store.lock().unwrap().replace_file(path, hash, chunks);
+        }
+        let current: BTreeSet<&str> = files.iter().map(|(path, _)| path.as_str()).collect();
+        let mut store = self.store.lock().unwrap();
+        let removed: Vec<String> = store
+            .hashed_files()
+            .filter(|path| !current.contains(path))
+            .map(String::from)
+            .collect();
+        for path in &removed {
+            store.remove_file(path);
+        }
+        stats.removed = removed.len();
+        self.pending.lock().unwrap().clear();
+        Ok(stats)
+    }
+
+    /// 1つのファイルをインデックスし直し、インデックスが変わったかを返す
+    ///
+    /// 内容が前回と同じ場合は埋め込みを計算しない。削除された・対象外のファイルはインデックスから除く。
+    pub async fn index_file(&self, rel_path: &str) -> Result<bool> {
+        let abs_path = self.workspace_path.join(rel_path);
+        let content = tokio::task::spawn_blocking(move || read_indexable_file(&abs_path)).await?;
+        let Some(content) = content else {
+            return Ok(self.remove_file(rel_path));
+        };
+        let hash = content_hash(content.as_bytes());
+        if self.store.lock().unwrap().file_hash(rel_path) == Some(hash.as_str()) {
+            return Ok(false);
+        }
+        let chunks = self.embed_content(rel_path, &content).await?;
+        self.store
+            .lock()
+            .unwrap()
+            .replace_file(rel_path, &hash, chunks);
+        Ok(true)
+    }
+
+    pub fn remove_file(&self, rel_path: &str) -> bool {
+        self.store.lock().unwrap().remove_file(rel_path)
+    }
+
+    /// 変更されたファイルを記録する（次の `apply_pending_changes` でインデックスし直す）
+    pub fn mark_changed(&self, rel_path: &str) {
+        self.pending.lock().unwrap().insert(rel_path.to_string());
+    }
+
+    pub fn pending_changes(&self) -> Vec<String> {
+        self.pending.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// 記録した変更のあるファイルだけインデックスし直す
+    pub async fn apply_pending_changes(&self) -> Result<IndexSyncStats> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        let mut stats = IndexSyncStats::default();
+        for path in pending {
+            let existed = self.store.lock().unwrap().file_hash(&path).is_some();
+            if !self.index_file(&path).await? {
+                continue;
+            }
+            let exists = self.store.lock().unwrap().file_hash(&path).is_some();
+            match (existed, exists) {
+                (false, _) => stats.added += 1,
+                (true, true) => stats.updated += 1,
+                (true, false) => stats.removed += 1,
+            }
+        }
+        Ok(stats)
+    }
+
+    async fn read_workspace_files(&self) -> Result<Vec<(String, String)>> {
+        let workspace_path = self.workspace_path.clone();
+        tokio::task::spawn_blocking(move || {
+            Ok(workspace_files(&workspace_path)?
+                .into_iter()
+                .filter_map(|path| {
+                    read_indexable_file(&workspace_path.join(&path)).map(|content| (path, content))
+                })
+                .collect())
+        })
+        .await?
+    }
+
+    async fn embed_content(&self, rel_path: &str, content: &str) -> Result<Vec<IndexedChunk>> {
+        let chunks = chunk_file(rel_path, content);
+        if chunks.is_empty() {
+            return Ok(Vec::new());
+        }
+        // ファイル名も埋め込みに含め、パスでも検索できるようにする
+        let texts: Vec<String> = chunks
+            .iter()
+            .map(|chunk| format!("{}\n{}", chunk.path, chunk.content))
+            .collect();
+        let embeddings = self.embedder.embed(&texts).await?;
+        Ok(chunks
+            .into_iter()
+            .zip(embeddings)
+            .map(|(chunk, embedding)| IndexedChunk { chunk, embedding })
+            .collect())
+    }
+
+    /// クエリに意味的に近いチャンクを返す
+    pub async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        path_prefix: Option<&str>,
+    ) -> Result<Vec<SearchResult>> {
+        let embedding = self
+            .embedder
+            .embed(&[query.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("The embedder returned no embedding"))?;
+        Ok(self
+            .store
+            .lock()
+            .unwrap()
+            .search(&embedding, limit, path_prefix))
+    }
+
+    /// インデックスを保存する（保存先を指定していない場合は何もしない）
+    pub async fn save(&self) -> Result<()> {
+        let Some(index_path) = &self.index_path else {
+            return Ok(());
+        };
+        let store = self.store.lock().unwrap().clone();
+        store.save(index_path).await
+    }
+}
+
+/// ワークスペースを監視し、変更されたファイルをインデックスの更新対象として記録する
+///
+/// 埋め込みの計算は `apply_pending_changes` まで遅らせる。返されたウォッチャーを破棄すると監視は停止する。
+pub fn watch_codebase_index(index: Arc<CodebaseIndex>) -> Result<RecommendedWatcher> {
+    let workspace_path = index.workspace_path.clone();
+    let roots = [
+        workspace_path.clone(),
+        workspace_path
+            .canonicalize()
+            .unwrap_or_else(|_| workspace_path.clone()),
+    ];
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else {
+            return;
+        };
+        if event.kind.is_access() {
+            return;
+        }
+        for path in &event.paths {
+            if let Some(rel_path) = roots
+                .iter()
+                .find_map(|root| path.strip_prefix(root).ok())
+                .filter(|rel_path| is_watched_path(rel_path))
+            {
+                index.mark_changed(&rel_path.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    })?;
+    watcher.watch(&workspace_path, RecursiveMode::Recursive)?;
+    Ok(watcher)
+}
+
+/// `workspace_files` と同じく、隠しディレクトリや依存関係の下のパスは除く
+fn is_watched_path(rel_path: &Path) -> bool {
+    let components: Vec<_> = rel_path.components().collect();
+    !components.is_empty()
+        && components[..components.len() - 1]
+            .iter()
+            .all(|component| match component {
+                Component::Normal(name) => {
+                    let name = name.to_string_lossy();
+                    !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref())
+                }
+                _ => false,
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_codebase_index_build_and_search() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let workspace = temp_dir.path().join("workspace");
+        std::fs::create_dir_all(workspace.join("src")).unwrap();
+        std::fs::write(
+            workspace.join("src/http.rs"),
+            "pub fn parse_http_request(raw: &str) -> Request {\n    todo!()\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            workspace.join("src/sidebar.rs"),
+            "pub fn render_sidebar(items: &[Item]) {}\n",
+        )
+        .unwrap();
+
+        let index_path = temp_dir.path().join("index.json");
+        let embedder: Arc<dyn Embedder> = Arc::new(HashEmbedder::default());
+        let index = CodebaseIndex::open(workspace.clone(), embedder.clone(), index_path.clone())
+            .await
+            .unwrap();
+        assert_eq!(index.build().await.unwrap(), 2);
+
+        let results = index
+            .search("parse an HTTP request", 1, None)
+            .await
+            .unwrap();
+        assert_eq!(results[0].chunk.path, "src/http.rs");
+        assert_eq!(
+            (results[0].chunk.start_line, results[0].chunk.end_line),
+            (1, 3)
+        );
+
+        std::fs::remove_file(workspace.join("src/http.rs")).unwrap();
+        assert!(index.index_file("src/http.rs").await.unwrap());
+        assert_eq!(index.indexed_files(), vec!["src/sidebar.rs"]);
+
+        index.save().await.unwrap();
+        let reopened = CodebaseIndex::open(workspace.clone(), embedder, index_path.clone())
+            .await
+            .unwrap();
+        assert_eq!(reopened.chunk_count(), 1);
+        // モデルが変わった場合は読み込まない
+        let reopened = CodebaseIndex::open(workspace, Arc::new(HashEmbedder::new(64)), index_path)
+            .await
+            .unwrap();
+        assert_eq!(reopened.chunk_count(), 0);
+    }
+
+    /// 埋め込みを計算したテキストの数を数える
+    #[derive(Debug, Default)]
+    struct CountingEmbedder {
+        inner: HashEmbedder,
+        embedded: Mutex<usize>,
+    }
+
+    #[async_trait::async_trait]
+    impl Embedder for CountingEmbedder {
+        fn model_id(&self) -> String {
+            self.inner.model_id()
+        }
+
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            *self.embedded.lock().unwrap() += texts.len();
+            self.inner.embed(texts).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_codebase_index_incremental_updates() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let workspace = temp_dir.path().join("workspace");
+        std::fs::create_dir_all(&workspace).unwrap();
+        for name in ["a.rs", "b.rs", "c.rs"] {
+            std::fs::write(workspace.join(name), format!("fn {}() {{}}\n", name)).unwrap();
+        }
+        let index_path = temp_dir.path().join("index.json");
+        let embedder = Arc::new(CountingEmbedder::default());
+        let index = CodebaseIndex::open(workspace.clone(), embedder.clone(), index_path.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            index.sync().await.unwrap(),
+            IndexSyncStats {
+                added: 3,
+                updated: 0,
+                removed: 0
+            }
+        );
+        index.save().await.unwrap();
+
+        // 起動時に保存したインデックスとワークスペースの差分だけを反映する
+        std::fs::write(workspace.join("a.rs"), "fn changed() {}\n").unwrap();
+        std::fs::remove_file(workspace.join("b.rs")).unwrap();
+        std::fs::write(workspace.join("d.rs"), "fn d() {}\n").unwrap();
+        let embedder = Arc::new(CountingEmbedder::default());
+        let index = CodebaseIndex::open(workspace.clone(), embedder.clone(), index_path)
+            .await
+            .unwrap();
+        assert_eq!(
+            index.sync().await.unwrap(),
+            IndexSyncStats {
+                added: 1,
+                updated: 1,
+                removed: 1
+            }
+        );
+        assert_eq!(*embedder.embedded.lock().unwrap(), 2);
+        assert!(index.sync().await.unwrap().is_empty());
+        assert_eq!(*embedder.embedded.lock().unwrap(), 2);
+
+        // 変更を記録したファイルのうち、内容が変わったものだけを計算し直す
+        std::fs::write(workspace.join("c.rs"), "fn c_changed() {}\n").unwrap();
+        index.mark_changed("c.rs");
+        index.mark_changed("d.rs");
+        index.mark_changed("a.rs");
+        std::fs::remove_file(workspace.join("a.rs")).unwrap();
+        assert_eq!(
+            index.apply_pending_changes().await.unwrap(),
+            IndexSyncStats {
+                added: 0,
+                updated: 1,
+                removed: 1
+            }
+        );
+        assert_eq!(*embedder.embedded.lock().unwrap(), 3);
+        assert!(index.pending_changes().is_empty());
+        assert_eq!(index.indexed_files(), vec!["c.rs", "d.rs"]);
+    }
+
+    #[test]
+    fn test_is_watched_path() {
+        assert!(is_watched_path(Path::new("src/lib.rs")));
+        assert!(is_watched_path(Path::new(".env")));
+        assert!(!is_watched_path(Path::new(".git/index")));
+        assert!(!is_watched_path(Path::new("target/debug/build")));
+        assert!(!is_watched_path(Path::new("")));
+    }
+}
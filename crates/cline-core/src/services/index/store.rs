@@ -0,0 +1,216 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::chunker::Chunk;
+use super::embedding::cosine_similarity;
+use crate::storage::{parse_versioned_json, to_versioned_json, write_atomic};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedChunk {
+    pub chunk: Chunk,
+    pub embedding: Vec<f32>,
+}
+
+/// 検索結果のチャンク
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub chunk: Chunk,
+    /// クエリとのコサイン類似度
+    pub score: f32,
+}
+
+/// チャンクの埋め込みをファイルごとに保持し、保存・読み込みできるベクトルストア
+///
+/// 検索は全件とのコサイン類似度を計算する（ワークスペース規模では近似索引より十分速く、結果も厳密）。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VectorStore {
+    /// 埋め込みを計算したモデル
+    pub model_id: String,
+    files: BTreeMap<String, Vec<IndexedChunk>>,
+    /// インデックスしたファイルの内容のハッシュ（チャンクがないファイルも含む）
+    #[serde(default)]
+    hashes: BTreeMap<String, String>,
+}
+
+impl VectorStore {
+    pub fn new(model_id: impl Into<String>) -> Self {
+        Self {
+            model_id: model_id.into(),
+            files: BTreeMap::new(),
+            hashes: BTreeMap::new(),
+        }
+    }
+
+    /// ファイルのチャンクを置き換え、内容のハッシュを記録する
+    pub fn replace_file(&mut self, path: &str, hash: &str, chunks: Vec<IndexedChunk>) {
+        self.hashes.insert(path.to_string(), hash.to_string());
+        if chunks.is_empty() {
+            self.files.remove(path);
+        } else {
+            self.files.insert(path.to_string(), chunks);
+        }
+    }
+
+    pub fn remove_file(&mut self, path: &str) -> bool {
+        self.files.remove(path);
+        self.hashes.remove(path).is_some()
+    }
+
+    /// インデックスしたときのファイルの内容のハッシュ
+    pub fn file_hash(&self, path: &str) -> Option<&str> {
+        self.hashes.get(path).map(String::as_str)
+    }
+
+    /// ハッシュを記録したファイル（チャンクがないファイルも含む）
+    pub fn hashed_files(&self) -> impl Iterator<Item = &str> {
+        self.hashes.keys().map(String::as_str)
+    }
+
+    /// 記録したハッシュ全体のルートハッシュ（ワークスペースと比べて変更を検出する）
+    pub fn manifest_hash(&self) -> String {
+        manifest_hash(
+            self.hashes
+                .iter()
+                .map(|(path, hash)| (path.as_str(), hash.as_str())),
+        )
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.files.values().map(Vec::len).sum()
+    }
+
+    /// 類似度の高い順に最大 `limit` 件を返す（`path_prefix` を指定した場合はその下のファイルのみ）
+    pub fn search(
+        &self,
+        embedding: &[f32],
+        limit: usize,
+        path_prefix: Option<&str>,
+    ) -> Vec<SearchResult> {
+        let prefix = path_prefix
+            .map(|prefix| prefix.trim_start_matches("./").trim_end_matches('/'))
+            .filter(|prefix| !prefix.is_empty() && *prefix != ".");
+        let mut results: Vec<SearchResult> = self
+            .files
+            .iter()
+            .filter(|(path, _)| {
+                prefix.is_none_or(|prefix| {
+                    path.as_str() == prefix || path.starts_with(&format!("{}/", prefix))
+                })
+            })
+            .flat_map(|(_, chunks)| chunks)
+            .map(|indexed| SearchResult {
+                chunk: indexed.chunk.clone(),
+                score: cosine_similarity(embedding, &indexed.embedding),
+            })
+            .collect();
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results.truncate(limit);
+        results
+    }
+
+    pub async fn load(path: &Path) -> Result<Self> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let (_, data) = parse_versioned_json(&content)?;
+        Ok(serde_json::from_value(data)?)
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        write_atomic(path, to_versioned_json(self)?.as_bytes()).await
+    }
+}
+
+/// 内容のハッシュ（FNV-1a）
+pub fn content_hash(content: &[u8]) -> String {
+    format!("{:016x}", fnv1a(0xcbf29ce484222325, content))
+}
+
+/// パス順に並んだ（パス, ハッシュ）の組からルートハッシュを計算する
+pub fn manifest_hash<'a>(entries: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    let hash = entries
+        .into_iter()
+        .fold(0xcbf29ce484222325, |hash, (path, file_hash)| {
+            let hash = fnv1a(hash, path.as_bytes());
+            fnv1a(fnv1a(hash, b"\0"), file_hash.as_bytes())
+        });
+    format!("{:016x}", hash)
+}
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn indexed(path: &str, start_line: usize, embedding: Vec<f32>) -> IndexedChunk {
+        IndexedChunk {
+            chunk: Chunk {
+                path: path.to_string(),
+                start_line,
+                end_line: start_line + 1,
+                content: format!("{}:{}", path, start_line),
+            },
+            embedding,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_vector_store_search_and_persistence() {
+        let mut store = VectorStore::new("hash-2");
+        store.replace_file(
+            "src/api.rs",
+            "1",
+            vec![
+                indexed("src/api.rs", 1, vec![1.0, 0.0]),
+                indexed("src/api.rs", 10, vec![0.6, 0.8]),
+            ],
+        );
+        store.replace_file(
+            "docs/api.md",
+            "2",
+            vec![indexed("docs/api.md", 1, vec![0.9, 0.1])],
+        );
+        store.replace_file("empty.rs", "3", Vec::new());
+        assert_eq!(store.chunk_count(), 3);
+        assert_eq!(
+            store.hashed_files().collect::<Vec<_>>(),
+            vec!["docs/api.md", "empty.rs", "src/api.rs"]
+        );
+        assert_eq!(
+            store.manifest_hash(),
+            manifest_hash([("docs/api.md", "2"), ("empty.rs", "3"), ("src/api.rs", "1")])
+        );
+        assert_ne!(
+            store.manifest_hash(),
+            manifest_hash([("docs/api.md", "2"), ("empty.rs", "3"), ("src/api.rs", "4")])
+        );
+
+        let results = store.search(&[1.0, 0.0], 2, None);
+        let found: Vec<_> = results.iter().map(|r| r.chunk.content.as_str()).collect();
+        assert_eq!(found, vec!["src/api.rs:1", "docs/api.md:1"]);
+        let results = store.search(&[1.0, 0.0], 5, Some("./src/"));
+        assert_eq!(results.len(), 2);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("index/codebase.json");
+        store.save(&path).await.unwrap();
+        assert_eq!(VectorStore::load(&path).await.unwrap(), store);
+
+        assert!(store.remove_file("src/api.rs"));
+        assert_eq!(store.files().collect::<Vec<_>>(), vec!["docs/api.md"]);
+        assert_eq!(store.file_hash("src/api.rs"), None);
+    }
+}
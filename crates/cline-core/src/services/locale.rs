@@ -0,0 +1,209 @@
+use std::fmt::Display;
+
+use crate::budget::BudgetLimit;
+
+/// ユーザーとのやり取りに使う言語
+///
+/// `preferred_language` から選び、確認の質問・通知・ツールのエラーを翻訳する。
+/// 翻訳のない言語は英語にする。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Ja,
+}
+
+impl Locale {
+    /// `preferred_language`（`Japanese`・`日本語`・`ja-JP` など）から言語を選ぶ
+    pub fn from_preferred_language(language: &str) -> Self {
+        let language = language.trim().to_lowercase();
+        let primary = language.split(['-', '_']).next().unwrap_or_default();
+        match primary {
+            "ja" | "jp" | "japanese" | "日本語" => Self::Ja,
+            _ => Self::En,
+        }
+    }
+
+    /// システムプロンプトに含める使用言語の指示
+    pub fn language_preference_section(&self, language: &str) -> String {
+        match self {
+            Self::En => format!(
+                "Language Preference:\nYou should always speak and think in the {} language.",
+                language
+            ),
+            Self::Ja => "言語設定:\n常に日本語で考え、日本語で応答してください。".to_string(),
+        }
+    }
+
+    pub fn tool_error(&self, message: impl Display) -> String {
+        match self {
+            Self::En => format!("Tool execution error: {}", message),
+            Self::Ja => format!("ツールの実行エラー: {}", message),
+        }
+    }
+
+    pub fn missing_tool_parameter_error(&self, param_name: &str) -> String {
+        match self {
+            Self::En => format!("Missing required parameter: {}", param_name),
+            Self::Ja => format!("必須のパラメータがありません: {}", param_name),
+        }
+    }
+
+    /// 必須のパラメータなしでツールが呼ばれたときにユーザーに表示するメッセージ
+    pub fn missing_tool_parameter_notice(
+        &self,
+        tool_name: impl Display,
+        rel_path: Option<&str>,
+        param_name: &str,
+    ) -> String {
+        match self {
+            Self::En => format!(
+                "Roo tried to use {}{} without value for required parameter '{}'. Retrying...",
+                tool_name,
+                rel_path
+                    .map(|p| format!(" for '{}'", p))
+                    .unwrap_or_default(),
+                param_name
+            ),
+            Self::Ja => format!(
+                "{}{}を必須のパラメータ「{}」なしで使おうとしました。再試行します...",
+                rel_path
+                    .map(|p| format!("「{}」に対して", p))
+                    .unwrap_or_default(),
+                tool_name,
+                param_name
+            ),
+        }
+    }
+
+    pub fn question_title(&self) -> &'static str {
+        match self {
+            Self::En => "Question from Cline",
+            Self::Ja => "Clineからの質問",
+        }
+    }
+
+    pub fn approval_required_title(&self) -> &'static str {
+        match self {
+            Self::En => "Approval required",
+            Self::Ja => "承認が必要です",
+        }
+    }
+
+    pub fn outside_workspace_access(&self, path: impl Display) -> String {
+        match self {
+            Self::En => format!("Access outside the workspace: {}", path),
+            Self::Ja => format!("ワークスペース外へのアクセス: {}", path),
+        }
+    }
+
+    pub fn ambiguous_path(&self, path: &str) -> String {
+        match self {
+            Self::En => format!("Which file did you mean by {}?", path),
+            Self::Ja => format!("{}はどのファイルですか？", path),
+        }
+    }
+
+    pub fn fetch_approval(&self, method: &str, url: &str) -> String {
+        match self {
+            Self::En => format!("Send {} request to {}", method, url),
+            Self::Ja => format!("{}への{}リクエストの送信", url, method),
+        }
+    }
+
+    pub fn mcp_tool_approval(&self, server_name: &str, tool_name: &str) -> String {
+        match self {
+            Self::En => format!("Use MCP tool {} on {}", tool_name, server_name),
+            Self::Ja => format!("MCPサーバー{}のツール{}の使用", server_name, tool_name),
+        }
+    }
+
+    pub fn mcp_sampling_approval(&self, server_name: &str) -> String {
+        match self {
+            Self::En => format!("MCP server {} requests a model completion", server_name),
+            Self::Ja => format!(
+                "MCPサーバー{}がモデルの呼び出しを要求しています",
+                server_name
+            ),
+        }
+    }
+
+    pub fn budget_exceeded_title(&self) -> &'static str {
+        match self {
+            Self::En => "Budget exceeded",
+            Self::Ja => "予算を超えました",
+        }
+    }
+
+    /// 予算の上限に達したことの説明
+    pub fn budget_exceeded(&self, limit: &BudgetLimit) -> String {
+        match (self, limit) {
+            (Self::En, limit) => format!("The task {}", limit),
+            (Self::Ja, BudgetLimit::Cost { used, max }) => format!(
+                "タスクの料金が予算の${:.2}に達しました（${:.2}使用）",
+                max, used
+            ),
+            (Self::Ja, BudgetLimit::Tokens { used, max }) => format!(
+                "タスクのトークン数が予算の{}に達しました（{}使用）",
+                max, used
+            ),
+            (Self::Ja, BudgetLimit::Requests { used, max }) => format!(
+                "タスクのリクエスト数が予算の{}に達しました（{}使用）",
+                max, used
+            ),
+        }
+    }
+
+    /// 予算の上限に達したときの続行の確認
+    pub fn budget_exceeded_question(&self, limit: &BudgetLimit) -> String {
+        match self {
+            Self::En => format!("{}. Do you want to continue?", self.budget_exceeded(limit)),
+            Self::Ja => format!("{}。続行しますか？", self.budget_exceeded(limit)),
+        }
+    }
+
+    pub fn task_completed_title(&self) -> &'static str {
+        match self {
+            Self::En => "Task completed",
+            Self::Ja => "タスクが完了しました",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_locale_from_preferred_language() {
+        for language in ["Japanese", "日本語", "ja", "ja-JP", "ja_JP"] {
+            assert_eq!(Locale::from_preferred_language(language), Locale::Ja);
+        }
+        for language in ["English", "en-US", "Français", ""] {
+            assert_eq!(Locale::from_preferred_language(language), Locale::En);
+        }
+
+        assert_eq!(
+            Locale::Ja.missing_tool_parameter_notice("read_file", Some("a.rs"), "path"),
+            "「a.rs」に対してread_fileを必須のパラメータ「path」なしで使おうとしました。再試行します..."
+        );
+        assert_eq!(
+            Locale::En.tool_error(Locale::En.missing_tool_parameter_error("path")),
+            "Tool execution error: Missing required parameter: path"
+        );
+        let limit = BudgetLimit::Requests { used: 11, max: 10 };
+        assert_eq!(
+            Locale::En.budget_exceeded_question(&limit),
+            "The task request budget of 10 reached (11 used). Do you want to continue?"
+        );
+        assert_eq!(
+            Locale::Ja.budget_exceeded_question(&limit),
+            "タスクのリクエスト数が予算の10に達しました（11使用）。続行しますか？"
+        );
+        assert_eq!(
+            Locale::En.language_preference_section("French"),
+            "Language Preference:\nYou should always speak and think in the French language."
+        );
+    }
+}
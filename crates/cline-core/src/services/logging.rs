@@ -0,0 +1,220 @@
+use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// タスクディレクトリ内のログファイル名
+pub const TASK_LOG_FILE_NAME: &str = "task_log.jsonl";
+
+lazy_static! {
+    /// ログに残さない値のパターン（置き換え後の文字列の前置部分をキャプチャする）
+    static ref SECRET_PATTERNS: Vec<Regex> = vec![
+        Regex::new(r"(sk-ant-)[A-Za-z0-9_\-]+").unwrap(),
+        Regex::new(r"(sk-)[A-Za-z0-9_\-]{16,}").unwrap(),
+        Regex::new(r"(?i)(bearer\s+)[A-Za-z0-9._\-]+").unwrap(),
+        Regex::new(
+            r#"(?i)((?:api[_-]?key|x-api-key|token|secret|password)["']?\s*[:=]\s*["']?)[^\s"',}]+"#
+        )
+        .unwrap(),
+    ];
+}
+
+/// APIキーなどの秘密情報を伏せ字にする
+pub fn redact(text: &str) -> String {
+    SECRET_PATTERNS
+        .iter()
+        .fold(text.to_string(), |text, pattern| {
+            pattern.replace_all(&text, "${1}[REDACTED]").to_string()
+        })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// JSON Lines形式で保存するログの1行
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub ts: i64,
+    pub level: LogLevel,
+    /// ログの発生元（`api`、`tool` など）
+    pub target: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+struct LogFile {
+    path: Option<PathBuf>,
+    file: Option<File>,
+}
+
+/// タスクごとのログをタスクディレクトリに書き出す
+///
+/// ファイルは最初に書き込む際に作成する。`tracing` にも同じ内容を出力する。
+#[derive(Debug, Clone, Default)]
+pub struct TaskLogger {
+    inner: Arc<Mutex<LogFile>>,
+}
+
+impl TaskLogger {
+    /// ファイルに書き出さない（`tracing` への出力のみ行う）
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub fn new(task_dir: &Path) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(LogFile {
+                path: Some(task_dir.join(TASK_LOG_FILE_NAME)),
+                file: None,
+            })),
+        }
+    }
+
+    pub fn path(&self) -> Option<PathBuf> {
+        self.inner.lock().unwrap().path.clone()
+    }
+
+    pub fn debug(&self, target: &str, message: impl AsRef<str>) {
+        self.log(LogLevel::Debug, target, message.as_ref());
+    }
+
+    pub fn info(&self, target: &str, message: impl AsRef<str>) {
+        self.log(LogLevel::Info, target, message.as_ref());
+    }
+
+    pub fn warn(&self, target: &str, message: impl AsRef<str>) {
+        self.log(LogLevel::Warn, target, message.as_ref());
+    }
+
+    pub fn error(&self, target: &str, message: impl AsRef<str>) {
+        self.log(LogLevel::Error, target, message.as_ref());
+    }
+
+    pub fn log(&self, level: LogLevel, target: &str, message: &str) {
+        let message = redact(message);
+        match level {
+            LogLevel::Debug => tracing::debug!(target: "cline", "[{}] {}", target, message),
+            LogLevel::Info => tracing::info!(target: "cline", "[{}] {}", target, message),
+            LogLevel::Warn => tracing::warn!(target: "cline", "[{}] {}", target, message),
+            LogLevel::Error => tracing::error!(target: "cline", "[{}] {}", target, message),
+        }
+
+        let entry = LogEntry {
+            ts: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64,
+            level,
+            target: target.to_string(),
+            message,
+        };
+        // ログの書き込みに失敗してもタスクは続行する
+        if let Err(e) = self.write_entry(&entry) {
+            tracing::warn!("Failed to write task log: {}", e);
+        }
+    }
+
+    fn write_entry(&self, entry: &LogEntry) -> Result<()> {
+        let mut log_file = self.inner.lock().unwrap();
+        let Some(path) = log_file.path.clone() else {
+            return Ok(());
+        };
+        if log_file.file.is_none() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            log_file.file = Some(OpenOptions::new().create(true).append(true).open(&path)?);
+        }
+        let file = log_file.file.as_mut().unwrap();
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// 最新のログを最大 `limit` 件取得する（解析できない行は読み飛ばす）
+    pub fn tail(&self, limit: usize) -> Result<Vec<LogEntry>> {
+        let Some(path) = self.path() else {
+            return Ok(Vec::new());
+        };
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = VecDeque::with_capacity(limit);
+        for line in BufReader::new(File::open(&path)?).lines() {
+            let Ok(entry) = serde_json::from_str::<LogEntry>(&line?) else {
+                continue;
+            };
+            if entries.len() == limit {
+                entries.pop_front();
+            }
+            if limit > 0 {
+                entries.push_back(entry);
+            }
+        }
+        Ok(entries.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact("key sk-ant-api03-abcDEF123_xyz used"),
+            "key sk-ant-[REDACTED] used"
+        );
+        assert_eq!(
+            redact(r#"{"x-api-key": "secret-value", "model": "claude"}"#),
+            r#"{"x-api-key": "[REDACTED]", "model": "claude"}"#
+        );
+        assert_eq!(
+            redact("Authorization: Bearer abc.def-123"),
+            "Authorization: Bearer [REDACTED]"
+        );
+        assert_eq!(redact("nothing to hide"), "nothing to hide");
+    }
+
+    #[test]
+    fn test_task_log_tail() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let task_dir = temp_dir.path().join("task-1");
+        let logger = TaskLogger::new(&task_dir);
+        assert!(logger.tail(10).unwrap().is_empty());
+
+        logger.info("task", "started");
+        logger.warn("api", "retrying with api_key=sk-ant-secret");
+        logger.error("tool", "command failed");
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(task_dir.join(TASK_LOG_FILE_NAME))
+            .unwrap()
+            .write_all(b"not json\n")
+            .unwrap();
+
+        let entries = logger.tail(2).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].level, LogLevel::Warn);
+        assert_eq!(entries[0].message, "retrying with api_key=[REDACTED]");
+        assert_eq!(entries[1].target, "tool");
+        assert_eq!(logger.tail(10).unwrap().len(), 3);
+
+        let disabled = TaskLogger::disabled();
+        disabled.info("task", "started");
+        assert!(disabled.tail(10).unwrap().is_empty());
+    }
+}
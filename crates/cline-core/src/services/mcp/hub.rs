@@ -1,21 +1,63 @@
 use std::collections::HashMap;
 use std::fs;
+use std::future::Future;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use serde_json::json;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Semaphore};
 
+use super::install::{install, validate_server_name, McpServerDescriptor};
+use super::oauth::{FileCredentialStore, McpAuthHandler, McpCredentialStore, McpOAuth};
 use super::types::*;
 
+/// `timeout` を指定しないサーバーのツールの呼び出しの制限時間
+pub const DEFAULT_TOOL_CALL_TIMEOUT: Duration = Duration::from_secs(60);
+/// `maxConcurrency` を指定しないサーバーで同時に実行するツールの呼び出しの上限
+pub const DEFAULT_MAX_CONCURRENT_TOOL_CALLS: u32 = 4;
+/// 設定ファイルと同じディレクトリに置く、リモートのサーバーのトークンのファイル名
+pub const MCP_CREDENTIALS_FILE_NAME: &str = "mcp_credentials.json";
+
 #[derive(Debug)]
 pub struct McpConnection {
     #[allow(dead_code)]
     pub server: McpServer,
     #[allow(dead_code)]
     pub client: reqwest::Client,
+    /// 変数を展開した起動に使う設定（展開できなければ `None` で、`server.error` に理由を記録する）
+    pub launch_config: Option<StdioConfig>,
+    /// サーバーにまだ送っていない通知のメソッド
+    pub pending_notifications: Vec<String>,
+    /// 接続を作成した時刻（稼働時間の計算に使う）
+    pub connected_at: Instant,
+    pub last_ping_rtt: Option<Duration>,
+    pub tool_calls: u32,
+    pub tool_call_failures: u32,
+    pub call_timeout: Duration,
+    /// 同時に実行するツールの呼び出しの枠
+    pub call_permits: Arc<Semaphore>,
+    /// `oauth` を設定したリモートのサーバーのトークン
+    pub oauth: Option<Arc<McpOAuth>>,
+}
+
+impl McpConnection {
+    pub fn health(&self) -> McpServerHealth {
+        let disabled = self.server.disabled.unwrap_or(false);
+        let running = !disabled && self.server.status != McpServerStatus::Disconnected;
+        McpServerHealth {
+            name: self.server.name.clone(),
+            status: self.server.status,
+            disabled,
+            uptime_ms: running.then(|| self.connected_at.elapsed().as_millis() as u64),
+            last_error: self.server.error.clone(),
+            ping_rtt_ms: self.last_ping_rtt.map(|rtt| rtt.as_millis() as u64),
+            tool_calls: self.tool_calls,
+            tool_call_failures: self.tool_call_failures,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -23,6 +65,14 @@ pub struct McpHub {
     connections: Arc<Mutex<Vec<McpConnection>>>,
     settings_path: PathBuf,
     workspace_path: PathBuf,
+    /// サーバーに公開するワークスペースのフォルダ（複製したハブとも共有する）
+    roots: Arc<Mutex<Vec<McpRoot>>>,
+    /// サーバーの稼働状況の変化を受け取る（複製したハブとも共有する）
+    status_listeners: Arc<Mutex<Vec<Arc<dyn McpStatusListener>>>>,
+    /// リモートのサーバーのトークンの保存先（複製したハブとも共有する）
+    credential_store: Arc<Mutex<Arc<dyn McpCredentialStore>>>,
+    /// 認可に必要なユーザーの操作を案内する（複製したハブとも共有する）
+    auth_handler: Arc<Mutex<Option<Arc<dyn McpAuthHandler>>>>,
     is_connecting: bool,
     #[allow(dead_code)]
     file_watchers: HashMap<String, RecommendedWatcher>,
@@ -31,16 +81,26 @@ pub struct McpHub {
 #[allow(dead_code)]
 impl McpHub {
     pub fn new(workspace_path: PathBuf, settings_path: PathBuf) -> Result<Self> {
+        let credential_store: Arc<dyn McpCredentialStore> = Arc::new(FileCredentialStore::new(
+            settings_path.with_file_name(MCP_CREDENTIALS_FILE_NAME),
+        ));
         let hub = Self {
             connections: Arc::new(Mutex::new(Vec::new())),
             settings_path,
             workspace_path,
+            roots: Arc::new(Mutex::new(Vec::new())),
+            status_listeners: Arc::new(Mutex::new(Vec::new())),
+            credential_store: Arc::new(Mutex::new(credential_store)),
+            auth_handler: Arc::new(Mutex::new(None)),
             is_connecting: false,
             file_watchers: HashMap::new(),
         };
 
         // 設定ファイルが存在しない場合は作成
         if !hub.settings_path.exists() {
+            if let Some(parent) = hub.settings_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
             fs::write(
                 &hub.settings_path,
                 serde_json::to_string_pretty(&json!({
@@ -115,18 +175,51 @@ impl McpHub {
     fn create_connection(&self, name: &str, config: &StdioConfig) -> Result<McpConnection> {
         let client = reqwest::Client::new();
 
+        // `server.config` には秘密情報を含めないように展開前の設定を残す
+        let (launch_config, error) = match config.interpolate(&self.workspace_path) {
+            Ok(launch_config) => (Some(launch_config), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
         let server = McpServer {
             name: name.to_string(),
             config: serde_json::to_string(config)?,
-            status: McpServerStatus::Connecting,
-            error: None,
+            status: if error.is_some() {
+                McpServerStatus::Disconnected
+            } else {
+                McpServerStatus::Connecting
+            },
+            error,
             disabled: config.disabled,
             tools: None,
             resources: None,
             resource_templates: None,
         };
-
-        Ok(McpConnection { server, client })
+        let oauth = launch_config
+            .as_ref()
+            .filter(|config| config.url.is_some())
+            .and_then(|config| config.oauth.clone())
+            .map(|oauth| Arc::new(McpOAuth::new(name, oauth)));
+
+        Ok(McpConnection {
+            server,
+            client,
+            launch_config,
+            pending_notifications: Vec::new(),
+            connected_at: Instant::now(),
+            last_ping_rtt: None,
+            tool_calls: 0,
+            tool_call_failures: 0,
+            call_timeout: config.timeout.map_or(DEFAULT_TOOL_CALL_TIMEOUT, |secs| {
+                Duration::from_secs(secs.into())
+            }),
+            call_permits: Arc::new(Semaphore::new(
+                config
+                    .max_concurrency
+                    .unwrap_or(DEFAULT_MAX_CONCURRENT_TOOL_CALLS)
+                    .max(1) as usize,
+            )),
+            oauth,
+        })
     }
 
     #[allow(dead_code)]
@@ -161,6 +254,11 @@ impl McpHub {
         Ok(())
     }
 
+    /// すべてのサーバーとの接続を閉じる（複製したハブとも共有する）
+    pub fn dispose(&self) {
+        self.connections.lock().unwrap().clear();
+    }
+
     #[allow(dead_code)]
     pub fn get_servers(&self) -> Vec<McpServer> {
         let connections = self.connections.lock().unwrap();
@@ -171,28 +269,304 @@ impl McpHub {
             .collect()
     }
 
-    #[allow(dead_code)]
+    /// 初期化時にサーバーに伝えるクライアントの機能
+    pub fn client_capabilities() -> serde_json::Value {
+        json!({
+            "roots": { "listChanged": true },
+            "sampling": {},
+        })
+    }
+
+    /// サーバーに公開するワークスペースのフォルダ（`roots/list` の結果）
+    pub fn roots(&self) -> Vec<McpRoot> {
+        self.roots.lock().unwrap().clone()
+    }
+
+    /// ワークスペースのフォルダを更新し、変わった場合は有効なサーバーに通知する
+    pub fn set_roots(&self, roots: Vec<McpRoot>) {
+        let mut current = self.roots.lock().unwrap();
+        if *current == roots {
+            return;
+        }
+        *current = roots;
+        let mut connections = self.connections.lock().unwrap();
+        for conn in connections
+            .iter_mut()
+            .filter(|conn| !conn.server.disabled.unwrap_or(false))
+        {
+            let pending = &mut conn.pending_notifications;
+            if !pending.iter().any(|m| m == ROOTS_LIST_CHANGED_NOTIFICATION) {
+                pending.push(ROOTS_LIST_CHANGED_NOTIFICATION.to_string());
+            }
+        }
+    }
+
+    /// サーバーに送る通知を取り出す
+    pub fn take_notifications(&self, server_name: &str) -> Vec<String> {
+        let mut connections = self.connections.lock().unwrap();
+        connections
+            .iter_mut()
+            .find(|c| c.server.name == server_name)
+            .map(|c| std::mem::take(&mut c.pending_notifications))
+            .unwrap_or_default()
+    }
+
+    /// 変数を展開したサーバーの起動に使う設定
+    pub fn launch_config(&self, server_name: &str) -> Result<StdioConfig> {
+        let connections = self.connections.lock().unwrap();
+        let connection = connections
+            .iter()
+            .find(|c| c.server.name == server_name)
+            .context("Server not found")?;
+        match &connection.launch_config {
+            Some(config) => Ok(config.clone()),
+            None => anyhow::bail!(
+                "Invalid configuration for {}: {}",
+                server_name,
+                connection
+                    .server
+                    .error
+                    .as_deref()
+                    .unwrap_or("unknown error")
+            ),
+        }
+    }
+
+    /// リモートのサーバーのトークンの保存先を変える（既定では設定ファイルの隣のファイル）
+    pub fn set_credential_store(&self, store: Arc<dyn McpCredentialStore>) {
+        *self.credential_store.lock().unwrap() = store;
+    }
+
+    pub fn set_auth_handler(&self, handler: Arc<dyn McpAuthHandler>) {
+        *self.auth_handler.lock().unwrap() = Some(handler);
+    }
+
+    /// リモートのサーバーへのリクエストにアクセストークンを付ける
+    ///
+    /// 有効なトークンがなければ更新し、必要なら認可の操作を案内して待つ。
+    pub async fn authorize_request(
+        &self,
+        server_name: &str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::RequestBuilder> {
+        let oauth = {
+            let connections = self.connections.lock().unwrap();
+            connections
+                .iter()
+                .find(|c| c.server.name == server_name)
+                .context("Server not found")?
+                .oauth
+                .clone()
+        };
+        let Some(oauth) = oauth else {
+            return Ok(request);
+        };
+        let store = Arc::clone(&*self.credential_store.lock().unwrap());
+        let handler = self.auth_handler.lock().unwrap().clone();
+        let token = oauth
+            .access_token(store.as_ref(), handler.as_deref())
+            .await?;
+        Ok(request.bearer_auth(token))
+    }
+
+    /// `url` で接続するリモートのサーバーにJSON-RPCのメッセージを送る
+    ///
+    /// アクセストークンを拒否された場合は、トークンを更新して一度だけ送り直す。
+    pub async fn send_http_request(
+        &self,
+        server_name: &str,
+        message: &serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let url = self
+            .launch_config(server_name)?
+            .url
+            .with_context(|| format!("MCP server {} has no url", server_name))?;
+        let (client, oauth) = {
+            let connections = self.connections.lock().unwrap();
+            let connection = connections
+                .iter()
+                .find(|c| c.server.name == server_name)
+                .context("Server not found")?;
+            (connection.client.clone(), connection.oauth.clone())
+        };
+
+        let mut retried = false;
+        loop {
+            let request = client
+                .post(&url)
+                .header(reqwest::header::ACCEPT, "application/json")
+                .json(message);
+            let response = self
+                .authorize_request(server_name, request)
+                .await?
+                .send()
+                .await?;
+            let status = response.status();
+            if status == reqwest::StatusCode::UNAUTHORIZED && !retried {
+                if let Some(oauth) = &oauth {
+                    oauth.invalidate().await;
+                    retried = true;
+                    continue;
+                }
+            }
+            if !status.is_success() {
+                anyhow::bail!(
+                    "MCP request to {} failed ({}): {}",
+                    server_name,
+                    status,
+                    response.text().await.unwrap_or_default()
+                );
+            }
+            return Ok(response.json().await?);
+        }
+    }
+
+    /// リモートのサーバーの保存したトークンを削除する
+    pub async fn sign_out(&self, server_name: &str) -> Result<()> {
+        let oauth = {
+            let connections = self.connections.lock().unwrap();
+            connections
+                .iter()
+                .find(|c| c.server.name == server_name)
+                .context("Server not found")?
+                .oauth
+                .clone()
+        };
+        let store = Arc::clone(&*self.credential_store.lock().unwrap());
+        match oauth {
+            Some(oauth) => oauth.sign_out(store.as_ref()).await,
+            None => store.remove(server_name).await,
+        }
+    }
+
+    /// サーバーの `alwaysAllow` に含まれるツール（承認なしで呼び出せる）
+    pub fn is_tool_always_allowed(&self, server_name: &str, tool_name: &str) -> bool {
+        let connections = self.connections.lock().unwrap();
+        connections
+            .iter()
+            .find(|c| c.server.name == server_name)
+            .and_then(|c| serde_json::from_str::<StdioConfig>(&c.server.config).ok())
+            .and_then(|config| config.always_allow)
+            .is_some_and(|tools| tools.iter().any(|tool| tool == tool_name))
+    }
+
+    /// サーバーのツールを呼び出す
+    ///
+    /// サーバーの `timeout` と同時実行数の上限を守り、上限を超えた呼び出しは順に待つ。
     pub async fn call_tool(
         &self,
         server_name: &str,
         _tool_name: &str,
         tool_arguments: Option<serde_json::Value>,
     ) -> Result<McpToolCallResponse> {
+        let (disabled, call_timeout, call_permits) = {
+            let connections = self.connections.lock().unwrap();
+            let connection = connections
+                .iter()
+                .find(|c| c.server.name == server_name)
+                .context("Server not found")?;
+            (
+                connection.server.disabled.unwrap_or(false),
+                connection.call_timeout,
+                Arc::clone(&connection.call_permits),
+            )
+        };
+        let result = if disabled {
+            Err(anyhow::anyhow!("Server is disabled"))
+        } else {
+            run_limited(call_permits, call_timeout, async {
+                // ツール呼び出しの実装
+                // 実際のAPIエンドポイントやプロトコルに合わせて実装する必要があります
+                Ok(McpToolCallResponse {
+                    result: tool_arguments.unwrap_or(json!({})),
+                })
+            })
+            .await
+        };
+
+        let health = {
+            let mut connections = self.connections.lock().unwrap();
+            let connection = connections
+                .iter_mut()
+                .find(|c| c.server.name == server_name)
+                .context("Server not found")?;
+            connection.tool_calls += 1;
+            if let Err(e) = &result {
+                connection.tool_call_failures += 1;
+                connection.server.error = Some(e.to_string());
+            }
+            connection.health()
+        };
+        self.publish_status(&health);
+        result
+    }
+
+    /// サーバーに `ping` を送り、往復時間を記録する
+    pub async fn ping_server(&self, server_name: &str) -> Result<Duration> {
+        let started = Instant::now();
+        let (result, health) = {
+            let mut connections = self.connections.lock().unwrap();
+            let connection = connections
+                .iter_mut()
+                .find(|c| c.server.name == server_name)
+                .context("Server not found")?;
+
+            let result = if connection.server.disabled.unwrap_or(false) {
+                Err(anyhow::anyhow!("Server is disabled"))
+            } else if connection.launch_config.is_none() {
+                Err(anyhow::anyhow!(
+                    "Invalid configuration: {}",
+                    connection
+                        .server
+                        .error
+                        .as_deref()
+                        .unwrap_or("unknown error")
+                ))
+            } else {
+                // 実際のプロトコルでは `ping` 要求の応答を待つ
+                Ok(started.elapsed())
+            };
+            match &result {
+                Ok(rtt) => {
+                    connection.last_ping_rtt = Some(*rtt);
+                    connection.server.status = McpServerStatus::Connected;
+                    connection.server.error = None;
+                }
+                Err(e) => {
+                    connection.server.status = McpServerStatus::Disconnected;
+                    connection.server.error = Some(e.to_string());
+                }
+            }
+            (result, connection.health())
+        };
+        self.publish_status(&health);
+        result
+    }
+
+    /// サーバーの稼働状況（稼働時間・最後のエラー・`ping` の往復時間・ツールの呼び出し回数）
+    pub fn get_server_status(&self, server_name: &str) -> Option<McpServerHealth> {
         let connections = self.connections.lock().unwrap();
-        let connection = connections
+        connections
             .iter()
             .find(|c| c.server.name == server_name)
-            .context("Server not found")?;
+            .map(McpConnection::health)
+    }
 
-        if connection.server.disabled.unwrap_or(false) {
-            anyhow::bail!("Server is disabled");
-        }
+    /// すべてのサーバーの稼働状況
+    pub fn get_server_statuses(&self) -> Vec<McpServerHealth> {
+        let connections = self.connections.lock().unwrap();
+        connections.iter().map(McpConnection::health).collect()
+    }
 
-        // ツール呼び出しの実装
-        // 実際のAPIエンドポイントやプロトコルに合わせて実装する必要があります
-        Ok(McpToolCallResponse {
-            result: tool_arguments.unwrap_or(json!({})),
-        })
+    /// サーバーの稼働状況が変わったときに通知を受け取る
+    pub fn add_status_listener(&self, listener: Arc<dyn McpStatusListener>) {
+        self.status_listeners.lock().unwrap().push(listener);
+    }
+
+    fn publish_status(&self, health: &McpServerHealth) {
+        for listener in self.status_listeners.lock().unwrap().iter() {
+            listener.on_server_status(health);
+        }
     }
 
     #[allow(dead_code)]
@@ -238,16 +612,72 @@ impl McpHub {
             } else {
                 always_allow.retain(|t| t != tool_name);
             }
+            let config = serde_json::to_string(server_config)?;
 
             fs::write(
                 &self.settings_path,
                 serde_json::to_string_pretty(&settings)?,
             )?;
+
+            // 設定ファイルの再読み込みを待たずに次の呼び出しから反映する
+            let mut connections = self.connections.lock().unwrap();
+            if let Some(conn) = connections
+                .iter_mut()
+                .find(|c| c.server.name == server_name)
+            {
+                conn.server.config = config;
+                for tool in conn.server.tools.iter_mut().flatten() {
+                    if tool.name == tool_name {
+                        tool.always_allow = should_allow;
+                    }
+                }
+            }
         }
 
         Ok(())
     }
 
+    /// 一覧の情報からサーバーをインストールし、設定ファイルに追加して接続する
+    ///
+    /// インストールは `get_mcp_servers_path` のサーバー名のディレクトリで行う。
+    /// 同じ名前のサーバーが設定済みの場合はエラーにする。
+    pub async fn install_server(&self, descriptor: &McpServerDescriptor) -> Result<McpServer> {
+        validate_server_name(&descriptor.name)?;
+        let content = fs::read_to_string(&self.settings_path)?;
+        let mut settings: McpSettings = serde_json::from_str(&content)?;
+        if settings.mcp_servers.contains_key(&descriptor.name) {
+            anyhow::bail!("MCP server {} is already configured", descriptor.name);
+        }
+        let install_dir = PathBuf::from(self.get_mcp_servers_path().await).join(&descriptor.name);
+        if install_dir.exists() {
+            anyhow::bail!("Install directory {} already exists", install_dir.display());
+        }
+        let config = match install(descriptor, &install_dir).await {
+            Ok(config) => config,
+            Err(e) => {
+                // 途中までインストールしたファイルを残さない
+                let _ = tokio::fs::remove_dir_all(&install_dir).await;
+                return Err(e);
+            }
+        };
+
+        let connection = self.create_connection(&descriptor.name, &config)?;
+        let server = connection.server.clone();
+        settings.mcp_servers.insert(descriptor.name.clone(), config);
+        fs::write(
+            &self.settings_path,
+            serde_json::to_string_pretty(&settings)?,
+        )?;
+        let health = connection.health();
+        {
+            let mut connections = self.connections.lock().unwrap();
+            connections.retain(|c| c.server.name != descriptor.name);
+            connections.push(connection);
+        }
+        self.publish_status(&health);
+        Ok(server)
+    }
+
     #[allow(dead_code)]
     pub async fn toggle_server_disabled(&self, server_name: &str, disabled: bool) -> Result<()> {
         let content = fs::read_to_string(&self.settings_path)?;
@@ -261,12 +691,18 @@ impl McpHub {
                 serde_json::to_string_pretty(&settings)?,
             )?;
 
-            let mut connections = self.connections.lock().unwrap();
-            if let Some(conn) = connections
-                .iter_mut()
-                .find(|c| c.server.name == server_name)
-            {
-                conn.server.disabled = Some(disabled);
+            let health = {
+                let mut connections = self.connections.lock().unwrap();
+                connections
+                    .iter_mut()
+                    .find(|c| c.server.name == server_name)
+                    .map(|conn| {
+                        conn.server.disabled = Some(disabled);
+                        conn.health()
+                    })
+            };
+            if let Some(health) = health {
+                self.publish_status(&health);
             }
         }
 
@@ -280,11 +716,22 @@ impl McpHub {
 
         if let Some(server_config) = settings.mcp_servers.get_mut(server_name) {
             server_config.timeout = Some(timeout);
+            let config = serde_json::to_string(server_config)?;
 
             fs::write(
                 &self.settings_path,
                 serde_json::to_string_pretty(&settings)?,
             )?;
+
+            // 設定ファイルの再読み込みを待たずに次の呼び出しから反映する
+            let mut connections = self.connections.lock().unwrap();
+            if let Some(conn) = connections
+                .iter_mut()
+                .find(|c| c.server.name == server_name)
+            {
+                conn.server.config = config;
+                conn.call_timeout = Duration::from_secs(timeout.into());
+            }
         }
 
         Ok(())
@@ -304,14 +751,213 @@ impl McpHub {
     }
 }
 
+/// 同時実行数の枠を得てから `future` を実行する（`timeout` は枠を待つ時間を含む）
+async fn run_limited<T>(
+    permits: Arc<Semaphore>,
+    timeout: Duration,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let limited = async {
+        let _permit = permits.acquire_owned().await?;
+        future.await
+    };
+    match tokio::time::timeout(timeout, limited).await {
+        Ok(result) => result,
+        Err(_) => anyhow::bail!("MCP tool call timed out after {}s", timeout.as_secs_f64()),
+    }
+}
+
 impl Clone for McpHub {
     fn clone(&self) -> Self {
         Self {
             connections: Arc::clone(&self.connections),
             settings_path: self.settings_path.clone(),
             workspace_path: self.workspace_path.clone(),
+            roots: Arc::clone(&self.roots),
+            status_listeners: Arc::clone(&self.status_listeners),
+            credential_store: Arc::clone(&self.credential_store),
+            auth_handler: Arc::clone(&self.auth_handler),
             is_connecting: self.is_connecting,
             file_watchers: HashMap::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::mcp::McpOAuthToken;
+    use pretty_assertions::assert_eq;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Default)]
+    struct RecordingListener(Mutex<Vec<McpServerHealth>>);
+
+    impl McpStatusListener for RecordingListener {
+        fn on_server_status(&self, health: &McpServerHealth) {
+            self.0.lock().unwrap().push(health.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_status() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let settings_path = temp_dir.path().join("mcp_settings.json");
+        fs::write(
+            &settings_path,
+            r#"{"mcpServers": {"weather": {"command": "node"}, "broken": {"command": "${env:CLINE_TEST_UNSET_VAR}"}}}"#,
+        )
+        .unwrap();
+        let hub = McpHub::new(temp_dir.path().to_path_buf(), settings_path).unwrap();
+        let listener = Arc::new(RecordingListener::default());
+        hub.add_status_listener(listener.clone());
+
+        hub.ping_server("weather").await.unwrap();
+        hub.call_tool("weather", "get_forecast", None)
+            .await
+            .unwrap();
+        let health = hub.get_server_status("weather").unwrap();
+        assert_eq!(health.status, McpServerStatus::Connected);
+        assert!(health.uptime_ms.is_some() && health.ping_rtt_ms.is_some());
+        assert_eq!((health.tool_calls, health.tool_call_failures), (1, 0));
+
+        hub.toggle_server_disabled("weather", true).await.unwrap();
+        assert!(hub
+            .call_tool("weather", "get_forecast", None)
+            .await
+            .is_err());
+        let health = hub.get_server_status("weather").unwrap();
+        assert_eq!(health.uptime_ms, None);
+        assert_eq!((health.tool_calls, health.tool_call_failures), (2, 1));
+        assert_eq!(health.last_error.as_deref(), Some("Server is disabled"));
+
+        let error = hub.ping_server("broken").await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid configuration: Environment variable CLINE_TEST_UNSET_VAR is not set"
+        );
+        assert_eq!(
+            hub.get_server_status("broken").unwrap().status,
+            McpServerStatus::Disconnected
+        );
+        assert_eq!(hub.get_server_statuses().len(), 2);
+
+        let events = listener.0.lock().unwrap();
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0].status, McpServerStatus::Connected);
+        assert!(events[2].disabled);
+    }
+
+    #[tokio::test]
+    async fn test_send_http_request_with_oauth() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut authorizations = Vec::new();
+            for (status, body) in [
+                ("401 Unauthorized", "{}"),
+                ("200 OK", r#"{"access_token":"fresh","expires_in":3600}"#),
+                ("200 OK", r#"{"jsonrpc":"2.0","id":1,"result":{}}"#),
+            ] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = vec![0; 8192];
+                let len = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(&request[..len]).into_owned();
+                authorizations.push(
+                    request
+                        .lines()
+                        .find_map(|line| line.strip_prefix("authorization: "))
+                        .map(str::to_string),
+                );
+                stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            status,
+                            body.len(),
+                            body
+                        )
+                        .as_bytes(),
+                    )
+                    .unwrap();
+            }
+            authorizations
+        });
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let settings_path = temp_dir.path().join("mcp_settings.json");
+        fs::write(
+            &settings_path,
+            json!({"mcpServers": {"remote": {
+                "url": format!("{}/mcp", base_url),
+                "oauth": {"clientId": "cline", "tokenEndpoint": format!("{}/token", base_url)}
+            }}})
+            .to_string(),
+        )
+        .unwrap();
+        let hub = McpHub::new(temp_dir.path().to_path_buf(), settings_path).unwrap();
+        let store = FileCredentialStore::new(temp_dir.path().join(MCP_CREDENTIALS_FILE_NAME));
+        store
+            .save(
+                "remote",
+                &McpOAuthToken {
+                    access_token: "stale".to_string(),
+                    refresh_token: Some("refresh".to_string()),
+                    expires_at: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        let response = hub
+            .send_http_request(
+                "remote",
+                &json!({"jsonrpc": "2.0", "id": 1, "method": "ping"}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(
+            server.join().unwrap(),
+            vec![
+                Some("Bearer stale".to_string()),
+                None,
+                Some("Bearer fresh".to_string())
+            ]
+        );
+        let saved = store.load("remote").await.unwrap().unwrap();
+        assert_eq!(saved.access_token, "fresh");
+    }
+
+    #[tokio::test]
+    async fn test_run_limited() {
+        let permits = Arc::new(Semaphore::new(2));
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let calls = (0..5).map(|i| {
+            let (running, peak) = (Arc::clone(&running), Arc::clone(&peak));
+            run_limited(Arc::clone(&permits), Duration::from_secs(5), async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(i)
+            })
+        });
+        let results = futures_util::future::join_all(calls).await;
+        assert_eq!(
+            results.into_iter().map(|r| r.unwrap()).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 4]
+        );
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+
+        // 枠を待つ時間も制限時間に含める
+        let _held = Arc::clone(&permits).acquire_many_owned(2).await.unwrap();
+        let error = run_limited(permits, Duration::from_millis(50), async { Ok(()) })
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "MCP tool call timed out after 0.05s");
+    }
+}
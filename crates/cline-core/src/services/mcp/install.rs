@@ -0,0 +1,312 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use super::types::StdioConfig;
+
+/// インストールするMCPサーバーの取得元
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum McpServerSource {
+    /// npmのパッケージ（`@scope/name@version` も指定できる）
+    Npm { package: String },
+    /// PyPIのパッケージ（uvで仮想環境にインストールする）
+    Uvx { package: String },
+    /// gitのリポジトリ（`build` のコマンドを順に実行する）
+    Git {
+        url: String,
+        #[serde(default)]
+        build: Vec<String>,
+    },
+}
+
+/// マーケットプレイスなどの一覧に載っているMCPサーバーの情報
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpServerDescriptor {
+    /// 設定ファイルでのサーバー名（インストール先のディレクトリ名にも使う）
+    pub name: String,
+    pub source: McpServerSource,
+    /// 起動するコマンド（npm・uvxでは省略するとパッケージの実行ファイルを使う。
+    /// gitではリポジトリ内の相対パスも指定できる）
+    #[serde(default)]
+    pub command: Option<String>,
+    /// 起動時の引数（gitではリポジトリ内に存在する相対パスを絶対パスにする）
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+/// インストール先のディレクトリ名に使えるサーバー名か確認する
+pub(super) fn validate_server_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        anyhow::bail!("Invalid MCP server name: {:?}", name);
+    }
+    Ok(())
+}
+
+/// `install_dir` にサーバーをインストールし、起動に使う設定を返す
+pub(super) async fn install(
+    descriptor: &McpServerDescriptor,
+    install_dir: &Path,
+) -> Result<StdioConfig> {
+    let (command, args) = match &descriptor.source {
+        McpServerSource::Npm { package } => {
+            tokio::fs::create_dir_all(install_dir).await?;
+            run(
+                Command::new("npm")
+                    .args(["install", "--no-audit", "--no-fund", "--prefix"])
+                    .arg(install_dir)
+                    .arg(package),
+                install_dir,
+            )
+            .await?;
+            let bin = match &descriptor.command {
+                Some(command) => command.clone(),
+                None => npm_bin_name(install_dir, package).await?,
+            };
+            let command = install_dir.join("node_modules").join(".bin").join(bin);
+            (
+                command.to_string_lossy().into_owned(),
+                descriptor.args.clone(),
+            )
+        }
+        McpServerSource::Uvx { package } => {
+            tokio::fs::create_dir_all(install_dir).await?;
+            let venv = install_dir.join(".venv");
+            run(Command::new("uv").arg("venv").arg(&venv), install_dir).await?;
+            run(
+                Command::new("uv")
+                    .args(["pip", "install", "--python"])
+                    .arg(&venv)
+                    .arg(package),
+                install_dir,
+            )
+            .await?;
+            let bin = descriptor
+                .command
+                .clone()
+                .unwrap_or_else(|| python_package_name(package));
+            let command = venv.join("bin").join(bin);
+            (
+                command.to_string_lossy().into_owned(),
+                descriptor.args.clone(),
+            )
+        }
+        McpServerSource::Git { url, build } => {
+            let Some(command) = &descriptor.command else {
+                anyhow::bail!("A command is required to run a server installed from git");
+            };
+            if let Some(parent) = install_dir.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            run(
+                Command::new("git")
+                    .args(["clone", "--depth", "1", url])
+                    .arg(install_dir),
+                install_dir.parent().unwrap_or(install_dir),
+            )
+            .await?;
+            for step in build {
+                run(&mut shell(step), install_dir).await?;
+            }
+            (
+                absolutize(install_dir, command),
+                descriptor
+                    .args
+                    .iter()
+                    .map(|arg| absolutize(install_dir, arg))
+                    .collect(),
+            )
+        }
+    };
+    Ok(StdioConfig {
+        command,
+        args: Some(args),
+        env: (!descriptor.env.is_empty()).then(|| descriptor.env.clone()),
+        always_allow: None,
+        disabled: Some(false),
+        timeout: None,
+        max_concurrency: None,
+        url: None,
+        oauth: None,
+    })
+}
+
+/// インストール先のディレクトリでコマンドを実行し、失敗した場合は出力を含めたエラーにする
+async fn run(command: &mut Command, cwd: &Path) -> Result<()> {
+    let output = command
+        .current_dir(cwd)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {:?}", command.as_std().get_program()))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{:?} failed ({}): {}",
+            command.as_std().get_program(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn shell(script: &str) -> Command {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C");
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c");
+        command
+    };
+    command.arg(script);
+    command
+}
+
+/// インストールしたnpmパッケージの `package.json` の `bin` から実行ファイル名を調べる
+async fn npm_bin_name(install_dir: &Path, package: &str) -> Result<String> {
+    let name = npm_package_name(package);
+    let manifest_path = install_dir
+        .join("node_modules")
+        .join(name)
+        .join("package.json");
+    let manifest: serde_json::Value =
+        serde_json::from_str(&tokio::fs::read_to_string(&manifest_path).await?)?;
+    let unscoped = name.rsplit('/').next().unwrap_or(name);
+    match &manifest["bin"] {
+        serde_json::Value::String(_) => Ok(unscoped.to_string()),
+        serde_json::Value::Object(bins) if bins.contains_key(unscoped) => Ok(unscoped.to_string()),
+        serde_json::Value::Object(bins) if bins.len() == 1 => {
+            Ok(bins.keys().next().unwrap().clone())
+        }
+        _ => anyhow::bail!(
+            "Could not determine the executable of {}; specify a command",
+            name
+        ),
+    }
+}
+
+/// バージョンの指定を除いたnpmのパッケージ名（`@scope/name@1.0` → `@scope/name`）
+fn npm_package_name(package: &str) -> &str {
+    let start = usize::from(package.starts_with('@'));
+    match package[start..].find('@') {
+        Some(index) => &package[..start + index],
+        None => package,
+    }
+}
+
+/// バージョンやextrasの指定を除いたPythonのパッケージ名
+fn python_package_name(package: &str) -> String {
+    package
+        .split(['=', '<', '>', '~', '!', '[', ' '])
+        .next()
+        .unwrap_or(package)
+        .to_string()
+}
+
+/// リポジトリ内に存在する相対パスを絶対パスにする
+fn absolutize(install_dir: &Path, value: &str) -> String {
+    let path = PathBuf::from(value);
+    if path.is_relative() && install_dir.join(&path).exists() {
+        install_dir.join(path).to_string_lossy().into_owned()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::mcp::{McpHub, McpSettings};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_package_names() {
+        assert_eq!(
+            npm_package_name("@modelcontextprotocol/server-github@1.2.0"),
+            "@modelcontextprotocol/server-github"
+        );
+        assert_eq!(npm_package_name("mcp-server"), "mcp-server");
+        assert_eq!(
+            python_package_name("mcp-server-git==0.6.2"),
+            "mcp-server-git"
+        );
+        assert!(validate_server_name("github").is_ok());
+        assert!(validate_server_name("../evil").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_install_server_from_git() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = temp_dir.path().join("weather-server");
+        std::fs::create_dir_all(&repo).unwrap();
+        std::fs::write(repo.join("index.js"), "console.log('ok')\n").unwrap();
+        for args in [
+            vec!["init", "-q"],
+            vec!["add", "."],
+            vec![
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "-q",
+                "-m",
+                "init",
+            ],
+        ] {
+            let status = std::process::Command::new("git")
+                .args(args)
+                .current_dir(&repo)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+
+        let workspace = temp_dir.path().join("workspace");
+        let settings_path = temp_dir.path().join("mcp_settings.json");
+        let hub = McpHub::new(workspace.clone(), settings_path.clone()).unwrap();
+        let descriptor: McpServerDescriptor = serde_json::from_value(serde_json::json!({
+            "name": "weather",
+            "source": { "type": "git", "url": repo.to_string_lossy(), "build": ["touch built"] },
+            "command": "node",
+            "args": ["index.js", "--verbose"],
+            "env": { "API_KEY": "${env:WEATHER_API_KEY}" }
+        }))
+        .unwrap();
+        let server = hub.install_server(&descriptor).await.unwrap();
+        assert_eq!(server.name, "weather");
+
+        let install_dir = workspace.join("mcp-servers").join("weather");
+        assert!(install_dir.join("built").exists());
+        let settings: McpSettings =
+            serde_json::from_str(&std::fs::read_to_string(&settings_path).unwrap()).unwrap();
+        let config = &settings.mcp_servers["weather"];
+        assert_eq!(config.command, "node");
+        assert_eq!(
+            config.args.clone().unwrap(),
+            vec![
+                install_dir.join("index.js").to_string_lossy().into_owned(),
+                "--verbose".to_string()
+            ]
+        );
+        assert!(hub.get_servers().iter().any(|s| s.name == "weather"));
+
+        let error = hub.install_server(&descriptor).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "MCP server weather is already configured"
+        );
+    }
+}
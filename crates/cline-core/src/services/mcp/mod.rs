@@ -1,5 +1,9 @@
 mod hub;
+mod install;
+mod oauth;
 mod types;
 
 pub use hub::*;
+pub use install::{McpServerDescriptor, McpServerSource};
+pub use oauth::*;
 pub use types::*;
@@ -0,0 +1,760 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::storage::{parse_versioned_json, to_versioned_json, write_atomic};
+
+/// 認可サーバーでユーザーの操作を待つ時間の上限
+pub const AUTHORIZATION_TIMEOUT: Duration = Duration::from_secs(300);
+/// 有効期限の直前のアクセストークンは期限切れとみなす（秒）
+const EXPIRY_MARGIN_SECS: i64 = 60;
+
+/// リモートのMCPサーバーのOAuthの設定（`deviceAuthorizationEndpoint` があればデバイスコード、
+/// なければループバックのリダイレクトで認可する）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpOAuthConfig {
+    pub client_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorization_endpoint: Option<String>,
+    pub token_endpoint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_authorization_endpoint: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// ループバックのリダイレクトを受け取るポート（省略すると空いているポートを使う）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirect_port: Option<u16>,
+}
+
+/// サーバーごとに保存するトークン
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpOAuthToken {
+    pub access_token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    /// アクセストークンの有効期限（UNIX時間の秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+}
+
+impl McpOAuthToken {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| {
+            chrono::Utc::now().timestamp() + EXPIRY_MARGIN_SECS >= expires_at
+        })
+    }
+}
+
+/// リフレッシュトークンなどを保存する（ホストがOSのキーチェーンなどで実装できる）
+#[async_trait]
+pub trait McpCredentialStore: Debug + Send + Sync {
+    async fn load(&self, server_name: &str) -> Result<Option<McpOAuthToken>>;
+    async fn save(&self, server_name: &str, token: &McpOAuthToken) -> Result<()>;
+    async fn remove(&self, server_name: &str) -> Result<()>;
+}
+
+/// 認可に必要なユーザーの操作を案内する（ヘッドレス実行時のホストが実装する）
+#[async_trait]
+pub trait McpAuthHandler: Debug + Send + Sync {
+    /// デバイスコードの入力を案内する
+    async fn show_device_code(
+        &self,
+        server_name: &str,
+        verification_uri: &str,
+        user_code: &str,
+    ) -> Result<()>;
+
+    /// ブラウザで認可のページを開く
+    async fn open_authorization_url(&self, server_name: &str, url: &str) -> Result<()>;
+}
+
+/// 設定ファイルの隣のJSONファイルにトークンを保存する（本人だけが読み書きできる権限にする）
+#[derive(Debug)]
+pub struct FileCredentialStore {
+    path: PathBuf,
+    /// 同時に書き込んで他のサーバーのトークンを失わないようにする
+    lock: Mutex<()>,
+}
+
+impl FileCredentialStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    async fn read_all(&self) -> Result<BTreeMap<String, McpOAuthToken>> {
+        let content = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let (_, data) = parse_versioned_json(&content)?;
+        serde_json::from_value(data)
+            .with_context(|| format!("Invalid MCP credentials {}", self.path.display()))
+    }
+
+    async fn write_all(&self, tokens: &BTreeMap<String, McpOAuthToken>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        write_atomic(&self.path, to_versioned_json(tokens)?.as_bytes()).await?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            tokio::fs::set_permissions(&self.path, std::fs::Permissions::from_mode(0o600)).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl McpCredentialStore for FileCredentialStore {
+    async fn load(&self, server_name: &str) -> Result<Option<McpOAuthToken>> {
+        let _guard = self.lock.lock().await;
+        Ok(self.read_all().await?.remove(server_name))
+    }
+
+    async fn save(&self, server_name: &str, token: &McpOAuthToken) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        let mut tokens = self.read_all().await?;
+        tokens.insert(server_name.to_string(), token.clone());
+        self.write_all(&tokens).await
+    }
+
+    async fn remove(&self, server_name: &str) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        let mut tokens = self.read_all().await?;
+        if tokens.remove(server_name).is_some() {
+            self.write_all(&tokens).await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeviceAuthorizationResponse {
+    device_code: String,
+    user_code: String,
+    #[serde(alias = "verification_url")]
+    verification_uri: String,
+    verification_uri_complete: Option<String>,
+    expires_in: u64,
+    #[serde(default = "default_poll_interval")]
+    interval: u64,
+}
+
+fn default_poll_interval() -> u64 {
+    5
+}
+
+enum TokenError {
+    /// デバイスコードの認可を待っている（`slow_down` なら間隔を広げる）
+    Pending {
+        slow_down: bool,
+    },
+    Failed(anyhow::Error),
+}
+
+/// 1つのサーバーのアクセストークンを取得・更新する
+///
+/// 有効なトークンがなければリフレッシュトークンで更新し、それもなければ認可からやり直す。
+#[derive(Debug)]
+pub struct McpOAuth {
+    server_name: String,
+    config: McpOAuthConfig,
+    client: Client,
+    /// 取得済みのトークン（同時に更新しないように認可の間もロックする）
+    token: Mutex<Option<McpOAuthToken>>,
+}
+
+impl McpOAuth {
+    pub fn new(server_name: &str, config: McpOAuthConfig) -> Self {
+        Self {
+            server_name: server_name.to_string(),
+            config,
+            client: Client::new(),
+            token: Mutex::new(None),
+        }
+    }
+
+    /// HTTPのリクエストに付けるアクセストークン
+    pub async fn access_token(
+        &self,
+        store: &dyn McpCredentialStore,
+        handler: Option<&dyn McpAuthHandler>,
+    ) -> Result<String> {
+        let mut cached = self.token.lock().await;
+        if cached.is_none() {
+            *cached = store.load(&self.server_name).await?;
+        }
+        if let Some(token) = cached.as_ref().filter(|token| !token.is_expired()) {
+            return Ok(token.access_token.clone());
+        }
+
+        let refreshed = match cached.as_ref().and_then(|t| t.refresh_token.clone()) {
+            Some(refresh_token) => match self.refresh(&refresh_token).await {
+                Ok(token) => Some(token),
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to refresh the token for MCP server {}: {}",
+                        self.server_name,
+                        e
+                    );
+                    None
+                }
+            },
+            None => None,
+        };
+        let token = match refreshed {
+            Some(token) => token,
+            None => {
+                let Some(handler) = handler else {
+                    anyhow::bail!("MCP server {} requires authorization", self.server_name);
+                };
+                self.authorize(handler).await?
+            }
+        };
+        store.save(&self.server_name, &token).await?;
+        let access_token = token.access_token.clone();
+        *cached = Some(token);
+        Ok(access_token)
+    }
+
+    /// サーバーに拒否されたアクセストークンを破棄する（リフレッシュトークンは残す）
+    pub async fn invalidate(&self) {
+        if let Some(token) = self.token.lock().await.as_mut() {
+            token.expires_at = Some(0);
+        }
+    }
+
+    /// 保存したトークンを削除する
+    pub async fn sign_out(&self, store: &dyn McpCredentialStore) -> Result<()> {
+        *self.token.lock().await = None;
+        store.remove(&self.server_name).await
+    }
+
+    async fn authorize(&self, handler: &dyn McpAuthHandler) -> Result<McpOAuthToken> {
+        let flow = async {
+            match &self.config.device_authorization_endpoint {
+                Some(endpoint) => self.device_flow(endpoint, handler).await,
+                None => self.loopback_flow(handler).await,
+            }
+        };
+        tokio::time::timeout(AUTHORIZATION_TIMEOUT, flow)
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "Authorization for MCP server {} timed out",
+                    self.server_name
+                )
+            })?
+    }
+
+    async fn refresh(&self, refresh_token: &str) -> Result<McpOAuthToken> {
+        let mut token = match self
+            .request_token(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+            ])
+            .await
+        {
+            Ok(token) => token,
+            Err(TokenError::Pending { .. }) => anyhow::bail!("Unexpected authorization_pending"),
+            Err(TokenError::Failed(e)) => return Err(e),
+        };
+        // 新しいリフレッシュトークンを返さないサーバーでは元のものを使い続ける
+        token
+            .refresh_token
+            .get_or_insert_with(|| refresh_token.to_string());
+        Ok(token)
+    }
+
+    async fn device_flow(
+        &self,
+        endpoint: &str,
+        handler: &dyn McpAuthHandler,
+    ) -> Result<McpOAuthToken> {
+        let scope = self.config.scopes.join(" ");
+        let response = self
+            .client
+            .post(endpoint)
+            .form(&[
+                ("client_id", self.config.client_id.as_str()),
+                ("scope", scope.as_str()),
+            ])
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!(
+                "Device authorization request failed ({}): {}",
+                status,
+                response.text().await.unwrap_or_default()
+            );
+        }
+        let device: DeviceAuthorizationResponse = response.json().await?;
+        handler
+            .show_device_code(
+                &self.server_name,
+                device
+                    .verification_uri_complete
+                    .as_deref()
+                    .unwrap_or(&device.verification_uri),
+                &device.user_code,
+            )
+            .await?;
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(device.expires_in);
+        let mut interval = Duration::from_secs(device.interval);
+        loop {
+            match self
+                .request_token(&[
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                    ("device_code", device.device_code.as_str()),
+                ])
+                .await
+            {
+                Ok(token) => return Ok(token),
+                Err(TokenError::Pending { slow_down }) => {
+                    if slow_down {
+                        interval += Duration::from_secs(5);
+                    }
+                }
+                Err(TokenError::Failed(e)) => return Err(e),
+            }
+            if tokio::time::Instant::now() + interval >= deadline {
+                anyhow::bail!(
+                    "The device code for MCP server {} expired",
+                    self.server_name
+                );
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    async fn loopback_flow(&self, handler: &dyn McpAuthHandler) -> Result<McpOAuthToken> {
+        let endpoint = self.config.authorization_endpoint.as_deref().with_context(|| {
+            format!(
+                "MCP server {} has neither an authorization endpoint nor a device authorization endpoint",
+                self.server_name
+            )
+        })?;
+        let listener =
+            TcpListener::bind(("127.0.0.1", self.config.redirect_port.unwrap_or(0))).await?;
+        let redirect_uri = format!(
+            "http://127.0.0.1:{}/callback",
+            listener.local_addr()?.port()
+        );
+        let state = Uuid::new_v4().simple().to_string();
+        let verifier = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let challenge = URL_SAFE_NO_PAD.encode(sha256(verifier.as_bytes()));
+
+        let mut url = Url::parse(endpoint)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &redirect_uri)
+            .append_pair("state", &state)
+            .append_pair("code_challenge", &challenge)
+            .append_pair("code_challenge_method", "S256");
+        if !self.config.scopes.is_empty() {
+            url.query_pairs_mut()
+                .append_pair("scope", &self.config.scopes.join(" "));
+        }
+        handler
+            .open_authorization_url(&self.server_name, url.as_str())
+            .await?;
+
+        let code = accept_redirect(&listener, &state).await?;
+        self.request_token(&[
+            ("grant_type", "authorization_code"),
+            ("code", code.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("code_verifier", verifier.as_str()),
+        ])
+        .await
+        .map_err(|e| match e {
+            TokenError::Pending { .. } => anyhow::anyhow!("Unexpected authorization_pending"),
+            TokenError::Failed(e) => e,
+        })
+    }
+
+    async fn request_token(&self, params: &[(&str, &str)]) -> Result<McpOAuthToken, TokenError> {
+        let mut form = params.to_vec();
+        form.push(("client_id", &self.config.client_id));
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret));
+        }
+        let response = self
+            .client
+            .post(&self.config.token_endpoint)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| TokenError::Failed(e.into()))?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| TokenError::Failed(e.into()))?;
+        if let Ok(error) = serde_json::from_str::<TokenErrorResponse>(&body) {
+            return Err(match error.error.as_str() {
+                "authorization_pending" => TokenError::Pending { slow_down: false },
+                "slow_down" => TokenError::Pending { slow_down: true },
+                _ => TokenError::Failed(anyhow::anyhow!(
+                    "Token request failed: {}{}",
+                    error.error,
+                    error
+                        .error_description
+                        .map(|description| format!(" ({})", description))
+                        .unwrap_or_default()
+                )),
+            });
+        }
+        if !status.is_success() {
+            return Err(TokenError::Failed(anyhow::anyhow!(
+                "Token request failed ({}): {}",
+                status,
+                body
+            )));
+        }
+        let token: TokenResponse =
+            serde_json::from_str(&body).map_err(|e| TokenError::Failed(e.into()))?;
+        Ok(McpOAuthToken {
+            access_token: token.access_token,
+            refresh_token: token.refresh_token,
+            expires_at: token
+                .expires_in
+                .map(|secs| chrono::Utc::now().timestamp() + secs),
+        })
+    }
+}
+
+/// ループバックのリダイレクトを待ち、認可コードを取り出す
+async fn accept_redirect(listener: &TcpListener, state: &str) -> Result<String> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let mut request_line = String::new();
+        BufReader::new(&mut stream)
+            .read_line(&mut request_line)
+            .await?;
+        let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let url = Url::parse(&format!("http://127.0.0.1{}", target))?;
+        // ブラウザが続けて送る favicon などの要求は無視する
+        if url.path() != "/callback" {
+            let _ = stream
+                .write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await;
+            continue;
+        }
+        let params: BTreeMap<_, _> = url.query_pairs().into_owned().collect();
+        let result = if let Some(error) = params.get("error") {
+            Err(anyhow::anyhow!("Authorization was denied: {}", error))
+        } else if params.get("state").map(String::as_str) != Some(state) {
+            Err(anyhow::anyhow!(
+                "Authorization response has an invalid state"
+            ))
+        } else {
+            params
+                .get("code")
+                .cloned()
+                .context("Authorization response has no code")
+        };
+        let message = match &result {
+            Ok(_) => "Authorization complete. You can close this window.".to_string(),
+            Err(e) => format!("Authorization failed: {}", e),
+        };
+        let _ = stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    message.len(),
+                    message
+                )
+                .as_bytes(),
+            )
+            .await;
+        return result;
+    }
+}
+
+/// PKCEの `code_challenge` に使うSHA-256（依存を増やさないため最小限の実装を持つ）
+fn sha256(data: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::{Read, Write};
+    use std::sync::Mutex as StdMutex;
+
+    /// 受け取った順に `responses` を返すトークンサーバー（受け取った要求の本文を返す）
+    fn serve(responses: Vec<&'static str>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for body in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 8192];
+                // ヘッダーと本文が分かれて届いても Content-Length まで読む
+                loop {
+                    let len = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..len]);
+                    let text = String::from_utf8_lossy(&request).to_lowercase();
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length = text
+                            .lines()
+                            .find_map(|line| line.strip_prefix("content-length: "))
+                            .map_or(0, |n| n.trim().parse().unwrap());
+                        if request.len() >= end + 4 + length {
+                            break;
+                        }
+                    }
+                }
+                requests.push(String::from_utf8_lossy(&request).into_owned());
+                let status = if body.contains("\"error\"") {
+                    "400 Bad Request"
+                } else {
+                    "200 OK"
+                };
+                stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            status,
+                            body.len(),
+                            body
+                        )
+                        .as_bytes(),
+                    )
+                    .unwrap();
+            }
+            requests
+        });
+        (base_url, server)
+    }
+
+    #[derive(Debug, Default)]
+    struct RecordingHandler {
+        device_codes: StdMutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl McpAuthHandler for RecordingHandler {
+        async fn show_device_code(
+            &self,
+            _server_name: &str,
+            verification_uri: &str,
+            user_code: &str,
+        ) -> Result<()> {
+            self.device_codes
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", verification_uri, user_code));
+            Ok(())
+        }
+
+        async fn open_authorization_url(&self, _server_name: &str, url: &str) -> Result<()> {
+            // ブラウザの代わりにリダイレクト先へ認可コードを送る
+            let url = Url::parse(url).unwrap();
+            let params: BTreeMap<_, _> = url.query_pairs().into_owned().collect();
+            let callback = format!(
+                "{}?code=auth-code&state={}",
+                params["redirect_uri"], params["state"]
+            );
+            tokio::spawn(async move { reqwest::get(callback).await.unwrap() });
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_device_flow_and_refresh() {
+        let verifier = "dBjftJeZ4CVP-mJ0Q6Ug2cgNCHfsFSQ8a5H5O2ZjNPMrrrRo9oW";
+        assert_eq!(
+            URL_SAFE_NO_PAD.encode(sha256(verifier.as_bytes())),
+            "RXEJ9A1-M4FywLJ5hekyyE6UoHJWr-R2j8T-Ei_OXa8"
+        );
+        assert_eq!(
+            URL_SAFE_NO_PAD.encode(sha256(verifier.repeat(3).as_bytes())),
+            "7OK1d72s3-Rn0GoYsiscDNb7tY8t2_hIoT9kzYudgTs"
+        );
+
+        let (base_url, server) = serve(vec![
+            r#"{"device_code":"dev","user_code":"ABCD-EFGH","verification_uri":"https://example.com/device","expires_in":60,"interval":0}"#,
+            r#"{"error":"authorization_pending"}"#,
+            r#"{"access_token":"access-1","refresh_token":"refresh-1","expires_in":3600}"#,
+            r#"{"access_token":"access-2","expires_in":3600}"#,
+        ]);
+        let config = McpOAuthConfig {
+            client_id: "cline".to_string(),
+            client_secret: None,
+            authorization_endpoint: None,
+            token_endpoint: format!("{}/token", base_url),
+            device_authorization_endpoint: Some(format!("{}/device", base_url)),
+            scopes: vec!["tools".to_string()],
+            redirect_port: None,
+        };
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = FileCredentialStore::new(temp_dir.path().join("mcp_credentials.json"));
+        let handler = RecordingHandler::default();
+
+        let oauth = McpOAuth::new("remote", config.clone());
+        assert!(oauth.access_token(&store, None).await.is_err());
+        assert_eq!(
+            oauth.access_token(&store, Some(&handler)).await.unwrap(),
+            "access-1"
+        );
+        assert_eq!(
+            *handler.device_codes.lock().unwrap(),
+            vec!["https://example.com/device ABCD-EFGH".to_string()]
+        );
+        // 保存したトークンを別のインスタンスでも使い、期限切れならリフレッシュトークンで更新する
+        let oauth = McpOAuth::new("remote", config);
+        assert_eq!(oauth.access_token(&store, None).await.unwrap(), "access-1");
+        oauth.invalidate().await;
+        assert_eq!(oauth.access_token(&store, None).await.unwrap(), "access-2");
+        let saved = store.load("remote").await.unwrap().unwrap();
+        assert_eq!(saved.refresh_token.as_deref(), Some("refresh-1"));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].ends_with("client_id=cline&scope=tools"));
+        assert!(requests[2].contains("device_code=dev"));
+        assert!(requests[3].contains("grant_type=refresh_token&refresh_token=refresh-1"));
+
+        oauth.sign_out(&store).await.unwrap();
+        assert_eq!(store.load("remote").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_loopback_flow() {
+        let (base_url, server) = serve(vec![r#"{"access_token":"access","token_type":"bearer"}"#]);
+        let oauth = McpOAuth::new(
+            "remote",
+            McpOAuthConfig {
+                client_id: "cline".to_string(),
+                client_secret: Some("secret".to_string()),
+                authorization_endpoint: Some(format!("{}/authorize", base_url)),
+                token_endpoint: format!("{}/token", base_url),
+                device_authorization_endpoint: None,
+                scopes: Vec::new(),
+                redirect_port: None,
+            },
+        );
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = FileCredentialStore::new(temp_dir.path().join("mcp_credentials.json"));
+        assert_eq!(
+            oauth
+                .access_token(&store, Some(&RecordingHandler::default()))
+                .await
+                .unwrap(),
+            "access"
+        );
+        let request = server.join().unwrap().remove(0);
+        assert!(request.contains("grant_type=authorization_code&code=auth-code"));
+        assert!(request.contains("code_verifier="));
+        assert!(request.ends_with("client_id=cline&client_secret=secret"));
+    }
+}
@@ -1,5 +1,21 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::Path;
+
+use super::oauth::McpOAuthConfig;
+use crate::cline::ToolApproval;
+
+lazy_static! {
+    /// 設定の値の中で展開する変数（`${env:VAR}`・`${workspaceFolder}`）
+    static ref CONFIG_VARIABLE: Regex =
+        Regex::new(r"\$\{(?:env:(?P<env>[A-Za-z_][A-Za-z0-9_]*)|(?P<workspace>workspaceFolder))\}")
+            .unwrap();
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpServer {
@@ -13,7 +29,7 @@ pub struct McpServer {
     pub resource_templates: Option<Vec<McpResourceTemplate>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum McpServerStatus {
     #[serde(rename = "connecting")]
     Connecting,
@@ -57,16 +73,299 @@ pub struct McpResourceResponse {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct StdioConfig {
+    /// 起動するコマンド（`url` で接続するリモートのサーバーでは省略する）
+    #[serde(default)]
     pub command: String,
     pub args: Option<Vec<String>>,
     pub env: Option<HashMap<String, String>>,
+    /// 承認なしで呼び出せるツール
     pub always_allow: Option<Vec<String>>,
     pub disabled: Option<bool>,
+    /// ツールの呼び出しの制限時間（秒、同時実行数の上限による待ち時間を含む）
     pub timeout: Option<u32>,
+    /// 同時に実行するツールの呼び出しの上限（超えた呼び出しは順に待つ）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<u32>,
+    /// HTTPで接続するリモートのサーバーのURL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// リモートのサーバーのOAuthの設定
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth: Option<McpOAuthConfig>,
+}
+
+impl StdioConfig {
+    /// 起動に使う、`command`・`args`・`env`・`url` の `${env:VAR}` と `${workspaceFolder}` を展開した設定
+    ///
+    /// 設定ファイルにはマシン固有のパスや秘密情報を書かずにコミットできる。
+    /// 未定義の環境変数はエラーにする。
+    pub fn interpolate(&self, workspace_path: &Path) -> Result<Self> {
+        self.interpolate_with(workspace_path, |name| std::env::var(name).ok())
+    }
+
+    fn interpolate_with(
+        &self,
+        workspace_path: &Path,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let workspace = workspace_path.to_string_lossy();
+        let expand = |value: &str| -> Result<String> {
+            let mut missing = None;
+            let expanded = CONFIG_VARIABLE.replace_all(value, |captures: &Captures| match captures
+                .name("env")
+            {
+                Some(name) => env(name.as_str()).unwrap_or_else(|| {
+                    missing.get_or_insert_with(|| name.as_str().to_string());
+                    String::new()
+                }),
+                None => workspace.to_string(),
+            });
+            match missing {
+                Some(name) => anyhow::bail!("Environment variable {} is not set", name),
+                None => Ok(expanded.into_owned()),
+            }
+        };
+        Ok(Self {
+            command: expand(&self.command)?,
+            url: self.url.as_deref().map(expand).transpose()?,
+            args: self
+                .args
+                .as_ref()
+                .map(|args| args.iter().map(|arg| expand(arg)).collect())
+                .transpose()?,
+            env: self
+                .env
+                .as_ref()
+                .map(|env| {
+                    env.iter()
+                        .map(|(key, value)| expand(value).map(|value| (key.clone(), value)))
+                        .collect()
+                })
+                .transpose()?,
+            ..self.clone()
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct McpSettings {
     pub mcp_servers: HashMap<String, StdioConfig>,
 }
+
+/// `alwaysAllow` にないMCPのツールの呼び出しと、サーバーからのモデルの呼び出しを確認する
+/// （ヘッドレス実行時のホストが実装する）
+#[async_trait]
+pub trait McpToolApprover: Debug + Send + Sync {
+    async fn approve(
+        &self,
+        server_name: &str,
+        tool_name: &str,
+        arguments: Option<&serde_json::Value>,
+    ) -> bool;
+
+    /// 拒否の理由をモデルに伝える場合に実装する（既定では `approve` の結果を使う）
+    async fn review(
+        &self,
+        server_name: &str,
+        tool_name: &str,
+        arguments: Option<&serde_json::Value>,
+    ) -> ToolApproval {
+        self.approve(server_name, tool_name, arguments).await.into()
+    }
+
+    /// `sampling/createMessage` を許可するか（既定では拒否する）
+    async fn approve_sampling(&self, _server_name: &str, _request: &McpSamplingRequest) -> bool {
+        false
+    }
+}
+
+/// サーバーの稼働状況（ホストのダッシュボードの表示に使う）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpServerHealth {
+    pub name: String,
+    pub status: McpServerStatus,
+    pub disabled: bool,
+    /// 接続してからの時間（ミリ秒、無効・切断中は `None`）
+    pub uptime_ms: Option<u64>,
+    pub last_error: Option<String>,
+    /// 最後の `ping` の往復時間（ミリ秒）
+    pub ping_rtt_ms: Option<u64>,
+    pub tool_calls: u32,
+    pub tool_call_failures: u32,
+}
+
+/// サーバーの稼働状況の変化を受け取る（ヘッドレス実行時のホストが実装する）
+pub trait McpStatusListener: Debug + Send + Sync {
+    fn on_server_status(&self, health: &McpServerHealth);
+}
+
+/// ワークスペースのフォルダが変わったことをサーバーに知らせる通知
+pub const ROOTS_LIST_CHANGED_NOTIFICATION: &str = "notifications/roots/list_changed";
+
+/// サーバーに公開するワークスペースのフォルダ（`roots/list` の結果の要素）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct McpRoot {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl McpRoot {
+    pub fn from_path(path: &Path, name: Option<&str>) -> Self {
+        let uri = reqwest::Url::from_file_path(path)
+            .map(String::from)
+            .unwrap_or_else(|_| format!("file://{}", path.display()));
+        Self {
+            uri,
+            name: name.map(str::to_string),
+        }
+    }
+}
+
+/// MCPサーバーからのモデルの呼び出し（`sampling/createMessage` のパラメータ）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpSamplingRequest {
+    pub messages: Vec<McpSamplingMessage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    pub max_tokens: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct McpSamplingMessage {
+    pub role: String,
+    pub content: McpSamplingContent,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum McpSamplingContent {
+    Text {
+        text: String,
+    },
+    Image {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+}
+
+/// `sampling/createMessage` の結果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpSamplingResult {
+    pub role: String,
+    pub content: McpSamplingContent,
+    pub model: String,
+    pub stop_reason: Option<String>,
+}
+
+impl McpSamplingRequest {
+    /// プロバイダに送る1つのプロンプトにする（画像には対応しない）
+    pub fn to_prompt(&self) -> Result<String> {
+        let mut turns = Vec::new();
+        for message in &self.messages {
+            let McpSamplingContent::Text { text } = &message.content else {
+                anyhow::bail!("Image content is not supported in sampling requests");
+            };
+            turns.push((message.role.as_str(), text.as_str()));
+        }
+        let conversation = match turns.as_slice() {
+            [] => anyhow::bail!("The sampling request has no messages"),
+            [("user", text)] => text.to_string(),
+            turns => turns
+                .iter()
+                .map(|(role, text)| {
+                    let role = if *role == "assistant" {
+                        "Assistant"
+                    } else {
+                        "User"
+                    };
+                    format!("{}: {}", role, text)
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        };
+        Ok(match &self.system_prompt {
+            Some(system_prompt) if !system_prompt.trim().is_empty() => {
+                format!("{}\n\n{}", system_prompt.trim(), conversation)
+            }
+            _ => conversation,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_interpolate_stdio_config() {
+        let config: StdioConfig = serde_json::from_str(
+            r#"{
+                "command": "${workspaceFolder}/bin/server",
+                "args": ["--root", "${workspaceFolder}", "--token=${env:API_TOKEN}", "${other}"],
+                "env": {"API_KEY": "${env:API_TOKEN}"}
+            }"#,
+        )
+        .unwrap();
+        let env = |name: &str| (name == "API_TOKEN").then(|| "secret".to_string());
+        let resolved = config.interpolate_with(Path::new("/work"), env).unwrap();
+        assert_eq!(resolved.command, "/work/bin/server");
+        assert_eq!(
+            resolved.args.unwrap(),
+            vec!["--root", "/work", "--token=secret", "${other}"]
+        );
+        assert_eq!(resolved.env.unwrap()["API_KEY"], "secret");
+
+        let error = config
+            .interpolate_with(Path::new("/work"), |_| None)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Environment variable API_TOKEN is not set"
+        );
+    }
+
+    #[test]
+    fn test_sampling_request_to_prompt() {
+        let request: McpSamplingRequest = serde_json::from_value(serde_json::json!({
+            "messages": [
+                { "role": "user", "content": { "type": "text", "text": "Summarize the log" } }
+            ],
+            "systemPrompt": "You are a log analyzer.",
+            "maxTokens": 100
+        }))
+        .unwrap();
+        assert_eq!(
+            request.to_prompt().unwrap(),
+            "You are a log analyzer.\n\nSummarize the log"
+        );
+
+        let request = McpSamplingRequest {
+            messages: vec![
+                McpSamplingMessage {
+                    role: "user".to_string(),
+                    content: McpSamplingContent::Text {
+                        text: "Hi".to_string(),
+                    },
+                },
+                McpSamplingMessage {
+                    role: "assistant".to_string(),
+                    content: McpSamplingContent::Text {
+                        text: "Hello".to_string(),
+                    },
+                },
+            ],
+            system_prompt: None,
+            max_tokens: 10,
+        };
+        assert_eq!(request.to_prompt().unwrap(), "User: Hi\n\nAssistant: Hello");
+    }
+}
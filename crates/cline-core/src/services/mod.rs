@@ -1,7 +1,24 @@
 pub mod anthropic;
+pub mod api;
+pub mod audit;
 pub mod browser;
+pub mod cost;
 pub mod diagnostics;
 pub mod diff;
+pub mod editor;
+pub mod enhance;
+pub mod environment;
+pub mod fetch;
+pub mod fs;
 pub mod git;
+pub mod index;
+pub mod locale;
+pub mod logging;
 pub mod mcp;
+pub mod models;
+pub mod notification;
+pub mod scm;
+pub mod scratch;
+pub mod secrets;
+pub mod telemetry;
 pub mod terminal;
@@ -0,0 +1,154 @@
+use crate::services::cost::{get_model_pricing, ModelPricing};
+
+/// これより小さいコンテキストウィンドウのモデルには短いツールの説明を送る
+pub const SMALL_CONTEXT_WINDOW: u32 = 64_000;
+
+/// モデルの性能と料金
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelCapabilities {
+    /// コンテキストウィンドウのトークン数
+    pub context_window: u32,
+    pub supports_images: bool,
+    /// ネイティブのツール呼び出しを使える
+    pub supports_native_tools: bool,
+    pub supports_prompt_caching: bool,
+    /// 拡張思考（`thinking`）を使える（使えないモデルに送るとAPIがエラーを返す）
+    pub supports_thinking: bool,
+    /// 料金（不明な場合は `None`）
+    pub pricing: Option<ModelPricing>,
+}
+
+impl Default for ModelCapabilities {
+    /// 登録されていないモデル（ローカルモデルなど）は控えめに見積もる
+    fn default() -> Self {
+        Self {
+            context_window: 32_000,
+            supports_images: false,
+            supports_native_tools: false,
+            supports_prompt_caching: false,
+            supports_thinking: false,
+            pricing: None,
+        }
+    }
+}
+
+impl ModelCapabilities {
+    /// Claudeのモデル（料金は `cost` の料金表から取得する）
+    fn claude(model_prefix: &str, supports_native_tools: bool, supports_thinking: bool) -> Self {
+        Self {
+            context_window: 200_000,
+            supports_images: true,
+            supports_native_tools,
+            supports_prompt_caching: true,
+            supports_thinking,
+            pricing: get_model_pricing(model_prefix),
+        }
+    }
+
+    /// プロンプトを短くするべき小さいコンテキストのモデル
+    pub fn is_small_context(&self) -> bool {
+        self.context_window < SMALL_CONTEXT_WINDOW
+    }
+}
+
+/// モデルIDの接頭辞ごとの性能の一覧
+///
+/// ツールの定義とコンテキストの管理はここで調べた性能に合わせる。
+/// 後から登録した接頭辞から順に調べ、最初に一致したものを使う。
+#[derive(Debug, Clone)]
+pub struct ModelRegistry {
+    entries: Vec<(String, ModelCapabilities)>,
+}
+
+impl ModelRegistry {
+    /// モデルを登録しない空の一覧
+    pub fn empty() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// 接頭辞に一致するモデルの性能を登録する（既存の登録より優先する）
+    pub fn register(&mut self, model_prefix: &str, capabilities: ModelCapabilities) {
+        self.entries.retain(|(prefix, _)| prefix != model_prefix);
+        self.entries
+            .insert(0, (model_prefix.to_string(), capabilities));
+    }
+
+    /// モデルの性能（登録されていなければ既定値）
+    pub fn get(&self, model_id: &str) -> ModelCapabilities {
+        self.entries
+            .iter()
+            .find(|(prefix, _)| model_id.starts_with(prefix.as_str()))
+            .map(|(_, capabilities)| *capabilities)
+            .unwrap_or_default()
+    }
+}
+
+impl Default for ModelRegistry {
+    /// 組み込みのClaudeのモデル
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        // 拡張思考は3.7 Sonnet以降
+        for (prefix, supports_thinking) in [
+            ("claude-3-opus", false),
+            ("claude-3-sonnet", false),
+            ("claude-3-haiku", false),
+            ("claude-3-5-sonnet", false),
+            ("claude-3-7-sonnet", true),
+            ("claude-sonnet-4", true),
+            ("claude-opus-4", true),
+        ] {
+            registry.register(
+                prefix,
+                ModelCapabilities::claude(prefix, true, supports_thinking),
+            );
+        }
+        // 3.5 Haikuは画像の入力に対応していない
+        registry.register(
+            "claude-3-5-haiku",
+            ModelCapabilities {
+                supports_images: false,
+                ..ModelCapabilities::claude("claude-3-5-haiku", true, false)
+            },
+        );
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_model_registry() {
+        let mut registry = ModelRegistry::default();
+        let sonnet = registry.get("claude-3-5-sonnet-20241022");
+        assert_eq!(sonnet.context_window, 200_000);
+        assert!(sonnet.supports_images && sonnet.supports_prompt_caching);
+        assert_eq!(
+            sonnet.pricing,
+            get_model_pricing("claude-3-5-sonnet-20241022")
+        );
+        assert!(!registry.get("claude-3-5-haiku-20241022").supports_images);
+        assert!(!registry.get("claude-3-sonnet-20240229").supports_thinking);
+        assert!(registry.get("claude-3-7-sonnet-20250219").supports_thinking);
+
+        let local = registry.get("qwen2.5-coder:7b");
+        assert_eq!(local, ModelCapabilities::default());
+        assert!(local.is_small_context());
+
+        registry.register(
+            "qwen2.5-coder",
+            ModelCapabilities {
+                context_window: 128_000,
+                supports_native_tools: true,
+                ..Default::default()
+            },
+        );
+        let local = registry.get("qwen2.5-coder:7b");
+        assert!(!local.is_small_context());
+        assert!(local.supports_native_tools);
+    }
+}
@@ -0,0 +1,192 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::json;
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Webhookの送信のタイムアウト
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 通知の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    /// 承認や回答など、ユーザーの対応が必要
+    AttentionRequired,
+    TaskCompleted,
+}
+
+/// タスクの通知
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub task_id: String,
+    pub title: String,
+    pub message: String,
+}
+
+/// 通知の送信先（ヘッドレス実行時に `sound_enabled` の代わりに使う）
+#[async_trait]
+pub trait NotificationSink: Debug + Send + Sync {
+    async fn notify(&self, notification: &Notification) -> Result<()>;
+}
+
+/// 何もしない送信先
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopNotificationSink;
+
+#[async_trait]
+impl NotificationSink for NoopNotificationSink {
+    async fn notify(&self, _notification: &Notification) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// デスクトップ通知（Linuxは `notify-send`、macOSは `osascript` を使う）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DesktopNotificationSink;
+
+#[async_trait]
+impl NotificationSink for DesktopNotificationSink {
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let mut command = if cfg!(target_os = "macos") {
+            let mut command = tokio::process::Command::new("osascript");
+            command.arg("-e").arg(format!(
+                "display notification {} with title {}",
+                applescript_string(&notification.message),
+                applescript_string(&notification.title)
+            ));
+            command
+        } else if cfg!(unix) {
+            let mut command = tokio::process::Command::new("notify-send");
+            command.arg(&notification.title).arg(&notification.message);
+            command
+        } else {
+            anyhow::bail!("Desktop notifications are not supported on this platform");
+        };
+        let status = command.kill_on_drop(true).status().await?;
+        if !status.success() {
+            anyhow::bail!("Desktop notification failed ({})", status);
+        }
+        Ok(())
+    }
+}
+
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Webhookの本文の形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WebhookFormat {
+    /// Slackの Incoming Webhook（`text`）
+    #[default]
+    Slack,
+    /// DiscordのWebhook（`content`）
+    Discord,
+    /// 通知の各項目をそのままJSONで送る
+    Json,
+}
+
+/// WebhookにPOSTする送信先
+#[derive(Debug, Clone)]
+pub struct WebhookNotificationSink {
+    url: String,
+    format: WebhookFormat,
+    client: reqwest::Client,
+}
+
+impl WebhookNotificationSink {
+    pub fn new(url: impl Into<String>, format: WebhookFormat) -> Result<Self> {
+        Ok(Self {
+            url: url.into(),
+            format,
+            client: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()?,
+        })
+    }
+
+    fn body(&self, notification: &Notification) -> serde_json::Value {
+        let text = format!("*{}*\n{}", notification.title, notification.message);
+        match self.format {
+            WebhookFormat::Slack => json!({ "text": text }),
+            WebhookFormat::Discord => json!({ "content": text }),
+            WebhookFormat::Json => json!({
+                "kind": match notification.kind {
+                    NotificationKind::AttentionRequired => "attention_required",
+                    NotificationKind::TaskCompleted => "task_completed",
+                },
+                "taskId": notification.task_id,
+                "title": notification.title,
+                "message": notification.message,
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationSink for WebhookNotificationSink {
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&self.body(notification))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Webhook returned {}: {}",
+                response.status(),
+                response.text().await.unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn notification() -> Notification {
+        Notification {
+            kind: NotificationKind::TaskCompleted,
+            task_id: "task-1".to_string(),
+            title: "Task completed".to_string(),
+            message: "Fixed the \"bug\"".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_webhook_posts_formatted_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .unwrap();
+            String::from_utf8_lossy(&request[..len]).into_owned()
+        });
+
+        let sink = WebhookNotificationSink::new(url, WebhookFormat::Discord).unwrap();
+        sink.notify(&notification()).await.unwrap();
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /hook HTTP/1.1"));
+        let body = request.split("\r\n\r\n").nth(1).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(body).unwrap(),
+            json!({ "content": "*Task completed*\nFixed the \"bug\"" })
+        );
+
+        let sink = WebhookNotificationSink::new("http://unused", WebhookFormat::Json).unwrap();
+        assert_eq!(sink.body(&notification())["kind"], "task_completed");
+        assert_eq!(applescript_string("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert!(NoopNotificationSink.notify(&notification()).await.is_ok());
+    }
+}
@@ -0,0 +1,268 @@
+use anyhow::Result;
+use git2::{Cred, PushOptions, RemoteCallbacks, Repository};
+use reqwest::{Client, Request};
+use serde::Deserialize;
+use serde_json::json;
+use std::path::Path;
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+const GITLAB_API_URL: &str = "https://gitlab.com/api/v4";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScmProvider {
+    GitHub,
+    GitLab,
+}
+
+/// プルリクエスト（マージリクエスト）を作成するリポジトリの設定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScmConfig {
+    pub provider: ScmProvider,
+    /// GitHubは `owner/repo`、GitLabはプロジェクトのパス（`group/project`）
+    pub repository: String,
+    pub token: String,
+    /// プッシュするリモート名
+    pub remote: String,
+    /// マージ先のブランチ
+    pub base_branch: String,
+    /// GitHub Enterprise・セルフホストのGitLab用（未指定の場合は公開サービスのAPI）
+    pub api_url: Option<String>,
+    /// `attempt_completion` の後に自動でプッシュして作成する
+    pub open_on_completion: bool,
+}
+
+impl ScmConfig {
+    pub fn new(provider: ScmProvider, repository: &str, token: &str) -> Self {
+        Self {
+            provider,
+            repository: repository.to_string(),
+            token: token.to_string(),
+            remote: "origin".to_string(),
+            base_branch: "main".to_string(),
+            api_url: None,
+            open_on_completion: false,
+        }
+    }
+
+    fn api_url(&self) -> &str {
+        self.api_url
+            .as_deref()
+            .unwrap_or(match self.provider {
+                ScmProvider::GitHub => GITHUB_API_URL,
+                ScmProvider::GitLab => GITLAB_API_URL,
+            })
+            .trim_end_matches('/')
+    }
+}
+
+/// 作成したプルリクエスト（マージリクエスト）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PullRequest {
+    /// GitHubの番号またはGitLabのIID
+    pub number: u64,
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubPullRequest {
+    number: u64,
+    html_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitLabMergeRequest {
+    iid: u64,
+    web_url: String,
+}
+
+/// タスクのブランチをプッシュし、プルリクエストを作成する
+#[derive(Debug, Clone)]
+pub struct ScmClient {
+    config: ScmConfig,
+    client: Client,
+}
+
+impl ScmClient {
+    pub fn new(config: ScmConfig) -> Self {
+        Self {
+            config,
+            client: Client::new(),
+        }
+    }
+
+    pub fn config(&self) -> &ScmConfig {
+        &self.config
+    }
+
+    /// トークンで認証してブランチをリモートにプッシュする
+    pub async fn push_branch(&self, workspace_path: &Path, branch: &str) -> Result<()> {
+        let workspace_path = workspace_path.to_path_buf();
+        let branch = branch.to_string();
+        let config = self.config.clone();
+        tokio::task::spawn_blocking(move || {
+            let repo = Repository::open(&workspace_path)
+                .map_err(|_| anyhow::anyhow!("Not a git repository"))?;
+            let mut remote = repo.find_remote(&config.remote)?;
+
+            let username = match config.provider {
+                ScmProvider::GitHub => "x-access-token",
+                ScmProvider::GitLab => "oauth2",
+            };
+            let mut callbacks = RemoteCallbacks::new();
+            callbacks.credentials(|_, _, _| Cred::userpass_plaintext(username, &config.token));
+            let mut options = PushOptions::new();
+            options.remote_callbacks(callbacks);
+
+            let refspec = format!("refs/heads/{0}:refs/heads/{0}", branch);
+            remote
+                .push(&[refspec], Some(&mut options))
+                .map_err(|e| anyhow::anyhow!("Failed to push {}: {}", branch, e.message()))
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Git task failed: {}", e))?
+    }
+
+    pub async fn create_pull_request(
+        &self,
+        branch: &str,
+        title: &str,
+        description: &str,
+    ) -> Result<PullRequest> {
+        let request = self.pull_request_request(branch, title, description)?;
+        let response = self.client.execute(request).await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            anyhow::bail!("Failed to create pull request ({}): {}", status, body);
+        }
+        parse_pull_request(self.config.provider, &body)
+    }
+
+    fn pull_request_request(
+        &self,
+        branch: &str,
+        title: &str,
+        description: &str,
+    ) -> Result<Request> {
+        let config = &self.config;
+        let request = match config.provider {
+            ScmProvider::GitHub => self
+                .client
+                .post(format!(
+                    "{}/repos/{}/pulls",
+                    config.api_url(),
+                    config.repository
+                ))
+                .bearer_auth(&config.token)
+                .header("Accept", "application/vnd.github+json")
+                .header("User-Agent", "headless-cline")
+                .json(&json!({
+                    "title": title,
+                    "head": branch,
+                    "base": config.base_branch,
+                    "body": description,
+                })),
+            ScmProvider::GitLab => self
+                .client
+                .post(format!(
+                    "{}/projects/{}/merge_requests",
+                    config.api_url(),
+                    config.repository.replace('/', "%2F")
+                ))
+                .header("PRIVATE-TOKEN", &config.token)
+                .json(&json!({
+                    "title": title,
+                    "source_branch": branch,
+                    "target_branch": config.base_branch,
+                    "description": description,
+                })),
+        };
+        Ok(request.build()?)
+    }
+}
+
+fn parse_pull_request(provider: ScmProvider, body: &str) -> Result<PullRequest> {
+    Ok(match provider {
+        ScmProvider::GitHub => {
+            let pull: GitHubPullRequest = serde_json::from_str(body)?;
+            PullRequest {
+                number: pull.number,
+                url: pull.html_url,
+            }
+        }
+        ScmProvider::GitLab => {
+            let merge: GitLabMergeRequest = serde_json::from_str(body)?;
+            PullRequest {
+                number: merge.iid,
+                url: merge.web_url,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::Value;
+
+    fn request_json(request: &Request) -> Value {
+        serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_pull_request_requests() {
+        let github = ScmClient::new(ScmConfig::new(ScmProvider::GitHub, "owner/repo", "ghp_x"));
+        let request = github
+            .pull_request_request("cline/task-1", "Fix bug", "Summary")
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://api.github.com/repos/owner/repo/pulls"
+        );
+        assert_eq!(request.headers()["authorization"], "Bearer ghp_x");
+        assert_eq!(
+            request_json(&request),
+            json!({ "title": "Fix bug", "head": "cline/task-1", "base": "main", "body": "Summary" })
+        );
+
+        let gitlab = ScmClient::new(ScmConfig {
+            api_url: Some("https://git.example.com/api/v4/".to_string()),
+            base_branch: "develop".to_string(),
+            ..ScmConfig::new(ScmProvider::GitLab, "group/project", "glpat")
+        });
+        let request = gitlab
+            .pull_request_request("cline/task-1", "Fix bug", "Summary")
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://git.example.com/api/v4/projects/group%2Fproject/merge_requests"
+        );
+        assert_eq!(request.headers()["private-token"], "glpat");
+        assert_eq!(request_json(&request)["target_branch"], "develop");
+    }
+
+    #[test]
+    fn test_parse_pull_request() {
+        assert_eq!(
+            parse_pull_request(
+                ScmProvider::GitHub,
+                r#"{"number": 12, "html_url": "https://github.com/owner/repo/pull/12"}"#
+            )
+            .unwrap(),
+            PullRequest {
+                number: 12,
+                url: "https://github.com/owner/repo/pull/12".to_string()
+            }
+        );
+        assert_eq!(
+            parse_pull_request(
+                ScmProvider::GitLab,
+                r#"{"iid": 3, "web_url": "https://gitlab.com/group/project/-/merge_requests/3"}"#
+            )
+            .unwrap()
+            .number,
+            3
+        );
+    }
+}
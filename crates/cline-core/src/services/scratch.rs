@@ -0,0 +1,74 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// ファイルツールでスクラッチディレクトリを参照するルートの名前（`scratch:path`）
+pub const SCRATCH_ROOT_NAME: &str = "scratch";
+
+/// タスクごとのスクラッチディレクトリの設定
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScratchOptions {
+    /// `execute_command` をワークスペースではなくスクラッチディレクトリで実行する
+    pub command_cwd: bool,
+}
+
+/// 使い捨てのスクリプトなどを書き込む、タスクの間だけ使うディレクトリ
+///
+/// 最初に使うときに作成し、タスクの完了時に削除する。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScratchDir {
+    path: PathBuf,
+    options: ScratchOptions,
+}
+
+impl ScratchDir {
+    pub fn new(path: PathBuf, options: ScratchOptions) -> Self {
+        Self { path, options }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn options(&self) -> ScratchOptions {
+        self.options
+    }
+
+    /// ディレクトリがなければ作成する
+    pub async fn ensure(&self) -> Result<&Path> {
+        tokio::fs::create_dir_all(&self.path).await?;
+        Ok(&self.path)
+    }
+
+    /// ディレクトリを中身ごと削除する（削除した場合は `true`）
+    pub async fn remove(&self) -> Result<bool> {
+        match tokio::fs::remove_dir_all(&self.path).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scratch_dir_lifecycle() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let scratch = ScratchDir::new(
+            temp_dir.path().join("scratch/task-1"),
+            ScratchOptions::default(),
+        );
+        assert!(!scratch.path().exists());
+        assert!(!scratch.remove().await.unwrap());
+
+        let path = scratch.ensure().await.unwrap().to_path_buf();
+        std::fs::write(path.join("probe.py"), "print(1)").unwrap();
+        scratch.ensure().await.unwrap();
+        assert!(path.join("probe.py").exists());
+
+        assert!(scratch.remove().await.unwrap());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,398 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::env;
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// テレメトリを有効にする環境変数（OpenTelemetryの標準の変数名）
+pub const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+const DEFAULT_SERVICE_NAME: &str = "headless-cline";
+
+/// スパンの属性値
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    String(String),
+    Int(i64),
+    Double(f64),
+    Bool(bool),
+}
+
+impl From<&str> for AttributeValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<i64> for AttributeValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<u32> for AttributeValue {
+    fn from(value: u32) -> Self {
+        Self::Int(value as i64)
+    }
+}
+
+impl From<usize> for AttributeValue {
+    fn from(value: usize) -> Self {
+        Self::Int(value as i64)
+    }
+}
+
+impl From<f64> for AttributeValue {
+    fn from(value: f64) -> Self {
+        Self::Double(value)
+    }
+}
+
+impl From<bool> for AttributeValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl AttributeValue {
+    /// OTLP/JSONの `AnyValue` 形式（64bit整数は文字列で表す）
+    fn to_otlp(&self) -> Value {
+        match self {
+            Self::String(value) => json!({ "stringValue": value }),
+            Self::Int(value) => json!({ "intValue": value.to_string() }),
+            Self::Double(value) => json!({ "doubleValue": value }),
+            Self::Bool(value) => json!({ "boolValue": value }),
+        }
+    }
+}
+
+/// 終了したスパン
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanRecord {
+    pub name: String,
+    pub trace_id: String,
+    pub span_id: String,
+    pub start_time: SystemTime,
+    pub end_time: SystemTime,
+    pub attributes: Vec<(String, AttributeValue)>,
+    /// 失敗した場合のエラー
+    pub error: Option<String>,
+}
+
+impl SpanRecord {
+    pub fn attribute(&self, key: &str) -> Option<&AttributeValue> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    fn to_otlp(&self) -> Value {
+        let nanos = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+                .to_string()
+        };
+        // ステータスコード: 1 = OK, 2 = ERROR
+        let status = match &self.error {
+            Some(error) => json!({ "code": 2, "message": error }),
+            None => json!({ "code": 1 }),
+        };
+        json!({
+            "traceId": self.trace_id,
+            "spanId": self.span_id,
+            "name": self.name,
+            "kind": 1,
+            "startTimeUnixNano": nanos(self.start_time),
+            "endTimeUnixNano": nanos(self.end_time),
+            "attributes": otlp_attributes(&self.attributes),
+            "status": status,
+        })
+    }
+}
+
+fn otlp_attributes(attributes: &[(String, AttributeValue)]) -> Vec<Value> {
+    attributes
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "value": value.to_otlp() }))
+        .collect()
+}
+
+#[derive(Debug)]
+struct TelemetryInner {
+    service_name: String,
+    /// `None` の場合は記録のみ行い、送信しない
+    endpoint: Option<String>,
+    trace_id: String,
+    client: Client,
+    spans: Mutex<Vec<SpanRecord>>,
+}
+
+/// APIリクエストやツール実行のスパンを記録し、OTLPで送信する（オプトイン）
+///
+/// 既定では無効で、スパンを作成しても何も記録しない。
+#[derive(Debug, Clone, Default)]
+pub struct Telemetry {
+    inner: Option<Arc<TelemetryInner>>,
+}
+
+impl Telemetry {
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// `OTEL_EXPORTER_OTLP_ENDPOINT` が設定されている場合のみ有効にする
+    pub fn from_env() -> Self {
+        match env::var(OTLP_ENDPOINT_ENV) {
+            Ok(endpoint) if !endpoint.is_empty() => Self::otlp(endpoint, DEFAULT_SERVICE_NAME),
+            _ => Self::disabled(),
+        }
+    }
+
+    /// OTLP/HTTP（JSON）のエンドポイントに送信する
+    pub fn otlp(endpoint: impl Into<String>, service_name: impl Into<String>) -> Self {
+        Self::with_endpoint(Some(endpoint.into()), service_name.into())
+    }
+
+    /// 送信せずに記録のみ行う（デバッグ・テスト用）
+    pub fn in_memory() -> Self {
+        Self::with_endpoint(None, DEFAULT_SERVICE_NAME.to_string())
+    }
+
+    fn with_endpoint(endpoint: Option<String>, service_name: String) -> Self {
+        Self {
+            inner: Some(Arc::new(TelemetryInner {
+                service_name,
+                endpoint,
+                trace_id: Uuid::new_v4().simple().to_string(),
+                client: Client::new(),
+                spans: Mutex::new(Vec::new()),
+            })),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    pub fn start_span(&self, name: &str) -> Span {
+        Span {
+            telemetry: self.clone(),
+            name: name.to_string(),
+            start_time: SystemTime::now(),
+            attributes: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// まだ送信していない終了済みのスパン
+    pub fn finished_spans(&self) -> Vec<SpanRecord> {
+        self.inner
+            .as_ref()
+            .map(|inner| inner.spans.lock().unwrap().clone())
+            .unwrap_or_default()
+    }
+
+    /// 記録したスパンをOTLPのJSON形式に変換する
+    pub fn to_otlp_json(&self, spans: &[SpanRecord]) -> Value {
+        let service_name = self
+            .inner
+            .as_ref()
+            .map(|inner| inner.service_name.as_str())
+            .unwrap_or(DEFAULT_SERVICE_NAME);
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": otlp_attributes(&[(
+                        "service.name".to_string(),
+                        service_name.into(),
+                    )]),
+                },
+                "scopeSpans": [{
+                    "scope": { "name": "cline-core" },
+                    "spans": spans.iter().map(SpanRecord::to_otlp).collect::<Vec<_>>(),
+                }],
+            }],
+        })
+    }
+
+    /// 記録したスパンを送信する（送信に失敗したスパンは破棄する）
+    pub async fn flush(&self) -> Result<()> {
+        let Some(inner) = &self.inner else {
+            return Ok(());
+        };
+        let Some(endpoint) = &inner.endpoint else {
+            return Ok(());
+        };
+        let spans = std::mem::take(&mut *inner.spans.lock().unwrap());
+        if spans.is_empty() {
+            return Ok(());
+        }
+
+        let url = if endpoint.ends_with("/v1/traces") {
+            endpoint.clone()
+        } else {
+            format!("{}/v1/traces", endpoint.trim_end_matches('/'))
+        };
+        let response = inner
+            .client
+            .post(url)
+            .json(&self.to_otlp_json(&spans))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to export telemetry: {}", response.status());
+        }
+        Ok(())
+    }
+
+    fn record(&self, span: SpanRecord) {
+        if let Some(inner) = &self.inner {
+            inner.spans.lock().unwrap().push(span);
+        }
+    }
+}
+
+/// 計測中のスパン（終了せずに破棄した場合は失敗として記録する）
+#[derive(Debug)]
+pub struct Span {
+    telemetry: Telemetry,
+    name: String,
+    start_time: SystemTime,
+    attributes: Vec<(String, AttributeValue)>,
+    finished: bool,
+}
+
+impl Span {
+    pub fn set_attribute(&mut self, key: &str, value: impl Into<AttributeValue>) {
+        if self.telemetry.is_enabled() {
+            self.attributes.push((key.to_string(), value.into()));
+        }
+    }
+
+    /// 成功として終了する
+    pub fn end(mut self) {
+        self.finish(None);
+    }
+
+    /// 失敗として終了する
+    pub fn fail(mut self, error: impl Display) {
+        self.finish(Some(error.to_string()));
+    }
+
+    fn finish(&mut self, error: Option<String>) {
+        self.finished = true;
+        let Some(inner) = &self.telemetry.inner else {
+            return;
+        };
+        let end_time = SystemTime::now();
+        let mut attributes = std::mem::take(&mut self.attributes);
+        let duration_ms = end_time
+            .duration_since(self.start_time)
+            .unwrap_or_default()
+            .as_millis() as i64;
+        attributes.push(("duration_ms".to_string(), duration_ms.into()));
+        attributes.push(("success".to_string(), error.is_none().into()));
+
+        let span_id = Uuid::new_v4().simple().to_string()[..16].to_string();
+        let record = SpanRecord {
+            name: std::mem::take(&mut self.name),
+            trace_id: inner.trace_id.clone(),
+            span_id,
+            start_time: self.start_time,
+            end_time,
+            attributes,
+            error,
+        };
+        self.telemetry.record(record);
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.finish(Some("Span dropped before completion".to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_spans_are_recorded_only_when_enabled() {
+        let disabled = Telemetry::disabled();
+        disabled.start_span("api_request").end();
+        assert!(disabled.finished_spans().is_empty());
+
+        let telemetry = Telemetry::in_memory();
+        let mut span = telemetry.start_span("api_request");
+        span.set_attribute("tokens_in", 120u32);
+        span.end();
+        telemetry
+            .start_span("tool.execute_command")
+            .fail("exit code 1");
+        drop(telemetry.start_span("diff.apply"));
+
+        let spans = telemetry.finished_spans();
+        assert_eq!(spans.len(), 3);
+        assert_eq!(
+            spans[0].attribute("tokens_in"),
+            Some(&AttributeValue::Int(120))
+        );
+        assert_eq!(
+            spans[0].attribute("success"),
+            Some(&AttributeValue::Bool(true))
+        );
+        assert_eq!(spans[1].error.as_deref(), Some("exit code 1"));
+        assert_eq!(
+            spans[2].attribute("success"),
+            Some(&AttributeValue::Bool(false))
+        );
+        assert_eq!(spans[0].trace_id, spans[1].trace_id);
+        assert_eq!(spans[0].span_id.len(), 16);
+    }
+
+    #[test]
+    fn test_otlp_json() {
+        let span = SpanRecord {
+            name: "api_request".to_string(),
+            trace_id: "0af7651916cd43dd8448eb211c80319c".to_string(),
+            span_id: "b7ad6b7169203331".to_string(),
+            start_time: UNIX_EPOCH + std::time::Duration::from_millis(1),
+            end_time: UNIX_EPOCH + std::time::Duration::from_millis(3),
+            attributes: vec![("model".to_string(), "claude".into())],
+            error: Some("timeout".to_string()),
+        };
+        let json = Telemetry::in_memory().to_otlp_json(&[span]);
+        assert_eq!(
+            json["resourceSpans"][0]["resource"]["attributes"][0],
+            json!({ "key": "service.name", "value": { "stringValue": "headless-cline" } })
+        );
+        assert_eq!(
+            json["resourceSpans"][0]["scopeSpans"][0]["spans"][0],
+            json!({
+                "traceId": "0af7651916cd43dd8448eb211c80319c",
+                "spanId": "b7ad6b7169203331",
+                "name": "api_request",
+                "kind": 1,
+                "startTimeUnixNano": "1000000",
+                "endTimeUnixNano": "3000000",
+                "attributes": [{ "key": "model", "value": { "stringValue": "claude" } }],
+                "status": { "code": 2, "message": "timeout" },
+            })
+        );
+    }
+}
@@ -2,6 +2,14 @@ use std::fmt::Debug;
 
 use anyhow::Result;
 
+mod output;
+mod shell_integration;
+
+pub use output::{process_terminal_output, strip_ansi, DEFAULT_TERMINAL_OUTPUT_LINE_LIMIT};
+pub use shell_integration::{
+    parse_command_output, wrap_command_with_exit_code, CommandOutput, EXIT_CODE_MARKER,
+};
+
 pub trait TerminalManager: Debug + 'static {
     fn dispose_all(&mut self);
     fn get_or_create_terminal(&mut self, workspace_path: String) -> Result<TerminalInfo>;
@@ -0,0 +1,96 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// モデルに送るターミナル出力の既定の最大行数
+pub const DEFAULT_TERMINAL_OUTPUT_LINE_LIMIT: usize = 500;
+
+lazy_static! {
+    /// CSI・OSCなどのANSIエスケープシーケンス
+    static ref ANSI_ESCAPE_REGEX: Regex = Regex::new(
+        r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b[@-Z\\-_]"
+    )
+    .unwrap();
+    static ref DIGITS_REGEX: Regex = Regex::new(r"\d+").unwrap();
+}
+
+/// ANSIエスケープシーケンスを取り除く
+pub fn strip_ansi(text: &str) -> String {
+    ANSI_ESCAPE_REGEX.replace_all(text, "").to_string()
+}
+
+/// ターミナル出力をモデルに送る形に整える
+///
+/// ANSIエスケープを取り除き、`\r` で上書きされた行やプログレスバーの連続した行は最後の状態のみを残す。
+/// `line_limit` 行を超える場合は先頭と末尾を残して間を省略する（0の場合は省略しない）。
+pub fn process_terminal_output(output: &str, line_limit: usize) -> String {
+    let output = strip_ansi(output);
+
+    let mut lines: Vec<&str> = Vec::new();
+    let mut last_progress_key: Option<String> = None;
+    for line in output.split('\n') {
+        // `\r` で上書きされた行は最後に表示された内容のみを残す
+        let line = line
+            .trim_end_matches('\r')
+            .rsplit('\r')
+            .find(|segment| !segment.trim().is_empty())
+            .unwrap_or("");
+
+        // 数字だけが異なる行が続く場合はプログレス表示とみなして最後の行に置き換える
+        let progress_key = DIGITS_REGEX
+            .is_match(line)
+            .then(|| DIGITS_REGEX.replace_all(line, "0").to_string());
+        if progress_key.is_some() && progress_key == last_progress_key {
+            lines.pop();
+        }
+        lines.push(line);
+        last_progress_key = progress_key;
+    }
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+
+    if line_limit == 0 || lines.len() <= line_limit {
+        return lines.join("\n");
+    }
+    let head = line_limit / 2;
+    let tail = line_limit - head;
+    format!(
+        "{}\n\n... ({} lines omitted) ...\n\n{}",
+        lines[..head].join("\n"),
+        lines.len() - line_limit,
+        lines[lines.len() - tail..].join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_process_terminal_output() {
+        assert_eq!(
+            process_terminal_output(
+                "\x1b[1m\x1b[32m   Compiling\x1b[0m cline v0.1.0\r\n\x1b]0;title\x07ok\n",
+                0
+            ),
+            "   Compiling cline v0.1.0\nok"
+        );
+
+        let progress = "Downloading\n[#   ] 10%\r[##  ] 50%\r[####] 100%\n\
+                        Fetched 1/3\nFetched 2/3\nFetched 3/3\nDone";
+        assert_eq!(
+            process_terminal_output(progress, 0),
+            "Downloading\n[####] 100%\nFetched 3/3\nDone"
+        );
+
+        let long = (1..=10)
+            .map(|i| format!("line {}{}", i, "x".repeat(i)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            process_terminal_output(&long, 4),
+            "line 1x\nline 2xx\n\n... (6 lines omitted) ...\n\nline 9xxxxxxxxx\nline 10xxxxxxxxxx"
+        );
+    }
+}
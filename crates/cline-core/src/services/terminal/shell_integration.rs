@@ -0,0 +1,94 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::strip_ansi;
+
+/// シェル統合がない場合に終了コードを出力させるためのマーカー
+pub const EXIT_CODE_MARKER: &str = "__CLINE_EXIT_CODE__:";
+
+lazy_static! {
+    /// シェル統合のマーカー（VSCodeのOSC 633、FinalTerm互換のOSC 133）
+    static ref SHELL_INTEGRATION_REGEX: Regex =
+        Regex::new(r"\x1b\](?:633|133);([A-Z])(?:;([^\x07\x1b]*))?(?:\x07|\x1b\\)").unwrap();
+}
+
+/// コマンド出力を解析した結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// マーカーとANSIエスケープを取り除いた出力
+    pub output: String,
+    pub exit_code: Option<i32>,
+    /// シェル統合のマーカーが含まれていたか
+    pub has_shell_integration: bool,
+}
+
+/// シェル統合がない場合に、終了コードを出力するようにコマンドを包む
+pub fn wrap_command_with_exit_code(command: &str) -> String {
+    format!("{}\necho \"{}$?\"", command, EXIT_CODE_MARKER)
+}
+
+/// ターミナルの生の出力から終了コードを取り出す
+///
+/// OSC 633/133の `D;<code>` を優先し、ない場合は `wrap_command_with_exit_code` のマーカー行を探す。
+pub fn parse_command_output(raw_output: &str) -> CommandOutput {
+    let mut has_shell_integration = false;
+    let mut exit_code = None;
+    for captures in SHELL_INTEGRATION_REGEX.captures_iter(raw_output) {
+        has_shell_integration = true;
+        if &captures[1] == "D" {
+            exit_code = captures.get(2).and_then(|code| code.as_str().parse().ok());
+        }
+    }
+
+    let output = strip_ansi(raw_output);
+    let mut lines = Vec::new();
+    for line in output.lines() {
+        match line.trim().strip_prefix(EXIT_CODE_MARKER) {
+            Some(code) => {
+                if exit_code.is_none() {
+                    exit_code = code.trim().parse().ok();
+                }
+            }
+            None => lines.push(line),
+        }
+    }
+
+    CommandOutput {
+        output: lines.join("\n"),
+        exit_code,
+        has_shell_integration,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_command_output() {
+        assert_eq!(
+            parse_command_output("\x1b]633;C\x07hello\n\x1b]633;D;2\x07"),
+            CommandOutput {
+                output: "hello".to_string(),
+                exit_code: Some(2),
+                has_shell_integration: true,
+            }
+        );
+        assert_eq!(
+            parse_command_output("\x1b]133;C\x1b\\ok\n\x1b]133;D\x1b\\").exit_code,
+            None
+        );
+
+        let wrapped = wrap_command_with_exit_code("cargo build");
+        assert_eq!(wrapped, "cargo build\necho \"__CLINE_EXIT_CODE__:$?\"");
+        assert_eq!(
+            parse_command_output("error[E0425]\n__CLINE_EXIT_CODE__:101\n"),
+            CommandOutput {
+                output: "error[E0425]".to_string(),
+                exit_code: Some(101),
+                has_shell_integration: false,
+            }
+        );
+    }
+}
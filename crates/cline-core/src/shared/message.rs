@@ -1,193 +1,8 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct LanguageModelChatSelector {
-    pub vendor: Option<String>,
-    pub family: Option<String>,
-    pub version: Option<String>,
-    pub id: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ExtensionMessage {
-    #[serde(rename = "type")]
-    pub message_type: ExtensionMessageType,
-    pub text: Option<String>,
-    pub action: Option<ExtensionAction>,
-    pub invoke: Option<ExtensionInvoke>,
-    pub state: Option<ExtensionState>,
-    pub images: Option<Vec<String>>,
-    pub ollama_models: Option<Vec<String>>,
-    pub lm_studio_models: Option<Vec<String>>,
-    pub vs_code_lm_models: Option<Vec<LanguageModelChatSelector>>,
-    pub file_paths: Option<Vec<String>>,
-    pub opened_tabs: Option<Vec<OpenedTab>>,
-    pub partial_message: Option<ClineMessage>,
-    pub glama_models: Option<HashMap<String, ModelInfo>>,
-    pub open_router_models: Option<HashMap<String, ModelInfo>>,
-    pub open_ai_models: Option<Vec<String>>,
-    pub mcp_servers: Option<Vec<McpServer>>,
-    pub commits: Option<Vec<GitCommit>>,
-    pub list_api_config: Option<Vec<ApiConfigMeta>>,
-    pub mode: Option<Mode>,
-    pub custom_mode: Option<ModeConfig>,
-    pub slug: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub enum ExtensionMessageType {
-    Action,
-    State,
-    SelectedImages,
-    OllamaModels,
-    LmStudioModels,
-    Theme,
-    WorkspaceUpdated,
-    Invoke,
-    PartialMessage,
-    GlamaModels,
-    OpenRouterModels,
-    OpenAiModels,
-    McpServers,
-    EnhancedPrompt,
-    CommitSearchResults,
-    ListApiConfig,
-    VsCodeLmModels,
-    VsCodeLmApiAvailable,
-    RequestVsCodeLmModels,
-    UpdatePrompt,
-    SystemPrompt,
-    AutoApprovalEnabled,
-    UpdateCustomMode,
-    DeleteCustomMode,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub enum ExtensionAction {
-    ChatButtonClicked,
-    McpButtonClicked,
-    SettingsButtonClicked,
-    HistoryButtonClicked,
-    PromptsButtonClicked,
-    DidBecomeVisible,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub enum ExtensionInvoke {
-    SendMessage,
-    PrimaryButtonClick,
-    SecondaryButtonClick,
-    SetChatBoxMessage,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct OpenedTab {
-    pub label: String,
-    pub is_active: bool,
-    pub path: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ModelInfo {
-    // ModelInfoの具体的なフィールドは必要に応じて追加
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct McpServer {
-    pub name: String,
-    pub config: String,
-    #[serde(rename = "status")]
-    pub server_status: McpServerStatus,
-    pub error: Option<String>,
-    pub tools: Option<Vec<McpTool>>,
-    pub resources: Option<Vec<McpResource>>,
-    pub resource_templates: Option<Vec<McpResourceTemplate>>,
-    pub disabled: Option<bool>,
-    pub timeout: Option<i32>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum McpServerStatus {
-    Connected,
-    Connecting,
-    Disconnected,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct McpTool {
-    pub name: String,
-    pub description: Option<String>,
-    pub input_schema: Option<serde_json::Value>,
-    pub always_allow: Option<bool>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct McpResource {
-    pub uri: String,
-    pub name: String,
-    pub mime_type: Option<String>,
-    pub description: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct McpResourceTemplate {
-    pub uri_template: String,
-    pub name: String,
-    pub description: Option<String>,
-    pub mime_type: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct McpResourceResponse {
-    pub _meta: Option<HashMap<String, serde_json::Value>>,
-    pub contents: Vec<McpResourceContent>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct McpResourceContent {
-    pub uri: String,
-    pub mime_type: Option<String>,
-    pub text: Option<String>,
-    pub blob: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct McpToolCallResponse {
-    pub _meta: Option<HashMap<String, serde_json::Value>>,
-    pub content: Vec<McpToolCallResponseContent>,
-    pub is_error: Option<bool>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "type")]
-#[serde(rename_all = "snake_case")]
-pub enum McpToolCallResponseContent {
-    Text { text: String },
-    Image { data: String, mime_type: String },
-    Resource { resource: McpResourceContent },
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct GitCommit {
-    // GitCommitの具体的なフィールドは必要に応じて追加
-}
+use crate::shared::modes::DEFAULT_MODE_SLUG;
+use crate::tools::ToolSettings;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -203,11 +18,8 @@ pub struct ApiProvider {
     // ApiProviderの具体的なフィールドは必要に応じて追加
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
-#[serde(rename_all = "camelCase")]
-pub enum Mode {
-    // Modeの具体的な列挙値は必要に応じて追加
-}
+/// モードのスラッグ（`code`・`architect` など。カスタムモードも含むため文字列で持つ）
+pub type Mode = String;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -215,27 +27,120 @@ pub struct ModeConfig {
     // ModeConfigの具体的なフィールドは必要に応じて追加
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(tag = "type")]
+/// UIに表示するメッセージ（`ui_messages.json` に保存される）
+///
+/// 拡張機能と同じく `"type": "say"` / `"type": "ask"` の形式でシリアライズする。
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum ClineMessage {
     Ask {
         ts: i64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         text: Option<String>,
         ask: ClineAsk,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         partial: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         reasoning: Option<String>,
+        /// 回答の候補（`ask_followup_question` の `<suggest>` など）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        suggestions: Option<Vec<String>>,
     },
     Say {
         ts: i64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         text: Option<String>,
         say: ClineSay,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         images: Option<Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         partial: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         reasoning: Option<String>,
     },
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+impl ClineMessage {
+    pub fn ts(&self) -> i64 {
+        match self {
+            Self::Ask { ts, .. } | Self::Say { ts, .. } => *ts,
+        }
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Self::Ask { text, .. } | Self::Say { text, .. } => text.as_deref(),
+        }
+    }
+
+    pub fn is_partial(&self) -> bool {
+        match self {
+            Self::Ask { partial, .. } | Self::Say { partial, .. } => partial.unwrap_or(false),
+        }
+    }
+
+    /// 質問の回答の候補（ホストが選択肢として表示する）
+    pub fn suggestions(&self) -> &[String] {
+        match self {
+            Self::Ask {
+                suggestions: Some(suggestions),
+                ..
+            } => suggestions,
+            _ => &[],
+        }
+    }
+
+    /// 返答を回答の文字列にする（候補の番号（1から）の場合はその候補）
+    pub fn resolve_reply(&self, reply: &str) -> String {
+        let suggestions = self.suggestions();
+        match reply.trim().parse::<usize>() {
+            Ok(number) if (1..=suggestions.len()).contains(&number) => {
+                suggestions[number - 1].clone()
+            }
+            _ => reply.to_string(),
+        }
+    }
+}
+
+/// `parse_cline_messages` で以前の形式の `ClineMessage` の一覧も読み込む（`#[serde(deserialize_with)]` 用）
+fn deserialize_cline_messages<'de, D>(deserializer: D) -> Result<Vec<ClineMessage>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    parse_cline_messages(value)
+        .map(|(messages, _)| messages)
+        .map_err(serde::de::Error::custom)
+}
+
+/// 保存された `ClineMessage` の一覧を読み込む
+///
+/// 以前の形式（`"type": "Say"` / `"type": "Ask"`）も読み込み、変換したかどうかを返す。
+pub fn parse_cline_messages(
+    value: serde_json::Value,
+) -> serde_json::Result<(Vec<ClineMessage>, bool)> {
+    let mut values: Vec<serde_json::Value> = serde_json::from_value(value)?;
+    let mut migrated = false;
+    for value in &mut values {
+        let Some(tag) = value.get_mut("type") else {
+            continue;
+        };
+        let new_tag = match tag.as_str() {
+            Some("Say") => "say",
+            Some("Ask") => "ask",
+            _ => continue,
+        };
+        *tag = new_tag.into();
+        migrated = true;
+    }
+    let messages = values
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<serde_json::Result<_>>()?;
+    Ok((messages, migrated))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum ClineAsk {
     Followup,
@@ -247,11 +152,12 @@ pub enum ClineAsk {
     ResumeTask,
     ResumeCompletedTask,
     MistakeLimitReached,
+    BudgetExceeded,
     BrowserActionLaunch,
     UseMcpServer,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum ClineSay {
     Task,
@@ -275,44 +181,42 @@ pub enum ClineSay {
     McpServerResponse,
     NewTaskStarted,
     NewTask,
-}
-
-#[allow(dead_code)]
-pub const BROWSER_ACTIONS: [&str; 6] = [
-    "launch",
-    "click",
-    "type",
-    "scroll_down",
-    "scroll_up",
-    "close",
-];
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum BrowserAction {
-    Launch,
-    Click,
-    Type,
-    ScrollDown,
-    ScrollUp,
-    Close,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+    /// 自動コミットを作成した（テキストはコミットのハッシュ）
+    CheckpointSaved,
+    /// プルリクエストを作成した（テキストはURL）
+    PullRequestCreated,
+    /// 実行中のタスクにユーザーメッセージを追加した（次のリクエストで送る）
+    QueuedMessage,
+    /// Todoリストを更新した（テキストは項目のJSON配列）
+    TodoListUpdated,
+}
+
+/// ツール使用の表示内容（`ClineSay::Tool`・`ClineAsk::Tool` のテキストにJSONで格納する）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
-pub struct ClineSayBrowserAction {
-    pub action: BrowserAction,
-    pub coordinate: Option<String>,
-    pub text: Option<String>,
+pub struct ClineSayTool {
+    pub tool: ClineSayToolType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+    /// 書き込む内容（ストリーミング中は途中までの内容）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// ドライランのため書き込まなかった場合は `true`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dry_run: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
-pub struct BrowserActionResult {
-    pub screenshot: Option<String>,
-    pub logs: Option<String>,
-    pub current_url: Option<String>,
-    pub current_mouse_position: Option<String>,
+pub enum ClineSayToolType {
+    EditedExistingFile,
+    NewFileCreated,
+    OutsideWorkspace,
+    ReadFile,
+    /// `fetch` のリクエスト（`path` にURL、`content` に本文を入れる）
+    Fetch,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -331,9 +235,11 @@ pub struct ClineAskUseMcpServer {
 pub enum ClineAskUseMcpServerType {
     UseMcpTool,
     AccessMcpResource,
+    /// サーバーからのモデルの呼び出し（`sampling/createMessage`）
+    Sampling,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ClineApiReqInfo {
     pub request: Option<String>,
@@ -353,10 +259,14 @@ pub enum ClineApiReqCancelReason {
     UserCancelled,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+/// 拡張機能の状態
+///
+/// 以前のリリースで保存した状態も読み込めるよう、ない項目は既定値にし、名前を変えた項目は以前の名前も受け付ける。
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct ExtensionState {
     pub version: String,
+    #[serde(deserialize_with = "deserialize_cline_messages")]
     pub cline_messages: Vec<ClineMessage>,
     pub task_history: Vec<HistoryItem>,
     pub should_show_announcement: bool,
@@ -364,6 +274,7 @@ pub struct ExtensionState {
     pub current_api_config_name: Option<String>,
     pub list_api_config_meta: Option<Vec<ApiConfigMeta>>,
     pub custom_instructions: Option<String>,
+    #[serde(alias = "customPrompts")]
     pub custom_mode_prompts: Option<CustomModePrompts>,
     pub custom_support_prompts: Option<CustomSupportPrompts>,
     pub always_allow_read_only: Option<bool>,
@@ -377,17 +288,21 @@ pub struct ExtensionState {
     pub rate_limit_seconds: i32,
     pub uri_scheme: Option<String>,
     pub allowed_commands: Option<Vec<String>>,
+    /// ヘッドレス実行では使わない（代わりに `NotificationSink` で通知する）
     pub sound_enabled: Option<bool>,
     pub sound_volume: Option<f32>,
     pub diff_enabled: Option<bool>,
     pub browser_viewport_size: Option<String>,
     pub screenshot_quality: Option<i32>,
+    /// 前回と同じ画面でもスクリーンショットを送る
+    pub force_full_screenshots: Option<bool>,
     pub fuzzy_match_threshold: Option<f32>,
     pub preferred_language: String,
     pub write_delay_ms: i32,
     pub terminal_output_line_limit: Option<i32>,
     pub mcp_enabled: bool,
     pub enable_mcp_server_creation: bool,
+    #[serde(default = "default_mode")]
     pub mode: Mode,
     pub mode_api_configs: Option<HashMap<Mode, String>>,
     pub enhancement_api_config_id: Option<String>,
@@ -395,6 +310,12 @@ pub struct ExtensionState {
     pub auto_approval_enabled: Option<bool>,
     pub custom_modes: Vec<ModeConfig>,
     pub tool_requirements: Option<HashMap<String, bool>>,
+    /// 無効にするツールと置き換えるツールの説明
+    pub tool_settings: ToolSettings,
+}
+
+fn default_mode() -> Mode {
+    DEFAULT_MODE_SLUG.to_string()
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -425,5 +346,147 @@ pub struct CustomModePrompts {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CustomSupportPrompts {
-    // CustomSupportPromptsの具体的なフィールドは必要に応じて追加
+    /// プロンプトの改善に使うサポートプロンプト（`${userInput}` を入力に置き換える）
+    pub enhance: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_cline_message_round_trip() {
+        let messages = vec![
+            ClineMessage::Say {
+                ts: 1,
+                text: Some("Fix the bug".to_string()),
+                say: ClineSay::Task,
+                images: Some(vec!["data:image/png;base64,AAAA".to_string()]),
+                partial: None,
+                reasoning: None,
+            },
+            ClineMessage::Ask {
+                ts: 2,
+                text: Some("ls".to_string()),
+                ask: ClineAsk::Command,
+                partial: Some(true),
+                reasoning: None,
+                suggestions: None,
+            },
+        ];
+        let json = serde_json::to_string(&messages).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"type":"say","ts":1,"text":"Fix the bug","say":"task","images":["data:image/png;base64,AAAA"]},{"type":"ask","ts":2,"text":"ls","ask":"command","partial":true}]"#
+        );
+
+        let (restored, migrated) =
+            parse_cline_messages(serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(restored, messages);
+        assert!(!migrated);
+        assert_eq!(restored[1].ts(), 2);
+        assert_eq!(restored[1].text(), Some("ls"));
+        assert!(restored[1].is_partial());
+    }
+
+    #[test]
+    fn test_parse_legacy_cline_messages() {
+        let legacy = r#"[
+            {"type":"Say","ts":1,"text":"hello","say":"text","images":null,"partial":null,"reasoning":null},
+            {"type":"Ask","ts":2,"text":null,"ask":"resume_task","partial":null,"reasoning":null}
+        ]"#;
+        let (messages, migrated) =
+            parse_cline_messages(serde_json::from_str(legacy).unwrap()).unwrap();
+        assert!(migrated);
+        assert_eq!(
+            messages,
+            vec![
+                ClineMessage::Say {
+                    ts: 1,
+                    text: Some("hello".to_string()),
+                    say: ClineSay::Text,
+                    images: None,
+                    partial: None,
+                    reasoning: None,
+                },
+                ClineMessage::Ask {
+                    ts: 2,
+                    text: None,
+                    ask: ClineAsk::ResumeTask,
+                    partial: None,
+                    reasoning: None,
+                    suggestions: None,
+                },
+            ]
+        );
+    }
+
+    /// 以前のリリースの形式で保存したファイル（`tests/fixtures/schema`）
+    fn fixture(name: &str) -> &'static str {
+        match name {
+            "ui_messages_v0" => include_str!("../../tests/fixtures/schema/ui_messages_v0.json"),
+            "ui_messages_v1" => include_str!("../../tests/fixtures/schema/ui_messages_v1.json"),
+            "extension_state_legacy" => {
+                include_str!("../../tests/fixtures/schema/extension_state_legacy.json")
+            }
+            _ => panic!("unknown fixture {}", name),
+        }
+    }
+
+    #[test]
+    fn test_load_previous_ui_messages() {
+        use crate::storage::{parse_versioned_json, to_versioned_json, SCHEMA_VERSION};
+
+        let mut loaded = Vec::new();
+        for (name, version, expect_migrated) in
+            [("ui_messages_v0", 0, true), ("ui_messages_v1", 1, false)]
+        {
+            let (stored_version, data) = parse_versioned_json(fixture(name)).unwrap();
+            assert_eq!(stored_version, version, "{}", name);
+            let (messages, migrated) = parse_cline_messages(data).unwrap();
+            assert_eq!(migrated, expect_migrated, "{}", name);
+            loaded.push(messages);
+        }
+        // どのバージョンから読み込んでも同じメッセージになる
+        assert_eq!(loaded[0], loaded[1]);
+        let messages = &loaded[0];
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[3].text(), Some("Which license?"));
+        assert!(messages[3].suggestions().is_empty());
+        let info: ClineApiReqInfo = serde_json::from_str(messages[1].text().unwrap()).unwrap();
+        assert_eq!(info.tokens_in, Some(120));
+        let tool: ClineSayTool = serde_json::from_str(messages[2].text().unwrap()).unwrap();
+        assert_eq!(tool.tool, ClineSayToolType::NewFileCreated);
+        assert_eq!(tool.content, None);
+
+        // 現在の形式で保存し直すと最新のバージョンとして同じ内容を読み込む
+        let (version, data) = parse_versioned_json(&to_versioned_json(messages).unwrap()).unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+        assert_eq!(
+            parse_cline_messages(data).unwrap(),
+            (messages.clone(), false)
+        );
+    }
+
+    #[test]
+    fn test_load_previous_extension_state() {
+        let state: ExtensionState =
+            serde_json::from_str(fixture("extension_state_legacy")).unwrap();
+        assert_eq!(state.version, "3.1.0");
+        assert_eq!(state.cline_messages.len(), 1);
+        assert_eq!(state.cline_messages[0].text(), Some("Add a README"));
+        assert_eq!(state.task_history[0].cache_writes, None);
+        // 以前の `customPrompts` を読み込み、ない項目は既定値にする
+        assert!(state.custom_mode_prompts.is_some());
+        assert_eq!(state.mode, DEFAULT_MODE_SLUG);
+        assert!(state.experiments.is_empty());
+        assert_eq!(state.always_allow_read_only, Some(true));
+
+        // 保存し直しても同じ内容になる
+        let value = serde_json::to_value(&state).unwrap();
+        assert!(value.get("customModePrompts").is_some());
+        let restored: ExtensionState = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&restored).unwrap(), value);
+    }
 }
@@ -0,0 +1,278 @@
+use std::fmt::Debug;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use super::message::{ClineAsk, ClineMessage, ClineSay};
+
+/// メッセージの種類（部分メッセージの対応付けに使う）
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageKind {
+    Ask(ClineAsk),
+    Say(ClineSay),
+}
+
+impl ClineMessage {
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            Self::Ask { ask, .. } => MessageKind::Ask(ask.clone()),
+            Self::Say { say, .. } => MessageKind::Say(say.clone()),
+        }
+    }
+
+    fn set_partial(&mut self, new_partial: Option<bool>) {
+        match self {
+            Self::Ask { partial, .. } | Self::Say { partial, .. } => *partial = new_partial,
+        }
+    }
+
+    fn set_ts(&mut self, new_ts: i64) {
+        match self {
+            Self::Ask { ts, .. } | Self::Say { ts, .. } => *ts = new_ts,
+        }
+    }
+}
+
+/// メッセージの一覧の変更
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageEvent {
+    Added {
+        index: usize,
+        message: ClineMessage,
+    },
+    Updated {
+        index: usize,
+        message: ClineMessage,
+    },
+    Removed {
+        index: usize,
+    },
+    /// 一覧全体を置き換えた（空にした場合も含む）
+    Replaced,
+}
+
+/// メッセージの変更を受け取る（ヘッドレス実行時のホストが進捗の表示に使う）
+pub trait MessageListener: Debug + Send + Sync {
+    fn on_message_event(&self, event: &MessageEvent);
+}
+
+/// タスクのメッセージ（`ClineMessage`）の一覧
+///
+/// 部分メッセージは `ts` と種類で対応付けるため、別のメッセージが間に追加されても
+/// ストリーミング中のメッセージを正しく更新できる。変更はリスナーに通知する。
+#[derive(Debug, Clone, Default)]
+pub struct MessageStore {
+    messages: Vec<ClineMessage>,
+    listeners: Vec<Arc<dyn MessageListener>>,
+}
+
+impl Deref for MessageStore {
+    type Target = [ClineMessage];
+
+    fn deref(&self) -> &[ClineMessage] {
+        self.messages()
+    }
+}
+
+impl MessageStore {
+    pub fn messages(&self) -> &[ClineMessage] {
+        &self.messages
+    }
+
+    pub fn add_listener(&mut self, listener: Arc<dyn MessageListener>) {
+        self.listeners.push(listener);
+    }
+
+    pub fn push(&mut self, message: ClineMessage) -> usize {
+        let index = self.messages.len();
+        self.messages.push(message.clone());
+        self.emit(MessageEvent::Added { index, message });
+        index
+    }
+
+    /// 一覧全体を置き換える
+    pub fn replace(&mut self, messages: Vec<ClineMessage>) {
+        self.messages = messages;
+        self.emit(MessageEvent::Replaced);
+    }
+
+    pub fn clear(&mut self) {
+        self.replace(Vec::new());
+    }
+
+    /// `index` のメッセージを変更する（範囲外の場合は何もしない）
+    pub fn update(&mut self, index: usize, update: impl FnOnce(&mut ClineMessage)) {
+        let Some(message) = self.messages.get_mut(index) else {
+            return;
+        };
+        update(message);
+        let message = message.clone();
+        self.emit(MessageEvent::Updated { index, message });
+    }
+
+    fn emit(&self, event: MessageEvent) {
+        for listener in &self.listeners {
+            listener.on_message_event(&event);
+        }
+    }
+
+    fn position(&self, predicate: impl Fn(&ClineMessage) -> bool) -> Option<usize> {
+        self.messages.iter().rposition(predicate)
+    }
+
+    fn partial_position(&self, ts: i64, kind: &MessageKind) -> Option<usize> {
+        self.position(|m| m.is_partial() && m.ts() == ts && m.kind() == *kind)
+    }
+
+    /// 指定した種類で最後に追加した、完了していない部分メッセージの `ts`
+    pub fn open_partial(&self, kind: &MessageKind) -> Option<i64> {
+        self.position(|m| m.is_partial() && m.kind() == *kind)
+            .map(|index| self.messages[index].ts())
+    }
+
+    /// `ts` の部分メッセージを `message` の内容で更新する（ない場合は部分メッセージとして追加する）
+    pub fn upsert_partial(&mut self, ts: i64, mut message: ClineMessage) -> usize {
+        message.set_partial(Some(true));
+        self.replace_partial(ts, message)
+    }
+
+    /// `ts` の部分メッセージを `message` の内容で完了させる（ない場合は完了したメッセージとして追加する）
+    pub fn finalize(&mut self, ts: i64, mut message: ClineMessage) -> usize {
+        message.set_partial(Some(false));
+        self.replace_partial(ts, message)
+    }
+
+    /// `ts` の部分メッセージを `message` で置き換える（ない場合は追加する）
+    ///
+    /// `upsert_partial`・`finalize` と異なり、`partial` は `message` の値のまま変えない。
+    pub fn replace_partial(&mut self, ts: i64, mut message: ClineMessage) -> usize {
+        message.set_ts(ts);
+        match self.partial_position(ts, &message.kind()) {
+            Some(index) => {
+                self.update(index, |existing| *existing = message);
+                index
+            }
+            None => self.push(message),
+        }
+    }
+
+    /// 完了していない部分メッセージをすべて完了させる（タスクの中断時に使う）
+    pub fn close_partials(&mut self) {
+        let indices: Vec<usize> = (0..self.messages.len())
+            .filter(|index| self.messages[*index].is_partial())
+            .collect();
+        for index in indices {
+            self.update(index, |message| message.set_partial(Some(false)));
+        }
+    }
+
+    /// `ts` の部分メッセージを取り除く
+    pub fn remove_partial(&mut self, ts: i64, kind: &MessageKind) -> Option<ClineMessage> {
+        let index = self.partial_position(ts, kind)?;
+        let message = self.messages.remove(index);
+        self.emit(MessageEvent::Removed { index });
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct RecordingListener(Mutex<Vec<MessageEvent>>);
+
+    impl MessageListener for RecordingListener {
+        fn on_message_event(&self, event: &MessageEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    fn say(say: ClineSay, text: &str) -> ClineMessage {
+        ClineMessage::Say {
+            ts: 0,
+            text: Some(text.to_string()),
+            say,
+            images: None,
+            partial: None,
+            reasoning: None,
+        }
+    }
+
+    fn texts(store: &MessageStore) -> Vec<(&str, bool)> {
+        store
+            .iter()
+            .map(|m| (m.text().unwrap_or_default(), m.is_partial()))
+            .collect()
+    }
+
+    #[test]
+    fn test_interleaved_partial_streams() {
+        let listener = Arc::new(RecordingListener::default());
+        let mut store = MessageStore::default();
+        store.add_listener(listener.clone());
+
+        store.upsert_partial(1, say(ClineSay::Reasoning, "Let"));
+        store.upsert_partial(1, say(ClineSay::Text, "Hel"));
+        // 別のメッセージが間に入っても、それぞれの部分メッセージを更新できる
+        store.push(say(ClineSay::ApiReqRetryDelayed, "Retrying"));
+        store.upsert_partial(1, say(ClineSay::Reasoning, "Let me"));
+        store.upsert_partial(1, say(ClineSay::Text, "Hello"));
+        assert_eq!(
+            store.open_partial(&MessageKind::Say(ClineSay::Text)),
+            Some(1)
+        );
+        store.finalize(1, say(ClineSay::Text, "Hello!"));
+        store.finalize(1, say(ClineSay::Reasoning, "Let me think"));
+
+        assert_eq!(
+            texts(&store),
+            vec![
+                ("Let me think", false),
+                ("Hello!", false),
+                ("Retrying", false)
+            ]
+        );
+        assert_eq!(store.open_partial(&MessageKind::Say(ClineSay::Text)), None);
+        // 完了したメッセージは更新しない
+        store.finalize(1, say(ClineSay::Text, "Again"));
+        assert_eq!(store.len(), 4);
+
+        let events = listener.0.lock().unwrap();
+        assert_eq!(events.len(), 8);
+        assert_eq!(
+            events[4],
+            MessageEvent::Updated {
+                index: 1,
+                message: ClineMessage::Say {
+                    ts: 1,
+                    text: Some("Hello".to_string()),
+                    say: ClineSay::Text,
+                    images: None,
+                    partial: Some(true),
+                    reasoning: None,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_remove_partial() {
+        let mut store = MessageStore::default();
+        store.upsert_partial(5, say(ClineSay::Tool, "{}"));
+        store.push(say(ClineSay::Text, "done"));
+        let kind = MessageKind::Say(ClineSay::Tool);
+        assert!(store
+            .remove_partial(5, &MessageKind::Say(ClineSay::Text))
+            .is_none());
+        assert!(store.remove_partial(5, &kind).is_some());
+        assert_eq!(texts(&store), vec![("done", false)]);
+
+        store.upsert_partial(6, say(ClineSay::Text, "Hel"));
+        store.close_partials();
+        assert_eq!(texts(&store), vec![("done", false), ("Hel", false)]);
+        store.clear();
+        assert!(store.is_empty());
+    }
+}
@@ -1,2 +1,3 @@
 pub mod message;
+pub mod message_store;
 pub mod modes;
@@ -3,12 +3,76 @@ use serde::{Deserialize, Serialize};
 
 pub type Mode = String;
 
+/// モードで使えるツールのグループ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolGroup {
+    Read,
+    Edit,
+    Browser,
+    Command,
+    Mcp,
+}
+
+/// グループごとのツール
+pub const TOOL_GROUPS: &[(ToolGroup, &[&str])] = &[
+    (
+        ToolGroup::Read,
+        &[
+            "read_file",
+            "search_files",
+            "list_files",
+            "list_code_definition_names",
+            "codebase_search",
+        ],
+    ),
+    (
+        ToolGroup::Edit,
+        &[
+            "write_to_file",
+            "apply_diff",
+            "insert_content",
+            "search_and_replace",
+        ],
+    ),
+    (
+        ToolGroup::Browser,
+        &["browser_action", "get_page_text", "fetch"],
+    ),
+    (ToolGroup::Command, &["execute_command"]),
+    (ToolGroup::Mcp, &["use_mcp_tool", "access_mcp_resource"]),
+];
+
+/// グループによらずどのモードでも使えるツール
+pub const ALWAYS_AVAILABLE_TOOLS: &[&str] = &[
+    "ask_followup_question",
+    "attempt_completion",
+    "switch_mode",
+    "new_task",
+    "update_todo_list",
+];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModeConfig {
     pub slug: String,
     pub name: String,
     pub role_definition: String,
     pub custom_instructions: Option<String>,
+    /// 使えるツールのグループ（`ALWAYS_AVAILABLE_TOOLS` は常に使える）
+    pub groups: Vec<ToolGroup>,
+}
+
+impl ModeConfig {
+    /// モードで `tool` を使えるか（どのグループにも属さないホストのツールなどは使える）
+    pub fn is_tool_allowed(&self, tool: &str) -> bool {
+        if ALWAYS_AVAILABLE_TOOLS.contains(&tool) {
+            return true;
+        }
+        match TOOL_GROUPS.iter().find(|(_, tools)| tools.contains(&tool)) {
+            Some((group, _)) => self.groups.contains(group),
+            None => true,
+        }
+    }
 }
 
 // Mode-specific prompts only
@@ -29,12 +93,20 @@ pub static MODES: Lazy<Vec<ModeConfig>> = Lazy::new(|| {
             name: "Code".to_string(),
             role_definition: "A general-purpose coding assistant".to_string(),
             custom_instructions: None,
+            groups: vec![
+                ToolGroup::Read,
+                ToolGroup::Edit,
+                ToolGroup::Browser,
+                ToolGroup::Command,
+                ToolGroup::Mcp,
+            ],
         },
         ModeConfig {
             slug: "architect".to_string(),
             name: "Architect".to_string(),
             role_definition: "A software architect focused on high-level design".to_string(),
             custom_instructions: None,
+            groups: vec![ToolGroup::Read, ToolGroup::Browser, ToolGroup::Mcp],
         },
         ModeConfig {
             slug: "security".to_string(),
@@ -42,6 +114,13 @@ pub static MODES: Lazy<Vec<ModeConfig>> = Lazy::new(|| {
             role_definition: "A security expert focused on identifying and fixing vulnerabilities"
                 .to_string(),
             custom_instructions: None,
+            groups: vec![
+                ToolGroup::Read,
+                ToolGroup::Edit,
+                ToolGroup::Browser,
+                ToolGroup::Command,
+                ToolGroup::Mcp,
+            ],
         },
     ]
 });
@@ -55,8 +134,67 @@ pub fn get_mode_by_slug(mode: Mode, custom_modes: Option<&[ModeConfig]>) -> Opti
     MODES.iter().find(|m| m.slug == mode)
 }
 
+/// モードで `tool` を使えるか（見つからないモードは最初の組み込みのモードとみなす）
+pub fn is_tool_allowed_for_mode(
+    tool: &str,
+    mode: &str,
+    custom_modes: Option<&[ModeConfig]>,
+) -> bool {
+    get_mode_by_slug(mode.to_string(), custom_modes)
+        .unwrap_or(&MODES[0])
+        .is_tool_allowed(tool)
+}
+
 pub fn get_role_definition(mode: &str, custom_modes: Option<&[ModeConfig]>) -> String {
     get_mode_by_slug(mode.to_string(), custom_modes)
         .map(|m| m.role_definition.clone())
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_tool_allowed_for_mode() {
+        assert!(is_tool_allowed_for_mode("write_to_file", "code", None));
+        assert!(!is_tool_allowed_for_mode(
+            "write_to_file",
+            "architect",
+            None
+        ));
+        assert!(!is_tool_allowed_for_mode(
+            "execute_command",
+            "architect",
+            None
+        ));
+        assert!(is_tool_allowed_for_mode("read_file", "architect", None));
+        assert!(is_tool_allowed_for_mode(
+            "attempt_completion",
+            "architect",
+            None
+        ));
+        // どのグループにも属さないツール
+        assert!(is_tool_allowed_for_mode("create_ticket", "architect", None));
+
+        let custom_modes = vec![ModeConfig {
+            slug: "reviewer".to_string(),
+            name: "Reviewer".to_string(),
+            role_definition: "A code reviewer".to_string(),
+            custom_instructions: None,
+            groups: vec![],
+        }];
+        assert!(!is_tool_allowed_for_mode(
+            "read_file",
+            "reviewer",
+            Some(&custom_modes)
+        ));
+        assert!(is_tool_allowed_for_mode(
+            "new_task",
+            "reviewer",
+            Some(&custom_modes)
+        ));
+        // 見つからないモードは `code` とみなす
+        assert!(is_tool_allowed_for_mode("write_to_file", "unknown", None));
+    }
+}
@@ -0,0 +1,297 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+use crate::storage::{parse_versioned_json, to_versioned_json, write_atomic};
+
+/// 状態を保存するファイル名
+pub const STATE_FILE_NAME: &str = "state.json";
+
+/// タスク一覧に表示するタスクの履歴
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskHistory {
+    pub id: String,
+    /// 最後のメッセージの時刻（ミリ秒）
+    pub ts: i64,
+    /// 最初のメッセージ（タスクの内容）
+    pub task: String,
+    pub tokens_in: u32,
+    pub tokens_out: u32,
+    /// キャッシュのトークン数がない履歴（拡張機能の `HistoryItem` など）では0
+    #[serde(default)]
+    pub cache_writes: u32,
+    #[serde(default)]
+    pub cache_reads: u32,
+    pub total_cost: f64,
+}
+
+/// タスクの履歴と設定を保存する（ホストが実装を選ぶ）
+#[async_trait]
+pub trait StateStore: Debug + Send + Sync {
+    /// タスクの履歴を追加する（同じIDの履歴は置き換える）
+    async fn update_task_history(&self, history: TaskHistory) -> Result<()>;
+
+    /// 新しい順のタスクの履歴
+    async fn task_history(&self) -> Result<Vec<TaskHistory>>;
+
+    /// タスクの履歴とタスクごとの値を削除する
+    async fn delete_task(&self, task_id: &str) -> Result<()>;
+
+    /// タスクをまたいで使う設定
+    async fn global_value(&self, key: &str) -> Result<Option<Value>>;
+
+    /// 設定を保存する（`None` で削除する）
+    async fn set_global_value(&self, key: &str, value: Option<Value>) -> Result<()>;
+
+    /// タスクごとの値
+    async fn task_value(&self, task_id: &str, key: &str) -> Result<Option<Value>>;
+
+    /// タスクごとの値を保存する（`None` で削除する）
+    async fn set_task_value(&self, task_id: &str, key: &str, value: Option<Value>) -> Result<()>;
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct State {
+    task_history: Vec<TaskHistory>,
+    global: BTreeMap<String, Value>,
+    tasks: BTreeMap<String, BTreeMap<String, Value>>,
+}
+
+/// 1つのJSONファイルに保存する（`StoragePaths::state_path` など）
+///
+/// 最初に使うときに読み込み、変更のたびにファイル全体を書き直す。
+#[derive(Debug)]
+pub struct JsonFileStateStore {
+    path: PathBuf,
+    state: Mutex<Option<State>>,
+}
+
+impl JsonFileStateStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            state: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    async fn load(&self) -> Result<State> {
+        let content = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(State::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let (_, data) = parse_versioned_json(&content)
+            .with_context(|| format!("Failed to read {}", self.path.display()))?;
+        Ok(serde_json::from_value(data)?)
+    }
+
+    /// 状態を読み込んで `f` を適用する（`f` が変更ありと返したらファイルに保存する）
+    async fn with_state<T>(&self, f: impl FnOnce(&mut State) -> (T, bool)) -> Result<T> {
+        let mut guard = self.state.lock().await;
+        if guard.is_none() {
+            *guard = Some(self.load().await?);
+        }
+        let state = guard.as_mut().expect("state is loaded above");
+        let (value, modified) = f(state);
+        if modified {
+            if let Some(parent) = self.path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            write_atomic(&self.path, to_versioned_json(state)?.as_bytes()).await?;
+        }
+        Ok(value)
+    }
+}
+
+#[async_trait]
+impl StateStore for JsonFileStateStore {
+    async fn update_task_history(&self, history: TaskHistory) -> Result<()> {
+        self.with_state(|state| {
+            state.task_history.retain(|item| item.id != history.id);
+            state.task_history.push(history);
+            state
+                .task_history
+                .sort_by_key(|item| std::cmp::Reverse(item.ts));
+            ((), true)
+        })
+        .await
+    }
+
+    async fn task_history(&self) -> Result<Vec<TaskHistory>> {
+        self.with_state(|state| (state.task_history.clone(), false))
+            .await
+    }
+
+    async fn delete_task(&self, task_id: &str) -> Result<()> {
+        self.with_state(|state| {
+            let count = state.task_history.len();
+            state.task_history.retain(|item| item.id != task_id);
+            let removed_values = state.tasks.remove(task_id).is_some();
+            ((), removed_values || state.task_history.len() != count)
+        })
+        .await
+    }
+
+    async fn global_value(&self, key: &str) -> Result<Option<Value>> {
+        self.with_state(|state| (state.global.get(key).cloned(), false))
+            .await
+    }
+
+    async fn set_global_value(&self, key: &str, value: Option<Value>) -> Result<()> {
+        self.with_state(|state| {
+            let previous = match value {
+                Some(value) => state.global.insert(key.to_string(), value.clone()),
+                None => state.global.remove(key),
+            };
+            let modified = previous.as_ref() != state.global.get(key);
+            ((), modified)
+        })
+        .await
+    }
+
+    async fn task_value(&self, task_id: &str, key: &str) -> Result<Option<Value>> {
+        self.with_state(|state| {
+            let value = state
+                .tasks
+                .get(task_id)
+                .and_then(|values| values.get(key))
+                .cloned();
+            (value, false)
+        })
+        .await
+    }
+
+    async fn set_task_value(&self, task_id: &str, key: &str, value: Option<Value>) -> Result<()> {
+        self.with_state(|state| {
+            let values = state.tasks.entry(task_id.to_string()).or_default();
+            let previous = match value {
+                Some(value) => values.insert(key.to_string(), value.clone()),
+                None => values.remove(key),
+            };
+            let modified = previous.as_ref() != values.get(key);
+            if values.is_empty() {
+                state.tasks.remove(task_id);
+            }
+            ((), modified)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn history(id: &str, ts: i64) -> TaskHistory {
+        TaskHistory {
+            id: id.to_string(),
+            ts,
+            task: format!("task {}", id),
+            tokens_in: 10,
+            tokens_out: 20,
+            cache_writes: 0,
+            cache_reads: 0,
+            total_cost: 0.01,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_file_state_store() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("state").join(STATE_FILE_NAME);
+        let store = JsonFileStateStore::new(&path);
+        assert!(store.task_history().await.unwrap().is_empty());
+        // 読み込むだけではファイルを作らない
+        assert!(!path.exists());
+
+        store.update_task_history(history("a", 1)).await.unwrap();
+        store.update_task_history(history("b", 2)).await.unwrap();
+        store.update_task_history(history("a", 3)).await.unwrap();
+        store
+            .set_global_value("mode", Some(json!("architect")))
+            .await
+            .unwrap();
+        store
+            .set_task_value("a", "checkpoint", Some(json!(4)))
+            .await
+            .unwrap();
+
+        // 別のインスタンスでファイルから読み込む
+        let store = JsonFileStateStore::new(&path);
+        let ids: Vec<String> = store
+            .task_history()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|item| item.id)
+            .collect();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert_eq!(
+            store.global_value("mode").await.unwrap(),
+            Some(json!("architect"))
+        );
+        assert_eq!(
+            store.task_value("a", "checkpoint").await.unwrap(),
+            Some(json!(4))
+        );
+        assert_eq!(store.task_value("b", "checkpoint").await.unwrap(), None);
+
+        store.set_global_value("mode", None).await.unwrap();
+        assert_eq!(store.global_value("mode").await.unwrap(), None);
+        store.delete_task("a").await.unwrap();
+        assert_eq!(store.task_history().await.unwrap(), vec![history("b", 2)]);
+        assert_eq!(store.task_value("a", "checkpoint").await.unwrap(), None);
+
+        let (version, _) = parse_versioned_json(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(version, crate::storage::SCHEMA_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_load_previous_state() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(STATE_FILE_NAME);
+        std::fs::write(
+            &path,
+            include_str!("../tests/fixtures/schema/state_v1.json"),
+        )
+        .unwrap();
+        let store = JsonFileStateStore::new(&path);
+
+        let histories = store.task_history().await.unwrap();
+        assert_eq!(histories.len(), 2);
+        // キャッシュのトークン数がない履歴は0として読み込む
+        assert_eq!(
+            (histories[0].cache_writes, histories[0].cache_reads),
+            (0, 0)
+        );
+        assert_eq!(
+            (histories[1].cache_writes, histories[1].cache_reads),
+            (10, 5)
+        );
+        assert_eq!(
+            store.global_value("mode").await.unwrap(),
+            Some(json!("architect"))
+        );
+
+        // 保存し直しても同じ履歴を読み込む
+        store
+            .set_task_value("task-1", "checkpoint", Some(json!(1)))
+            .await
+            .unwrap();
+        let reloaded = JsonFileStateStore::new(&path);
+        assert_eq!(reloaded.task_history().await.unwrap(), histories);
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::storage::{parse_versioned_json, to_versioned_json, write_atomic, StoragePaths};
+
+/// タスクディレクトリ内のツールの使用統計のファイル名
+pub const TASK_STATS_FILE_NAME: &str = "task_stats.json";
+
+/// ツールの呼び出しの結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolOutcome {
+    Success,
+    /// ツールがエラーを返した（タイムアウトを含む）
+    Failure,
+    /// ポリシーや承認によって実行しなかった
+    Rejected,
+}
+
+/// 1つのツールの使用統計
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ToolStats {
+    pub invocations: u32,
+    pub successes: u32,
+    pub failures: u32,
+    pub rejections: u32,
+    /// 実行にかかった時間の合計（ミリ秒）
+    pub total_duration_ms: u64,
+}
+
+impl ToolStats {
+    fn record(&mut self, outcome: ToolOutcome, duration: Duration) {
+        self.invocations += 1;
+        match outcome {
+            ToolOutcome::Success => self.successes += 1,
+            ToolOutcome::Failure => self.failures += 1,
+            ToolOutcome::Rejected => self.rejections += 1,
+        }
+        self.total_duration_ms += duration.as_millis() as u64;
+    }
+
+    fn merge(&mut self, other: &ToolStats) {
+        self.invocations += other.invocations;
+        self.successes += other.successes;
+        self.failures += other.failures;
+        self.rejections += other.rejections;
+        self.total_duration_ms += other.total_duration_ms;
+    }
+
+    /// 実行したうち成功した割合（拒否は含めない、実行していなければ `None`）
+    pub fn success_rate(&self) -> Option<f64> {
+        let executed = self.successes + self.failures;
+        (executed > 0).then(|| self.successes as f64 / executed as f64)
+    }
+
+    /// 実行したうち失敗した割合（拒否は含めない、実行していなければ `None`）
+    pub fn failure_rate(&self) -> Option<f64> {
+        self.success_rate().map(|rate| 1.0 - rate)
+    }
+
+    /// 1回あたりの平均の実行時間
+    pub fn average_duration(&self) -> Duration {
+        if self.invocations == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_millis(self.total_duration_ms / self.invocations as u64)
+    }
+}
+
+/// タスク中のツールごとの使用統計
+///
+/// タスクディレクトリに保存し、`aggregate` で複数のタスクを集計できる。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskStats {
+    tools: BTreeMap<String, ToolStats>,
+}
+
+impl TaskStats {
+    pub fn record(&mut self, tool: &str, outcome: ToolOutcome, duration: Duration) {
+        self.tools
+            .entry(tool.to_string())
+            .or_default()
+            .record(outcome, duration);
+    }
+
+    pub fn tool(&self, tool: &str) -> Option<&ToolStats> {
+        self.tools.get(tool)
+    }
+
+    /// ツール名の順のすべてのツールの統計
+    pub fn tools(&self) -> impl Iterator<Item = (&str, &ToolStats)> {
+        self.tools
+            .iter()
+            .map(|(name, stats)| (name.as_str(), stats))
+    }
+
+    /// すべてのツールを合わせた統計
+    pub fn total(&self) -> ToolStats {
+        let mut total = ToolStats::default();
+        for stats in self.tools.values() {
+            total.merge(stats);
+        }
+        total
+    }
+
+    /// 呼び出し回数の多い順のツール
+    pub fn most_used(&self) -> Vec<(&str, &ToolStats)> {
+        let mut tools: Vec<_> = self.tools().collect();
+        tools.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.invocations));
+        tools
+    }
+
+    /// 失敗と拒否の多い順のツール（一度も失敗・拒否していないツールは含めない）
+    pub fn most_failing(&self) -> Vec<(&str, &ToolStats)> {
+        let mut tools: Vec<_> = self
+            .tools()
+            .filter(|(_, stats)| stats.failures + stats.rejections > 0)
+            .collect();
+        tools.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.failures + stats.rejections));
+        tools
+    }
+
+    pub fn merge(&mut self, other: &TaskStats) {
+        for (tool, stats) in &other.tools {
+            self.tools.entry(tool.clone()).or_default().merge(stats);
+        }
+    }
+
+    /// 複数のタスクの統計を合計する
+    pub fn aggregate<'a>(stats: impl IntoIterator<Item = &'a TaskStats>) -> TaskStats {
+        let mut total = TaskStats::default();
+        for stats in stats {
+            total.merge(stats);
+        }
+        total
+    }
+
+    /// タスクディレクトリの統計を読み込む（保存されていなければ空）
+    pub async fn load(task_dir: &Path) -> Result<Self> {
+        let path = task_dir.join(TASK_STATS_FILE_NAME);
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let (_, data) = parse_versioned_json(&content)?;
+        serde_json::from_value(data)
+            .with_context(|| format!("Invalid task stats {}", path.display()))
+    }
+
+    pub async fn save(&self, task_dir: &Path) -> Result<()> {
+        tokio::fs::create_dir_all(task_dir).await?;
+        write_atomic(
+            &task_dir.join(TASK_STATS_FILE_NAME),
+            to_versioned_json(self)?.as_bytes(),
+        )
+        .await
+    }
+
+    /// 保存先のすべてのタスクの統計を合計する（読み込めないタスクは読み飛ばす）
+    pub async fn load_all(storage: &StoragePaths) -> Result<Self> {
+        let mut total = TaskStats::default();
+        let mut entries = match tokio::fs::read_dir(storage.tasks_root()).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(total),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            if !entry.path().join(TASK_STATS_FILE_NAME).exists() {
+                continue;
+            }
+            match Self::load(&entry.path()).await {
+                Ok(stats) => total.merge(&stats),
+                Err(e) => tracing::warn!(
+                    "Failed to load task stats in {}: {}",
+                    entry.path().display(),
+                    e
+                ),
+            }
+        }
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_task_stats() {
+        let mut stats = TaskStats::default();
+        stats.record("read_file", ToolOutcome::Success, Duration::from_millis(10));
+        stats.record("read_file", ToolOutcome::Success, Duration::from_millis(30));
+        stats.record("read_file", ToolOutcome::Failure, Duration::from_millis(20));
+        stats.record("execute_command", ToolOutcome::Rejected, Duration::ZERO);
+
+        let read_file = stats.tool("read_file").unwrap();
+        assert_eq!(read_file.invocations, 3);
+        assert_eq!(read_file.average_duration(), Duration::from_millis(20));
+        assert_eq!(read_file.success_rate(), Some(2.0 / 3.0));
+        assert_eq!(stats.tool("execute_command").unwrap().success_rate(), None);
+        assert_eq!(stats.most_used()[0].0, "read_file");
+        assert_eq!(stats.total().invocations, 4);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage = StoragePaths::global_in(temp_dir.path(), Path::new("/work"));
+        stats.save(&storage.task_dir("task-1")).await.unwrap();
+        let mut other = TaskStats::default();
+        other.record("execute_command", ToolOutcome::Failure, Duration::ZERO);
+        other.record("execute_command", ToolOutcome::Failure, Duration::ZERO);
+        other.save(&storage.task_dir("task-2")).await.unwrap();
+        assert_eq!(
+            TaskStats::load(&storage.task_dir("task-1")).await.unwrap(),
+            stats
+        );
+
+        let all = TaskStats::load_all(&storage).await.unwrap();
+        assert_eq!(all, TaskStats::aggregate([&stats, &other]));
+        let execute_command = all.tool("execute_command").unwrap();
+        assert_eq!(
+            (execute_command.failures, execute_command.rejections),
+            (2, 1)
+        );
+        assert_eq!(all.most_failing()[0].0, "execute_command");
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+/// タスクファイルの現在のスキーマバージョン
+///
+/// バージョン0はバージョン情報を持たない以前の形式（JSON配列をそのまま保存）を表す。
+///
+/// # 変更履歴
+///
+/// - 0: JSON配列をそのまま保存する。`ClineMessage` の種類は `"type": "Say"` / `"type": "Ask"` で、
+///   値のない項目は `null` で出力する。
+/// - 1: `{"version": 1, "data": ...}` で包む。`ClineMessage` の種類を `"say"` / `"ask"` にし、
+///   値のない項目は出力しない。
+///
+/// 省略時に既定値で読み込める項目の追加（`ClineMessage::Ask` の `suggestions`、`ClineSayTool` の
+/// `content`、`TaskHistory` のキャッシュのトークン数など）ではバージョンを上げない。以前の形式を
+/// 読み込めなくなる変更ではバージョンを上げ、`tests/fixtures/schema` に以前の形式のファイルを追加する。
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct VersionedRef<'a, T> {
+    version: u32,
+    data: &'a T,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredFile {
+    Versioned {
+        version: u32,
+        data: serde_json::Value,
+    },
+    Legacy(serde_json::Value),
+}
+
+/// バージョン付きのタスクファイルの形式に変換する
+pub fn to_versioned_json<T: Serialize>(data: &T) -> Result<String> {
+    Ok(serde_json::to_string(&VersionedRef {
+        version: SCHEMA_VERSION,
+        data,
+    })?)
+}
+
+/// タスクファイルを読み込み、スキーマバージョンとデータを返す
+pub fn parse_versioned_json(content: &str) -> Result<(u32, serde_json::Value)> {
+    let (version, data) = match serde_json::from_str(content)? {
+        StoredFile::Versioned { version, data } => (version, data),
+        StoredFile::Legacy(data) => (0, data),
+    };
+    if version > SCHEMA_VERSION {
+        anyhow::bail!(
+            "Task file schema version {} is newer than supported version {}",
+            version,
+            SCHEMA_VERSION
+        );
+    }
+    Ok((version, data))
+}
+
+/// グローバルな保存先のディレクトリ名
+const GLOBAL_STORAGE_DIR_NAME: &str = "headless-cline";
+
+/// タスクデータや設定の保存先
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoragePaths {
+    /// ワークスペースごとのデータのルート
+    workspace_root: PathBuf,
+    /// ワークスペースをまたいで共有する設定のルート
+    settings_root: PathBuf,
+    /// 以前と同じくワークスペース内の `.cline` に保存する
+    legacy: bool,
+}
+
+impl StoragePaths {
+    /// ユーザーのデータディレクトリ（Linuxでは `$XDG_DATA_HOME`）に保存する
+    ///
+    /// ワークスペースごとのデータはパスのハッシュで区別する。
+    pub fn global(workspace_path: &Path) -> Result<Self> {
+        let root = dirs::data_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not determine the user data directory"))?
+            .join(GLOBAL_STORAGE_DIR_NAME);
+        Ok(Self::global_in(&root, workspace_path))
+    }
+
+    /// 指定したディレクトリをグローバルな保存先として使う
+    pub fn global_in(root: &Path, workspace_path: &Path) -> Self {
+        Self {
+            workspace_root: root.join("workspaces").join(workspace_key(workspace_path)),
+            settings_root: root.join("settings"),
+            legacy: false,
+        }
+    }
+
+    /// ワークスペース内の `.cline` に保存する（以前の保存先）
+    pub fn workspace(workspace_path: &Path) -> Self {
+        let root = workspace_path.join(".cline");
+        Self {
+            settings_root: root.join("settings"),
+            workspace_root: root,
+            legacy: true,
+        }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+
+    /// タスクのディレクトリを置くディレクトリ
+    ///
+    /// 以前の配置ではタスク以外のディレクトリ（`checkpoints` など）も含む。
+    pub fn tasks_root(&self) -> PathBuf {
+        if self.legacy {
+            // 以前の配置（`.cline/<task_id>`）をそのまま使う
+            self.workspace_root.clone()
+        } else {
+            self.workspace_root.join("tasks")
+        }
+    }
+
+    /// タスクのファイルを保存するディレクトリ
+    pub fn task_dir(&self, task_id: &str) -> PathBuf {
+        self.tasks_root().join(task_id)
+    }
+
+    /// タスクのチェックポイントを保存するディレクトリ
+    pub fn checkpoints_dir(&self, task_id: &str) -> PathBuf {
+        self.workspace_root.join("checkpoints").join(task_id)
+    }
+
+    /// タスクのスクラッチディレクトリ（タスクの完了時に削除する）
+    pub fn scratch_dir(&self, task_id: &str) -> PathBuf {
+        self.workspace_root.join("scratch").join(task_id)
+    }
+
+    /// `codebase_search` のインデックス
+    pub fn codebase_index_path(&self) -> PathBuf {
+        self.workspace_root.join("codebase_index.json")
+    }
+
+    /// タスクの履歴と設定（`JsonFileStateStore` の保存先）
+    pub fn state_path(&self) -> PathBuf {
+        self.workspace_root.join(crate::state::STATE_FILE_NAME)
+    }
+
+    /// MCPサーバーの設定ファイル
+    pub fn mcp_settings_path(&self) -> PathBuf {
+        self.settings_root.join("cline_mcp_settings.json")
+    }
+}
+
+/// ワークスペースのディレクトリ名（読みやすさのため末尾の名前とパスのハッシュを組み合わせる）
+fn workspace_key(workspace_path: &Path) -> String {
+    let path = workspace_path
+        .canonicalize()
+        .unwrap_or_else(|_| workspace_path.to_path_buf());
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "root".to_string());
+    // Rustのバージョンによって変わらないよう、FNV-1aでハッシュを計算する
+    let hash = path
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    format!("{}-{:016x}", name, hash)
+}
+
+/// 一時ファイルに書き込んでから置き換えることで、書き込み途中の内容が残らないようにする
+pub async fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Invalid file path: {}", path.display()))?
+        .to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, Uuid::new_v4()));
+
+    let result = async {
+        let mut file = fs::File::create(&tmp_path).await?;
+        file.write_all(contents).await?;
+        file.sync_all().await?;
+        fs::rename(&tmp_path, path).await?;
+        Ok(())
+    }
+    .await;
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path).await;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_versioned_atomic_write() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("ui_messages.json");
+        std::fs::write(&path, "[1,2]").unwrap();
+
+        let (version, data) =
+            parse_versioned_json(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!((version, data), (0, serde_json::json!([1, 2])));
+
+        write_atomic(&path, to_versioned_json(&vec![3]).unwrap().as_bytes())
+            .await
+            .unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content, r#"{"version":1,"data":[3]}"#);
+        assert_eq!(
+            parse_versioned_json(&content).unwrap(),
+            (SCHEMA_VERSION, serde_json::json!([3]))
+        );
+        // 一時ファイルは残らない
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+
+        assert!(parse_versioned_json(r#"{"version":99,"data":[]}"#).is_err());
+    }
+
+    #[test]
+    fn test_storage_paths() {
+        let workspace = Path::new("/nonexistent/projects/app");
+        let global = StoragePaths::global_in(Path::new("/data/headless-cline"), workspace);
+        let task_dir = global.task_dir("task-1");
+        assert!(task_dir.starts_with("/data/headless-cline/workspaces"));
+        assert!(task_dir.ends_with("tasks/task-1"));
+        assert_eq!(
+            global.mcp_settings_path(),
+            PathBuf::from("/data/headless-cline/settings/cline_mcp_settings.json")
+        );
+
+        // 同じワークスペースは同じディレクトリ、別のワークスペースは別のディレクトリになる
+        let key = workspace_key(workspace);
+        assert!(key.starts_with("app-"));
+        assert_eq!(key, workspace_key(workspace));
+        assert_ne!(key, workspace_key(Path::new("/nonexistent/other/app")));
+
+        let legacy = StoragePaths::workspace(workspace);
+        assert!(legacy.is_legacy());
+        assert_eq!(
+            legacy.task_dir("task-1"),
+            PathBuf::from("/nonexistent/projects/app/.cline/task-1")
+        );
+        assert_eq!(
+            legacy.checkpoints_dir("task-1"),
+            PathBuf::from("/nonexistent/projects/app/.cline/checkpoints/task-1")
+        );
+        assert_eq!(
+            legacy.scratch_dir("task-1"),
+            PathBuf::from("/nonexistent/projects/app/.cline/scratch/task-1")
+        );
+    }
+}
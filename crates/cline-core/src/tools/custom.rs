@@ -0,0 +1,88 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use crate::assistant_message::TOOL_NAMES;
+
+/// ホストが追加するツール（MCPサーバーを用意せずにアプリ固有の操作をモデルに公開する）
+#[async_trait]
+pub trait CustomTool: Debug + Send + Sync {
+    /// ツール名（組み込みのツールと重ならない、英小文字と `_` の名前）
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    /// 引数のJSON Schema（`"type": "object"` で `properties`・`required` を持つ）
+    fn input_schema(&self) -> Value;
+
+    /// ツールを実行してモデルに返す結果を作る（エラーはツールの失敗としてモデルに返す）
+    async fn execute(&self, input: Value) -> Result<String>;
+}
+
+/// 追加できるツール名か確認する
+pub fn validate_custom_tool_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+        anyhow::bail!(
+            "Invalid custom tool name {:?}: use lowercase letters and underscores",
+            name
+        );
+    }
+    if TOOL_NAMES.contains(&name) {
+        anyhow::bail!("Custom tool {} conflicts with a built-in tool", name);
+    }
+    Ok(())
+}
+
+/// XML形式のパラメータ（文字列）を、スキーマの型に合わせて引数のJSONにする
+///
+/// 文字列以外の型のパラメータはJSONとして解釈し、解釈できない場合は文字列のまま渡す。
+pub fn custom_tool_input(schema: &Value, params: &HashMap<String, String>) -> Value {
+    let input: Map<String, Value> = params
+        .iter()
+        .map(|(name, value)| {
+            let param_type = schema["properties"][name]["type"].as_str();
+            let value = match param_type {
+                Some("string") | None => Value::String(value.clone()),
+                Some(_) => serde_json::from_str(value.trim())
+                    .unwrap_or_else(|_| Value::String(value.clone())),
+            };
+            (name.clone(), value)
+        })
+        .collect();
+    Value::Object(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn test_custom_tool_input() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "ticket": { "type": "string" },
+                "priority": { "type": "integer" },
+                "labels": { "type": "array" },
+            },
+        });
+        let params = HashMap::from([
+            ("ticket".to_string(), "123".to_string()),
+            ("priority".to_string(), " 2 ".to_string()),
+            ("labels".to_string(), r#"["bug"]"#.to_string()),
+            ("note".to_string(), "urgent".to_string()),
+        ]);
+        assert_eq!(
+            custom_tool_input(&schema, &params),
+            json!({ "ticket": "123", "priority": 2, "labels": ["bug"], "note": "urgent" })
+        );
+
+        assert!(validate_custom_tool_name("create_ticket").is_ok());
+        assert!(validate_custom_tool_name("read_file").is_err());
+        assert!(validate_custom_tool_name("Create-Ticket").is_err());
+    }
+}
@@ -0,0 +1,250 @@
+use anyhow::Result;
+use regex::{NoExpand, Regex, RegexBuilder};
+use serde::Deserialize;
+use std::path::PathBuf;
+
+use crate::services::fs::Rope;
+
+/// ファイル編集ツールが計算した変更
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileEdit {
+    /// ツールに渡されたパス
+    pub rel_path: String,
+    pub abs_path: PathBuf,
+    /// 新規作成の場合は `None`
+    pub original_content: Option<String>,
+    pub new_content: String,
+}
+
+impl FileEdit {
+    pub fn is_new_file(&self) -> bool {
+        self.original_content.is_none()
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.original_content.as_deref() != Some(self.new_content.as_str())
+    }
+
+    /// 変更前後の差分（unified diff形式）
+    pub fn diff(&self) -> String {
+        let patch = diffy::create_patch(
+            self.original_content.as_deref().unwrap_or(""),
+            &self.new_content,
+        )
+        .to_string();
+        let original = if self.is_new_file() {
+            "/dev/null".to_string()
+        } else {
+            format!("a/{}", self.rel_path)
+        };
+        // diffyの既定のヘッダーをファイル名に置き換える
+        let hunks = patch.splitn(3, '\n').nth(2).unwrap_or("");
+        format!("--- {}\n+++ b/{}\n{}", original, self.rel_path, hunks)
+    }
+}
+
+/// `insert_content` の操作
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct InsertOperation {
+    /// 挿入する行（1始まり、元の内容の行番号）。行数+1で末尾に追加する
+    pub start_line: usize,
+    pub content: String,
+}
+
+/// 元の内容の行番号を基準に内容を挿入する
+pub fn apply_insertions(original: &str, operations: &[InsertOperation]) -> Result<String> {
+    let mut rope = Rope::new(original);
+    let line_count = rope.len_lines();
+    let mut operations = operations.to_vec();
+    operations.sort_by_key(|operation| operation.start_line);
+    if let Some(operation) = operations
+        .iter()
+        .find(|operation| operation.start_line == 0 || operation.start_line > line_count + 1)
+    {
+        anyhow::bail!(
+            "Invalid start_line {}: the file has {} lines",
+            operation.start_line,
+            line_count
+        );
+    }
+
+    // 後ろから挿入して元の行番号をずらさない（同じ行への挿入は指定した順に並ぶ）
+    for operation in operations.iter().rev() {
+        rope.insert(
+            operation.start_line - 1,
+            operation.content.lines().map(String::from),
+        );
+    }
+    if original.is_empty() {
+        rope.set_trailing_newline(true);
+    }
+    Ok(rope.to_string())
+}
+
+/// `search_and_replace` の操作
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SearchReplaceOperation {
+    pub search: String,
+    pub replace: String,
+    #[serde(default)]
+    pub start_line: Option<usize>,
+    #[serde(default)]
+    pub end_line: Option<usize>,
+    #[serde(default)]
+    pub use_regex: bool,
+    #[serde(default)]
+    pub ignore_case: bool,
+    /// 正規表現のフラグ（`i`、`m`、`s`、`x` に対応し、`g` などは無視する）
+    #[serde(default)]
+    pub regex_flags: Option<String>,
+}
+
+impl SearchReplaceOperation {
+    fn regex(&self) -> Result<Regex> {
+        let pattern = if self.use_regex {
+            self.search.clone()
+        } else {
+            regex::escape(&self.search)
+        };
+        let flags = self.regex_flags.as_deref().unwrap_or("");
+        RegexBuilder::new(&pattern)
+            .case_insensitive(self.ignore_case || flags.contains('i'))
+            .multi_line(flags.contains('m'))
+            .dot_matches_new_line(flags.contains('s'))
+            .ignore_whitespace(flags.contains('x'))
+            .build()
+            .map_err(|e| anyhow::anyhow!("Invalid search pattern '{}': {}", self.search, e))
+    }
+
+    fn apply(&self, content: &str) -> Result<String> {
+        let regex = self.regex()?;
+        if self.use_regex {
+            // JavaScriptの `$&`（マッチ全体）に合わせる
+            let replace = self.replace.replace("$&", "${0}");
+            Ok(regex.replace_all(content, replace.as_str()).to_string())
+        } else {
+            Ok(regex
+                .replace_all(content, NoExpand(&self.replace))
+                .to_string())
+        }
+    }
+}
+
+/// 検索・置換を順に適用する（行範囲を指定した場合はその範囲内のみ置換する）
+pub fn apply_search_and_replace(
+    original: &str,
+    operations: &[SearchReplaceOperation],
+) -> Result<String> {
+    let mut content = original.to_string();
+    for operation in operations {
+        if operation.start_line.is_none() && operation.end_line.is_none() {
+            content = operation.apply(&content)?;
+            continue;
+        }
+
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let start = operation.start_line.unwrap_or(1).max(1) - 1;
+        let end = operation.end_line.unwrap_or(lines.len()).min(lines.len());
+        if start >= end {
+            anyhow::bail!(
+                "Invalid line range {}-{}: the file has {} lines",
+                start + 1,
+                end,
+                lines.len()
+            );
+        }
+        let replaced = operation.apply(&lines[start..end].concat())?;
+        content = format!(
+            "{}{}{}",
+            lines[..start].concat(),
+            replaced,
+            lines[end..].concat()
+        );
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_apply_insertions() {
+        let original = "use a;\nfn main() {}\n";
+        let operations: Vec<InsertOperation> = serde_json::from_str(
+            r#"[
+                {"start_line": 3, "content": "fn helper() {}"},
+                {"start_line": 1, "content": "use b;\nuse c;"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            apply_insertions(original, &operations).unwrap(),
+            "use b;\nuse c;\nuse a;\nfn main() {}\nfn helper() {}\n"
+        );
+        assert!(apply_insertions(
+            original,
+            &[InsertOperation {
+                start_line: 5,
+                content: "x".to_string()
+            }]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_apply_search_and_replace() {
+        let original = "foo one\nfoo two\nFOO three\n";
+        let operations: Vec<SearchReplaceOperation> = serde_json::from_str(
+            r#"[
+                {"search": "foo", "replace": "bar", "start_line": 2, "end_line": 3},
+                {"search": "fo+", "replace": "[$&]", "use_regex": true, "ignore_case": true}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            apply_search_and_replace(original, &operations).unwrap(),
+            "[foo] one\nbar two\n[FOO] three\n"
+        );
+
+        // 正規表現でない場合は `$` を展開しない
+        let operations = [SearchReplaceOperation {
+            search: "(one)".to_string(),
+            replace: "$1".to_string(),
+            start_line: None,
+            end_line: None,
+            use_regex: false,
+            ignore_case: false,
+            regex_flags: None,
+        }];
+        assert_eq!(
+            apply_search_and_replace("call(one)", &operations).unwrap(),
+            "call$1"
+        );
+    }
+
+    #[test]
+    fn test_file_edit_diff() {
+        let edit = FileEdit {
+            rel_path: "src/main.rs".to_string(),
+            abs_path: PathBuf::from("/workspace/src/main.rs"),
+            original_content: Some("a\nb\n".to_string()),
+            new_content: "a\nc\n".to_string(),
+        };
+        assert!(edit.has_changes());
+        assert_eq!(
+            edit.diff(),
+            "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n"
+        );
+
+        let edit = FileEdit {
+            original_content: None,
+            ..edit
+        };
+        assert!(edit.is_new_file());
+        assert!(edit
+            .diff()
+            .starts_with("--- /dev/null\n+++ b/src/main.rs\n"));
+    }
+}
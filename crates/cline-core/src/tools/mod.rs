@@ -0,0 +1,17 @@
+mod custom;
+mod file_edit;
+mod patch;
+mod settings;
+mod subtask;
+mod timeout;
+mod todo;
+
+pub use custom::{custom_tool_input, validate_custom_tool_name, CustomTool};
+pub use file_edit::{
+    apply_insertions, apply_search_and_replace, FileEdit, InsertOperation, SearchReplaceOperation,
+};
+pub use patch::{PatchCollector, TaskPatch};
+pub use settings::ToolSettings;
+pub use subtask::{format_subtask_results, Subtask, SubtaskResult};
+pub use timeout::{format_tool_timeout, watchdog, ToolTimeouts};
+pub use todo::{format_todo_list, parse_todo_list, TodoItem, TodoStatus};
@@ -0,0 +1,100 @@
+use anyhow::Result;
+use std::path::Path;
+
+use super::FileEdit;
+use crate::storage::write_atomic;
+
+/// タスク中の編集をファイルごとにまとめて保持する
+///
+/// 同じファイルへの複数の編集は、最初の編集前の内容と最後の編集後の内容の差分にまとめる。
+#[derive(Debug, Clone, Default)]
+pub struct PatchCollector {
+    edits: Vec<FileEdit>,
+}
+
+impl PatchCollector {
+    /// 編集を記録する（`rel_path` はパッチのファイル名になるため、ワークスペースからの相対パスにしておく）
+    pub fn record(&mut self, edit: &FileEdit) {
+        match self
+            .edits
+            .iter_mut()
+            .find(|recorded| recorded.abs_path == edit.abs_path)
+        {
+            Some(recorded) => recorded.new_content = edit.new_content.clone(),
+            None => self.edits.push(edit.clone()),
+        }
+    }
+
+    /// 変更のあるファイルの編集（最初に編集した順）
+    pub fn edits(&self) -> Vec<&FileEdit> {
+        self.edits
+            .iter()
+            .filter(|edit| edit.has_changes())
+            .collect()
+    }
+
+    pub fn to_patch(&self) -> TaskPatch {
+        let edits = self.edits();
+        TaskPatch {
+            patch: edits.iter().map(|edit| edit.diff()).collect(),
+            files: edits.iter().map(|edit| edit.rel_path.clone()).collect(),
+        }
+    }
+}
+
+/// タスクの全ての編集をまとめたunified diff形式のパッチ
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskPatch {
+    pub patch: String,
+    /// 変更したファイル（ワークスペースからの相対パス）
+    pub files: Vec<String>,
+}
+
+impl TaskPatch {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// `git apply` で適用できるパッチファイルとして保存する
+    pub async fn write_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        write_atomic(path, self.patch.as_bytes()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn edit(rel_path: &str, original: Option<&str>, new_content: &str) -> FileEdit {
+        FileEdit {
+            rel_path: rel_path.to_string(),
+            abs_path: PathBuf::from("/workspace").join(rel_path),
+            original_content: original.map(String::from),
+            new_content: new_content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_patch_collector_merges_edits_per_file() {
+        let mut collector = PatchCollector::default();
+        collector.record(&edit("a.txt", Some("one\n"), "two\n"));
+        collector.record(&edit("b.txt", None, "new\n"));
+        collector.record(&edit("a.txt", Some("two\n"), "three\n"));
+        collector.record(&edit("c.txt", Some("same\n"), "changed\n"));
+        collector.record(&edit("c.txt", Some("changed\n"), "same\n"));
+
+        let patch = collector.to_patch();
+        assert_eq!(patch.files, vec!["a.txt", "b.txt"]);
+        assert_eq!(
+            patch.patch,
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-one\n+three\n\
+             --- /dev/null\n+++ b/b.txt\n@@ -0,0 +1 @@\n+new\n"
+        );
+        assert!(PatchCollector::default().to_patch().is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// ツールごとの設定（`ExtensionState::tool_settings`）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ToolSettings {
+    /// 無効にするツール（ツールの説明に含めず、呼び出してもエラーを返す）
+    pub disabled_tools: Vec<String>,
+    /// ツール名ごとに置き換える説明（小さいモデル向けに短くする場合など）
+    pub description_overrides: HashMap<String, String>,
+}
+
+impl ToolSettings {
+    pub fn is_disabled(&self, tool: &str) -> bool {
+        self.disabled_tools.iter().any(|disabled| disabled == tool)
+    }
+
+    pub fn description_override(&self, tool: &str) -> Option<&str> {
+        self.description_overrides.get(tool).map(String::as_str)
+    }
+
+    /// XML形式のツールの説明（`## name` で始まる）に設定を適用する（無効なツールは `None`）
+    ///
+    /// 説明を置き換える場合は `Description:` から `Parameters:` の前までを置き換え、
+    /// パラメータと使い方は残す。
+    pub fn apply_to_description(&self, description: String) -> Option<String> {
+        let Some(name) = description
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix("## "))
+        else {
+            return Some(description);
+        };
+        if self.is_disabled(name.trim()) {
+            return None;
+        }
+        let Some(text) = self.description_override(name.trim()) else {
+            return Some(description);
+        };
+        let Some(start) = description.find("Description: ") else {
+            return Some(description);
+        };
+        let start = start + "Description: ".len();
+        let end = description[start..]
+            .find("\nParameters:")
+            .map_or(description.len(), |end| start + end);
+        Some(format!(
+            "{}{}{}",
+            &description[..start],
+            text,
+            &description[end..]
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_apply_to_description() {
+        let settings: ToolSettings = serde_json::from_str(
+            r#"{
+                "disabledTools": ["fetch"],
+                "descriptionOverrides": { "read_file": "Read a file." }
+            }"#,
+        )
+        .unwrap();
+        let description = "## read_file\nDescription: Request to read the contents of a file.\nIt outputs line numbers.\nParameters:\n- path: (required) The path\nUsage:\n<read_file>\n<path>File path here</path>\n</read_file>";
+        assert_eq!(
+            settings.apply_to_description(description.to_string()),
+            Some("## read_file\nDescription: Read a file.\nParameters:\n- path: (required) The path\nUsage:\n<read_file>\n<path>File path here</path>\n</read_file>".to_string())
+        );
+        assert_eq!(
+            settings.apply_to_description("## fetch\nDescription: Fetch a URL.".to_string()),
+            None
+        );
+        assert_eq!(
+            settings.apply_to_description("## list_files\nDescription: List files.".to_string()),
+            Some("## list_files\nDescription: List files.".to_string())
+        );
+    }
+}
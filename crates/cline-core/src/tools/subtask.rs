@@ -0,0 +1,75 @@
+use serde::Deserialize;
+
+/// 並列に実行するサブタスク
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Subtask {
+    pub message: String,
+    /// 担当するファイル・ディレクトリ（ワークスペースからの相対パス）。他のサブタスクと重ならないこと
+    pub files: Vec<String>,
+}
+
+/// サブタスクの実行結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubtaskResult {
+    pub message: String,
+    pub files: Vec<String>,
+    /// `attempt_completion` の結果（完了しなかった場合は `None`）
+    pub result: Option<String>,
+    pub error: Option<String>,
+}
+
+impl SubtaskResult {
+    pub fn is_completed(&self) -> bool {
+        self.error.is_none() && self.result.is_some()
+    }
+}
+
+/// サブタスクの結果をまとめてモデルに返すテキストにする
+pub fn format_subtask_results(results: &[SubtaskResult]) -> String {
+    let completed = results
+        .iter()
+        .filter(|result| result.is_completed())
+        .count();
+    let mut text = format!("{} of {} subtasks completed.\n", completed, results.len());
+    for (index, result) in results.iter().enumerate() {
+        text.push_str(&format!(
+            "\n<subtask index=\"{}\" files=\"{}\">\n",
+            index + 1,
+            result.files.join(", ")
+        ));
+        match (&result.error, &result.result) {
+            (Some(error), _) => text.push_str(&format!("Error: {}\n", error)),
+            (None, Some(completion)) => text.push_str(&format!("{}\n", completion.trim_end())),
+            (None, None) => text.push_str("The subtask finished without attempting completion.\n"),
+        }
+        text.push_str("</subtask>\n");
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_format_subtask_results() {
+        let result = |files: &str, result: Option<&str>, error: Option<&str>| SubtaskResult {
+            message: "task".to_string(),
+            files: vec![files.to_string()],
+            result: result.map(String::from),
+            error: error.map(String::from),
+        };
+        assert_eq!(
+            format_subtask_results(&[
+                result("src/api", Some("Refactored the API.\n"), None),
+                result("src/ui", None, Some("API request failed")),
+                result("docs", None, None),
+            ]),
+            "1 of 3 subtasks completed.\n\
+             \n<subtask index=\"1\" files=\"src/api\">\nRefactored the API.\n</subtask>\n\
+             \n<subtask index=\"2\" files=\"src/ui\">\nError: API request failed\n</subtask>\n\
+             \n<subtask index=\"3\" files=\"docs\">\nThe subtask finished without attempting completion.\n</subtask>\n"
+        );
+    }
+}
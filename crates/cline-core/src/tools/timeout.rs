@@ -0,0 +1,103 @@
+use std::future::Future;
+use std::time::Duration;
+
+/// ツールの種類ごとの実行のタイムアウト（`None` で無制限）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ToolTimeouts {
+    /// `execute_command`
+    pub command: Option<Duration>,
+    /// `browser_action`・`get_page_text`
+    pub browser: Option<Duration>,
+    /// `use_mcp_tool`・`access_mcp_resource`
+    pub mcp: Option<Duration>,
+    /// `apply_diff`
+    pub diff: Option<Duration>,
+}
+
+impl Default for ToolTimeouts {
+    fn default() -> Self {
+        Self {
+            command: Some(Duration::from_secs(10 * 60)),
+            browser: Some(Duration::from_secs(2 * 60)),
+            mcp: Some(Duration::from_secs(5 * 60)),
+            diff: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+impl ToolTimeouts {
+    /// タイムアウトしない設定
+    pub fn unlimited() -> Self {
+        Self {
+            command: None,
+            browser: None,
+            mcp: None,
+            diff: None,
+        }
+    }
+
+    /// ツール名に対応するタイムアウト
+    pub fn for_tool(&self, tool: &str) -> Option<Duration> {
+        match tool {
+            "execute_command" => self.command,
+            "browser_action" | "get_page_text" => self.browser,
+            "use_mcp_tool" | "access_mcp_resource" => self.mcp,
+            "apply_diff" => self.diff,
+            _ => None,
+        }
+    }
+}
+
+/// `timeout` までに終わらないツールを中断する（超過した場合は `Err(timeout)`）
+pub async fn watchdog<T>(
+    timeout: Option<Duration>,
+    future: impl Future<Output = T>,
+) -> Result<T, Duration> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future)
+            .await
+            .map_err(|_| timeout),
+        None => Ok(future.await),
+    }
+}
+
+/// タイムアウトしたツールの実行結果としてモデルに返すメッセージ
+pub fn format_tool_timeout(tool: &str, timeout: Duration) -> String {
+    let timeout = match timeout.as_secs() {
+        0 => format!("{}ms", timeout.as_millis()),
+        secs if secs % 60 == 0 => format!("{} minutes", secs / 60),
+        secs => format!("{} seconds", secs),
+    };
+    format!(
+        "The {} tool timed out after {} and was cancelled. If the operation is expected to take longer, try a different approach or split it into smaller steps.",
+        tool, timeout
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_watchdog() {
+        assert_eq!(watchdog(None, async { 1 }).await, Ok(1));
+        let timeout = Duration::from_millis(10);
+        assert_eq!(
+            watchdog(Some(timeout), std::future::pending::<()>()).await,
+            Err(timeout)
+        );
+
+        let timeouts = ToolTimeouts::default();
+        assert_eq!(
+            timeouts.for_tool("apply_diff"),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(timeouts.for_tool("read_file"), None);
+        assert_eq!(ToolTimeouts::unlimited().for_tool("execute_command"), None);
+        assert!(
+            format_tool_timeout("execute_command", Duration::from_secs(600))
+                .starts_with("The execute_command tool timed out after 10 minutes")
+        );
+    }
+}
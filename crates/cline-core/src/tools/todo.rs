@@ -0,0 +1,94 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoStatus {
+    Pending,
+    InProgress,
+    Completed,
+}
+
+/// `update_todo_list` で管理するタスクの項目
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoItem {
+    pub content: String,
+    pub status: TodoStatus,
+}
+
+/// Markdownのチェックリスト（`[ ]`・`[-]`・`[x]`）を解析する（先頭の `-` や `*` は無視する）
+pub fn parse_todo_list(todos: &str) -> Result<Vec<TodoItem>> {
+    let mut items = Vec::new();
+    for line in todos.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let line = line
+            .strip_prefix("- ")
+            .or_else(|| line.strip_prefix("* "))
+            .unwrap_or(line)
+            .trim_start();
+        let (status, content) = if let Some(content) = line.strip_prefix("[ ]") {
+            (TodoStatus::Pending, content)
+        } else if let Some(content) = line.strip_prefix("[-]") {
+            (TodoStatus::InProgress, content)
+        } else if let Some(content) = line
+            .strip_prefix("[x]")
+            .or_else(|| line.strip_prefix("[X]"))
+        {
+            (TodoStatus::Completed, content)
+        } else {
+            anyhow::bail!("Invalid todo item '{}': expected [ ], [-] or [x]", line);
+        };
+        let content = content.trim();
+        if content.is_empty() {
+            anyhow::bail!("Todo item '{}' has no content", line);
+        }
+        items.push(TodoItem {
+            content: content.to_string(),
+            status,
+        });
+    }
+    Ok(items)
+}
+
+/// チェックリストの形式に戻す（環境情報に含める）
+pub fn format_todo_list(items: &[TodoItem]) -> String {
+    items
+        .iter()
+        .map(|item| {
+            let mark = match item.status {
+                TodoStatus::Pending => "[ ]",
+                TodoStatus::InProgress => "[-]",
+                TodoStatus::Completed => "[x]",
+            };
+            format!("{} {}", mark, item.content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_todo_list() {
+        let items =
+            parse_todo_list("\n- [x] Analyze\n[-]  Implement \n* [ ] Test\n[X] Plan\n").unwrap();
+        assert_eq!(
+            items.iter().map(|item| item.status).collect::<Vec<_>>(),
+            vec![
+                TodoStatus::Completed,
+                TodoStatus::InProgress,
+                TodoStatus::Pending,
+                TodoStatus::Completed
+            ]
+        );
+        assert_eq!(
+            format_todo_list(&items),
+            "[x] Analyze\n[-] Implement\n[ ] Test\n[x] Plan"
+        );
+        assert!(parse_todo_list("Implement it").is_err());
+        assert!(parse_todo_list("[ ]").is_err());
+        assert!(parse_todo_list("").unwrap().is_empty());
+    }
+}
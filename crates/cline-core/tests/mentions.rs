@@ -73,7 +73,7 @@ async fn test_parse_mentions_integration() -> Result<()> {
     let mut browser_session = setup_test_browser().await?;
 
     // 単一のファイルメンションのテスト
-    let text = "Check #test.txt";
+    let text = "Check @test.txt";
     let result = parse_mentions(text, &mut browser_session, workspace_path).await?;
     assert!(
         result.contains("This is a test file"),
@@ -90,7 +90,7 @@ async fn test_parse_mentions_with_invalid_paths() -> Result<()> {
     let mut browser_session = setup_test_browser().await?;
 
     // 存在しないファイルのテスト
-    let text = "Check #nonexistent.txt";
+    let text = "Check @nonexistent.txt";
     let result = parse_mentions(text, &mut browser_session, workspace_path).await;
     assert!(result.is_err(), "Should fail with non-existent file");
 
@@ -103,10 +103,10 @@ async fn test_parse_mentions_with_problems() -> Result<()> {
     let workspace_path = temp_dir.path();
     let mut browser_session = setup_test_browser().await?;
 
-    let text = "Check #problems";
+    let text = "Check @problems";
     let result = parse_mentions(text, &mut browser_session, workspace_path).await?;
     assert!(
-        result.contains("#problems"),
+        result.contains("@problems"),
         "Original mention should be included in the result"
     );
     assert!(
@@ -124,7 +124,7 @@ async fn test_parse_mentions_with_urls() -> Result<()> {
     let mut browser_session = setup_test_browser().await?;
 
     // 単一のURLメンションのテスト
-    let text = "Check https://example.com";
+    let text = "Check @https://example.com";
     let result = parse_mentions(text, &mut browser_session, workspace_path).await?;
     assert!(
         result.contains("Example Domain"),
@@ -132,7 +132,7 @@ async fn test_parse_mentions_with_urls() -> Result<()> {
     );
 
     // 複数のURLメンションのテスト
-    let text = "Check https://example.com and https://www.rust-lang.org";
+    let text = "Check @https://example.com and @https://www.rust-lang.org";
     let result = parse_mentions(text, &mut browser_session, workspace_path).await?;
     assert!(
         result.contains("Example Domain") && result.contains("Rust Programming Language"),
@@ -140,7 +140,7 @@ async fn test_parse_mentions_with_urls() -> Result<()> {
     );
 
     // 無効なURLのテスト
-    let text = "Check https://invalid.example.com";
+    let text = "Check @https://invalid.example.com";
     let result = parse_mentions(text, &mut browser_session, workspace_path).await;
     assert!(result.is_err(), "Should fail with invalid URL");
 
@@ -154,7 +154,7 @@ async fn test_git_functionality() -> Result<()> {
     let mut browser_session = setup_test_browser().await?;
 
     // 1. 初期状態のテスト（変更なし）
-    let text = "Check #git";
+    let text = "Check @git";
     let result = parse_mentions(text, &mut browser_session, workspace_path).await?;
     println!("\n=== 初期状態のGit結果 ===\n{}\n", result);
     assert!(
@@ -192,7 +192,7 @@ async fn test_git_functionality() -> Result<()> {
     let commit = head.peel_to_commit()?;
     let commit_hash = commit.id().to_string();
 
-    let text = format!("Check #git:{}", &commit_hash[..7]);
+    let text = format!("Check @git:{}", &commit_hash[..7]);
     let result = parse_mentions(&text, &mut browser_session, workspace_path).await?;
     println!("\n=== コミット情報の結果 ===\n{}\n", result);
     assert!(
@@ -203,7 +203,7 @@ async fn test_git_functionality() -> Result<()> {
 
     // 5. 無効なGitリポジトリのテスト
     let invalid_dir = tempfile::tempdir()?;
-    let text = "Check #git";
+    let text = "Check @git";
     let result = parse_mentions(text, &mut browser_session, invalid_dir.path()).await;
     println!("\n=== 無効なGitリポジトリのエラー ===\n{:?}\n", result);
     assert!(result.is_err(), "Should fail with non-git directory");
@@ -218,6 +218,75 @@ async fn test_git_functionality() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_git_compare_functionality() -> Result<()> {
+    let temp_dir = setup_test_workspace();
+    let workspace_path = temp_dir.path();
+    // Gitのメンションはブラウザを使わない
+    let mut browser_session = BrowserSession::new();
+
+    // featureブランチを作成してコミットを追加
+    let repo = git2::Repository::open(workspace_path)?;
+    let head_commit = repo.head()?.peel_to_commit()?;
+    repo.branch("feature", &head_commit, false)?;
+    repo.set_head("refs/heads/feature")?;
+
+    fs::write(workspace_path.join("feature.txt"), "Feature content").unwrap();
+    let mut index = repo.index()?;
+    index.add_path(std::path::Path::new("feature.txt"))?;
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let signature = git2::Signature::now("Test User", "test@example.com")?;
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        "Add feature file",
+        &tree,
+        &[&head_commit],
+    )?;
+    let base_branch = repo
+        .branches(Some(git2::BranchType::Local))?
+        .filter_map(|b| b.ok())
+        .filter_map(|(b, _)| b.name().ok().flatten().map(String::from))
+        .find(|name| name != "feature")
+        .unwrap();
+
+    let text = format!("Review @git-compare:{}..feature", base_branch);
+    let result = parse_mentions(&text, &mut browser_session, workspace_path).await?;
+    println!("\n=== ブランチ比較の結果 ===\n{}\n", result);
+    assert!(
+        result.contains("Add feature file"),
+        "Should list commits on the compared branch"
+    );
+    assert!(
+        result.contains("feature.txt"),
+        "Should include the diffstat"
+    );
+    assert!(
+        !result.contains("Initial commit"),
+        "Should not list commits shared with the base"
+    );
+
+    // 相対参照のコミット指定
+    let result = parse_mentions("Check @git:HEAD~1", &mut browser_session, workspace_path).await?;
+    assert!(
+        result.contains("Initial commit"),
+        "Should resolve relative revisions"
+    );
+
+    // 存在しないブランチ
+    let result = parse_mentions(
+        "Review @git-compare:main..does-not-exist",
+        &mut browser_session,
+        workspace_path,
+    )
+    .await;
+    assert!(result.is_err(), "Should fail with unknown revision");
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_parse_mentions_with_mixed_content() -> Result<()> {
     let temp_dir = setup_test_workspace();
@@ -225,7 +294,7 @@ async fn test_parse_mentions_with_mixed_content() -> Result<()> {
     let mut browser_session = setup_test_browser().await?;
 
     // ファイル、URL、Git変更の組み合わせテスト
-    let text = "Check #test.txt and https://example.com";
+    let text = "Check @test.txt and @https://example.com";
     let result = parse_mentions(text, &mut browser_session, workspace_path).await?;
     println!("\n=== ファイルとURLの組み合わせ結果 ===\n{}\n", result);
 
@@ -240,7 +309,7 @@ async fn test_parse_mentions_with_mixed_content() -> Result<()> {
 
     // Gitの変更を追加
     fs::write(workspace_path.join("test.txt"), "Modified content").unwrap();
-    let text = "Check #git";
+    let text = "Check @git";
     let result = parse_mentions(text, &mut browser_session, workspace_path).await?;
     println!("\n=== Git変更の結果 ===\n{}\n", result);
     assert!(
@@ -253,10 +322,10 @@ async fn test_parse_mentions_with_mixed_content() -> Result<()> {
 
 #[test]
 fn test_should_process_mentions_integration() {
-    assert!(should_process_mentions("Check #test.txt"));
-    assert!(should_process_mentions("Check https://example.com"));
+    assert!(should_process_mentions("Check @test.txt"));
+    assert!(should_process_mentions("Check @https://example.com"));
     assert!(!should_process_mentions("No mentions here"));
-    assert!(should_process_mentions("#git changes"));
-    assert!(should_process_mentions("Multiple #mentions #here"));
-    assert!(!should_process_mentions("Text with hash# but not mention"));
+    assert!(should_process_mentions("@git changes"));
+    assert!(should_process_mentions("Multiple @mentions @here"));
+    assert!(!should_process_mentions("Text with at@ but not mention"));
 }
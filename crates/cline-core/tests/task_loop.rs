@@ -0,0 +1,581 @@
+use anyhow::Result;
+use cline_core::services::anthropic::ApiStreamChunk;
+use cline_core::services::anthropic::ContentBlock;
+use cline_core::services::api::{ScriptedTurn, TaskHarness};
+use cline_core::services::git::{AutoCommitConfig, CommitAuthor};
+use cline_core::services::notification::{Notification, NotificationKind, NotificationSink};
+use cline_core::tools::Subtask;
+use cline_core::{
+    AskResponse, BudgetApprover, BudgetLimit, BudgetUsage, ClineError, MessageEvent,
+    MessageListener, TaskBudget, TaskHook, ToolResponse, VerifyConfig,
+};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[tokio::test]
+async fn test_task_loop_retries_until_tool_use() -> Result<()> {
+    let mut harness = TaskHarness::new([
+        ScriptedTurn::text("Let me think about it.").with_usage(100, 10),
+        ScriptedTurn::text("<attempt_completion>\n<result>Done</result>\n</attempt_completion>")
+            .with_usage(120, 20),
+    ])?;
+    harness.write_file("src/main.rs", "fn main() {}")?;
+
+    harness.run("Fix the bug").await?;
+
+    let requests = harness.provider().requests();
+    assert_eq!(requests.len(), 2);
+    let ContentBlock::Text { text } = &requests[0][0] else {
+        panic!("expected text block, got {:?}", requests[0][0]);
+    };
+    assert!(text.starts_with("<task>\nFix the bug\n</task>"));
+    assert!(text.contains(&harness.workspace_path().display().to_string()));
+    assert_eq!(
+        requests[1],
+        vec![ContentBlock::text(
+            "No tools were used in the response. Please either use a tool or attempt completion."
+        )]
+    );
+    assert_eq!(harness.provider().remaining(), 0);
+
+    // user → assistant → user → assistant
+    let history = harness.cline().conversation_history();
+    assert_eq!(history.len(), 4);
+    assert_eq!(
+        history[3].text(),
+        "<attempt_completion>\n<result>Done</result>\n</attempt_completion>"
+    );
+    assert!(harness.cline().total_cost() > 0.0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_task_loop_returns_xml_tool_results() -> Result<()> {
+    let mut harness = TaskHarness::new([
+        ScriptedTurn::text("<read_file>\n<path>src/main.rs</path>\n</read_file>"),
+        ScriptedTurn::text("<attempt_completion>\n<result>Done</result>\n</attempt_completion>"),
+    ])?;
+    harness.write_file("src/main.rs", "fn main() {}")?;
+
+    harness.run("Read main.rs").await?;
+
+    // 次のリクエストで `[ツール for 'パス'] Result:` に続けて結果を返す
+    let requests = harness.provider().requests();
+    assert_eq!(requests.len(), 2);
+    let [ContentBlock::Text { text }] = requests[1].as_slice() else {
+        panic!("expected a single text block, got {:?}", requests[1]);
+    };
+    assert!(text.starts_with("[read_file for 'src/main.rs'] Result:\n"));
+    assert!(text.contains("fn main() {}"));
+    // 送るリクエストには、それまでの会話履歴をすべて含める
+    let messages = harness.provider().request_messages();
+    assert_eq!(messages[1].len(), 3);
+    assert_eq!(
+        messages[1][1].text(),
+        "<read_file>\n<path>src/main.rs</path>\n</read_file>"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_task_loop_runs_tools_in_order() -> Result<()> {
+    let mut harness = TaskHarness::new([
+        ScriptedTurn::default()
+            .with_tool_use(
+                "toolu_1",
+                "write_to_file",
+                serde_json::json!({ "path": "notes.txt", "content": "hello\n", "line_count": 1 }),
+            )
+            .with_tool_use(
+                "toolu_2",
+                "read_file",
+                serde_json::json!({ "path": "notes.txt" }),
+            ),
+        ScriptedTurn::text("<attempt_completion>\n<result>Done</result>\n</attempt_completion>"),
+    ])?;
+
+    harness.run("Write notes").await?;
+
+    assert_eq!(
+        std::fs::read_to_string(harness.workspace_path().join("notes.txt"))?,
+        "hello\n"
+    );
+    let requests = harness.provider().requests();
+    let ids: Vec<_> = requests[1]
+        .iter()
+        .map(|block| match block {
+            ContentBlock::ToolResult { tool_use_id, .. } => tool_use_id.as_str(),
+            block => panic!("expected tool_result block, got {:?}", block),
+        })
+        .collect();
+    assert_eq!(ids, vec!["toolu_1", "toolu_2"]);
+    assert!(matches!(
+        &requests[1][1],
+        ContentBlock::ToolResult { content, is_error: None, .. }
+            if matches!(content.as_slice(), [ContentBlock::Text { text }] if text.contains("hello"))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_task_loop_accepts_native_tool_use() -> Result<()> {
+    let mut harness = TaskHarness::new([
+        ScriptedTurn::default().with_tool_use(
+            "toolu_1",
+            "read_file",
+            serde_json::json!({ "path": "src/main.rs" }),
+        ),
+        ScriptedTurn::text("<attempt_completion>\n<result>Done</result>\n</attempt_completion>"),
+    ])?;
+    harness.write_file("src/main.rs", "fn main() {}")?;
+
+    harness.run("Read main.rs").await?;
+
+    let history = harness.cline().conversation_history();
+    assert!(matches!(
+        history[1].content.as_slice(),
+        [ContentBlock::ToolUse { name, .. }] if name == "read_file"
+    ));
+    // 次のリクエストでツール使用の `id` に対応する結果を返す
+    let requests = harness.provider().requests();
+    assert_eq!(requests.len(), 2);
+    let [ContentBlock::ToolResult {
+        tool_use_id,
+        content,
+        is_error: None,
+    }] = requests[1].as_slice()
+    else {
+        panic!("expected tool_result block, got {:?}", requests[1]);
+    };
+    assert_eq!(tool_use_id, "toolu_1");
+    assert!(matches!(
+        content.as_slice(),
+        [ContentBlock::Text { text }] if text.contains("fn main() {}")
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_task_loop_surfaces_api_errors() -> Result<()> {
+    let mut harness = TaskHarness::new([ScriptedTurn::error("overloaded")])?;
+
+    let error = harness.run("Fix the bug").await.unwrap_err();
+    assert_eq!(error.to_string(), "API request failed: overloaded");
+    assert!(matches!(
+        error,
+        ClineError::Provider {
+            status: None,
+            retryable: false,
+            ..
+        }
+    ));
+    let logs = harness.cline().recent_logs(1)?;
+    assert_eq!(
+        logs[0].message,
+        "API request failed: API request failed: overloaded"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_task_loop_commits_checkpoint_on_completion() -> Result<()> {
+    let mut harness = TaskHarness::new([ScriptedTurn::text(
+        "<attempt_completion>\n<result>Done</result>\n</attempt_completion>",
+    )])?;
+    let repo = git2::Repository::init(harness.workspace_path())?;
+    harness.write_file("main.rs", "fn main() {}\n")?;
+    let mut index = repo.index()?;
+    index.add_path(std::path::Path::new("main.rs"))?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let signature = git2::Signature::now("test", "test@example.com")?;
+    repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+
+    harness.cline_mut().set_auto_commit(Some(AutoCommitConfig {
+        author: CommitAuthor {
+            name: "Agent".to_string(),
+            email: "agent@example.com".to_string(),
+        },
+        ..Default::default()
+    }));
+    harness
+        .cline_mut()
+        .write_to_file_tool("main.rs", "fn main() { run() }\n")
+        .await?;
+    harness.run("Fix the bug").await?;
+
+    let task_id = harness.cline().task_id().to_string();
+    let commit = repo
+        .find_branch(&format!("cline/{}", task_id), git2::BranchType::Local)?
+        .get()
+        .peel_to_commit()?;
+    assert_eq!(
+        commit.message(),
+        Some(format!("cline: checkpoint 1 for task {}\n\n- main.rs", task_id).as_str())
+    );
+    assert_eq!(commit.author().name(), Some("Agent"));
+    assert!(harness
+        .cline()
+        .cline_messages()
+        .iter()
+        .any(|message| { message.text() == Some(commit.id().to_string().as_str()) }));
+
+    // 変更がなければ次のチェックポイントは作成しない
+    assert_eq!(harness.cline_mut().save_checkpoint_commit().await?, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_subtasks_run_in_parallel() -> Result<()> {
+    let completion = "<attempt_completion>\n<result>Migrated</result>\n</attempt_completion>";
+    let mut harness = TaskHarness::new([
+        ScriptedTurn::text(completion),
+        ScriptedTurn::text(completion),
+    ])?;
+    harness.write_file("src/api/mod.rs", "pub fn api() {}")?;
+    harness.write_file("src/ui/mod.rs", "pub fn ui() {}")?;
+
+    let subtask = |message: &str, files: &str| Subtask {
+        message: message.to_string(),
+        files: vec![files.to_string()],
+    };
+    let overlapping = harness
+        .cline_mut()
+        .run_subtasks_in_parallel(vec![
+            subtask("Migrate src", "src"),
+            subtask("Migrate UI", "src/ui"),
+        ])
+        .await;
+    assert!(overlapping.is_err());
+    assert!(harness.provider().requests().is_empty());
+
+    let results = harness
+        .cline_mut()
+        .run_subtasks_in_parallel(vec![
+            subtask("Migrate the API", "src/api"),
+            subtask("Migrate the UI", "src/ui"),
+        ])
+        .await?;
+
+    assert_eq!(harness.provider().remaining(), 0);
+    let mut tasks: Vec<String> = harness
+        .provider()
+        .requests()
+        .iter()
+        .map(|request| match &request[0] {
+            ContentBlock::Text { text } => text.clone(),
+            block => panic!("expected text block, got {:?}", block),
+        })
+        .collect();
+    tasks.sort();
+    assert!(tasks[0].starts_with("<task>\nMigrate the API\n</task>"));
+    assert!(tasks[1].starts_with("<task>\nMigrate the UI\n</task>"));
+    assert_eq!(results.len(), 2);
+    assert!(results
+        .iter()
+        .all(|result| result.is_completed() && result.result.as_deref() == Some("Migrated")));
+    assert_eq!(results[1].files, vec!["src/ui"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_queued_user_messages_are_sent_with_next_request() -> Result<()> {
+    let mut harness = TaskHarness::new([
+        ScriptedTurn::text("Working on it."),
+        ScriptedTurn::text("<attempt_completion>\n<result>Done</result>\n</attempt_completion>"),
+    ])?;
+    let queue = harness.cline().user_message_queue();
+    queue.push("Please also update the README.", None);
+    assert_eq!(queue.len(), 1);
+
+    harness.run("Fix the bug").await?;
+
+    assert!(queue.is_empty());
+    let requests = harness.provider().requests();
+    assert_eq!(
+        requests[0].last(),
+        Some(&ContentBlock::text(
+            "<user_message>\nPlease also update the README.\n</user_message>"
+        ))
+    );
+    assert_eq!(requests[1].len(), 1);
+    Ok(())
+}
+
+/// 1回目の完了では失敗を報告し、2回目で通すフック
+#[derive(Debug, Default)]
+struct LintHook {
+    events: std::sync::Mutex<Vec<String>>,
+}
+
+#[async_trait::async_trait]
+impl TaskHook for LintHook {
+    async fn on_task_start(&self, task: Option<&str>) -> Result<()> {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("start: {}", task.unwrap_or_default()));
+        Ok(())
+    }
+
+    async fn on_tool_result(&self, tool: &str, _response: &ToolResponse) -> Result<()> {
+        self.events.lock().unwrap().push(format!("tool: {}", tool));
+        Ok(())
+    }
+
+    async fn on_completion(&self, result: Option<&str>) -> Result<Option<String>> {
+        let mut events = self.events.lock().unwrap();
+        events.push(format!("completion: {}", result.unwrap_or_default()));
+        let completions = events
+            .iter()
+            .filter(|e| e.starts_with("completion"))
+            .count();
+        Ok((completions == 1).then(|| "cargo clippy failed: unused variable `x`".to_string()))
+    }
+}
+
+#[tokio::test]
+async fn test_completion_hook_feedback_continues_task() -> Result<()> {
+    let mut harness = TaskHarness::new([
+        ScriptedTurn::text("<attempt_completion>\n<result>Done</result>\n</attempt_completion>"),
+        ScriptedTurn::text(
+            "<attempt_completion>\n<result>Fixed lint</result>\n</attempt_completion>",
+        ),
+    ])?;
+    let hook = Arc::new(LintHook::default());
+    harness.cline_mut().add_hook(hook.clone());
+    harness
+        .cline_mut()
+        .write_to_file_tool("main.rs", "fn main() {}\n")
+        .await?;
+
+    harness.run("Fix the bug").await?;
+
+    let requests = harness.provider().requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[1],
+        vec![ContentBlock::text(
+            "<feedback>\ncargo clippy failed: unused variable `x`\n</feedback>"
+        )]
+    );
+    assert_eq!(harness.cline().completion_result(), Some("Fixed lint"));
+    assert_eq!(
+        *hook.events.lock().unwrap(),
+        vec![
+            "tool: write_to_file",
+            "start: Fix the bug",
+            "completion: Done",
+            "completion: Fixed lint",
+        ]
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_verify_command_feedback_until_retry_cap() -> Result<()> {
+    let mut harness = TaskHarness::new([
+        ScriptedTurn::text("<attempt_completion>\n<result>Done</result>\n</attempt_completion>"),
+        ScriptedTurn::text("<attempt_completion>\n<result>Done</result>\n</attempt_completion>"),
+    ])?;
+    harness.cline_mut().set_verify(Some(VerifyConfig {
+        max_retries: 1,
+        ..VerifyConfig::new("echo 'test it_works ... FAILED'; exit 101")
+    }));
+
+    harness.run("Fix the bug").await?;
+
+    // 上限に達した後の完了では検証の失敗を伝えない
+    let requests = harness.provider().requests();
+    assert_eq!(requests.len(), 2);
+    let ContentBlock::Text { text } = &requests[1][0] else {
+        panic!("expected text block, got {:?}", requests[1][0]);
+    };
+    assert!(text.starts_with("<feedback>\nThe verification command"));
+    assert!(text.contains("(exit code 101, attempt 1 of 1)"));
+    assert!(text.contains("test it_works ... FAILED"));
+    Ok(())
+}
+
+/// 指定した回数だけ続行を承認する
+#[derive(Debug, Default)]
+struct CountingApprover {
+    remaining: AtomicU32,
+    asked: AtomicU32,
+}
+
+#[async_trait::async_trait]
+impl BudgetApprover for CountingApprover {
+    async fn approve(&self, _limit: &BudgetLimit, _usage: &BudgetUsage) -> bool {
+        self.asked.fetch_add(1, Ordering::SeqCst);
+        self.remaining
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+    }
+}
+
+#[tokio::test]
+async fn test_budget_stops_runaway_task() -> Result<()> {
+    let turns = (0..10).map(|i| ScriptedTurn::text(format!("Thinking {}", i)).with_usage(100, 10));
+    let mut harness = TaskHarness::new(turns)?;
+    let approver = Arc::new(CountingApprover {
+        remaining: AtomicU32::new(1),
+        ..Default::default()
+    });
+    harness.cline_mut().set_budget(Some(TaskBudget {
+        max_requests: Some(2),
+        ..Default::default()
+    }));
+    harness.cline_mut().set_budget_approver(approver.clone());
+
+    let error = harness.run("Fix the bug").await.unwrap_err();
+    assert!(matches!(error, ClineError::BudgetExceeded(_)));
+    assert_eq!(
+        error.to_string(),
+        "Task stopped: request budget of 2 reached (2 used)"
+    );
+    // 1回目の確認で続行し、さらに2回リクエストした後の確認で停止する
+    assert_eq!(approver.asked.load(Ordering::SeqCst), 2);
+    assert_eq!(harness.provider().requests().len(), 4);
+    assert_eq!(harness.cline().budget_usage().tokens, 440);
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct RecordingSink(Mutex<Vec<Notification>>);
+
+#[async_trait::async_trait]
+impl NotificationSink for RecordingSink {
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        self.0.lock().unwrap().push(notification.clone());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_notifies_on_budget_ask_and_completion() -> Result<()> {
+    let mut harness = TaskHarness::new([
+        ScriptedTurn::text("Thinking"),
+        ScriptedTurn::text("<attempt_completion>\n<result>Done</result>\n</attempt_completion>"),
+    ])?;
+    let sink = Arc::new(RecordingSink::default());
+    harness.cline_mut().set_notification_sink(sink.clone());
+    harness.cline_mut().set_budget(Some(TaskBudget {
+        max_requests: Some(1),
+        ..Default::default()
+    }));
+    harness
+        .cline_mut()
+        .set_budget_approver(Arc::new(CountingApprover {
+            remaining: AtomicU32::new(1),
+            ..Default::default()
+        }));
+
+    harness.run("Fix the bug").await?;
+
+    let notifications = sink.0.lock().unwrap();
+    let summary: Vec<_> = notifications
+        .iter()
+        .map(|n| (n.kind, n.title.as_str(), n.message.as_str()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                NotificationKind::AttentionRequired,
+                "Budget exceeded",
+                "The task request budget of 1 reached (1 used)"
+            ),
+            (NotificationKind::TaskCompleted, "Task completed", "Done"),
+        ]
+    );
+    assert_eq!(notifications[1].task_id, harness.cline().task_id());
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct RecordingListener(Mutex<Vec<MessageEvent>>);
+
+impl MessageListener for RecordingListener {
+    fn on_message_event(&self, event: &MessageEvent) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+#[tokio::test]
+async fn test_streamed_partial_messages_reach_task() -> Result<()> {
+    let mut harness = TaskHarness::new([ScriptedTurn::new(vec![
+        ApiStreamChunk::Reasoning("Check main".to_string()),
+        ApiStreamChunk::Text("<attempt_completion>\n<result>".to_string()),
+        ApiStreamChunk::Text("Done</result>\n</attempt_completion>".to_string()),
+    ])])?;
+    let listener = Arc::new(RecordingListener::default());
+    harness.cline_mut().add_message_listener(listener.clone());
+
+    harness.run("Fix the bug").await?;
+
+    let partial_texts: Vec<_> = listener
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|event| match event {
+            MessageEvent::Added { message, .. } | MessageEvent::Updated { message, .. }
+                if message.is_partial() =>
+            {
+                message.text().map(String::from)
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        partial_texts,
+        vec![
+            "Check main",
+            "<attempt_completion>\n<result>",
+            "<attempt_completion>\n<result>Done</result>\n</attempt_completion>",
+        ]
+    );
+    // 部分メッセージは完了したメッセージに置き換わり、重複して残らない
+    let messages = harness.cline().cline_messages();
+    assert!(messages.iter().all(|message| !message.is_partial()));
+    let texts: Vec<_> = messages.iter().skip(2).filter_map(|m| m.text()).collect();
+    assert_eq!(
+        texts,
+        vec![
+            "Check main",
+            "<attempt_completion>\n<result>Done</result>\n</attempt_completion>",
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_abort_cancels_stream_and_shutdown_persists_messages() -> Result<()> {
+    let mut harness = TaskHarness::new([ScriptedTurn::text("Looking into").with_hang()])?;
+    let handle = harness.cline().abort_handle();
+    // リクエストが応答を待ち始めた後に中断する
+    tokio::spawn(async move { handle.abort() });
+
+    let error = harness.run("Fix the bug").await.unwrap_err();
+    assert!(error.is_aborted());
+    assert!(harness.cline().is_aborted());
+    let (response, _, _) = harness
+        .cline_mut()
+        .ask("followup".to_string(), Some("Continue?".to_string()), None)
+        .await?;
+    assert!(matches!(response, AskResponse::Aborted));
+
+    harness.cline_mut().shutdown().await?;
+    let saved = harness.cline().get_saved_cline_messages().await?;
+    let streamed = saved
+        .iter()
+        .find(|message| message.text() == Some("Looking into"))
+        .expect("streamed text should be saved");
+    assert!(!streamed.is_partial());
+    assert!(saved[1]
+        .text()
+        .is_some_and(|text| text.contains("\"cancelReason\":\"user_cancelled\"")));
+    assert!(harness.run("Fix the bug").await.is_err());
+    Ok(())
+}
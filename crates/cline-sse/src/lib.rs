@@ -0,0 +1,211 @@
+//! Server-Sent Eventsの逐次デコーダ（cline-coreとcline-wasmで共有する）
+
+/// 受信したイベント
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// `event:` で指定された種類（未指定の場合は `None`）
+    pub event: Option<String>,
+    /// `data:` の行を改行で連結した内容
+    pub data: String,
+    pub id: Option<String>,
+}
+
+impl SseEvent {
+    /// イベントの種類（未指定の場合は `message`）
+    pub fn event_type(&self) -> &str {
+        self.event.as_deref().unwrap_or("message")
+    }
+}
+
+/// SSEのバイト列からイベントを組み立てる
+///
+/// ネットワークのチャンク境界で行やマルチバイト文字、`\r\n` が分割されても扱える。
+/// 改行は `\n`・`\r\n`・`\r` のいずれも受け付け、空行でイベントを確定する。
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+    /// 直前のチャンクが `\r` で終わった（続く `\n` を無視する）
+    pending_cr: bool,
+    event: Option<String>,
+    data: Option<String>,
+    id: Option<String>,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// バイト列を追加し、確定したイベントを返す
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        // 空のチャンクでは直前の `\r` の後に `\n` が続くか判断できない
+        if bytes.is_empty() {
+            return Vec::new();
+        }
+        let mut bytes = bytes;
+        if self.pending_cr {
+            self.pending_cr = false;
+            bytes = bytes.strip_prefix(b"\n").unwrap_or(bytes);
+        }
+        self.buffer.extend_from_slice(bytes);
+
+        let mut events = Vec::new();
+        let mut start = 0;
+        while let Some(offset) = self.buffer[start..]
+            .iter()
+            .position(|&b| b == b'\n' || b == b'\r')
+        {
+            let end = start + offset;
+            let line = String::from_utf8_lossy(&self.buffer[start..end]).into_owned();
+            start = end + 1;
+            if self.buffer[end] == b'\r' {
+                match self.buffer.get(start) {
+                    Some(b'\n') => start += 1,
+                    Some(_) => {}
+                    None => self.pending_cr = true,
+                }
+            }
+            events.extend(self.process_line(&line));
+        }
+        self.buffer.drain(..start);
+        events
+    }
+
+    /// ストリームの終了時に、空行で確定していない最後のイベントを返す
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        let line = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+        self.pending_cr = false;
+        if !line.is_empty() {
+            if let Some(event) = self.process_line(&line) {
+                return Some(event);
+            }
+        }
+        self.dispatch()
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        // `:` で始まる行はコメント
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => match &mut self.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            },
+            "id" => self.id = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+
+    /// `data:` のないイベントは捨てる
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        let data = self.data.take()?;
+        Some(SseEvent {
+            event,
+            data,
+            id: self.id.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_in_pieces(input: &[u8], size: usize) -> Vec<SseEvent> {
+        let mut decoder = SseDecoder::new();
+        let mut events: Vec<_> = input
+            .chunks(size)
+            .flat_map(|piece| decoder.push(piece))
+            .collect();
+        events.extend(decoder.finish());
+        events
+    }
+
+    fn event(event: Option<&str>, data: &str) -> SseEvent {
+        SseEvent {
+            event: event.map(str::to_string),
+            data: data.to_string(),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn test_split_events() {
+        let input = "event: ping\ndata: {\"type\":\"ping\"}\n\ndata: caf\u{e9}\n\n".as_bytes();
+        let expected = vec![
+            event(Some("ping"), "{\"type\":\"ping\"}"),
+            event(None, "caf\u{e9}"),
+        ];
+        // どの位置で分割しても同じイベントになる
+        for size in 1..input.len() {
+            assert_eq!(decode_in_pieces(input, size), expected, "size {}", size);
+        }
+        assert_eq!(expected[0].event_type(), "ping");
+        assert_eq!(expected[1].event_type(), "message");
+    }
+
+    #[test]
+    fn test_line_ending_variants() {
+        let expected = vec![event(Some("delta"), "a\nb"), event(None, "c")];
+        for input in [
+            "event: delta\ndata: a\ndata: b\n\ndata: c\n\n",
+            "event: delta\r\ndata: a\r\ndata: b\r\n\r\ndata: c\r\n\r\n",
+            "event: delta\rdata: a\rdata: b\r\rdata: c\r\r",
+        ] {
+            for size in 1..input.len() {
+                assert_eq!(
+                    decode_in_pieces(input.as_bytes(), size),
+                    expected,
+                    "{:?} size {}",
+                    input,
+                    size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty_chunk_after_cr() {
+        let mut decoder = SseDecoder::new();
+        assert_eq!(decoder.push(b"data: x\r"), vec![]);
+        assert_eq!(decoder.push(b""), vec![]);
+        // `\n` は直前の `\r` と合わせて1つの改行になり、`x` と `y` は同じイベントになる
+        assert_eq!(decoder.push(b"\ndata: y\n\n"), vec![event(None, "x\ny")]);
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[test]
+    fn test_fields_and_comments() {
+        let mut decoder = SseDecoder::new();
+        let events = decoder.push(
+            b": keep-alive\nid: 7\nevent: error\ndata:{\"x\":1}\nretry: 10\n\nevent: empty\n\n",
+        );
+        assert_eq!(
+            events,
+            vec![SseEvent {
+                event: Some("error".to_string()),
+                data: "{\"x\":1}".to_string(),
+                id: Some("7".to_string()),
+            }]
+        );
+        // 空行で確定していない最後のイベントは終了時に返す
+        assert!(decoder.push(b"data: [DONE]").is_empty());
+        assert_eq!(decoder.finish().map(|e| e.data).as_deref(), Some("[DONE]"));
+        assert_eq!(decoder.finish(), None);
+    }
+}
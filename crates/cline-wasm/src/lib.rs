@@ -1,21 +1,177 @@
+//! ブラウザからMessages APIを呼ぶためのwasmバインディング
+//!
+//! タスクの実行（cline-coreの `Cline`）はwasm32向けにビルドせず、ストリーミングのリクエストとSSEのデコードだけを提供する。
+//! cline-coreはコマンドの実行（`tokio::process`）・ブラウザの操作（headless_chrome）・Git（git2）・
+//! ファイルの監視（notify）などネイティブの環境を前提にしており、ホストのJSのコールバックで置き換えるには
+//! これらを機能フラグで切り離す必要があるため。
+
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 
+use cline_sse::{SseDecoder, SseEvent};
+use futures_util::future::{AbortHandle, Abortable, Aborted};
 use futures_util::StreamExt;
 use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen::JsValue;
 
-const API_KEY: &str = "";
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+const DEFAULT_MODEL: &str = "claude-3-5-sonnet-latest";
+const DEFAULT_MAX_TOKENS: u32 = 1000;
+
+/// `ClineClient` のオプション（JSのオブジェクトから読み込む）
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClientOptions {
+    api_key: Option<String>,
+    base_url: Option<String>,
+    model: Option<String>,
+    max_tokens: Option<u32>,
+    /// 指定するとAPIキーを付けずにこのURLへ送る（キーはバックエンドで付与する）
+    ///
+    /// `/api/claude` のような相対URLはページのオリジンを基準にする。
+    proxy_url: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ClientConfig {
+    api_key: Option<String>,
+    endpoint: String,
+    model: String,
+    max_tokens: u32,
+}
+
+impl ClientConfig {
+    /// `origin` はページのオリジン（`window.location.origin`）で、相対URLの `proxyUrl` の解決に使う
+    fn from_options(options: ClientOptions, origin: Option<&str>) -> anyhow::Result<Self> {
+        let api_key = options.api_key.filter(|key| !key.is_empty());
+        // プロキシ経由の場合はキーをブラウザから送らない
+        let (api_key, base_url) = match (options.proxy_url, api_key) {
+            (Some(proxy_url), _) => (None, resolve_proxy_url(&proxy_url, origin)?),
+            (None, Some(api_key)) => (
+                Some(api_key),
+                options
+                    .base_url
+                    .unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+            ),
+            (None, None) => anyhow::bail!("Either apiKey or proxyUrl is required"),
+        };
+        Ok(Self {
+            api_key,
+            endpoint: format!("{}/v1/messages", base_url.trim_end_matches('/')),
+            model: options.model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            max_tokens: options.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        })
+    }
+
+    fn request(&self, message: String, stream: bool) -> reqwest::RequestBuilder {
+        let request_body = ClaudeRequest {
+            model: self.model.clone(),
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: message,
+            }],
+            max_tokens: self.max_tokens,
+            stream,
+        };
+        let request = reqwest::Client::new()
+            .post(&self.endpoint)
+            .header("accept", "application/json")
+            .header("content-type", "application/json")
+            .header("anthropic-version", "2023-06-01")
+            .json(&request_body);
+        match &self.api_key {
+            // ブラウザから直接呼び出す場合はCORSの許可を求める
+            Some(api_key) => request
+                .header("x-api-key", api_key)
+                .header("anthropic-dangerous-direct-browser-access", "true"),
+            None => request,
+        }
+    }
+}
+
+/// `proxyUrl` を絶対URLにする（reqwestは相対URLに送れない）
+fn resolve_proxy_url(proxy_url: &str, origin: Option<&str>) -> anyhow::Result<String> {
+    if let Ok(url) = reqwest::Url::parse(proxy_url) {
+        return Ok(url.to_string());
+    }
+    let Some(origin) = origin else {
+        anyhow::bail!(
+            "proxyUrl must be an absolute URL outside a browser page: {}",
+            proxy_url
+        );
+    };
+    let url = reqwest::Url::parse(origin)
+        .and_then(|origin| origin.join(proxy_url))
+        .map_err(|e| anyhow::anyhow!("Invalid proxyUrl {}: {}", proxy_url, e))?;
+    Ok(url.to_string())
+}
+
+/// ページのオリジン（Workerなど `location` のない環境では `None`）
+fn page_origin() -> Option<String> {
+    let location = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("location")).ok()?;
+    js_sys::Reflect::get(&location, &JsValue::from_str("origin"))
+        .ok()?
+        .as_string()
+        .filter(|origin| origin != "null")
+}
 
+/// JSから使うClaude APIのクライアント
+///
+/// `new ClineClient({ apiKey, baseUrl, model, maxTokens, proxyUrl })` で作成する。
 #[wasm_bindgen]
-pub async fn hellp_world() -> Result<String, JsValue> {
-    console_error_panic_hook::set_once();
-    tracing_wasm::try_set_as_global_default()
-        .unwrap_or_else(|e| tracing::warn!("failed to set tracing: {}", e));
+pub struct ClineClient {
+    config: ClientConfig,
+}
 
-    claude_api("こんにちは！")
-        .await
-        .map_err(|e| JsValue::from_str(e.to_string().as_str()))
+#[wasm_bindgen]
+impl ClineClient {
+    #[wasm_bindgen(constructor)]
+    pub fn new(options: JsValue) -> Result<ClineClient, JsValue> {
+        console_error_panic_hook::set_once();
+        tracing_wasm::try_set_as_global_default()
+            .unwrap_or_else(|e| tracing::warn!("failed to set tracing: {}", e));
+
+        let options = if options.is_undefined() || options.is_null() {
+            ClientOptions::default()
+        } else {
+            let json = js_sys::JSON::stringify(&options)?
+                .as_string()
+                .unwrap_or_default();
+            serde_json::from_str(&json).map_err(|e| to_js_error(e.into()))?
+        };
+        let config =
+            ClientConfig::from_options(options, page_origin().as_deref()).map_err(to_js_error)?;
+        Ok(ClineClient { config })
+    }
+
+    pub fn model(&self) -> String {
+        self.config.model.clone()
+    }
+
+    /// メッセージを送り、応答のテキストを返す
+    pub async fn send_message(&self, message: String) -> Result<String, JsValue> {
+        claude_api(&self.config, message).await.map_err(to_js_error)
+    }
+
+    /// 応答をストリーミングし、テキストの差分ごとに `callback` を呼び出す
+    pub fn stream_response(&self, message: String, callback: js_sys::Function) -> StreamHandle {
+        let (abort, registration) = AbortHandle::new_pair();
+        let stream = Abortable::new(
+            stream_deltas(self.config.clone(), message, callback),
+            registration,
+        );
+        let done = wasm_bindgen_futures::future_to_promise(async move {
+            match stream.await {
+                Ok(result) => result.map(|()| JsValue::undefined()),
+                Err(Aborted) => Err(JsValue::from_str("Stream aborted")),
+            }
+        });
+        StreamHandle { abort, done }
+    }
+}
+
+fn to_js_error(error: anyhow::Error) -> JsValue {
+    JsValue::from_str(&error.to_string())
 }
 
 #[derive(Serialize)]
@@ -56,36 +212,38 @@ struct StreamResponse {
 struct Delta {
     #[serde(rename = "type")]
     delta_type: String,
+    #[serde(default)]
     text: String,
 }
 
-pub async fn claude_api(message: &str) -> anyhow::Result<String> {
-    tracing::info!("start claude api process");
-    let client = reqwest::Client::new();
-
-    let request_body = ClaudeRequest {
-        model: "claude-3-5-sonnet-latest".to_string(),
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: message.to_string(),
-        }],
-        max_tokens: 1000,
-        stream: false,
-    };
+/// SSEのイベントからテキストの差分を取り出す
+fn text_delta(event: &SseEvent) -> Option<String> {
+    let data = event.data.trim();
+    if data.is_empty() || data == "[DONE]" {
+        tracing::debug!("Stream completed");
+        return None;
+    }
+    match serde_json::from_str::<StreamResponse>(data) {
+        Ok(response) => response
+            .delta
+            .filter(|delta| delta.delta_type == "text_delta")
+            .map(|delta| delta.text),
+        Err(e) => {
+            tracing::warn!("Failed to parse response JSON: {}", e);
+            None
+        }
+    }
+}
 
-    let response = client
-        .post("https://api.anthropic.com/v1/messages")
-        .header("accept", "application/json")
-        .header("content-type", "application/json")
-        .header("x-api-key", API_KEY)
-        .header("anthropic-version", "2023-06-01")
-        .json(&request_body)
-        .send()
-        .await?;
+async fn claude_api(config: &ClientConfig, message: String) -> anyhow::Result<String> {
+    tracing::info!("start claude api process");
+    let response = config.request(message, false).send().await?;
 
     if response.status() != StatusCode::OK {
-        tracing::error!("error {}", &response.text().await?);
-        anyhow::bail!("http status")
+        let status = response.status();
+        let error_text = response.text().await?;
+        tracing::error!("error {}", &error_text);
+        anyhow::bail!("API request failed ({}): {}", status, error_text)
     }
 
     let claude_response: ClaudeResponse = response
@@ -93,71 +251,173 @@ pub async fn claude_api(message: &str) -> anyhow::Result<String> {
         .await
         .inspect_err(|x| tracing::error!("{:#?}", x))?;
 
-    Ok(claude_response.content[0].text.clone())
+    claude_response
+        .content
+        .into_iter()
+        .next()
+        .map(|content| content.text)
+        .ok_or_else(|| anyhow::anyhow!("Empty response"))
 }
 
+/// `ClineClient::stream_response` の実行中のストリーム（`AbortController` と同様に中断できる）
 #[wasm_bindgen]
-pub async fn stream_response(message: String, callback: js_sys::Function) -> Result<(), JsValue> {
-    console_error_panic_hook::set_once();
-    tracing_wasm::try_set_as_global_default()
-        .unwrap_or_else(|e| tracing::warn!("failed to set tracing: {}", e));
-
-    let client = reqwest::Client::new();
-
-    let request_body = ClaudeRequest {
-        model: "claude-3-sonnet-20240229".to_string(),
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: message,
-        }],
-        max_tokens: 1000,
-        stream: true,
-    };
+pub struct StreamHandle {
+    abort: AbortHandle,
+    done: js_sys::Promise,
+}
+
+#[wasm_bindgen]
+impl StreamHandle {
+    /// ストリームを中断する（リクエストを破棄し、以降のコールバックは呼ばない）
+    pub fn abort(&self) {
+        self.abort.abort();
+    }
 
-    let response = client
-        .post("https://api.anthropic.com/v1/messages")
-        .header("accept", "application/json")
-        .header("content-type", "application/json")
-        .header("x-api-key", API_KEY)
-        .header("anthropic-version", "2023-06-01")
-        .json(&request_body)
+    pub fn aborted(&self) -> bool {
+        self.abort.is_aborted()
+    }
+
+    /// ストリームの完了で解決し、エラーや中断で拒否されるPromise
+    pub fn done(&self) -> js_sys::Promise {
+        self.done.clone()
+    }
+}
+
+async fn stream_deltas(
+    config: ClientConfig,
+    message: String,
+    callback: js_sys::Function,
+) -> Result<(), JsValue> {
+    let response = config
+        .request(message, true)
         .send()
         .await
         .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    if response.status() != StatusCode::OK {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(JsValue::from_str(&format!(
+            "API request failed ({}): {}",
+            status, error_text
+        )));
+    }
 
     let mut stream = response.bytes_stream();
+    let mut decoder = SseDecoder::new();
     let this = JsValue::null();
+    let emit = |event: &SseEvent| -> Result<(), JsValue> {
+        tracing::debug!("Received event: {}", event.event_type());
+        if event.event_type() == "error" {
+            return Err(JsValue::from_str(&format!(
+                "API stream error: {}",
+                event.data
+            )));
+        }
+        if let Some(delta) = text_delta(event) {
+            callback
+                .call1(&this, &JsValue::from_str(&delta))
+                .map_err(|e| JsValue::from_str(&format!("Callback error: {:?}", e)))?;
+        }
+        Ok(())
+    };
 
     while let Some(chunk) = stream.next().await {
         let chunk = chunk.map_err(|e| JsValue::from_str(&e.to_string()))?;
-        let text = String::from_utf8_lossy(&chunk);
-
-        for line in text.lines() {
-            tracing::debug!("Received line: {}", line);
-            if let Some(data) = line.strip_prefix("data: ") {
-                if data == "[DONE]" {
-                    tracing::debug!("Stream completed");
-                    continue;
-                }
-
-                tracing::debug!("Parsing data: {}", data);
-                if let Ok(response) = serde_json::from_str::<StreamResponse>(data) {
-                    if let Some(delta) = response.delta {
-                        if delta.delta_type == "text_delta" {
-                            tracing::debug!("Received content: {}", delta.text);
-                            callback
-                                .call1(&this, &JsValue::from_str(&delta.text))
-                                .map_err(|e| {
-                                    JsValue::from_str(&format!("Callback error: {:?}", e))
-                                })?;
-                        }
-                    }
-                } else {
-                    tracing::warn!("Failed to parse response JSON");
-                }
-            }
+        for event in decoder.push(&chunk) {
+            emit(&event)?;
         }
     }
+    if let Some(event) = decoder.finish() {
+        emit(&event)?;
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(json: &str) -> ClientOptions {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_client_config_from_options() {
+        let config = ClientConfig::from_options(
+            options(r#"{"apiKey":"sk-1","model":"claude-3-haiku"}"#),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            ClientConfig {
+                api_key: Some("sk-1".to_string()),
+                endpoint: "https://api.anthropic.com/v1/messages".to_string(),
+                model: "claude-3-haiku".to_string(),
+                max_tokens: DEFAULT_MAX_TOKENS,
+            }
+        );
+
+        let config = ClientConfig::from_options(
+            options(r#"{"apiKey":"sk-1","baseUrl":"https://gateway.example.com/","maxTokens":64}"#),
+            None,
+        )
+        .unwrap();
+        assert_eq!(config.endpoint, "https://gateway.example.com/v1/messages");
+        assert_eq!(config.max_tokens, 64);
+
+        // プロキシ経由ではキーを送らず、相対URLはページのオリジンを基準にする
+        let config = ClientConfig::from_options(
+            options(r#"{"apiKey":"sk-1","proxyUrl":"/api/claude"}"#),
+            Some("https://app.example.com"),
+        )
+        .unwrap();
+        assert_eq!(config.api_key, None);
+        assert_eq!(
+            config.endpoint,
+            "https://app.example.com/api/claude/v1/messages"
+        );
+        let config = ClientConfig::from_options(
+            options(r#"{"proxyUrl":"https://proxy.example.com/claude/"}"#),
+            Some("https://app.example.com"),
+        )
+        .unwrap();
+        assert_eq!(
+            config.endpoint,
+            "https://proxy.example.com/claude/v1/messages"
+        );
+        // オリジンがなければ相対URLは使えない
+        assert!(
+            ClientConfig::from_options(options(r#"{"proxyUrl":"/api/claude"}"#), None).is_err()
+        );
+
+        assert!(ClientConfig::from_options(options(r#"{"apiKey":""}"#), None).is_err());
+    }
+
+    fn data(data: &str) -> SseEvent {
+        SseEvent {
+            data: data.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_text_delta_ignores_other_events() {
+        assert_eq!(
+            text_delta(&data(
+                "{\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"hi\"}}"
+            ))
+            .as_deref(),
+            Some("hi")
+        );
+        assert_eq!(text_delta(&data("{\"type\":\"ping\"}")), None);
+        assert_eq!(text_delta(&data("[DONE]")), None);
+        assert_eq!(
+            text_delta(&data(
+                "{\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{}\"}}"
+            )),
+            None
+        );
+    }
+}
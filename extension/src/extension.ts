@@ -47,9 +47,17 @@ export function activate(context: vscode.ExtensionContext) {
                 console.log('Received update:', text);
             };
 
+            let stream: InstanceType<typeof module.StreamHandle> | undefined;
             try {
-                await module.stream_response("こんにちは！", updateContent);
+                const client = new module.ClineClient({ apiKey: process.env.ANTHROPIC_API_KEY });
+                // パネルを閉じたらストリームを中断する
+                stream = client.stream_response("こんにちは！", updateContent);
+                currentPanel?.onDidDispose(() => stream?.abort(), null, context.subscriptions);
+                await stream.done();
             } catch (error) {
+                if (stream?.aborted()) {
+                    return;
+                }
                 console.error('Error in stream_response:', error);
                 if (currentPanel) {
                     currentPanel.webview.html = getWebviewContent(`Error: ${error}`);